@@ -1,8 +1,8 @@
 //! Benchmarks for the Leaky Bucket rate limiter.
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::sync::Barrier;
 use std::thread;
 use std::time::Duration;
@@ -97,6 +97,52 @@ fn leaky_bucket_contention_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
+fn leaky_bucket_drain_interval_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("leaky_bucket_drain_interval");
+
+    // Saturated steady state on the real clock, where every millisecond of clock
+    // movement triggers a drain unless it is amortized
+    let num_threads = 4;
+    for interval_ms in [0, 1, 10] {
+        group.bench_function(format!("interval_{}ms", interval_ms), |b| {
+            b.iter_custom(|iters| {
+                let bucket = Arc::new(
+                    LeakyBucket::new(100_000.0, Some(100))
+                        .with_drain_interval(Duration::from_millis(interval_ms)),
+                );
+
+                let barrier = Arc::new(Barrier::new(num_threads + 1));
+                let mut handles = vec![];
+
+                for _ in 0..num_threads {
+                    let bucket = bucket.clone();
+                    let barrier = barrier.clone();
+
+                    let handle = thread::spawn(move || {
+                        barrier.wait();
+                        for _ in 0..(iters / num_threads as u64) {
+                            let _ = black_box(bucket.try_acquire(1));
+                        }
+                    });
+
+                    handles.push(handle);
+                }
+
+                let start = std::time::Instant::now();
+                barrier.wait();
+
+                for handle in handles {
+                    handle.join().unwrap();
+                }
+
+                start.elapsed()
+            });
+        });
+    }
+
+    group.finish();
+}
+
 fn leaky_bucket_update_benchmark(c: &mut Criterion) {
     let mut group = c.benchmark_group("leaky_bucket_update");
 
@@ -112,6 +158,17 @@ fn leaky_bucket_update_benchmark(c: &mut Criterion) {
         });
     });
 
+    group.bench_function("update_config_noop", |b| {
+        let clock = MockClock::default();
+        let bucket = LeakyBucket::with_clock(10.0, Some(100), clock);
+
+        b.iter(|| {
+            bucket
+                .update_config(black_box(100), black_box(10.0))
+                .unwrap();
+        });
+    });
+
     group.finish();
 }
 
@@ -119,6 +176,7 @@ criterion_group!(
     benches,
     leaky_bucket_acquire_benchmark,
     leaky_bucket_contention_benchmark,
+    leaky_bucket_drain_interval_benchmark,
     leaky_bucket_update_benchmark
 );
 criterion_main!(benches);
@@ -1,7 +1,6 @@
 use bucketboss::{LeakyBucket, RateLimiter, TokenBucket};
 use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
 use std::sync::Arc;
-use std::time::Duration;
 use tokio::runtime::Runtime;
 
 fn bench_token_bucket_single_thread(c: &mut Criterion) {
@@ -128,11 +127,54 @@ fn bench_leaky_bucket_concurrent(c: &mut Criterion) {
     group.finish();
 }
 
+#[cfg(feature = "thread-local-clock")]
+fn bench_thread_local_clock(c: &mut Criterion) {
+    use bucketboss::clock::{SystemClock, ThreadLocalClock};
+
+    let mut group = c.benchmark_group("thread_local_clock");
+
+    group.bench_function("system_clock", |b| {
+        b.iter_batched(
+            || TokenBucket::with_clock(1_000_000, 1_000_000.0, SystemClock),
+            |bucket| {
+                for _ in 0..1000 {
+                    let _ = black_box(bucket.try_acquire(1));
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.bench_function("thread_local_clock", |b| {
+        b.iter_batched(
+            || TokenBucket::with_clock(1_000_000, 1_000_000.0, ThreadLocalClock::new()),
+            |bucket| {
+                for _ in 0..1000 {
+                    let _ = black_box(bucket.try_acquire(1));
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+#[cfg(not(feature = "thread-local-clock"))]
+criterion_group!(
+    benches,
+    bench_token_bucket_single_thread,
+    bench_leaky_bucket_single_thread,
+    bench_token_bucket_concurrent,
+    bench_leaky_bucket_concurrent,
+);
+#[cfg(feature = "thread-local-clock")]
 criterion_group!(
     benches,
     bench_token_bucket_single_thread,
     bench_leaky_bucket_single_thread,
     bench_token_bucket_concurrent,
     bench_leaky_bucket_concurrent,
+    bench_thread_local_clock,
 );
 criterion_main!(benches);
@@ -13,13 +13,13 @@ use axum::{
 };
 use bucketboss::{RateLimiter, TokenBucket};
 use std::{net::SocketAddr, sync::Arc};
-use tokio::sync::Mutex;
 use tower::ServiceBuilder;
 
-// A simple state that holds our rate limiter
+// A simple state that holds our rate limiter. The limiter is thread-safe, so it can be
+// shared directly without a lock.
 #[derive(Clone)]
 struct AppState {
-    rate_limiter: Arc<Mutex<TokenBucket>>,
+    rate_limiter: Arc<TokenBucket>,
 }
 
 // Custom error type for our application
@@ -45,9 +45,7 @@ async fn rate_limiter_middleware(
     request: Request,
     next: Next,
 ) -> Result<Response, AppError> {
-    let limiter = state.rate_limiter.lock().await;
-    
-    if limiter.try_acquire(1).is_err() {
+    if state.rate_limiter.try_acquire(1).is_err() {
         return Err(AppError::RateLimitExceeded);
     }
     
@@ -62,7 +60,7 @@ async fn hello_world() -> &'static str {
 
 // A handler that returns the current rate limit status
 async fn status(State(state): State<AppState>) -> String {
-    let limiter = state.rate_limiter.lock().await;
+    let limiter = &state.rate_limiter;
     format!(
         "Available tokens: {}/{}",
         limiter.available_tokens(),
@@ -73,7 +71,7 @@ async fn status(State(state): State<AppState>) -> String {
 #[tokio::main]
 async fn main() {
     // Create a rate limiter that allows 10 requests per second with a burst of 5
-    let rate_limiter = Arc::new(Mutex::new(TokenBucket::new(5, 10.0)));
+    let rate_limiter = Arc::new(TokenBucket::new(5, 10.0));
     
     // Create the application state
     let state = AppState { rate_limiter };
@@ -113,7 +111,6 @@ mod tests {
         Router,
     };
     use std::sync::Arc;
-    use tokio::sync::Mutex;
     use tokio::net::TcpListener;
     use std::sync::mpsc;
 
@@ -122,7 +119,7 @@ mod tests {
         // Create a rate limiter that allows 2 requests per second with a burst of 1
         let rate_limiter = TokenBucket::new(1, 2.0);
         let state = AppState {
-            rate_limiter: Arc::new(Mutex::new(rate_limiter)),
+            rate_limiter: Arc::new(rate_limiter),
         };
 
         // Build our test application
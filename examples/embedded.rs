@@ -2,6 +2,12 @@
 //!
 //! This example demonstrates how to use the TokenBucket rate limiter in a `no_std` environment
 //! with a custom clock implementation.
+//!
+//! If the hardware timer already implements `embedded_time::Clock`, the `embedded-time`
+//! feature's `EmbeddedTimeClock` adapts it without a custom implementation.
+//!
+//! The limiter is wrapped in an `EmbeddedTraceLimiter`, whose ring buffer of recent
+//! decisions a debug console can dump without a heap.
 
 // Only enable no_std and no_main for actual embedded targets
 #![cfg_attr(not(test), no_std)]
@@ -15,19 +21,12 @@ use panic_halt as _;
 
 use bucketboss::{
     clock::Clock,
+    DecisionRecord,
+    EmbeddedTraceLimiter,
     RateLimiter,
     TokenBucket,
 };
 
-#[cfg(not(test))]
-use core::panic::PanicInfo;
-
-#[cfg(not(test))]
-#[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
-    loop {}
-}
-
 // A simple mock hardware timer that increments every millisecond
 struct HardwareTimer {
     counter: AtomicU64,
@@ -50,6 +49,7 @@ impl HardwareTimer {
 }
 
 // A clock that uses our hardware timer
+#[derive(Clone, Copy)]
 struct HardwareClock {
     timer: &'static HardwareTimer,
 }
@@ -75,8 +75,10 @@ pub extern "C" fn embedded_main() -> ! {
     // Create a clock that uses our hardware timer
     let clock = HardwareClock { timer: &TIMER };
 
-    // Create a rate limiter that allows 10 operations per second with a burst of 5
-    let rate_limiter = TokenBucket::with_clock(5, 10.0, clock);
+    // Create a rate limiter that allows 10 operations per second with a burst of 5,
+    // keeping its last 8 decisions for the debug console
+    let rate_limiter: EmbeddedTraceLimiter<_, _, 8> =
+        EmbeddedTraceLimiter::new(TokenBucket::with_clock(5, 10.0, clock), clock);
 
     // Simulate some operations
     for i in 0..20 {
@@ -99,6 +101,17 @@ pub extern "C" fn embedded_main() -> ! {
         }
     }
 
+    // The 20 decisions have wrapped around the ring; dump the last 8 of them, as a
+    // debug command would before writing them to the UART
+    let mut records = [DecisionRecord::default(); 8];
+    let len = rate_limiter.dump(&mut records);
+    for record in &records[..len] {
+        log::info(
+            "Recent decision",
+            format_args!("{} ms: allowed {}", record.timestamp, record.allowed),
+        );
+    }
+
     // The program should never reach here in a real embedded system
     loop {}
 }
@@ -0,0 +1,103 @@
+//! Coverage-guided fuzzing of the bucket state machines.
+//!
+//! Drives a `TokenBucket` or `LeakyBucket` on a `MockClock` with an arbitrary sequence
+//! of operations and checks invariants after every step:
+//!
+//! - no operation panics or overflows
+//! - available tokens never exceed capacity
+//! - a rejected request succeeds once the advertised `retry_after_ms` has elapsed
+//!
+//! Run with `cargo +nightly fuzz run bucket_ops` from the repository root.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use bucketboss::{
+    clock::MockClock, LeakyBucket, RateLimitError, ReconfigurableRateLimiter, TokenBucket,
+};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Debug, Arbitrary)]
+enum Op {
+    /// Advance the clock by the given number of milliseconds.
+    Advance(u32),
+    /// Try to acquire the given number of tokens.
+    TryAcquire(u16),
+    /// Reconfigure the bucket. The rate is in thousandths of a token per second.
+    UpdateConfig { capacity: u16, rate_milli: u32 },
+    /// Advance the clock by the last advertised retry-after and retry that request.
+    RetryLastRejection,
+}
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    token_bucket: bool,
+    capacity: u16,
+    rate_milli: u32,
+    ops: Vec<Op>,
+}
+
+fn rate(rate_milli: u32) -> f64 {
+    rate_milli.max(1) as f64 / 1000.0
+}
+
+fn run<L>(limiter: &L, clock: &MockClock, ops: &[Op])
+where
+    L: ReconfigurableRateLimiter,
+{
+    let mut last_rejection: Option<(u32, u64)> = None;
+
+    for op in ops {
+        match *op {
+            Op::Advance(ms) => clock.advance(ms as u64),
+            Op::TryAcquire(tokens) => {
+                let tokens = tokens as u32;
+                match limiter.try_acquire(tokens) {
+                    Ok(()) => last_rejection = None,
+                    Err(RateLimitError::RateLimitExceeded { retry_after_ms, .. }) => {
+                        // Requests above capacity can never succeed
+                        if tokens <= limiter.capacity() {
+                            last_rejection = Some((tokens, retry_after_ms));
+                        }
+                    }
+                    Err(err) => panic!("unexpected error: {}", err),
+                }
+            }
+            Op::UpdateConfig {
+                capacity,
+                rate_milli,
+            } => {
+                let capacity = (capacity as u32).max(1);
+                limiter.update_config(capacity, rate(rate_milli)).unwrap();
+                last_rejection = None;
+            }
+            Op::RetryLastRejection => {
+                if let Some((tokens, retry_after_ms)) = last_rejection.take() {
+                    clock.advance(retry_after_ms);
+                    assert!(
+                        limiter.try_acquire(tokens).is_ok(),
+                        "retry_after_ms {} was not enough for {} tokens",
+                        retry_after_ms,
+                        tokens
+                    );
+                }
+            }
+        }
+
+        assert!(limiter.available_tokens() <= limiter.capacity());
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let clock = MockClock::new(0);
+    let capacity = (input.capacity as u32).max(1);
+    let rate = rate(input.rate_milli);
+
+    if input.token_bucket {
+        let bucket = TokenBucket::with_clock(capacity, rate, clock.clone());
+        run(&bucket, &clock, &input.ops);
+    } else {
+        let bucket = LeakyBucket::with_clock(rate, Some(capacity), clock.clone());
+        run(&bucket, &clock, &input.ops);
+    }
+});
@@ -0,0 +1,314 @@
+//! Async support for the rate limiters.
+//!
+//! This module is only available with the `async` feature. It provides an
+//! [`AsyncRateLimiter`] extension trait that waits for tokens instead of failing,
+//! and a [`TokioClock`] that reads time from Tokio's timer so the buckets follow
+//! `tokio::time::pause` and `tokio::time::advance` in tests.
+
+use core::future::Future;
+use core::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::{
+    clock::Clock,
+    error::{RateLimitError, Result},
+    traits::RateLimiter,
+};
+
+/// A clock backed by Tokio's timer.
+///
+/// Time is reported in milliseconds since the clock was created. Because it reads
+/// `tokio::time::Instant`, a paused Tokio runtime drives this clock deterministically,
+/// keeping the limiter's view of time in step with `tokio::time::sleep`.
+#[derive(Debug, Clone, Copy)]
+pub struct TokioClock {
+    base: Instant,
+}
+
+impl TokioClock {
+    /// Creates a new `TokioClock` whose epoch is the current Tokio instant.
+    pub fn new() -> Self {
+        Self {
+            base: Instant::now(),
+        }
+    }
+}
+
+impl Default for TokioClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for TokioClock {
+    fn now(&self) -> u64 {
+        Instant::now().duration_since(self.base).as_millis() as u64
+    }
+}
+
+/// A point in time by which a chain of acquisitions must complete.
+///
+/// A request that passes through several rate-limited stages can share one `Deadline`
+/// between them: each [`acquire_deadline`](AsyncRateLimiter::acquire_deadline) call
+/// waits only as long as the time left, so the waits of all stages together respect a
+/// single end-to-end latency budget. Deadlines follow Tokio's timer, including
+/// `tokio::time::pause`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline {
+    at: Instant,
+}
+
+impl Deadline {
+    /// Creates a deadline `budget` from now.
+    pub fn after(budget: Duration) -> Self {
+        Self::at(Instant::now() + budget)
+    }
+
+    /// Creates a deadline at the given instant.
+    pub fn at(at: Instant) -> Self {
+        Self { at }
+    }
+
+    /// Returns the instant of the deadline.
+    pub fn instant(&self) -> Instant {
+        self.at
+    }
+
+    /// Returns the time left until the deadline, or zero if it has passed.
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+
+    /// Returns `true` if the deadline has passed.
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+}
+
+/// An extension trait for rate limiters that can wait for tokens asynchronously.
+///
+/// This trait is implemented for every [`RateLimiter`].
+pub trait AsyncRateLimiter: RateLimiter {
+    /// Waits until the specified number of tokens can be acquired, then acquires them.
+    ///
+    /// When the limiter rejects the request, the future sleeps for the advertised
+    /// `retry_after_ms` and tries again. Tokens are only consumed by a successful
+    /// `try_acquire`, so dropping the future early never consumes anything.
+    ///
+    /// Waiting uses `tokio::time::sleep`, and every wakeup re-checks with `try_acquire`
+    /// rather than assuming the tokens are there, so a waiter that was overtaken simply
+    /// waits again. For deterministic tests, give the limiter a [`TokioClock`] and run
+    /// with tokio's time paused, so that sleeping advances the limiter's clock too.
+    ///
+    /// A request for zero tokens resolves according to the limiter's
+    /// [`ZeroTokenPolicy`](crate::policy::ZeroTokenPolicy): under `ReflectAvailability`
+    /// it waits until at least one token is available, without consuming it.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` once the tokens have been acquired
+    /// * `Err(RateLimitError::RateLimitExceeded)` immediately if `tokens` exceeds the
+    ///   limiter's capacity, since waiting would never succeed
+    /// * `Err(RateLimitError::InvalidConfiguration)` if the rate limiter is misconfigured
+    fn acquire(&self, tokens: u32) -> impl Future<Output = Result<()>> + Send + '_ {
+        async move {
+            loop {
+                match self.try_acquire(tokens) {
+                    Ok(()) => return Ok(()),
+                    Err(err @ RateLimitError::RateLimitExceeded { retry_after_ms, .. }) => {
+                        if tokens > self.capacity() {
+                            return Err(err);
+                        }
+                        // Never sleep for zero time, which would spin on the executor
+                        tokio::time::sleep(Duration::from_millis(retry_after_ms.max(1))).await;
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+    }
+
+    /// Waits until the specified number of tokens can be acquired, giving up if that
+    /// would take past `deadline`.
+    ///
+    /// Unlike a per-call timeout, the same `deadline` can be passed to every stage of a
+    /// pipeline so that the stages share one latency budget. The call fails as soon as
+    /// the limiter's advertised wait exceeds the time left, rather than sleeping until
+    /// the deadline first. Once the deadline has passed, calls reject without trying to
+    /// acquire anything, so later stages short-circuit.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` once the tokens have been acquired
+    /// * `Err(RateLimitError::RateLimitExceeded)` if the tokens cannot be acquired
+    ///   before the deadline
+    /// * `Err(RateLimitError::InvalidConfiguration)` if the rate limiter is misconfigured
+    fn acquire_deadline<'a>(
+        &'a self,
+        tokens: u32,
+        deadline: &'a Deadline,
+    ) -> impl Future<Output = Result<()>> + Send + 'a {
+        async move {
+            loop {
+                if deadline.is_expired() {
+                    return Err(RateLimitError::rate_limit_exceeded(
+                        tokens,
+                        self.available_tokens(),
+                        self.time_until_available_ms(tokens).unwrap_or(0),
+                    ));
+                }
+                match self.try_acquire(tokens) {
+                    Ok(()) => return Ok(()),
+                    Err(err @ RateLimitError::RateLimitExceeded { retry_after_ms, .. }) => {
+                        let wait = Duration::from_millis(retry_after_ms.max(1));
+                        if tokens > self.capacity() || wait > deadline.remaining() {
+                            return Err(err);
+                        }
+                        tokio::time::sleep(wait).await;
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+    }
+}
+
+impl<L: RateLimiter + ?Sized> AsyncRateLimiter for L {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{policy::ZeroTokenPolicy, LeakyBucket, TokenBucket};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_zero_token_acquire_waits_for_availability() {
+        let bucket = TokenBucket::with_clock(1, 10.0, TokioClock::new())
+            .with_zero_token_policy(ZeroTokenPolicy::ReflectAvailability);
+        assert!(bucket.try_acquire(1).is_ok());
+        assert!(bucket.try_acquire(0).is_err());
+
+        let start = Instant::now();
+        bucket.acquire(0).await.unwrap();
+
+        // Resolves as soon as the next token accrues, without consuming it
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+        assert_eq!(bucket.available_tokens(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_zero_token_acquire_leaky_bucket() {
+        let bucket = LeakyBucket::with_clock(10.0, Some(1), TokioClock::new())
+            .with_zero_token_policy(ZeroTokenPolicy::ReflectAvailability);
+        assert!(bucket.try_acquire(1).is_ok());
+
+        let start = Instant::now();
+        bucket.acquire(0).await.unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(bucket.available_tokens(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_zero_token_acquire_always_admit() {
+        let bucket = TokenBucket::with_clock(1, 1.0, TokioClock::new());
+        assert!(bucket.try_acquire(1).is_ok());
+
+        // The default policy admits immediately even when exhausted
+        let start = Instant::now();
+        bucket.acquire(0).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_zero_token_acquire_in_select() {
+        let bucket = TokenBucket::with_clock(1, 1.0, TokioClock::new())
+            .with_zero_token_policy(ZeroTokenPolicy::ReflectAvailability);
+        assert!(bucket.try_acquire(1).is_ok());
+
+        // The readiness wait loses to a shorter timer and consumes nothing
+        tokio::select! {
+            _ = bucket.acquire(0) => panic!("bucket should still be exhausted"),
+            _ = tokio::time::sleep(Duration::from_millis(500)) => {}
+        }
+
+        bucket.acquire(0).await.unwrap();
+        assert_eq!(bucket.available_tokens(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_acquire_waits_for_tokens() {
+        let bucket = TokenBucket::with_clock(3, 10.0, TokioClock::new());
+        assert!(bucket.try_acquire(3).is_ok());
+
+        let start = Instant::now();
+        bucket.acquire(2).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(200));
+        assert_eq!(bucket.available_tokens(), 0);
+
+        // A request that can never fit fails at once instead of waiting forever
+        assert!(bucket.acquire(4).await.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancelled_acquire_consumes_nothing() {
+        let bucket = TokenBucket::with_clock(2, 10.0, TokioClock::new());
+        assert!(bucket.try_acquire(2).is_ok());
+
+        tokio::select! {
+            _ = bucket.acquire(2) => panic!("tokens should not have accrued yet"),
+            _ = tokio::time::sleep(Duration::from_millis(150)) => {}
+        }
+
+        // The token that accrued while waiting is still there
+        assert_eq!(bucket.available_tokens(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_overtaken_waiter_waits_again() {
+        let bucket = TokenBucket::with_clock(1, 10.0, TokioClock::new());
+        assert!(bucket.try_acquire(1).is_ok());
+
+        // Another caller takes the token the waiter woke up for
+        let start = Instant::now();
+        let (waited, ()) = tokio::join!(bucket.acquire(1), async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert!(bucket.try_acquire(1).is_ok());
+        });
+        waited.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(200));
+        assert_eq!(bucket.available_tokens(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_deadline_shared_across_stages() {
+        let first = TokenBucket::with_clock(1, 5.0, TokioClock::new());
+        let second = TokenBucket::with_clock(1, 2.0, TokioClock::new());
+        assert!(first.try_acquire(1).is_ok());
+        assert!(second.try_acquire(1).is_ok());
+
+        // The stages need 200ms and 500ms, but the whole pipeline only has 300ms
+        let start = Instant::now();
+        let deadline = Deadline::after(Duration::from_millis(300));
+        first.acquire_deadline(1, &deadline).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(200));
+
+        // The second stage fails at once instead of waiting out the remaining budget
+        let err = second.acquire_deadline(1, &deadline).await.unwrap_err();
+        assert!(err.is_rate_limit_exceeded());
+        assert_eq!(start.elapsed(), Duration::from_millis(200));
+        assert_eq!(deadline.remaining(), Duration::from_millis(100));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_expired_deadline_short_circuits() {
+        let bucket = TokenBucket::with_clock(5, 1.0, TokioClock::new());
+        let deadline = Deadline::after(Duration::from_millis(10));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(deadline.is_expired());
+
+        // Tokens are available, but nothing is acquired once the budget is spent
+        assert!(bucket.acquire_deadline(1, &deadline).await.is_err());
+        assert_eq!(bucket.available_tokens(), 5);
+    }
+}
@@ -0,0 +1,48 @@
+//! Bounded exponential backoff for contended compare-and-swap loops.
+//!
+//! When many threads race on the same atomic, a thread that retries immediately after
+//! losing tends to lose again to whichever thread already holds the cache line. Backing
+//! off for a growing number of spin hints spreads the retries out, so a losing thread
+//! gets a realistic chance at its next attempt.
+
+use core::hint;
+
+/// The largest backoff step; a retry spins for at most `2^SPIN_LIMIT` hints.
+const SPIN_LIMIT: u32 = 6;
+
+/// Tracks how long to spin before the next retry of a failed compare-and-swap.
+#[derive(Debug, Default)]
+pub(crate) struct Backoff {
+    step: u32,
+}
+
+impl Backoff {
+    /// Creates a backoff that starts with the shortest spin.
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spins before the next retry, doubling the wait up to the limit.
+    pub(crate) fn spin(&mut self) {
+        for _ in 0..1u32 << self.step {
+            hint::spin_loop();
+        }
+        if self.step < SPIN_LIMIT {
+            self.step += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_is_bounded() {
+        let mut backoff = Backoff::new();
+        for _ in 0..100 {
+            backoff.spin();
+        }
+        assert_eq!(backoff.step, SPIN_LIMIT);
+    }
+}
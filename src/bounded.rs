@@ -0,0 +1,255 @@
+//! Guardrails for automated reconfiguration.
+//!
+//! Limiters driven by an automated controller (autoscalers, adaptive algorithms,
+//! penalty systems) can be pushed to runaway values by a misbehaving control loop.
+//! [`BoundedReconfigurable`] clamps every reconfiguration into a fixed range before
+//! passing it to the inner limiter, so the controller can never configure a limit
+//! outside the bounds chosen by the operator.
+
+use core::ops::RangeInclusive;
+
+use crate::{
+    error::{RateLimitError, Result},
+    traits::{RateLimiter, ReconfigurableRateLimiter, Wrapper},
+};
+
+/// A wrapper that clamps every `update_config` call into configured bounds.
+///
+/// Out-of-range values are clamped, not rejected. A `NaN` rate cannot be clamped
+/// meaningfully and is rejected with `InvalidConfiguration`.
+#[derive(Debug)]
+pub struct BoundedReconfigurable<L> {
+    inner: L,
+    capacity_bounds: RangeInclusive<u32>,
+    rate_bounds: RangeInclusive<f64>,
+}
+
+impl<L> BoundedReconfigurable<L>
+where
+    L: ReconfigurableRateLimiter,
+{
+    /// Creates a new wrapper enforcing the given capacity and rate bounds.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The limiter to protect.
+    /// * `capacity_bounds` - The inclusive range every applied capacity is clamped into.
+    /// * `rate_bounds` - The inclusive range every applied rate, in tokens per second,
+    ///   is clamped into.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Self)` if the bounds are valid
+    /// * `Err(RateLimitError::InvalidConfiguration)` if a range is empty, the minimum
+    ///   capacity is 0, or the minimum rate is not positive
+    pub fn new(
+        inner: L,
+        capacity_bounds: RangeInclusive<u32>,
+        rate_bounds: RangeInclusive<f64>,
+    ) -> Result<Self> {
+        if capacity_bounds.is_empty() || *capacity_bounds.start() == 0 {
+            return Err(RateLimitError::invalid_config(
+                "capacity bounds must be a non-empty range starting above 0",
+            ));
+        }
+        if rate_bounds.is_empty() || *rate_bounds.start() <= 0.0 {
+            return Err(RateLimitError::invalid_config(
+                "rate bounds must be a non-empty range starting above 0",
+            ));
+        }
+
+        Ok(Self {
+            inner,
+            capacity_bounds,
+            rate_bounds,
+        })
+    }
+
+    /// Clamps a configuration into the bounds without applying it.
+    pub fn clamp(&self, capacity: u32, tokens_per_second: f64) -> (u32, f64) {
+        (
+            capacity.clamp(*self.capacity_bounds.start(), *self.capacity_bounds.end()),
+            tokens_per_second.clamp(*self.rate_bounds.start(), *self.rate_bounds.end()),
+        )
+    }
+
+    /// Clamps a configuration into the bounds and applies it to the inner limiter.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((capacity, tokens_per_second))` with the values actually applied
+    /// * `Err(RateLimitError::InvalidConfiguration)` if `tokens_per_second` is `NaN` or
+    ///   the inner limiter rejects the clamped configuration
+    pub fn update_config_clamped(
+        &self,
+        capacity: u32,
+        tokens_per_second: f64,
+    ) -> Result<(u32, f64)> {
+        if tokens_per_second.is_nan() {
+            return Err(RateLimitError::invalid_config(
+                "tokens_per_second must not be NaN",
+            ));
+        }
+
+        let (capacity, tokens_per_second) = self.clamp(capacity, tokens_per_second);
+        self.inner.update_config(capacity, tokens_per_second)?;
+        Ok((capacity, tokens_per_second))
+    }
+
+    /// Returns the inclusive capacity bounds.
+    pub fn capacity_bounds(&self) -> &RangeInclusive<u32> {
+        &self.capacity_bounds
+    }
+
+    /// Returns the inclusive rate bounds, in tokens per second.
+    pub fn rate_bounds(&self) -> &RangeInclusive<f64> {
+        &self.rate_bounds
+    }
+}
+
+impl<L> RateLimiter for BoundedReconfigurable<L>
+where
+    L: ReconfigurableRateLimiter,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        self.inner.try_acquire(tokens)
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        self.inner.try_acquire_u64(tokens)
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        self.inner.peek_acquire(tokens)
+    }
+
+    fn available_tokens(&self) -> u32 {
+        self.inner.available_tokens()
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        self.inner.available_tokens_u64()
+    }
+
+    fn capacity(&self) -> u32 {
+        self.inner.capacity()
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.inner.capacity_u64()
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        self.inner.rate_per_second()
+    }
+
+    fn effective_rate_per_second(&self) -> f64 {
+        self.inner.effective_rate_per_second()
+    }
+
+    fn resolution_warning(&self) -> Option<&'static str> {
+        self.inner.resolution_warning()
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        self.inner.time_until_next_token_ms()
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.inner.time_until_available_ms(tokens)
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        self.inner.retry_at(tokens)
+    }
+}
+
+impl<L> ReconfigurableRateLimiter for BoundedReconfigurable<L>
+where
+    L: ReconfigurableRateLimiter,
+{
+    fn update_config(&self, capacity: u32, tokens_per_second: f64) -> Result<()> {
+        self.update_config_clamped(capacity, tokens_per_second)
+            .map(|_| ())
+    }
+
+    /// Clamps the rate into the rate bounds and applies it to the inner limiter.
+    fn set_rate_only(&self, tokens_per_second: f64) -> Result<()> {
+        if tokens_per_second.is_nan() {
+            return Err(RateLimitError::invalid_config(
+                "tokens_per_second must not be NaN",
+            ));
+        }
+
+        self.inner.set_rate_only(
+            tokens_per_second.clamp(*self.rate_bounds.start(), *self.rate_bounds.end()),
+        )
+    }
+}
+
+impl<L> Wrapper for BoundedReconfigurable<L> {
+    type Inner = L;
+
+    fn get_ref(&self) -> &L {
+        &self.inner
+    }
+
+    fn get_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    fn into_inner(self) -> L {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, LeakyBucket, TokenBucket};
+
+    #[test]
+    fn test_out_of_range_reconfig_is_clamped() {
+        let bucket = TokenBucket::with_clock(10, 5.0, MockClock::new(0));
+        let bounded = BoundedReconfigurable::new(bucket, 5..=100, 1.0..=50.0).unwrap();
+
+        // Too high on both axes
+        assert_eq!(bounded.update_config_clamped(1000, 500.0), Ok((100, 50.0)));
+        assert_eq!(bounded.capacity(), 100);
+        assert_eq!(bounded.rate_per_second(), 50.0);
+
+        // Too low on both axes, including values the inner bucket would reject
+        assert!(bounded.update_config(0, 0.0).is_ok());
+        assert_eq!(bounded.capacity(), 5);
+        assert_eq!(bounded.rate_per_second(), 1.0);
+
+        // In range values pass through untouched
+        assert_eq!(bounded.update_config_clamped(20, 10.0), Ok((20, 10.0)));
+    }
+
+    #[test]
+    fn test_bounded_leaky_bucket() {
+        let bucket = LeakyBucket::with_clock(10.0, Some(10), MockClock::new(0));
+        let bounded = BoundedReconfigurable::new(bucket, 1..=20, 1.0..=20.0).unwrap();
+
+        assert_eq!(bounded.update_config_clamped(u32::MAX, 1e9), Ok((20, 20.0)));
+        assert_eq!(bounded.capacity(), 20);
+        assert_eq!(bounded.rate_per_second(), 20.0);
+    }
+
+    #[test]
+    fn test_invalid_bounds_and_nan() {
+        let bucket = || TokenBucket::with_clock(10, 5.0, MockClock::new(0));
+        assert!(BoundedReconfigurable::new(bucket(), 0..=10, 1.0..=2.0).is_err());
+        assert!(
+            BoundedReconfigurable::new(bucket(), RangeInclusive::new(10, 5), 1.0..=2.0).is_err()
+        );
+        assert!(BoundedReconfigurable::new(bucket(), 1..=10, 0.0..=2.0).is_err());
+
+        let bounded = BoundedReconfigurable::new(bucket(), 1..=10, 1.0..=2.0).unwrap();
+        assert!(bounded
+            .update_config(5, f64::NAN)
+            .unwrap_err()
+            .is_invalid_config());
+    }
+}
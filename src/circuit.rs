@@ -0,0 +1,484 @@
+//! Failure-driven load shedding for outbound calls.
+//!
+//! Rate limiting protects a service from its callers; a circuit breaker protects a
+//! failing downstream from the service itself. [`CircuitBreakerLimiter`] wraps a rate
+//! limiter and, once enough failures are reported within a window, rejects every
+//! request for a cooldown period regardless of token availability. After the cooldown
+//! a single probe request is let through to test whether the downstream recovered.
+
+use core::time::Duration;
+use std::sync::Mutex;
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::{RateLimitError, Result},
+    outcome::saturate_u32,
+    traits::{RateLimiter, Wrapper},
+};
+
+/// The state of a circuit breaker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are admitted subject to the inner limiter.
+    Closed,
+    /// Every request is rejected until the cooldown elapses.
+    Open,
+    /// A single probe request may pass to test whether the downstream recovered.
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct Breaker {
+    state: CircuitState,
+    /// Start of the current failure counting window.
+    window_start: u64,
+    /// Failures recorded in the current window.
+    failures: u32,
+    /// When the breaker last opened.
+    opened_at: u64,
+    /// Whether the half-open probe has been handed out.
+    probing: bool,
+    /// When the half-open probe was handed out.
+    probe_started: u64,
+}
+
+/// A rate limiter wrapper that stops admitting requests while a downstream is failing.
+///
+/// Report the result of each downstream call with [`record_success`] and
+/// [`record_failure`]. The breaker moves between three states:
+///
+/// - **Closed**: requests go to the inner limiter. `failure_threshold` failures within
+///   `window_ms` open the breaker.
+/// - **Open**: every request is rejected with the remaining cooldown as its
+///   retry-after. After `cooldown_ms` the breaker becomes half-open.
+/// - **Half-open**: the first request admitted by the inner limiter is a probe and
+///   every other request is rejected until the probe's result is reported, with the
+///   time left for the probe as their retry-after. A success closes the breaker; a
+///   failure opens it again for another cooldown. A probe whose result is not reported
+///   within the probe timeout counts as a failure, so a lost probe cannot hold the
+///   breaker half-open forever.
+///
+/// [`record_success`]: CircuitBreakerLimiter::record_success
+/// [`record_failure`]: CircuitBreakerLimiter::record_failure
+#[derive(Debug)]
+pub struct CircuitBreakerLimiter<L, C = SystemClock> {
+    inner: L,
+    clock: C,
+    failure_threshold: u32,
+    window_ms: u64,
+    cooldown_ms: u64,
+    probe_timeout_ms: u64,
+    breaker: Mutex<Breaker>,
+}
+
+impl<L> CircuitBreakerLimiter<L, SystemClock>
+where
+    L: RateLimiter,
+{
+    /// Creates a new circuit breaker around `inner`.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The limiter consulted while the breaker is closed.
+    /// * `failure_threshold` - The number of failures that opens the breaker.
+    /// * `window_ms` - The window, in milliseconds, within which failures are counted.
+    /// * `cooldown_ms` - How long, in milliseconds, the breaker stays open.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Self)` if the configuration is valid
+    /// * `Err(RateLimitError::InvalidConfiguration)` if `failure_threshold` or
+    ///   `window_ms` is 0
+    pub fn new(inner: L, failure_threshold: u32, window_ms: u64, cooldown_ms: u64) -> Result<Self> {
+        Self::with_clock(
+            inner,
+            failure_threshold,
+            window_ms,
+            cooldown_ms,
+            SystemClock,
+        )
+    }
+}
+
+impl<L, C> CircuitBreakerLimiter<L, C>
+where
+    L: RateLimiter,
+    C: Clock,
+{
+    /// Creates a new circuit breaker with the specified clock.
+    ///
+    /// See [`CircuitBreakerLimiter::new`] for the arguments.
+    pub fn with_clock(
+        inner: L,
+        failure_threshold: u32,
+        window_ms: u64,
+        cooldown_ms: u64,
+        clock: C,
+    ) -> Result<Self> {
+        if failure_threshold == 0 {
+            return Err(RateLimitError::invalid_config(
+                "failure_threshold must be greater than 0",
+            ));
+        }
+        if window_ms == 0 {
+            return Err(RateLimitError::invalid_config(
+                "window_ms must be greater than 0",
+            ));
+        }
+
+        let now = clock.now();
+        Ok(Self {
+            inner,
+            clock,
+            failure_threshold,
+            window_ms,
+            cooldown_ms,
+            probe_timeout_ms: cooldown_ms.max(1),
+            breaker: Mutex::new(Breaker {
+                state: CircuitState::Closed,
+                window_start: now,
+                failures: 0,
+                opened_at: 0,
+                probing: false,
+                probe_started: 0,
+            }),
+        })
+    }
+
+    /// Sets how long a half-open probe may go unreported before it counts as a failure
+    /// and the breaker opens again.
+    ///
+    /// Defaults to the cooldown. A zero timeout is raised to one millisecond.
+    pub fn with_probe_timeout(mut self, timeout: Duration) -> Self {
+        self.probe_timeout_ms = (timeout.as_millis().min(u64::MAX as u128) as u64).max(1);
+        self
+    }
+
+    /// Returns the current state of the breaker.
+    pub fn state(&self) -> CircuitState {
+        let mut breaker = self.breaker.lock().unwrap();
+        self.advance(&mut breaker, self.clock.now());
+        breaker.state
+    }
+
+    /// Reports a successful downstream call.
+    ///
+    /// A success while half-open closes the breaker.
+    pub fn record_success(&self) {
+        let mut breaker = self.breaker.lock().unwrap();
+        let now = self.clock.now();
+        self.advance(&mut breaker, now);
+        if breaker.state == CircuitState::HalfOpen {
+            breaker.state = CircuitState::Closed;
+            breaker.window_start = now;
+            breaker.failures = 0;
+            breaker.probing = false;
+        }
+    }
+
+    /// Reports a failed downstream call.
+    ///
+    /// Opens the breaker once `failure_threshold` failures fall within the window, or
+    /// immediately while half-open.
+    pub fn record_failure(&self) {
+        let mut breaker = self.breaker.lock().unwrap();
+        let now = self.clock.now();
+        self.advance(&mut breaker, now);
+        match breaker.state {
+            CircuitState::Closed => {
+                if now.saturating_sub(breaker.window_start) >= self.window_ms {
+                    breaker.window_start = now;
+                    breaker.failures = 0;
+                }
+                breaker.failures += 1;
+                if breaker.failures >= self.failure_threshold {
+                    Self::open(&mut breaker, now);
+                }
+            }
+            CircuitState::HalfOpen => Self::open(&mut breaker, now),
+            CircuitState::Open => {}
+        }
+    }
+
+    fn open(breaker: &mut Breaker, now: u64) {
+        breaker.state = CircuitState::Open;
+        breaker.opened_at = now;
+        breaker.failures = 0;
+        breaker.probing = false;
+    }
+
+    /// Moves an open breaker to half-open once its cooldown has elapsed, and a
+    /// half-open one back to open once its probe has timed out.
+    fn advance(&self, breaker: &mut Breaker, now: u64) {
+        if breaker.state == CircuitState::Open
+            && now.saturating_sub(breaker.opened_at) >= self.cooldown_ms
+        {
+            breaker.state = CircuitState::HalfOpen;
+            breaker.probing = false;
+        }
+        if breaker.state == CircuitState::HalfOpen
+            && breaker.probing
+            && now.saturating_sub(breaker.probe_started) >= self.probe_timeout_ms
+        {
+            Self::open(breaker, now);
+        }
+    }
+
+    /// Returns how long the breaker itself rejects requests, or `None` if it passes
+    /// them to the inner limiter.
+    fn blocked_for(&self, breaker: &Breaker, now: u64) -> Option<u64> {
+        match breaker.state {
+            CircuitState::Closed => None,
+            CircuitState::Open => Some(
+                breaker
+                    .opened_at
+                    .saturating_add(self.cooldown_ms)
+                    .saturating_sub(now),
+            ),
+            CircuitState::HalfOpen if breaker.probing => Some(
+                breaker
+                    .probe_started
+                    .saturating_add(self.probe_timeout_ms)
+                    .saturating_sub(now)
+                    .max(1),
+            ),
+            CircuitState::HalfOpen => None,
+        }
+    }
+
+    /// Passes a request for `tokens` to `acquire` unless the breaker rejects it, and
+    /// starts the probe if a half-open breaker lets it through.
+    fn acquire_with(&self, tokens: u32, acquire: impl FnOnce(&L) -> Result<()>) -> Result<()> {
+        let mut breaker = self.breaker.lock().unwrap();
+        let now = self.clock.now();
+        self.advance(&mut breaker, now);
+
+        if let Some(wait_ms) = self.blocked_for(&breaker, now) {
+            return Err(RateLimitError::rate_limit_exceeded(tokens, 0, wait_ms));
+        }
+        match breaker.state {
+            CircuitState::HalfOpen => {
+                acquire(&self.inner)?;
+                breaker.probing = true;
+                breaker.probe_started = now;
+                Ok(())
+            }
+            _ => acquire(&self.inner),
+        }
+    }
+}
+
+impl<L, C> RateLimiter for CircuitBreakerLimiter<L, C>
+where
+    L: RateLimiter,
+    C: Clock,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        self.acquire_with(tokens, |inner| inner.try_acquire(tokens))
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        self.acquire_with(saturate_u32(tokens), |inner| inner.try_acquire_u64(tokens))
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        let mut breaker = self.breaker.lock().unwrap();
+        let now = self.clock.now();
+        self.advance(&mut breaker, now);
+
+        match self.blocked_for(&breaker, now) {
+            Some(wait_ms) => Err(RateLimitError::rate_limit_exceeded(tokens, 0, wait_ms)),
+            None => self.inner.peek_acquire(tokens),
+        }
+    }
+
+    fn available_tokens(&self) -> u32 {
+        match self.state() {
+            CircuitState::Closed | CircuitState::HalfOpen => self.inner.available_tokens(),
+            CircuitState::Open => 0,
+        }
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        match self.state() {
+            CircuitState::Closed | CircuitState::HalfOpen => self.inner.available_tokens_u64(),
+            CircuitState::Open => 0,
+        }
+    }
+
+    fn capacity(&self) -> u32 {
+        self.inner.capacity()
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.inner.capacity_u64()
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        self.inner.rate_per_second()
+    }
+
+    fn effective_rate_per_second(&self) -> f64 {
+        // Only a single probe gets through until the breaker closes again
+        match self.state() {
+            CircuitState::Closed => self.inner.effective_rate_per_second(),
+            CircuitState::Open | CircuitState::HalfOpen => 0.0,
+        }
+    }
+
+    fn resolution_warning(&self) -> Option<&'static str> {
+        self.inner.resolution_warning()
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        let mut breaker = self.breaker.lock().unwrap();
+        let now = self.clock.now();
+        self.advance(&mut breaker, now);
+        match self.blocked_for(&breaker, now) {
+            Some(wait_ms) => Some(wait_ms),
+            None => self.inner.time_until_next_token_ms(),
+        }
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        let mut breaker = self.breaker.lock().unwrap();
+        let now = self.clock.now();
+        self.advance(&mut breaker, now);
+        let inner_ms = self.inner.time_until_available_ms(tokens)?;
+        Some(match self.blocked_for(&breaker, now) {
+            Some(wait_ms) => wait_ms.max(inner_ms),
+            None => inner_ms,
+        })
+    }
+}
+
+impl<L, C> Wrapper for CircuitBreakerLimiter<L, C> {
+    type Inner = L;
+
+    fn get_ref(&self) -> &L {
+        &self.inner
+    }
+
+    fn get_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    fn into_inner(self) -> L {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, TokenBucket};
+
+    fn breaker(clock: &MockClock) -> CircuitBreakerLimiter<TokenBucket<MockClock>, MockClock> {
+        let bucket = TokenBucket::with_clock(100, 100.0, clock.clone());
+        CircuitBreakerLimiter::with_clock(bucket, 3, 1000, 5000, clock.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_open_half_open_closed_transitions() {
+        let clock = MockClock::new(0);
+        let limiter = breaker(&clock);
+        assert_eq!(limiter.state(), CircuitState::Closed);
+
+        // Failures below the threshold keep the breaker closed
+        limiter.record_failure();
+        limiter.record_failure();
+        assert_eq!(limiter.state(), CircuitState::Closed);
+        assert!(limiter.try_acquire(1).is_ok());
+
+        // The third failure within the window opens it, despite plenty of tokens
+        limiter.record_failure();
+        assert_eq!(limiter.state(), CircuitState::Open);
+        assert_eq!(limiter.available_tokens(), 0);
+        let err = limiter.try_acquire(1).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(5000));
+
+        clock.advance(4999);
+        assert!(limiter.try_acquire(1).is_err());
+
+        // After the cooldown exactly one probe is admitted
+        clock.advance(1);
+        assert_eq!(limiter.state(), CircuitState::HalfOpen);
+        assert!(limiter.try_acquire(1).is_ok());
+        assert!(limiter.try_acquire(1).is_err());
+
+        // A failed probe reopens the breaker for another cooldown
+        limiter.record_failure();
+        assert_eq!(limiter.state(), CircuitState::Open);
+        clock.advance(5000);
+        assert!(limiter.try_acquire(1).is_ok());
+
+        // A successful probe closes it
+        limiter.record_success();
+        assert_eq!(limiter.state(), CircuitState::Closed);
+        assert!(limiter.try_acquire(1).is_ok());
+        assert!(limiter.try_acquire(1).is_ok());
+    }
+
+    #[test]
+    fn test_unreported_probe_times_out() {
+        let clock = MockClock::new(0);
+        let limiter = breaker(&clock).with_probe_timeout(Duration::from_millis(2000));
+        for _ in 0..3 {
+            limiter.record_failure();
+        }
+        assert_eq!(limiter.time_until_next_token_ms(), Some(5000));
+        clock.advance(1000);
+        assert_eq!(limiter.time_until_next_token_ms(), Some(4000));
+        assert_eq!(limiter.time_until_available_ms(1), Some(4000));
+
+        // The probe's result is never reported, so others wait for it to time out
+        clock.advance(4000);
+        assert!(limiter.try_acquire(1).is_ok());
+        clock.advance(500);
+        let err = limiter.try_acquire(1).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(1500));
+        assert_eq!(limiter.time_until_next_token_ms(), Some(1500));
+
+        // Then the breaker opens again for another cooldown
+        clock.advance(1500);
+        assert_eq!(limiter.state(), CircuitState::Open);
+        assert_eq!(
+            limiter.try_acquire(1).unwrap_err().retry_after_ms(),
+            Some(5000)
+        );
+        clock.advance(5000);
+        assert!(limiter.try_acquire(1).is_ok());
+        limiter.record_success();
+        assert_eq!(limiter.state(), CircuitState::Closed);
+        assert_eq!(
+            limiter.time_until_next_token_ms(),
+            limiter.get_ref().time_until_next_token_ms()
+        );
+    }
+
+    #[test]
+    fn test_failures_outside_window_do_not_accumulate() {
+        let clock = MockClock::new(0);
+        let limiter = breaker(&clock);
+
+        limiter.record_failure();
+        limiter.record_failure();
+        clock.advance(1000);
+        limiter.record_failure();
+        limiter.record_failure();
+        assert_eq!(limiter.state(), CircuitState::Closed);
+
+        // Successes while closed have no effect on the count
+        limiter.record_success();
+        limiter.record_failure();
+        assert_eq!(limiter.state(), CircuitState::Open);
+    }
+
+    #[test]
+    fn test_invalid_configuration() {
+        let clock = MockClock::new(0);
+        let bucket = || TokenBucket::with_clock(1, 1.0, clock.clone());
+        assert!(CircuitBreakerLimiter::with_clock(bucket(), 0, 1000, 0, clock.clone()).is_err());
+        assert!(CircuitBreakerLimiter::with_clock(bucket(), 1, 0, 0, clock.clone()).is_err());
+    }
+}
@@ -22,49 +22,197 @@ pub trait Clock: Send + Sync + 'static {
     fn now_duration(&self) -> Duration {
         Duration::from_millis(self.now())
     }
+
+    /// Returns the current time in nanoseconds since the clock's epoch.
+    ///
+    /// The buckets keep time in nanoseconds internally, so that rates whose interval
+    /// is not a whole number of milliseconds neither drift nor lose partial progress to
+    /// rounding. The default implementation scales [`now`](Clock::now), which is exact
+    /// for clocks that only tick in whole milliseconds; clocks with a finer source
+    /// should override it, keeping it consistent with `now`.
+    fn now_nanos(&self) -> u128 {
+        self.now() as u128 * 1_000_000
+    }
+
+    /// Returns the time between distinct readings of the clock, in milliseconds.
+    ///
+    /// The default of 1 suits clocks that only tick in whole milliseconds. Clocks that
+    /// override [`now_nanos`](Clock::now_nanos) with a finer source, or that tick more
+    /// coarsely, such as a hardware timer with a 10 ms period, should report their
+    /// actual tick.
+    fn resolution_ms(&self) -> f64 {
+        1.0
+    }
 }
 
-/// A clock that uses the system's monotonic clock.
+/// The number of nanoseconds in a millisecond.
+pub(crate) const NANOS_PER_MS: u64 = 1_000_000;
+
+/// Returns the clock's time in nanoseconds, saturating at `u64::MAX`.
 ///
-/// This is the default production clock that should be used in most cases.
+/// That is in the year 2554 for a clock counting from the Unix epoch.
+#[inline]
+pub(crate) fn now_ns<C: Clock + ?Sized>(clock: &C) -> u64 {
+    clock.now_nanos().min(u64::MAX as u128) as u64
+}
+
+/// The warning reported for rates the clock cannot resolve.
+const RATE_ABOVE_RESOLUTION: &str =
+    "rate exceeds the clock resolution: refills are bunched into bursts at each clock tick";
+
+/// Returns a warning if `tokens_per_second` refills more often than `clock` ticks.
+pub(crate) fn resolution_warning<C: Clock + ?Sized>(
+    clock: &C,
+    tokens_per_second: f64,
+) -> Option<&'static str> {
+    let ticks_per_second = 1000.0 / clock.resolution_ms();
+    if tokens_per_second > ticks_per_second {
+        Some(RATE_ABOVE_RESOLUTION)
+    } else {
+        None
+    }
+}
+
+/// A clock that can also report calendar time.
+///
+/// [`Clock::now`] is only meaningful relative to other readings of the same clock; its
+/// epoch is arbitrary. Limiters that need the actual date, such as calendar quotas,
+/// require this subtrait instead of assuming the epoch is Unix time.
+#[cfg(feature = "std")]
+pub trait WallClock: Clock {
+    /// Returns the current calendar time.
+    fn system_time(&self) -> std::time::SystemTime;
+}
+
+/// A clock that uses the system's wall-clock time.
+///
+/// This is the default production clock that should be used in most cases. Its
+/// readings are milliseconds since the Unix epoch, so they are comparable across
+/// processes, but they follow adjustments of the system time and can go backwards
+/// when NTP steps the clock. Use [`InstantClock`] where that matters more than a shared
+/// epoch.
+///
+/// It only reads the time with the `std` feature. Without it, the type still names the
+/// limiters' default clock, but they must be created with `with_clock` and a clock for
+/// the target's own timer.
 #[derive(Debug, Default, Clone, Copy)]
 pub struct SystemClock;
 
+#[cfg(feature = "std")]
 impl Clock for SystemClock {
     #[inline]
     fn now(&self) -> u64 {
-        #[cfg(feature = "std")]
-        {
-            use std::time::{SystemTime, UNIX_EPOCH};
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .expect("SystemTime before UNIX EPOCH!")
-                .as_millis() as u64
-        }
+        use std::time::{SystemTime, UNIX_EPOCH};
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("SystemTime before UNIX EPOCH!")
+            .as_millis() as u64
+    }
+
+    #[inline]
+    fn now_nanos(&self) -> u128 {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("SystemTime before UNIX EPOCH!")
+            .as_nanos()
+    }
+
+    fn resolution_ms(&self) -> f64 {
+        1e-6
+    }
+}
+
+#[cfg(feature = "std")]
+impl WallClock for SystemClock {
+    fn system_time(&self) -> std::time::SystemTime {
+        std::time::SystemTime::now()
+    }
+}
+
+/// A monotonic clock backed by [`std::time::Instant`].
+///
+/// Readings are milliseconds since the clock was created, so unlike [`SystemClock`]
+/// they never go backwards when the system time is adjusted, but they are only
+/// comparable between clocks sharing the same base: copies of one `InstantClock`
+/// agree with each other, while a clock created later starts again from 0. Absolute
+/// timestamps, such as those a bucket's `from_state` restores from a
+/// [`BucketState`](crate::BucketState), cannot be carried between processes with it.
+///
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+pub struct InstantClock {
+    base: std::time::Instant,
+}
 
-        #[cfg(not(feature = "std"))]
-        {
-            // In no_std environments, you'll need to provide a suitable implementation
-            // This is a placeholder that will cause a compilation error if used without std
-            // and no custom clock implementation
-            compile_error!("std feature is required for SystemClock");
+#[cfg(feature = "std")]
+impl InstantClock {
+    /// Creates a new `InstantClock` whose epoch is the current instant.
+    pub fn new() -> Self {
+        Self {
+            base: std::time::Instant::now(),
         }
     }
+
+    /// Returns the instant this clock counts from.
+    pub fn base(&self) -> std::time::Instant {
+        self.base
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for InstantClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for InstantClock {
+    #[inline]
+    fn now(&self) -> u64 {
+        self.base.elapsed().as_millis() as u64
+    }
+
+    fn now_duration(&self) -> Duration {
+        self.base.elapsed()
+    }
+
+    #[inline]
+    fn now_nanos(&self) -> u128 {
+        self.base.elapsed().as_nanos()
+    }
+
+    fn resolution_ms(&self) -> f64 {
+        1e-6
+    }
 }
 
 /// A mock clock for testing purposes.
 ///
 /// This clock allows manual control of the current time, making it ideal for
 /// deterministic testing of time-based functionality.
+///
+/// As a [`WallClock`], its calendar time starts at the Unix epoch when `now()` is 0 and
+/// moves with `advance` and `set`. Use [`set_system_time`](MockClock::set_system_time)
+/// to place it at a specific date.
+///
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
 #[derive(Debug, Default)]
 pub struct MockClock {
     now: std::sync::Arc<std::sync::atomic::AtomicU64>,
+    /// Milliseconds since the Unix epoch at `now() == 0`, with wrapping arithmetic.
+    wall_offset: std::sync::Arc<std::sync::atomic::AtomicU64>,
 }
 
+#[cfg(feature = "std")]
 impl Clone for MockClock {
     fn clone(&self) -> Self {
         Self {
             now: std::sync::Arc::clone(&self.now),
+            wall_offset: std::sync::Arc::clone(&self.wall_offset),
         }
     }
 }
@@ -75,18 +223,53 @@ impl MockClock {
     pub fn new(initial_time: u64) -> Self {
         Self {
             now: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(initial_time)),
+            wall_offset: std::sync::Arc::default(),
         }
     }
 
     /// Advances the clock by the specified number of milliseconds.
+    ///
+    /// The clock stops at `u64::MAX` rather than wrapping around to a time in the past;
+    /// see [`advance_saturating`](MockClock::advance_saturating).
     pub fn advance(&self, ms: u64) {
-        let _ = self.now.fetch_add(ms, std::sync::atomic::Ordering::SeqCst);
+        self.advance_saturating(ms);
+    }
+
+    /// Advances the clock by the specified number of milliseconds, stopping at
+    /// `u64::MAX`.
+    ///
+    /// A wrapping clock would jump backwards, which limiters treat as no time having
+    /// passed and which makes for confusing test failures far from the cause.
+    pub fn advance_saturating(&self, ms: u64) {
+        let _ = self.now.fetch_update(
+            std::sync::atomic::Ordering::SeqCst,
+            std::sync::atomic::Ordering::SeqCst,
+            |now| Some(now.saturating_add(ms)),
+        );
     }
 
     /// Sets the clock to the specified time in milliseconds.
     pub fn set(&self, ms: u64) {
         self.now.store(ms, std::sync::atomic::Ordering::SeqCst);
     }
+
+    /// Sets the calendar time reported by [`WallClock::system_time`].
+    ///
+    /// The monotonic time is unaffected; both move together from here on.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `time` is before the Unix epoch.
+    pub fn set_system_time(&self, time: std::time::SystemTime) {
+        let since_epoch = time
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("SystemTime before UNIX EPOCH!")
+            .as_millis() as u64;
+        self.wall_offset.store(
+            since_epoch.wrapping_sub(self.now()),
+            std::sync::atomic::Ordering::SeqCst,
+        );
+    }
 }
 
 #[cfg(feature = "std")]
@@ -96,6 +279,165 @@ impl Clock for MockClock {
     }
 }
 
+#[cfg(feature = "std")]
+impl WallClock for MockClock {
+    fn system_time(&self) -> std::time::SystemTime {
+        let offset = self.wall_offset.load(std::sync::atomic::Ordering::SeqCst);
+        std::time::UNIX_EPOCH + Duration::from_millis(offset.wrapping_add(self.now()))
+    }
+}
+
+/// A clock driven by an event counter instead of time.
+///
+/// Nothing about the limiters requires `now()` to measure time: they only need a
+/// monotonic count. A `CounterClock` advances when [`increment`](CounterClock::increment)
+/// is called, so a limiter using it paces admissions against events, for example "one
+/// action per 10 received messages".
+///
+/// Every duration the limiter deals in is then measured in counter units, where one
+/// unit stands for one millisecond: a rate of `tokens_per_second` admits
+/// `tokens_per_second / 1000` tokens per event, and `retry_after_ms` in a rejection is
+/// the number of events to wait for. One action per 10 messages is thus a rate of 100.
+///
+/// Clones share the same counter. Only available with the `std` feature.
+///
+/// ```
+/// use bucketboss::{clock::CounterClock, RateLimiter, TokenBucket};
+///
+/// let messages = CounterClock::new();
+/// let limiter = TokenBucket::with_clock(1, 100.0, messages.clone());
+///
+/// assert!(limiter.try_acquire(1).is_ok());
+/// assert_eq!(limiter.try_acquire(1).unwrap_err().retry_after_ms(), Some(10));
+///
+/// messages.increment_by(10);
+/// assert!(limiter.try_acquire(1).is_ok());
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default)]
+pub struct CounterClock {
+    count: std::sync::Arc<std::sync::atomic::AtomicU64>,
+}
+
+#[cfg(feature = "std")]
+impl CounterClock {
+    /// Creates a new `CounterClock` with a count of 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts one event.
+    pub fn increment(&self) {
+        self.increment_by(1);
+    }
+
+    /// Counts `events` events at once, stopping at `u64::MAX`.
+    pub fn increment_by(&self, events: u64) {
+        let _ = self.count.fetch_update(
+            std::sync::atomic::Ordering::AcqRel,
+            std::sync::atomic::Ordering::Acquire,
+            |count| Some(count.saturating_add(events)),
+        );
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for CounterClock {
+    fn now(&self) -> u64 {
+        self.count.load(std::sync::atomic::Ordering::Acquire)
+    }
+}
+
+/// A clock that caches [`SystemClock`] readings per thread.
+///
+/// Each thread keeps its own copy of the last reading and the [`Instant`] it was taken
+/// at, and only reads the system clock again once `max_staleness` has passed by that
+/// instant. Converting the system time to milliseconds since the Unix epoch costs more
+/// than reading the monotonic clock, so this removes most of the work from tight
+/// single-threaded acquisition loops. There is no locking and no shared state.
+///
+/// # Staleness
+///
+/// A reading is at most `max_staleness` behind the system clock, however long the
+/// thread was idle. Clocks with different bounds share the thread's cache, so a clock
+/// with a tighter bound refreshes it for the others, and each still gets readings
+/// within its own bound. A stale reading only ever lags behind real time, so the
+/// buckets under-refill rather than over-admit, and
+/// [`resolution_ms`](Clock::resolution_ms) reports the bound as the clock's tick.
+/// Readings never go backwards on a given thread.
+///
+/// Only available with the `thread-local-clock` feature.
+///
+/// [`Instant`]: std::time::Instant
+#[cfg(feature = "thread-local-clock")]
+#[derive(Debug, Clone, Copy)]
+pub struct ThreadLocalClock {
+    max_staleness: Duration,
+}
+
+#[cfg(feature = "thread-local-clock")]
+std::thread_local! {
+    /// The thread's last system clock reading and the instant it was taken at.
+    static CACHED_NOW: core::cell::Cell<Option<(u64, std::time::Instant)>> =
+        const { core::cell::Cell::new(None) };
+}
+
+#[cfg(feature = "thread-local-clock")]
+impl ThreadLocalClock {
+    /// The default age after which a cached reading is refreshed.
+    pub const DEFAULT_MAX_STALENESS: Duration = Duration::from_millis(1);
+
+    /// Creates a clock whose readings are at most
+    /// [`DEFAULT_MAX_STALENESS`](Self::DEFAULT_MAX_STALENESS) old.
+    pub fn new() -> Self {
+        Self::with_max_staleness(Self::DEFAULT_MAX_STALENESS)
+    }
+
+    /// Creates a clock that reads the system clock again once its cached reading is
+    /// `max_staleness` old.
+    ///
+    /// A zero duration reads the system clock every time.
+    pub fn with_max_staleness(max_staleness: Duration) -> Self {
+        Self { max_staleness }
+    }
+
+    /// Returns the age after which a cached reading is refreshed.
+    pub fn max_staleness(&self) -> Duration {
+        self.max_staleness
+    }
+}
+
+#[cfg(feature = "thread-local-clock")]
+impl Default for ThreadLocalClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "thread-local-clock")]
+impl Clock for ThreadLocalClock {
+    #[inline]
+    fn now(&self) -> u64 {
+        CACHED_NOW.with(|cache| {
+            let checked = std::time::Instant::now();
+            let cached = cache.get();
+            if let Some((now, taken)) = cached {
+                if checked.duration_since(taken) < self.max_staleness {
+                    return now;
+                }
+            }
+
+            let now = SystemClock.now().max(cached.map_or(0, |(now, _)| now));
+            cache.set(Some((now, checked)));
+            now
+        })
+    }
+
+    fn resolution_ms(&self) -> f64 {
+        (self.max_staleness.as_secs_f64() * 1000.0).max(1.0)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -112,6 +454,15 @@ mod tests {
         assert_eq!(clock.now(), 2000);
     }
 
+    #[test]
+    fn test_mock_clock_advance_saturates() {
+        let clock = MockClock::new(u64::MAX - 10);
+        clock.advance(100);
+        assert_eq!(clock.now(), u64::MAX);
+        clock.advance_saturating(1);
+        assert_eq!(clock.now(), u64::MAX);
+    }
+
     #[test]
     fn test_system_clock() {
         let clock = SystemClock;
@@ -119,4 +470,113 @@ mod tests {
         let t2 = clock.now();
         assert!(t2 >= t1, "System clock should be monotonic");
     }
+
+    #[test]
+    fn test_instant_clock_counts_from_creation() {
+        let clock = InstantClock::new();
+        let copy = clock;
+        assert!(clock.now() < 1000);
+
+        std::thread::sleep(Duration::from_millis(5));
+        let t1 = clock.now();
+        assert!(t1 >= 5);
+        assert!(copy.now() >= t1, "copies share the base instant");
+        assert!(InstantClock::new().now() < t1);
+    }
+
+    #[test]
+    fn test_counter_clock_paces_against_events() {
+        use crate::{RateLimiter, TokenBucket};
+
+        // One action per 10 messages, in bursts of up to 2
+        let messages = CounterClock::new();
+        let limiter = TokenBucket::with_clock(2, 100.0, messages.clone());
+        assert!(limiter.try_acquire(2).is_ok());
+        assert!(limiter.try_acquire(1).is_err());
+
+        // Real time does not refill the bucket
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(limiter.try_acquire(1).is_err());
+
+        for _ in 0..9 {
+            messages.increment();
+            assert!(limiter.try_acquire(1).is_err());
+        }
+        messages.increment();
+        assert_eq!(messages.now(), 10);
+        assert!(limiter.try_acquire(1).is_ok());
+
+        messages.increment_by(25);
+        assert_eq!(limiter.available_tokens(), 2);
+        assert_eq!(limiter.time_until_available_ms(2), Some(0));
+    }
+
+    #[test]
+    #[cfg(feature = "thread-local-clock")]
+    fn test_thread_local_clock_refreshes_by_age() {
+        let clock = ThreadLocalClock::with_max_staleness(Duration::from_millis(50));
+        let first = clock.now();
+        assert_eq!(clock.now(), first);
+
+        // Once the cached reading is too old, the next read refreshes it however few
+        // reads came in between
+        std::thread::sleep(Duration::from_millis(60));
+        let refreshed = clock.now();
+        assert!(refreshed >= first + 60);
+
+        // A tighter bound sharing the thread's cache still gets a fresh reading, which
+        // the looser clock then serves
+        std::thread::sleep(Duration::from_millis(5));
+        let tight = ThreadLocalClock::with_max_staleness(Duration::ZERO);
+        let fresh = tight.now();
+        assert!(fresh >= refreshed + 5);
+        assert_eq!(clock.now(), fresh);
+
+        // Other threads keep their own cache
+        let other = std::thread::spawn(move || clock.now()).join().unwrap();
+        assert!(other >= fresh);
+    }
+
+    #[test]
+    fn test_mock_wall_clock_day_rollover() {
+        use std::time::UNIX_EPOCH;
+
+        const DAY_MS: u64 = 86_400_000;
+        let day = |clock: &MockClock| {
+            clock
+                .system_time()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64
+                / DAY_MS
+        };
+
+        // Monotonic time starts at an arbitrary point unrelated to the calendar
+        let clock = MockClock::new(5_000);
+        assert_eq!(
+            clock.system_time(),
+            UNIX_EPOCH + Duration::from_millis(5_000)
+        );
+
+        // Half a second before midnight on day 20_000
+        let midnight = 20_000 * DAY_MS;
+        clock.set_system_time(UNIX_EPOCH + Duration::from_millis(midnight - 500));
+        assert_eq!(clock.now(), 5_000);
+        assert_eq!(day(&clock), 19_999);
+
+        clock.advance(499);
+        assert_eq!(day(&clock), 19_999);
+        clock.advance(1);
+        assert_eq!(day(&clock), 20_000);
+        assert_eq!(
+            clock.clone().system_time(),
+            UNIX_EPOCH + Duration::from_millis(midnight)
+        );
+    }
+
+    #[test]
+    fn test_system_wall_clock() {
+        let before = std::time::SystemTime::now();
+        assert!(SystemClock.system_time() >= before);
+    }
 }
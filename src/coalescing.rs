@@ -0,0 +1,377 @@
+//! Batching a single producer's acquires within a clock tick.
+//!
+//! The buckets update their state with a compare-and-swap on every acquire, even when
+//! the clock has not moved since the last one. A producer issuing many small acquires
+//! in a tight loop pays for that atomic traffic on every call. [`CoalescingLimiter`]
+//! instead decides the calls within one clock tick locally, against the tokens the inner
+//! limiter had at the start of the tick, and charges them to it in a single
+//! `try_acquire` when the tick ends.
+
+use core::cell::Cell;
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::{RateLimitError, Result},
+    traits::{RateLimiter, Wrapper},
+};
+
+/// A single-producer adapter that coalesces acquires within a clock tick.
+///
+/// The first acquire of each tick reads the inner limiter's available tokens. Later
+/// acquires in the same tick are admitted while that budget lasts, without touching the
+/// inner limiter. The admitted tokens are charged to the inner limiter with one
+/// `try_acquire` when the next tick starts, when [`flush`](Self::flush) is called, or
+/// when the adapter is dropped. A request the budget cannot cover flushes the batch
+/// and is passed to the inner limiter, so rejections carry its exact `retry_after`.
+///
+/// Within a tick, the decisions are those the inner limiter would have made for the
+/// same calls one by one. When the inner limiter was full, charging the batch on the
+/// next tick forfeits up to a tick's worth of refill, which errs on the strict side.
+///
+/// The adapter is deliberately not `Sync`: it is meant for one producer, such as a
+/// single thread or a thread-local.
+///
+/// # Sharing the inner limiter
+///
+/// The inner limiter must not be shared if its limit has to hold at every instant.
+/// Other users of the inner limiter do not see a batch until it is flushed, so they
+/// can be admitted against tokens already promised to it, and together with the batch
+/// go over the limit by up to one tick's batch. The flush then charges what is left
+/// and the adapter owes the rest. It admits nothing more until the debt has been
+/// charged as the inner limiter refills, so the batch is never admitted for free and
+/// the overshoot is paid back over the following refills. A debt still owed when the
+/// adapter is dropped is forgiven.
+#[derive(Debug)]
+pub struct CoalescingLimiter<L, C = SystemClock>
+where
+    L: RateLimiter,
+{
+    /// The inner limiter, only taken out by [`into_inner`](Wrapper::into_inner).
+    inner: Option<L>,
+    clock: C,
+    /// The tick the budget was read at.
+    tick: Cell<u64>,
+    /// The tokens left to admit in the current tick, or `None` if it must be read.
+    budget: Cell<Option<u64>>,
+    /// The tokens admitted but not yet charged to the inner limiter, including any a
+    /// flush could not charge.
+    pending: Cell<u64>,
+}
+
+#[cfg(feature = "std")]
+impl<L> CoalescingLimiter<L, SystemClock>
+where
+    L: RateLimiter,
+{
+    /// Creates a new adapter that coalesces acquires within each millisecond.
+    pub fn new(inner: L) -> Self {
+        Self::with_clock(inner, SystemClock)
+    }
+}
+
+impl<L, C> CoalescingLimiter<L, C>
+where
+    L: RateLimiter,
+    C: Clock,
+{
+    /// Creates a new adapter whose ticks are the milliseconds of `clock`.
+    ///
+    /// This should be the clock of the inner limiter.
+    pub fn with_clock(inner: L, clock: C) -> Self {
+        Self {
+            inner: Some(inner),
+            clock,
+            tick: Cell::new(0),
+            budget: Cell::new(None),
+            pending: Cell::new(0),
+        }
+    }
+
+    /// Attempts to acquire the specified number of tokens.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the tokens were admitted
+    /// * `Err(RateLimitError::RateLimitExceeded)` if the inner limiter rejects them
+    pub fn try_acquire(&self, tokens: u32) -> Result<()> {
+        self.try_acquire_u64(tokens as u64)
+    }
+
+    /// Attempts to acquire a token count wider than `u32`.
+    pub fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        if tokens == 0 {
+            return Ok(());
+        }
+
+        let now = self.clock.now();
+        let budget = match self.budget.get() {
+            Some(budget) if self.tick.get() == now => budget,
+            _ => {
+                self.flush();
+                self.tick.set(now);
+                if self.pending.get() > 0 {
+                    0
+                } else {
+                    self.inner().available_tokens_u64()
+                }
+            }
+        };
+
+        if tokens <= budget {
+            self.budget.set(Some(budget - tokens));
+            self.pending.set(self.pending.get() + tokens);
+            return Ok(());
+        }
+
+        // Let the inner limiter decide, so a rejection carries its exact retry time
+        self.flush();
+        let debt = self.pending.get();
+        if debt > 0 {
+            self.tick.set(now);
+            self.budget.set(Some(0));
+            let retry_after_ms = self
+                .inner()
+                .time_until_available_ms(debt.min(u32::MAX as u64) as u32)
+                .unwrap_or(0);
+            return Err(RateLimitError::rate_limit_exceeded(
+                tokens.min(u32::MAX as u64) as u32,
+                0,
+                retry_after_ms.max(1),
+            ));
+        }
+        let result = self.inner().try_acquire_u64(tokens);
+        if let Err(RateLimitError::RateLimitExceeded { available, .. }) = result {
+            self.tick.set(now);
+            self.budget.set(Some(available as u64));
+        }
+        result
+    }
+}
+
+impl<L, C> CoalescingLimiter<L, C>
+where
+    L: RateLimiter,
+{
+    /// Returns the number of tokens admitted but not yet charged to the inner limiter.
+    ///
+    /// After [`flush`](Self::flush), this is the debt the inner limiter could not
+    /// cover.
+    pub fn pending(&self) -> u64 {
+        self.pending.get()
+    }
+
+    /// Charges the tokens admitted so far to the inner limiter.
+    ///
+    /// If other users of the inner limiter took some of those tokens in the meantime,
+    /// what is available is charged and the rest stays [`pending`](Self::pending) as a
+    /// debt, charged by later flushes. The next acquire reads a fresh budget from the
+    /// inner limiter, or is rejected while a debt remains.
+    pub fn flush(&self) {
+        let owed = self.pending.get();
+        if owed > 0 {
+            let paid = match self.inner().try_acquire_u64(owed) {
+                Ok(()) => owed,
+                Err(RateLimitError::RateLimitExceeded { available, .. }) => {
+                    let available = (available as u64).min(owed);
+                    if available > 0 && self.inner().try_acquire_u64(available).is_ok() {
+                        available
+                    } else {
+                        0
+                    }
+                }
+                // A misconfigured inner limiter cannot be charged at all
+                Err(_) => owed,
+            };
+            self.pending.set(owed - paid);
+        }
+        self.budget.set(None);
+    }
+}
+
+impl<L, C> CoalescingLimiter<L, C>
+where
+    L: RateLimiter,
+{
+    fn inner(&self) -> &L {
+        self.inner
+            .as_ref()
+            .expect("the inner limiter is only taken out when the adapter is consumed")
+    }
+}
+
+impl<L, C> Drop for CoalescingLimiter<L, C>
+where
+    L: RateLimiter,
+{
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            self.flush();
+        }
+    }
+}
+
+/// The state seen through [`get_ref`](Wrapper::get_ref) does not include the tokens of
+/// the current batch until it is flushed. [`get_mut`](Wrapper::get_mut) and
+/// [`into_inner`](Wrapper::into_inner) flush the batch first, so the inner limiter is
+/// handed out with every admitted token charged. A debt it could not cover is
+/// forgiven by `into_inner` and kept by `get_mut`.
+impl<L, C> Wrapper for CoalescingLimiter<L, C>
+where
+    L: RateLimiter,
+{
+    type Inner = L;
+
+    fn get_ref(&self) -> &L {
+        self.inner()
+    }
+
+    fn get_mut(&mut self) -> &mut L {
+        self.flush();
+        self.inner
+            .as_mut()
+            .expect("the inner limiter is only taken out when the adapter is consumed")
+    }
+
+    fn into_inner(mut self) -> L {
+        self.flush();
+        self.inner
+            .take()
+            .expect("the inner limiter is only taken out when the adapter is consumed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, TokenBucket};
+
+    #[test]
+    fn test_batched_decisions_match_individual_acquires() {
+        let clock = MockClock::new(0);
+        let individual = TokenBucket::with_clock(10, 1.0, clock.clone());
+        let limiter = CoalescingLimiter::with_clock(
+            TokenBucket::with_clock(10, 1.0, clock.clone()),
+            clock.clone(),
+        );
+
+        for tick in 0..3 {
+            for tokens in [1, 3, 2, 4, 1, 2] {
+                let expected = individual.try_acquire(tokens);
+                assert_eq!(limiter.try_acquire(tokens), expected, "tick {tick}");
+            }
+            clock.advance(1000);
+        }
+
+        // The batch reaches the inner limiter only once it is flushed
+        assert!(limiter.try_acquire(1).is_ok());
+        assert_eq!(limiter.pending(), 1);
+        assert_eq!(limiter.get_ref().available_tokens(), 1);
+        limiter.flush();
+        assert_eq!(limiter.pending(), 0);
+        assert_eq!(limiter.get_ref().available_tokens(), 0);
+    }
+
+    #[test]
+    fn test_one_inner_update_per_tick() {
+        let clock = MockClock::new(0);
+        let limiter = CoalescingLimiter::with_clock(
+            TokenBucket::with_clock(100, 1.0, clock.clone()),
+            clock.clone(),
+        );
+        let bucket = limiter.get_ref();
+
+        for _ in 0..60 {
+            assert!(limiter.try_acquire(1).is_ok());
+        }
+        assert_eq!(bucket.available_tokens(), 100);
+
+        // The next tick charges the whole batch at once
+        clock.advance(1);
+        assert!(limiter.try_acquire(1).is_ok());
+        assert_eq!(bucket.available_tokens(), 40);
+
+        // Rejections come from the inner limiter
+        let err = limiter.try_acquire(50).unwrap_err();
+        assert_eq!(err, RateLimitError::rate_limit_exceeded(50, 39, 11_000));
+        assert_eq!(limiter.pending(), 0);
+        assert_eq!(bucket.available_tokens(), 39);
+    }
+
+    #[test]
+    fn test_into_inner_charges_the_batch() {
+        let clock = MockClock::new(0);
+        let limiter = CoalescingLimiter::with_clock(
+            TokenBucket::with_clock(10, 1.0, clock.clone()),
+            clock.clone(),
+        );
+        assert!(limiter.try_acquire(4).is_ok());
+        assert_eq!(limiter.get_ref().available_tokens(), 10);
+
+        let bucket = limiter.into_inner();
+        assert_eq!(bucket.available_tokens(), 6);
+    }
+
+    #[test]
+    fn test_shared_inner_limiter_overshoots_by_one_batch() {
+        let clock = MockClock::new(0);
+        let limiter = CoalescingLimiter::with_clock(
+            TokenBucket::with_clock(10, 1.0, clock.clone()),
+            clock.clone(),
+        );
+        let bucket = limiter.get_ref();
+        let mut admitted = 0;
+        for _ in 0..10 {
+            admitted += u32::from(limiter.try_acquire(1).is_ok());
+        }
+
+        // The other user is admitted against the tokens promised to the batch
+        for _ in 0..10 {
+            admitted += u32::from(bucket.try_acquire(1).is_ok());
+        }
+        assert_eq!(admitted, 20);
+
+        // The overshoot is paid back before the adapter admits again
+        limiter.flush();
+        assert_eq!(limiter.pending(), 10);
+        for _ in 0..10 {
+            clock.advance(1000);
+            assert!(limiter.try_acquire(1).is_err());
+        }
+        assert_eq!(limiter.pending(), 0);
+        clock.advance(1000);
+        assert!(limiter.try_acquire(1).is_ok());
+    }
+
+    #[test]
+    fn test_uncharged_batch_is_owed() {
+        let clock = MockClock::new(0);
+        let limiter = CoalescingLimiter::with_clock(
+            TokenBucket::with_clock(10, 1.0, clock.clone()),
+            clock.clone(),
+        );
+        let bucket = limiter.get_ref();
+        for _ in 0..8 {
+            assert!(limiter.try_acquire(1).is_ok());
+        }
+
+        // Another user takes tokens the batch was admitted against
+        assert!(bucket.try_acquire(5).is_ok());
+        limiter.flush();
+        assert_eq!(bucket.available_tokens(), 0);
+        assert_eq!(limiter.pending(), 3);
+
+        // Nothing is admitted until the debt is paid off by the refill
+        clock.advance(2000);
+        let err = limiter.try_acquire(1).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(1000));
+        assert_eq!(limiter.pending(), 1);
+        assert_eq!(bucket.available_tokens(), 0);
+
+        clock.advance(2000);
+        assert!(limiter.try_acquire(1).is_ok());
+        assert_eq!(limiter.pending(), 1);
+        limiter.flush();
+        assert_eq!(limiter.pending(), 0);
+        assert_eq!(bucket.available_tokens(), 0);
+    }
+}
@@ -0,0 +1,405 @@
+//! Combining several limiters into one.
+//!
+//! [`AnyOfLimiter`] admits a request if any of its limiters does, for example to spread
+//! client-side load across backends that each enforce their own rate limit.
+//!
+//! This module is only available with the `std` feature.
+
+use std::sync::Mutex;
+
+use crate::{
+    error::{RateLimitError, Result},
+    outcome::saturate_u32,
+    traits::{RateLimiter, Wrapper},
+};
+
+/// A limiter that admits a request if any of its limiters admits it.
+///
+/// [`try_acquire`](RateLimiter::try_acquire) tries the limiters in order and charges
+/// the first that admits. [`acquire_weighted_rr`](Self::acquire_weighted_rr) instead
+/// picks by weighted round-robin, so that with per-backend limiters the load spreads
+/// in proportion to each backend's rate.
+///
+/// As a [`RateLimiter`], the capacity, available tokens and rate are the sums over all
+/// limiters, and the wait for a token is the shortest wait of any limiter.
+#[derive(Debug)]
+pub struct AnyOfLimiter<L> {
+    limiters: Vec<L>,
+    /// The smooth weighted round-robin score of each limiter.
+    scores: Mutex<Vec<f64>>,
+}
+
+impl<L> AnyOfLimiter<L>
+where
+    L: RateLimiter,
+{
+    /// Creates a limiter admitting requests that any of `limiters` admits.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Self)` if there is at least one limiter
+    /// * `Err(RateLimitError::InvalidConfiguration)` if `limiters` is empty
+    pub fn new(limiters: Vec<L>) -> Result<Self> {
+        if limiters.is_empty() {
+            return Err(RateLimitError::invalid_config(
+                "at least one limiter is required",
+            ));
+        }
+
+        let scores = Mutex::new(vec![0.0; limiters.len()]);
+        Ok(Self { limiters, scores })
+    }
+
+    /// Returns the limiters, in the order they were given.
+    pub fn limiters(&self) -> &[L] {
+        &self.limiters
+    }
+
+    /// Acquires one token from a limiter chosen by weighted round-robin and returns its
+    /// index.
+    ///
+    /// Each limiter is weighted by its [`rate_per_second`](RateLimiter::rate_per_second),
+    /// so over many calls a limiter is chosen in proportion to its rate. The choice is
+    /// smooth: picks of heavily weighted limiters are interleaved with the others
+    /// rather than bunched. A limiter that rejects is skipped for this call without
+    /// losing its place in the rotation.
+    ///
+    /// Returns `None` if every limiter rejected;
+    /// [`time_until_available_ms`](RateLimiter::time_until_available_ms) then reports
+    /// the soonest one could admit.
+    pub fn acquire_weighted_rr(&self) -> Option<usize> {
+        let weights: Vec<f64> = self
+            .limiters
+            .iter()
+            .map(|limiter| limiter.rate_per_second().max(0.0))
+            .collect();
+        let mut scores = self.scores.lock().unwrap();
+        scores.resize(self.limiters.len(), 0.0);
+
+        for (score, weight) in scores.iter_mut().zip(&weights) {
+            *score += weight;
+        }
+        let mut order: Vec<usize> = (0..self.limiters.len()).collect();
+        order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+
+        // The chosen limiter pays back the weight handed out this round, keeping the
+        // scores balanced around zero; those that rejected hand theirs back
+        let mut total: f64 = weights.iter().sum();
+        for index in order {
+            if self.limiters[index].try_acquire(1).is_ok() {
+                scores[index] -= total;
+                return Some(index);
+            }
+            scores[index] -= weights[index];
+            total -= weights[index];
+        }
+        None
+    }
+}
+
+impl<L> RateLimiter for AnyOfLimiter<L>
+where
+    L: RateLimiter,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        AnyOf::acquire_first(&self.limiters, tokens, |limiter| {
+            limiter.try_acquire(tokens)
+        })
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        AnyOf::acquire_first(&self.limiters, saturate_u32(tokens), |limiter| {
+            limiter.try_acquire_u64(tokens)
+        })
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        AnyOf::acquire_first(&self.limiters, tokens, |limiter| {
+            limiter.peek_acquire(tokens)
+        })
+    }
+
+    fn available_tokens(&self) -> u32 {
+        AnyOf(&self.limiters).available_tokens()
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        AnyOf(&self.limiters).available_tokens_u64()
+    }
+
+    fn capacity(&self) -> u32 {
+        AnyOf(&self.limiters).capacity()
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        AnyOf(&self.limiters).capacity_u64()
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        AnyOf(&self.limiters).rate_per_second()
+    }
+
+    fn effective_rate_per_second(&self) -> f64 {
+        AnyOf(&self.limiters).effective_rate_per_second()
+    }
+
+    fn resolution_warning(&self) -> Option<&'static str> {
+        AnyOf(&self.limiters).resolution_warning()
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        AnyOf(&self.limiters).time_until_next_token_ms()
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        AnyOf(&self.limiters).time_until_available_ms(tokens)
+    }
+}
+
+/// A set of limiters admitting a request if any of them does, with the figures
+/// [`AnyOfLimiter`] and [`ShardedLimiter`](crate::ShardedLimiter) report for it.
+///
+/// The capacity, available tokens and rate are the sums over the set, and the wait
+/// for tokens is the shortest wait of any limiter that can hold them.
+pub(crate) struct AnyOf<'a, L>(pub(crate) &'a [L]);
+
+impl<L> AnyOf<'_, L>
+where
+    L: RateLimiter,
+{
+    /// Returns the first admission by `acquire`, trying `limiters` in order, or the
+    /// combined rejection of a request for `tokens` if every one rejects it.
+    ///
+    /// The rejection reports the shortest wait, naming the limiter it comes from.
+    /// Limiters whose capacity is below `tokens` can never admit the request, so their
+    /// wait of 0 is left out; the rejection reports a wait of 0 only if no limiter can
+    /// ever admit it, as for an empty set.
+    pub(crate) fn acquire_first<'b>(
+        limiters: impl IntoIterator<Item = &'b L>,
+        tokens: u32,
+        acquire: impl Fn(&L) -> Result<()>,
+    ) -> Result<()>
+    where
+        L: 'b,
+    {
+        let mut available = 0;
+        let mut retry_after_ms = None;
+        let mut rejected_by = None;
+        for limiter in limiters {
+            match acquire(limiter) {
+                Ok(()) => return Ok(()),
+                Err(RateLimitError::RateLimitExceeded {
+                    available: limiter_available,
+                    retry_after_ms: limiter_retry,
+                    rejected_by: limiter_label,
+                    ..
+                }) => {
+                    available = available.max(limiter_available);
+                    if u64::from(tokens) > limiter.capacity_u64() {
+                        continue;
+                    }
+                    // Name the limiter whose wait is reported
+                    if !matches!(retry_after_ms, Some(retry) if retry <= limiter_retry) {
+                        retry_after_ms = Some(limiter_retry);
+                        rejected_by = limiter_label;
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        let err =
+            RateLimitError::rate_limit_exceeded(tokens, available, retry_after_ms.unwrap_or(0));
+        Err(match rejected_by {
+            Some(label) => err.with_rejected_by(label),
+            None => err,
+        })
+    }
+
+    pub(crate) fn available_tokens(&self) -> u32 {
+        self.0.iter().fold(0u32, |sum, limiter| {
+            sum.saturating_add(limiter.available_tokens())
+        })
+    }
+
+    pub(crate) fn available_tokens_u64(&self) -> u64 {
+        self.0.iter().fold(0u64, |sum, limiter| {
+            sum.saturating_add(limiter.available_tokens_u64())
+        })
+    }
+
+    pub(crate) fn capacity(&self) -> u32 {
+        self.0
+            .iter()
+            .fold(0u32, |sum, limiter| sum.saturating_add(limiter.capacity()))
+    }
+
+    pub(crate) fn capacity_u64(&self) -> u64 {
+        self.0.iter().fold(0u64, |sum, limiter| {
+            sum.saturating_add(limiter.capacity_u64())
+        })
+    }
+
+    pub(crate) fn rate_per_second(&self) -> f64 {
+        self.0.iter().map(|limiter| limiter.rate_per_second()).sum()
+    }
+
+    pub(crate) fn effective_rate_per_second(&self) -> f64 {
+        self.0
+            .iter()
+            .map(|limiter| limiter.effective_rate_per_second())
+            .sum()
+    }
+
+    pub(crate) fn resolution_warning(&self) -> Option<&'static str> {
+        self.0
+            .iter()
+            .find_map(|limiter| limiter.resolution_warning())
+    }
+
+    pub(crate) fn time_until_next_token_ms(&self) -> Option<u64> {
+        if self.0.iter().any(|limiter| limiter.available_tokens() > 0) {
+            return None;
+        }
+        self.0
+            .iter()
+            .filter_map(|limiter| limiter.time_until_next_token_ms())
+            .min()
+    }
+
+    pub(crate) fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.0
+            .iter()
+            .filter_map(|limiter| limiter.time_until_available_ms(tokens))
+            .min()
+    }
+}
+
+/// The wrapped value is the set of limiters. Changing it through
+/// [`get_mut`](Wrapper::get_mut) restarts the weighted round-robin rotation, and an
+/// emptied set admits nothing.
+impl<L> Wrapper for AnyOfLimiter<L> {
+    type Inner = Vec<L>;
+
+    fn get_ref(&self) -> &Vec<L> {
+        &self.limiters
+    }
+
+    fn get_mut(&mut self) -> &mut Vec<L> {
+        self.scores.get_mut().unwrap().clear();
+        &mut self.limiters
+    }
+
+    fn into_inner(self) -> Vec<L> {
+        self.limiters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, LabeledLimiter, TokenBucket};
+
+    #[test]
+    fn test_weighted_rr_follows_rates() {
+        let clock = MockClock::new(0);
+        let limiter = AnyOfLimiter::new(vec![
+            TokenBucket::with_clock(1000, 1.0, clock.clone()),
+            TokenBucket::with_clock(1000, 2.0, clock.clone()),
+            TokenBucket::with_clock(1000, 3.0, clock.clone()),
+        ])
+        .unwrap();
+
+        let picks: Vec<usize> = (0..600)
+            .map(|_| limiter.acquire_weighted_rr().unwrap())
+            .collect();
+        let counts: Vec<usize> = (0..3)
+            .map(|backend| picks.iter().filter(|&&pick| pick == backend).count())
+            .collect();
+        assert_eq!(counts, [100, 200, 300]);
+
+        // Smooth: every round of six picks gives the heaviest backend exactly three
+        assert!(picks
+            .chunks(6)
+            .all(|round| round.iter().filter(|&&pick| pick == 2).count() == 3));
+    }
+
+    #[test]
+    fn test_weighted_rr_skips_exhausted_backends() {
+        let clock = MockClock::new(0);
+        let limiter = AnyOfLimiter::new(vec![
+            TokenBucket::with_clock(1, 1.0, clock.clone()),
+            TokenBucket::with_clock(2, 4.0, clock.clone()),
+        ])
+        .unwrap();
+
+        let mut picks: Vec<_> = (0..3)
+            .filter_map(|_| limiter.acquire_weighted_rr())
+            .collect();
+        picks.sort_unstable();
+        assert_eq!(picks, [0, 1, 1]);
+        assert_eq!(limiter.acquire_weighted_rr(), None);
+        assert_eq!(limiter.time_until_available_ms(1), Some(250));
+
+        let err = limiter.try_acquire(1).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(250));
+        clock.advance(250);
+        assert_eq!(limiter.acquire_weighted_rr(), Some(1));
+    }
+
+    #[test]
+    fn test_rejection_names_the_limiter_with_the_shortest_wait() {
+        let clock = MockClock::new(0);
+        let limiter = AnyOfLimiter::new(vec![
+            LabeledLimiter::new(TokenBucket::with_clock(1, 1.0, clock.clone()), "slow"),
+            LabeledLimiter::new(TokenBucket::with_clock(1, 4.0, clock.clone()), "fast"),
+        ])
+        .unwrap();
+        assert!(limiter.try_acquire(1).is_ok());
+        assert!(limiter.try_acquire(1).is_ok());
+
+        let err = limiter.try_acquire(1).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(250));
+        assert_eq!(err.rejected_by(), Some("fast"));
+    }
+
+    #[test]
+    fn test_too_small_limiter_does_not_hide_a_wait() {
+        let clock = MockClock::new(0);
+        let limiter = AnyOfLimiter::new(vec![
+            LabeledLimiter::new(TokenBucket::with_clock(1, 1.0, clock.clone()), "small"),
+            LabeledLimiter::new(TokenBucket::with_clock(4, 2.0, clock.clone()), "large"),
+        ])
+        .unwrap();
+        assert!(limiter.try_acquire(4).is_ok());
+
+        // Only the large limiter can ever hold 2 tokens
+        let err = limiter.try_acquire(2).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(1000));
+        assert_eq!(err.rejected_by(), Some("large"));
+        assert_eq!(limiter.time_until_available_ms(2), Some(1000));
+
+        // No limiter can hold 5
+        let err = limiter.try_acquire(5).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(0));
+        assert_eq!(limiter.time_until_available_ms(5), None);
+    }
+
+    #[test]
+    fn test_requires_a_limiter() {
+        assert!(AnyOfLimiter::<TokenBucket>::new(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_emptied_set_never_admits() {
+        let mut limiter =
+            AnyOfLimiter::new(vec![TokenBucket::with_clock(1, 1.0, MockClock::new(0))]).unwrap();
+        limiter.get_mut().clear();
+
+        // A wait of 0 tells the caller that retrying cannot help
+        let err = limiter.try_acquire(1).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(0));
+        assert_eq!(limiter.capacity(), 0);
+        assert_eq!(limiter.time_until_available_ms(1), None);
+    }
+}
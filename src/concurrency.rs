@@ -0,0 +1,179 @@
+//! Combined rate and concurrency limiting.
+//!
+//! A common policy caps both how often work starts and how much of it runs at once
+//! ("10 requests per second, at most 3 in flight"). [`RateAndConcurrencyLimiter`]
+//! enforces both in one call: a successful acquisition consumes tokens from the inner
+//! rate limiter and occupies a concurrency slot, returned as a [`Permit`] that frees the
+//! slot when dropped.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{
+    error::{RateLimitError, Result},
+    traits::{RateLimiter, Wrapper},
+};
+
+/// A limiter that enforces a rate limit and a cap on requests in flight.
+///
+/// The two limits bind independently: a request needs both a free slot and enough
+/// tokens. Tokens are only consumed when a slot is available, and a slot is only held
+/// when the tokens were granted.
+#[derive(Debug)]
+pub struct RateAndConcurrencyLimiter<L> {
+    inner: L,
+    max_in_flight: u32,
+    in_flight: AtomicU32,
+}
+
+/// A held concurrency slot, released when dropped.
+#[derive(Debug)]
+#[must_use = "the concurrency slot is released as soon as the permit is dropped"]
+pub struct Permit<'a> {
+    in_flight: &'a AtomicU32,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let _ = self.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl<L> RateAndConcurrencyLimiter<L>
+where
+    L: RateLimiter,
+{
+    /// Creates a new limiter allowing at most `max_in_flight` outstanding permits.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Self)` if `max_in_flight` is at least 1
+    /// * `Err(RateLimitError::InvalidConfiguration)` if `max_in_flight` is 0
+    pub fn new(inner: L, max_in_flight: u32) -> Result<Self> {
+        if max_in_flight == 0 {
+            return Err(RateLimitError::invalid_config(
+                "max_in_flight must be greater than 0",
+            ));
+        }
+
+        Ok(Self {
+            inner,
+            max_in_flight,
+            in_flight: AtomicU32::new(0),
+        })
+    }
+
+    /// Attempts to acquire `tokens` from the rate limiter and one concurrency slot.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Permit)` holding the slot until it is dropped
+    /// * `Err(RateLimitError::RateLimitExceeded)` if either limit would be exceeded.
+    ///   When every slot is taken, `available` is 0 and `retry_after_ms` is 0, since
+    ///   a slot frees as soon as another permit is dropped.
+    pub fn try_acquire_permit(&self, tokens: u32) -> Result<Permit<'_>> {
+        let reserved = self
+            .in_flight
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |in_flight| {
+                (in_flight < self.max_in_flight).then_some(in_flight + 1)
+            })
+            .is_ok();
+        if !reserved {
+            return Err(RateLimitError::rate_limit_exceeded(tokens, 0, 0));
+        }
+
+        // The permit releases the slot again if the rate limit rejects the request
+        let permit = Permit {
+            in_flight: &self.in_flight,
+        };
+        self.inner.try_acquire(tokens)?;
+        Ok(permit)
+    }
+
+    /// Returns the number of permits currently held.
+    pub fn in_flight(&self) -> u32 {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    /// Returns the maximum number of permits that can be held at once.
+    pub fn max_in_flight(&self) -> u32 {
+        self.max_in_flight
+    }
+
+    /// Returns the number of tokens currently available from the rate limiter.
+    pub fn available_tokens(&self) -> u32 {
+        self.inner.available_tokens()
+    }
+}
+
+impl<L> Wrapper for RateAndConcurrencyLimiter<L> {
+    type Inner = L;
+
+    fn get_ref(&self) -> &L {
+        &self.inner
+    }
+
+    fn get_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    fn into_inner(self) -> L {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, TokenBucket};
+
+    #[test]
+    fn test_concurrency_cap_binds() {
+        let clock = MockClock::new(0);
+        let limiter =
+            RateAndConcurrencyLimiter::new(TokenBucket::with_clock(10, 10.0, clock), 3).unwrap();
+
+        let permits: Vec<_> = (0..3)
+            .map(|_| limiter.try_acquire_permit(1).unwrap())
+            .collect();
+        assert_eq!(limiter.in_flight(), 3);
+
+        // Plenty of tokens left, but no free slot; no tokens are consumed
+        let err = limiter.try_acquire_permit(1).unwrap_err();
+        assert_eq!(err, RateLimitError::rate_limit_exceeded(1, 0, 0));
+        assert_eq!(limiter.available_tokens(), 7);
+
+        drop(permits);
+        assert_eq!(limiter.in_flight(), 0);
+        assert!(limiter.try_acquire_permit(1).is_ok());
+    }
+
+    #[test]
+    fn test_rate_cap_binds() {
+        let clock = MockClock::new(0);
+        let limiter =
+            RateAndConcurrencyLimiter::new(TokenBucket::with_clock(2, 1.0, clock.clone()), 3)
+                .unwrap();
+
+        // Each permit is dropped immediately, so only the rate limit applies
+        assert!(limiter.try_acquire_permit(1).is_ok());
+        assert!(limiter.try_acquire_permit(1).is_ok());
+        let err = limiter.try_acquire_permit(1).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(1000));
+
+        // A rate rejection does not hold on to the slot
+        assert_eq!(limiter.in_flight(), 0);
+
+        clock.advance(1000);
+        let permit = limiter.try_acquire_permit(1).unwrap();
+        assert_eq!(limiter.in_flight(), 1);
+        drop(permit);
+    }
+
+    #[test]
+    fn test_zero_slots_rejected() {
+        let bucket = TokenBucket::with_clock(1, 1.0, MockClock::new(0));
+        assert!(RateAndConcurrencyLimiter::new(bucket, 0)
+            .unwrap_err()
+            .is_invalid_config());
+    }
+}
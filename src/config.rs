@@ -0,0 +1,330 @@
+//! Limiter configurations that can be stored and rebuilt.
+//!
+//! [`BucketState`](crate::BucketState) persists a bucket's runtime state.
+//! [`SerializableLimiterConfig`] persists only what the limiter *is*: its algorithm,
+//! limits and label. A service can keep policy definitions in a database or a config
+//! file and instantiate fresh limiters from them at runtime:
+//!
+//! ```json
+//! {"algorithm":"token_bucket","capacity":10,"tokens_per_second":5.0,"label":"search"}
+//! {"algorithm":"sliding_window_counter","limit":100,"window_ms":60000}
+//! ```
+//!
+//! This module is only available with the `serde` and `std` features.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::{RateLimitError, Result},
+    gcra::{Gcra, MAX_GCRA_RATE},
+    interval,
+    labeled::LabeledLimiter,
+    leaky_bucket::LeakyBucket,
+    sliding_counter::SlidingWindowCounter,
+    token_bucket::TokenBucket,
+    traits::RateLimiter,
+    validate,
+};
+
+#[cfg(feature = "alloc")]
+use crate::sliding_log::SlidingWindowLog;
+
+/// The configuration of a limiter, tagged by its algorithm.
+///
+/// Each variant holds the arguments of the corresponding constructor, plus an optional
+/// label. A labelled configuration builds a [`LabeledLimiter`], which names the limiter
+/// as the `rejected_by` label of its rejections.
+///
+/// # Examples
+///
+/// ```
+/// use bucketboss::SerializableLimiterConfig;
+///
+/// let json = r#"{"algorithm":"token_bucket","capacity":2,"tokens_per_second":1.0,"label":"login"}"#;
+/// let config: SerializableLimiterConfig = serde_json::from_str(json)?;
+///
+/// let limiter = config.build()?;
+/// assert!(limiter.try_acquire(2).is_ok());
+/// assert_eq!(limiter.try_acquire(1).unwrap_err().rejected_by(), Some("login"));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "algorithm", rename_all = "snake_case")]
+pub enum SerializableLimiterConfig {
+    /// A [`TokenBucket`].
+    TokenBucket {
+        /// The maximum number of tokens the bucket can hold.
+        capacity: u32,
+        /// The rate at which tokens are replenished, in tokens per second.
+        tokens_per_second: f64,
+        /// The label attached to rejections, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+    },
+    /// A [`LeakyBucket`].
+    LeakyBucket {
+        /// The rate at which requests drain, in requests per second.
+        requests_per_second: f64,
+        /// The number of requests that can queue up, or `None` for one.
+        #[serde(default)]
+        burst_size: Option<u32>,
+        /// The label attached to rejections, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+    },
+    /// A [`Gcra`].
+    Gcra {
+        /// The rate at which tokens are replenished, in tokens per second.
+        tokens_per_second: f64,
+        /// The largest number of tokens admitted at once.
+        burst: u32,
+        /// The label attached to rejections, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+    },
+    /// A [`SlidingWindowCounter`].
+    SlidingWindowCounter {
+        /// The number of tokens admitted per window.
+        limit: u32,
+        /// The length of the window, in milliseconds.
+        window_ms: u64,
+        /// The label attached to rejections, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+    },
+    /// A [`SlidingWindowLog`].
+    ///
+    /// Only available with the `alloc` feature.
+    #[cfg(feature = "alloc")]
+    SlidingWindowLog {
+        /// The number of tokens admitted per window.
+        limit: u32,
+        /// The length of the window, in milliseconds.
+        window_ms: u64,
+        /// The label attached to rejections, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+    },
+}
+
+impl SerializableLimiterConfig {
+    /// Returns the label attached to rejections, if any.
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            Self::TokenBucket { label, .. }
+            | Self::LeakyBucket { label, .. }
+            | Self::Gcra { label, .. }
+            | Self::SlidingWindowCounter { label, .. } => label.as_deref(),
+            #[cfg(feature = "alloc")]
+            Self::SlidingWindowLog { label, .. } => label.as_deref(),
+        }
+    }
+
+    /// Builds a fresh limiter from the configuration.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(limiter)` if the configuration is valid
+    /// * `Err(RateLimitError::InvalidConfiguration)` if a capacity, burst, limit or
+    ///   window is 0, or a rate is not positive and finite or exceeds
+    ///   [`MAX_RATE`](crate::MAX_RATE)
+    pub fn build(&self) -> Result<Box<dyn RateLimiter>> {
+        self.build_with_clock(SystemClock)
+    }
+
+    /// Builds a fresh limiter from the configuration with the specified clock.
+    ///
+    /// # Returns
+    ///
+    /// The same as [`build`](Self::build).
+    pub fn build_with_clock<C>(&self, clock: C) -> Result<Box<dyn RateLimiter>>
+    where
+        C: Clock,
+    {
+        let limiter: Box<dyn RateLimiter> = match *self {
+            Self::TokenBucket {
+                capacity,
+                tokens_per_second,
+                ..
+            } => {
+                non_zero(capacity as u64, "capacity must be greater than 0")?;
+                validate::try_rate(tokens_per_second, "tokens_per_second must be positive")?;
+                interval::try_rate(tokens_per_second)?;
+                self.labeled(TokenBucket::with_clock(capacity, tokens_per_second, clock))
+            }
+            Self::LeakyBucket {
+                requests_per_second,
+                burst_size,
+                ..
+            } => self.labeled(LeakyBucket::try_with_clock(
+                requests_per_second,
+                burst_size,
+                clock,
+            )?),
+            Self::Gcra {
+                tokens_per_second,
+                burst,
+                ..
+            } => {
+                validate::try_rate(tokens_per_second, "tokens_per_second must be positive")?;
+                validate::try_rate_at_most(
+                    tokens_per_second,
+                    MAX_GCRA_RATE,
+                    "tokens_per_second must not exceed MAX_GCRA_RATE",
+                )?;
+                non_zero(burst as u64, "burst must be greater than 0")?;
+                self.labeled(Gcra::with_clock(tokens_per_second, burst, clock))
+            }
+            Self::SlidingWindowCounter {
+                limit, window_ms, ..
+            } => {
+                non_zero(limit as u64, "limit must be greater than 0")?;
+                non_zero(window_ms, "window_ms must be greater than 0")?;
+                self.labeled(SlidingWindowCounter::with_clock(limit, window_ms, clock))
+            }
+            #[cfg(feature = "alloc")]
+            Self::SlidingWindowLog {
+                limit, window_ms, ..
+            } => {
+                non_zero(limit as u64, "limit must be greater than 0")?;
+                non_zero(window_ms, "window_ms must be greater than 0")?;
+                self.labeled(SlidingWindowLog::with_clock(limit, window_ms, clock))
+            }
+        };
+        Ok(limiter)
+    }
+
+    /// Boxes `limiter`, wrapped in a [`LabeledLimiter`] if the configuration has a label.
+    fn labeled<L>(&self, limiter: L) -> Box<dyn RateLimiter>
+    where
+        L: RateLimiter,
+    {
+        match self.label() {
+            Some(label) => Box::new(LabeledLimiter::new(limiter, label.to_owned())),
+            None => Box::new(limiter),
+        }
+    }
+}
+
+/// Checks that a configured count is non-zero.
+fn non_zero(value: u64, message: &'static str) -> Result<()> {
+    if value == 0 {
+        return Err(RateLimitError::invalid_config(message));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    /// Round-trips `config` through JSON and builds a limiter from the result.
+    fn round_trip(config: &SerializableLimiterConfig, clock: &MockClock) -> Box<dyn RateLimiter> {
+        let json = serde_json::to_string(config).unwrap();
+        let decoded: SerializableLimiterConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(&decoded, config);
+        decoded.build_with_clock(clock.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_configs_round_trip_and_build() {
+        let clock = MockClock::new(0);
+        let configs = [
+            SerializableLimiterConfig::TokenBucket {
+                capacity: 4,
+                tokens_per_second: 4.0,
+                label: None,
+            },
+            SerializableLimiterConfig::LeakyBucket {
+                requests_per_second: 4.0,
+                burst_size: Some(4),
+                label: None,
+            },
+            SerializableLimiterConfig::Gcra {
+                tokens_per_second: 4.0,
+                burst: 4,
+                label: None,
+            },
+            SerializableLimiterConfig::SlidingWindowCounter {
+                limit: 4,
+                window_ms: 1000,
+                label: None,
+            },
+            #[cfg(feature = "alloc")]
+            SerializableLimiterConfig::SlidingWindowLog {
+                limit: 4,
+                window_ms: 1000,
+                label: None,
+            },
+        ];
+
+        // Every algorithm admits its burst of 4 and then rejects
+        for config in &configs {
+            let limiter = round_trip(config, &clock);
+            assert!(limiter.try_acquire(4).is_ok(), "{config:?}");
+            assert!(limiter.try_acquire(1).is_err(), "{config:?}");
+        }
+    }
+
+    #[test]
+    fn test_serialized_form() {
+        let config = SerializableLimiterConfig::TokenBucket {
+            capacity: 10,
+            tokens_per_second: 5.0,
+            label: Some("search".into()),
+        };
+        assert_eq!(
+            serde_json::to_string(&config).unwrap(),
+            r#"{"algorithm":"token_bucket","capacity":10,"tokens_per_second":5.0,"label":"search"}"#
+        );
+
+        // The label and the leaky bucket's burst may be left out
+        let json = r#"{"algorithm":"leaky_bucket","requests_per_second":2.0}"#;
+        let config: SerializableLimiterConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            config,
+            SerializableLimiterConfig::LeakyBucket {
+                requests_per_second: 2.0,
+                burst_size: None,
+                label: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_label_attributes_rejections() {
+        let config = SerializableLimiterConfig::Gcra {
+            tokens_per_second: 1.0,
+            burst: 1,
+            label: Some("per-user".into()),
+        };
+        let limiter = round_trip(&config, &MockClock::new(0));
+        assert!(limiter.try_acquire(1).is_ok());
+        assert_eq!(
+            limiter.try_acquire(1).unwrap_err().rejected_by(),
+            Some("per-user")
+        );
+    }
+
+    #[test]
+    fn test_invalid_configs_are_rejected() {
+        let configs = [
+            r#"{"algorithm":"token_bucket","capacity":0,"tokens_per_second":1.0}"#,
+            r#"{"algorithm":"token_bucket","capacity":1,"tokens_per_second":-1.0}"#,
+            r#"{"algorithm":"leaky_bucket","requests_per_second":0.0}"#,
+            r#"{"algorithm":"gcra","tokens_per_second":1.0,"burst":0}"#,
+            r#"{"algorithm":"gcra","tokens_per_second":2e9,"burst":1}"#,
+            r#"{"algorithm":"sliding_window_counter","limit":1,"window_ms":0}"#,
+        ];
+        for json in configs {
+            let config: SerializableLimiterConfig = serde_json::from_str(json).unwrap();
+            let err = config.build().err().unwrap();
+            assert!(err.is_invalid_config(), "{json}");
+        }
+        assert!(
+            serde_json::from_str::<SerializableLimiterConfig>(r#"{"algorithm":"magic"}"#).is_err()
+        );
+    }
+}
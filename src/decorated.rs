@@ -0,0 +1,403 @@
+//! Attaching decorators to a limiter while building it.
+//!
+//! Production limiters tend to collect decorators: a label for attributing rejections,
+//! jitter so rejected clients do not retry in lockstep, an observer for metrics.
+//! Nesting a wrapper for each gets unwieldy, so [`DecoratedBuilder`] collects them in
+//! the same fluent chain as the limiter's own settings:
+//!
+//! ```
+//! use std::time::Duration;
+//! use bucketboss::{AdmissionCounters, LeakyBucket, RateLimiter, RateLimiterBuilder};
+//!
+//! let limiter = LeakyBucket::builder()
+//!     .capacity(5)
+//!     .tokens_per_second(10.0)
+//!     .label("per-client")
+//!     .jitter(Duration::from_millis(50))
+//!     .observer(AdmissionCounters::new())
+//!     .build()
+//!     .unwrap();
+//!
+//! assert!(limiter.try_acquire(5).is_ok());
+//! assert_eq!(limiter.try_acquire(1).unwrap_err().rejected_by(), Some("per-client"));
+//! assert_eq!(limiter.observer().stats().rejected, 1);
+//! ```
+//!
+//! This module is only available with the `std` feature.
+
+use core::{fmt, time::Duration};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{
+    error::{RateLimitError, Result},
+    observer::Observer,
+    traits::{RateLimiter, RateLimiterBuilder, ReconfigurableRateLimiter, Wrapper},
+};
+
+/// A builder that attaches decorators to the limiter built by `B`.
+///
+/// Obtained from the decorator methods of [`RateLimiterBuilder`], such as
+/// [`label`](RateLimiterBuilder::label). The limiter's own settings, like the capacity
+/// and rate, can still be set afterwards; settings specific to one algorithm, such as
+/// its clock, must be set on its builder before the first decorator.
+pub struct DecoratedBuilder<B, O = ()> {
+    inner: B,
+    label: Option<&'static str>,
+    jitter_ms: u64,
+    observer: O,
+}
+
+impl<B> DecoratedBuilder<B>
+where
+    B: RateLimiterBuilder,
+{
+    /// Wraps `inner` without any decorators.
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            label: None,
+            jitter_ms: 0,
+            observer: (),
+        }
+    }
+}
+
+impl<B, O> DecoratedBuilder<B, O>
+where
+    B: RateLimiterBuilder,
+    O: Observer,
+{
+    /// Attributes rejections to `label`, as [`LabeledLimiter`](crate::LabeledLimiter)
+    /// does.
+    pub fn label(mut self, label: &'static str) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    /// Adds a random delay of up to `max` to the `retry_after` of every rejection.
+    pub fn jitter(mut self, max: Duration) -> Self {
+        self.jitter_ms = max.as_millis().min(u64::MAX as u128) as u64;
+        self
+    }
+
+    /// Calls `observer` with the result of every acquire, replacing any observer set
+    /// before.
+    pub fn observer<P>(self, observer: P) -> DecoratedBuilder<B, P>
+    where
+        P: Observer,
+    {
+        DecoratedBuilder {
+            inner: self.inner,
+            label: self.label,
+            jitter_ms: self.jitter_ms,
+            observer,
+        }
+    }
+}
+
+impl<B, O> RateLimiterBuilder for DecoratedBuilder<B, O>
+where
+    B: RateLimiterBuilder,
+    O: Observer,
+{
+    type Limiter = Decorated<B::Limiter, O>;
+
+    fn capacity(mut self, capacity: u32) -> Self {
+        self.inner = self.inner.capacity(capacity);
+        self
+    }
+
+    fn tokens_per_second(mut self, tokens_per_second: f64) -> Self {
+        self.inner = self.inner.tokens_per_second(tokens_per_second);
+        self
+    }
+
+    fn build(self) -> Result<Decorated<B::Limiter, O>> {
+        Ok(Decorated {
+            inner: self.inner.build()?,
+            label: self.label,
+            jitter_ms: self.jitter_ms,
+            jitter_state: AtomicU64::new(RandomState::new().build_hasher().finish()),
+            observer: self.observer,
+        })
+    }
+}
+
+impl<B, O> fmt::Debug for DecoratedBuilder<B, O>
+where
+    B: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DecoratedBuilder")
+            .field("inner", &self.inner)
+            .field("label", &self.label)
+            .field("jitter_ms", &self.jitter_ms)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A limiter built by a [`DecoratedBuilder`].
+///
+/// The decorators apply to each result in a fixed order, innermost first:
+///
+/// 1. the label is attached to a rejection that does not name a limiter yet;
+/// 2. jitter is added to the rejection's `retry_after`;
+/// 3. the observer sees the result exactly as the caller will.
+///
+/// Queries such as `available_tokens` are answered by the inner limiter.
+pub struct Decorated<L, O = ()> {
+    inner: L,
+    label: Option<&'static str>,
+    jitter_ms: u64,
+    /// The state of the generator drawing jitter.
+    jitter_state: AtomicU64,
+    observer: O,
+}
+
+impl<L, O> Decorated<L, O>
+where
+    O: Observer,
+{
+    /// Returns the label attached to rejections, if any.
+    pub fn label(&self) -> Option<&'static str> {
+        self.label
+    }
+
+    /// Returns the largest jitter added to retry times.
+    pub fn jitter(&self) -> Duration {
+        Duration::from_millis(self.jitter_ms)
+    }
+
+    /// Returns the observer of the limiter's results.
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    /// Names the label, if any, as the rejecting limiter when the inner one did not.
+    fn attribute(&self, result: Result<()>) -> Result<()> {
+        result.map_err(|err| match (self.label, &err) {
+            (
+                Some(label),
+                RateLimitError::RateLimitExceeded {
+                    rejected_by: None, ..
+                },
+            ) => err.with_rejected_by(label),
+            _ => err,
+        })
+    }
+
+    /// Applies the decorators to the result of acquiring `tokens`.
+    fn decorate(&self, tokens: u64, result: Result<()>) -> Result<()> {
+        let result = self.attribute(result).map_err(|mut err| {
+            if let RateLimitError::RateLimitExceeded { retry_after_ms, .. } = &mut err {
+                *retry_after_ms = retry_after_ms.saturating_add(self.next_jitter());
+            }
+            err
+        });
+        self.observer.on_acquire(tokens, result.is_ok());
+        if let Err(RateLimitError::RateLimitExceeded { retry_after_ms, .. }) = &result {
+            self.observer.on_reject(tokens, *retry_after_ms);
+        }
+        result
+    }
+
+    /// Draws a jitter in `0..=jitter_ms` with a SplitMix64 step.
+    fn next_jitter(&self) -> u64 {
+        if self.jitter_ms == 0 {
+            return 0;
+        }
+        let mut z = self
+            .jitter_state
+            .fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed)
+            .wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        z % self.jitter_ms.saturating_add(1)
+    }
+}
+
+impl<L, O> RateLimiter for Decorated<L, O>
+where
+    L: RateLimiter,
+    O: Observer,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        self.decorate(tokens as u64, self.inner.try_acquire(tokens))
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        self.decorate(tokens, self.inner.try_acquire_u64(tokens))
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        self.attribute(self.inner.peek_acquire(tokens))
+    }
+
+    fn available_tokens(&self) -> u32 {
+        self.inner.available_tokens()
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        self.inner.available_tokens_u64()
+    }
+
+    fn capacity(&self) -> u32 {
+        self.inner.capacity()
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.inner.capacity_u64()
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        self.inner.rate_per_second()
+    }
+
+    fn effective_rate_per_second(&self) -> f64 {
+        self.inner.effective_rate_per_second()
+    }
+
+    fn resolution_warning(&self) -> Option<&'static str> {
+        self.inner.resolution_warning()
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        self.inner.time_until_next_token_ms()
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.inner.time_until_available_ms(tokens)
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        self.inner.retry_at(tokens)
+    }
+}
+
+impl<L, O> ReconfigurableRateLimiter for Decorated<L, O>
+where
+    L: ReconfigurableRateLimiter,
+    O: Observer,
+{
+    fn update_config(&self, capacity: u32, tokens_per_second: f64) -> Result<()> {
+        self.inner.update_config(capacity, tokens_per_second)
+    }
+
+    fn set_rate_only(&self, tokens_per_second: f64) -> Result<()> {
+        self.inner.set_rate_only(tokens_per_second)
+    }
+}
+
+impl<L, O> Wrapper for Decorated<L, O> {
+    type Inner = L;
+
+    fn get_ref(&self) -> &L {
+        &self.inner
+    }
+
+    fn get_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    fn into_inner(self) -> L {
+        self.inner
+    }
+}
+
+impl<L, O> fmt::Debug for Decorated<L, O>
+where
+    L: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Decorated")
+            .field("inner", &self.inner)
+            .field("label", &self.label)
+            .field("jitter_ms", &self.jitter_ms)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{clock::MockClock, LeakyBucket};
+
+    /// Records every call, as `(tokens, granted, retry_after_ms)`.
+    #[derive(Debug, Default)]
+    struct Recorder(Mutex<Vec<(u64, bool, Option<u64>)>>);
+
+    impl Observer for Recorder {
+        fn on_acquire(&self, tokens: u64, granted: bool) {
+            self.0.lock().unwrap().push((tokens, granted, None));
+        }
+
+        fn on_reject(&self, tokens: u64, retry_after_ms: u64) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((tokens, false, Some(retry_after_ms)));
+        }
+    }
+
+    #[test]
+    fn test_built_limiter_has_every_decorator() {
+        let limiter = LeakyBucket::builder()
+            .clock(MockClock::new(0))
+            .label("per-client")
+            .jitter(Duration::from_millis(100))
+            .observer(Recorder::default())
+            .capacity(2)
+            .tokens_per_second(1.0)
+            .build()
+            .unwrap();
+        assert_eq!(limiter.label(), Some("per-client"));
+        assert_eq!(limiter.capacity(), 2);
+
+        assert!(limiter.try_acquire(2).is_ok());
+        let retries: Vec<u64> = (0..50)
+            .map(|_| {
+                let err = limiter.try_acquire(1).unwrap_err();
+                assert_eq!(err.rejected_by(), Some("per-client"));
+                err.retry_after_ms().unwrap()
+            })
+            .collect();
+
+        // Jittered: within the bounds, but not all the same
+        assert!(retries.iter().all(|&retry| (1000..=1100).contains(&retry)));
+        assert!(retries.iter().any(|&retry| retry != retries[0]));
+
+        // Observed after the other decorators, exactly as returned
+        let observed = limiter.observer().0.lock().unwrap();
+        assert_eq!(observed.len(), 101);
+        assert_eq!(observed[0], (2, true, None));
+        assert_eq!(observed[1], (1, false, None));
+        assert_eq!(observed[2], (1, false, Some(retries[0])));
+    }
+
+    #[test]
+    fn test_undecorated_settings_pass_through() {
+        let limiter = DecoratedBuilder::new(LeakyBucket::builder().clock(MockClock::new(0)))
+            .capacity(0)
+            .build();
+        assert!(limiter.unwrap_err().is_invalid_config());
+
+        let limiter = LeakyBucket::builder()
+            .clock(MockClock::new(0))
+            .label("inner")
+            .build()
+            .unwrap();
+        assert!(limiter.try_acquire(1).is_ok());
+        assert_eq!(
+            limiter.try_acquire(1).unwrap_err().retry_after_ms(),
+            Some(1000)
+        );
+        assert_eq!(limiter.jitter(), Duration::ZERO);
+    }
+}
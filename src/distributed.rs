@@ -0,0 +1,664 @@
+//! Distributed rate limiting backed by Redis.
+//!
+//! [`RedisTokenBucket`] keeps a token bucket in Redis, so every process using the same
+//! key shares one limit. Each decision runs a Lua script on the server, which refills
+//! and takes tokens in one atomic step, so concurrent processes never both spend the
+//! same token.
+//!
+//! # Key schema
+//!
+//! The bucket lives in a hash at the configured key, with two fields:
+//!
+//! * `tokens` - the tokens left after the last decision, as a decimal string that may
+//!   have a fractional part
+//! * `ts` - the time of the last decision, in milliseconds since the Unix epoch
+//!
+//! A missing key is a full bucket. Every decision sets the key to expire once the
+//! bucket would have refilled completely, plus one second, so idle buckets do not
+//! accumulate in Redis. Keys are used as given; prefix them to namespace limits, as in
+//! `ratelimit:{user_id}`.
+//!
+//! The time of each decision comes from the calling process's clock, so the processes
+//! sharing a key should keep their clocks in sync. A process whose clock lags behind
+//! the last decision sees no refill until it catches up rather than taking tokens back.
+//!
+//! # Failures
+//!
+//! When Redis cannot be reached, or a round trip outlives the caller's
+//! [`Deadline`], no shared decision is made. The bucket's [`FailureMode`] decides what
+//! happens instead: by default the request fails with the Redis error.
+//!
+//! This module is only available with the `redis` feature.
+
+use core::{fmt, time::Duration};
+use std::{boxed::Box, error, io, string::String};
+
+use redis::{aio::ConnectionManager, RedisError, Script};
+use tokio::time::Instant;
+
+use crate::{
+    asynchronous::Deadline,
+    clock::{Clock, SystemClock},
+    error::RateLimitError,
+    traits::RateLimiter,
+    validate,
+};
+
+/// Refills and takes tokens in one step, returning
+/// `{admitted, whole tokens left, retry_after_ms}`.
+const TOKEN_BUCKET_SCRIPT: &str = r#"
+local capacity = tonumber(ARGV[1])
+local rate = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+local requested = tonumber(ARGV[4])
+
+local state = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(state[1])
+local ts = tonumber(state[2])
+if tokens == nil or ts == nil then
+    tokens = capacity
+    ts = now
+end
+if now > ts then
+    tokens = math.min(capacity, tokens + (now - ts) * rate / 1000)
+    ts = now
+end
+
+local admitted = 0
+local retry_after_ms = 0
+if requested <= tokens then
+    tokens = tokens - requested
+    admitted = 1
+elseif requested <= capacity then
+    retry_after_ms = math.ceil((requested - tokens) * 1000 / rate)
+end
+
+redis.call('HSET', KEYS[1], 'tokens', string.format('%.17g', tokens), 'ts', ts)
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity * 1000 / rate) + 1000)
+return {admitted, math.floor(tokens), retry_after_ms}
+"#;
+
+/// The error returned by [`RedisTokenBucket`].
+#[derive(Debug)]
+pub enum RedisLimitError {
+    /// The bucket rejected the request.
+    Limited(RateLimitError),
+    /// The request to Redis failed, so no decision was made.
+    Redis(RedisError),
+}
+
+impl RedisLimitError {
+    /// Returns the time in milliseconds until the request could succeed, if the bucket
+    /// rejected it.
+    pub fn retry_after_ms(&self) -> Option<u64> {
+        match self {
+            Self::Limited(err) => err.retry_after_ms(),
+            Self::Redis(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for RedisLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Limited(err) => err.fmt(f),
+            Self::Redis(err) => write!(f, "Redis request failed: {err}"),
+        }
+    }
+}
+
+impl error::Error for RedisLimitError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Limited(err) => Some(err),
+            Self::Redis(err) => Some(err),
+        }
+    }
+}
+
+impl From<RateLimitError> for RedisLimitError {
+    fn from(err: RateLimitError) -> Self {
+        Self::Limited(err)
+    }
+}
+
+impl From<RedisError> for RedisLimitError {
+    fn from(err: RedisError) -> Self {
+        Self::Redis(err)
+    }
+}
+
+/// What a [`RedisTokenBucket`] does with a request when Redis cannot decide it.
+///
+/// This applies when the round trip fails, and when it is cut short by the deadline of
+/// [`acquire_deadline`](RedisTokenBucket::acquire_deadline).
+///
+/// A round trip that fails or is cut short may still have reached Redis: the script
+/// usually runs even though its answer is lost. `FailOpen` and `Local` then admit a
+/// request whose tokens the shared bucket may already have taken, or that it rejected,
+/// so during such failures a request can count twice against the shared limit, and the
+/// shared limit can be exceeded.
+#[derive(Default)]
+pub enum FailureMode {
+    /// Reject the request with the Redis error, so an outage never admits more than
+    /// the shared limit.
+    #[default]
+    FailClosed,
+    /// Admit the request, so an outage never blocks traffic.
+    FailOpen,
+    /// Decide the request with a local limiter. Each process enforces the local limit
+    /// on its own, so size it as a share of the shared one.
+    Local(Box<dyn RateLimiter>),
+}
+
+impl fmt::Debug for FailureMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FailClosed => f.write_str("FailClosed"),
+            Self::FailOpen => f.write_str("FailOpen"),
+            Self::Local(_) => f.debug_tuple("Local").finish_non_exhaustive(),
+        }
+    }
+}
+
+/// A token bucket stored in Redis and shared by every process using the same key.
+///
+/// This mirrors the [`RateLimiter`] interface with async methods,
+/// since every decision is a round trip to Redis. See the
+/// [module documentation](self) for the key schema.
+///
+/// The connection is cloned for each request. [`ConnectionManager`], the default, is
+/// cheap to clone and reconnects on its own; any other
+/// [`aio::ConnectionLike`](redis::aio::ConnectionLike) that is cheap to clone works too.
+///
+/// # Examples
+///
+/// ```no_run
+/// use bucketboss::RedisTokenBucket;
+///
+/// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = redis::Client::open("redis://127.0.0.1/")?;
+/// let connection = redis::aio::ConnectionManager::new(client).await?;
+///
+/// // 100 requests per minute per user, shared by every server
+/// let limiter = RedisTokenBucket::new(connection, "ratelimit:user:42", 100, 100.0 / 60.0);
+/// limiter.try_acquire(1).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct RedisTokenBucket<M = ConnectionManager, C = SystemClock> {
+    connection: M,
+    clock: C,
+    key: String,
+    capacity: u32,
+    tokens_per_second: f64,
+    script: Script,
+    failure_mode: FailureMode,
+}
+
+impl<M> RedisTokenBucket<M, SystemClock>
+where
+    M: redis::aio::ConnectionLike + Clone + Send + Sync,
+{
+    /// Creates a bucket at `key` holding up to `capacity` tokens and refilling
+    /// `tokens_per_second`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0, or if `tokens_per_second` is not positive or exceeds
+    /// [`MAX_RATE`](crate::MAX_RATE). With the `no-panic` feature this only happens in
+    /// debug builds; release builds clamp both into the valid range instead.
+    pub fn new(
+        connection: M,
+        key: impl Into<String>,
+        capacity: u32,
+        tokens_per_second: f64,
+    ) -> Self {
+        Self::with_clock(connection, key, capacity, tokens_per_second, SystemClock)
+    }
+}
+
+impl<M, C> RedisTokenBucket<M, C>
+where
+    M: redis::aio::ConnectionLike + Clone + Send + Sync,
+    C: Clock,
+{
+    /// Creates a bucket with the specified clock, which should count milliseconds
+    /// since the Unix epoch like [`SystemClock`] for processes to agree.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`RedisTokenBucket::new`].
+    pub fn with_clock(
+        connection: M,
+        key: impl Into<String>,
+        capacity: u32,
+        tokens_per_second: f64,
+        clock: C,
+    ) -> Self {
+        let capacity = validate::capacity(capacity as u64, "capacity must be greater than 0");
+        let tokens_per_second =
+            validate::rate(tokens_per_second, "tokens_per_second must be positive");
+
+        Self {
+            connection,
+            clock,
+            key: key.into(),
+            capacity: capacity as u32,
+            tokens_per_second,
+            script: Script::new(TOKEN_BUCKET_SCRIPT),
+            failure_mode: FailureMode::FailClosed,
+        }
+    }
+
+    /// Sets what happens to requests when Redis cannot decide them.
+    ///
+    /// See [`FailureMode`] for the available behaviors.
+    pub fn with_failure_mode(mut self, mode: FailureMode) -> Self {
+        self.failure_mode = mode;
+        self
+    }
+
+    /// Returns the Redis key holding the bucket.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the maximum number of tokens the bucket can hold.
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Returns the rate at which tokens are refilled, in tokens per second.
+    pub fn rate_per_second(&self) -> f64 {
+        self.tokens_per_second
+    }
+
+    /// Attempts to acquire `tokens` from the shared bucket.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the tokens were acquired
+    /// * `Err(RedisLimitError::Limited)` with the `RateLimitExceeded` error a local
+    ///   bucket would return, its `retry_after_ms` computed by the server
+    /// * `Err(RedisLimitError::Redis)` if Redis could not be reached and the
+    ///   [`FailureMode`] is `FailClosed`
+    pub async fn try_acquire(&self, tokens: u32) -> Result<(), RedisLimitError> {
+        match self.run(tokens).await {
+            Ok(decision) => Self::decide(tokens, decision),
+            Err(err) => self.fail(tokens, err),
+        }
+    }
+
+    /// Returns the number of tokens currently available in the shared bucket.
+    pub async fn available_tokens(&self) -> Result<u32, RedisError> {
+        let (_, available, _) = self.run(0).await?;
+        Ok(available)
+    }
+
+    /// Waits until `tokens` can be acquired from the shared bucket, then acquires them.
+    ///
+    /// Like [`AsyncRateLimiter::acquire`](crate::AsyncRateLimiter::acquire), this sleeps
+    /// for the advertised wait after each rejection and tries again, so it fails
+    /// immediately only if `tokens` exceeds the capacity or Redis cannot be reached.
+    pub async fn acquire(&self, tokens: u32) -> Result<(), RedisLimitError> {
+        loop {
+            match self.try_acquire(tokens).await {
+                Err(RedisLimitError::Limited(RateLimitError::RateLimitExceeded {
+                    retry_after_ms,
+                    ..
+                })) if tokens <= self.capacity => {
+                    // Never sleep for zero time, which would spin on the executor
+                    tokio::time::sleep(Duration::from_millis(retry_after_ms.max(1))).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Waits until `tokens` can be acquired from the shared bucket, giving up if that
+    /// would take past `deadline`.
+    ///
+    /// This behaves like
+    /// [`AsyncRateLimiter::acquire_deadline`](crate::AsyncRateLimiter::acquire_deadline),
+    /// and the deadline also bounds each round trip to Redis: one that would outlive it
+    /// is abandoned and the request decided by the [`FailureMode`], so a slow Redis
+    /// fails fast instead of using up the caller's whole budget.
+    ///
+    /// Abandoning a round trip does not cancel the script, which Redis has usually
+    /// run by the time the deadline passes. With `FailOpen` or `Local`, a timed-out
+    /// request may therefore be admitted although the shared bucket already charged or
+    /// rejected it; see [`FailureMode`].
+    ///
+    /// Once the deadline has passed, Redis is not asked again, so a slow Redis cannot
+    /// hold the caller past it. The rejection reports the level from the last answer
+    /// and what is left of the wait it advertised, or, if the deadline passed before
+    /// the first round trip, no tokens available and the time `tokens` take to refill.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` once the tokens have been acquired
+    /// * `Err(RedisLimitError::Limited)` if the tokens cannot be acquired before the
+    ///   deadline
+    /// * `Err(RedisLimitError::Redis)` if Redis could not be reached or did not answer
+    ///   before the deadline, and the [`FailureMode`] is `FailClosed`. A timeout is an
+    ///   I/O error for which [`RedisError::is_timeout`] is `true`.
+    pub async fn acquire_deadline(
+        &self,
+        tokens: u32,
+        deadline: &Deadline,
+    ) -> Result<(), RedisLimitError> {
+        // The level from the last rejection and when the wait it advertised ends
+        let mut last: Option<(u32, Instant)> = None;
+        loop {
+            if deadline.is_expired() {
+                let (available, retry_after_ms) = match last {
+                    // The wait has ended as far as we know, but 0 would mean never
+                    Some((available, retry_at)) => (
+                        available,
+                        retry_at
+                            .saturating_duration_since(Instant::now())
+                            .as_millis()
+                            .max(1) as u64,
+                    ),
+                    None => (0, self.time_until_available_ms(tokens, 0).unwrap_or(0)),
+                };
+                return Err(
+                    RateLimitError::rate_limit_exceeded(tokens, available, retry_after_ms).into(),
+                );
+            }
+            let decision = match tokio::time::timeout(deadline.remaining(), self.run(tokens)).await
+            {
+                Ok(Ok(decision)) => decision,
+                Ok(Err(err)) => return self.fail(tokens, err),
+                Err(_) => {
+                    return self.fail(tokens, io::Error::from(io::ErrorKind::TimedOut).into())
+                }
+            };
+            match Self::decide(tokens, decision) {
+                Err(RedisLimitError::Limited(RateLimitError::RateLimitExceeded {
+                    available,
+                    retry_after_ms,
+                    ..
+                })) if tokens <= self.capacity
+                    && Duration::from_millis(retry_after_ms.max(1)) <= deadline.remaining() =>
+                {
+                    let wait = Duration::from_millis(retry_after_ms.max(1));
+                    last = Some((available, Instant::now() + wait));
+                    tokio::time::sleep(wait).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Turns the script's answer for `tokens` into the result of an acquire.
+    fn decide(
+        tokens: u32,
+        (admitted, available, retry_after_ms): (bool, u32, u64),
+    ) -> Result<(), RedisLimitError> {
+        if admitted {
+            Ok(())
+        } else {
+            Err(RateLimitError::rate_limit_exceeded(tokens, available, retry_after_ms).into())
+        }
+    }
+
+    /// Returns the time until `tokens` are available in a bucket holding `available`
+    /// whole tokens, or `None` if they are available now or never fit.
+    ///
+    /// The fraction of a token the bucket may hold besides is not known here, so the
+    /// wait can be overstated by less than one token's refill time.
+    fn time_until_available_ms(&self, tokens: u32, available: u32) -> Option<u64> {
+        if tokens <= available || tokens > self.capacity {
+            return None;
+        }
+        let missing = (tokens - available) as f64;
+        Some((missing * 1000.0 / self.tokens_per_second).ceil() as u64)
+    }
+
+    /// Decides a request for `tokens` that Redis could not, according to the
+    /// [`FailureMode`].
+    fn fail(&self, tokens: u32, err: RedisError) -> Result<(), RedisLimitError> {
+        match &self.failure_mode {
+            FailureMode::FailClosed => Err(err.into()),
+            FailureMode::FailOpen => Ok(()),
+            FailureMode::Local(limiter) => Ok(limiter.try_acquire(tokens)?),
+        }
+    }
+
+    /// Runs the script for `tokens`, returning whether they were admitted, the whole
+    /// tokens left and the wait until they could be.
+    async fn run(&self, tokens: u32) -> Result<(bool, u32, u64), RedisError> {
+        let mut connection = self.connection.clone();
+        let (admitted, available, retry_after_ms): (i64, i64, i64) = self
+            .script
+            .key(&self.key)
+            .arg(self.capacity)
+            .arg(self.tokens_per_second)
+            .arg(self.clock.now())
+            .arg(tokens)
+            .invoke_async(&mut connection)
+            .await?;
+        Ok((
+            admitted == 1,
+            available.clamp(0, u32::MAX as i64) as u32,
+            retry_after_ms.max(0) as u64,
+        ))
+    }
+}
+
+impl<M, C> fmt::Debug for RedisTokenBucket<M, C>
+where
+    C: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisTokenBucket")
+            .field("key", &self.key)
+            .field("capacity", &self.capacity)
+            .field("tokens_per_second", &self.tokens_per_second)
+            .field("clock", &self.clock)
+            .field("failure_mode", &self.failure_mode)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use redis::{aio::ConnectionLike, Cmd, ErrorKind, Pipeline, RedisFuture, Value};
+    use redis_test::{MockCmd, MockRedisConnection};
+
+    use super::*;
+    use crate::{clock::MockClock, TokenBucket};
+
+    /// A connection that answers only after `delay`.
+    #[derive(Clone)]
+    struct SlowConnection {
+        inner: MockRedisConnection,
+        delay: Duration,
+    }
+
+    impl ConnectionLike for SlowConnection {
+        fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+            Box::pin(async move {
+                tokio::time::sleep(self.delay).await;
+                self.inner.req_packed_command(cmd).await
+            })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            cmd: &'a Pipeline,
+            offset: usize,
+            count: usize,
+        ) -> RedisFuture<'a, Vec<Value>> {
+            Box::pin(async move {
+                tokio::time::sleep(self.delay).await;
+                self.inner.req_packed_commands(cmd, offset, count).await
+            })
+        }
+
+        fn get_db(&self) -> i64 {
+            self.inner.get_db()
+        }
+    }
+
+    /// A bucket whose Redis takes `delay_ms` to answer a single admitting decision.
+    fn slow_limiter(
+        delay_ms: u64,
+        mode: FailureMode,
+    ) -> RedisTokenBucket<SlowConnection, MockClock> {
+        let connection = SlowConnection {
+            inner: MockRedisConnection::new([MockCmd::new(
+                evalsha("rl:slow", 0, 1),
+                reply(1, 9, 0),
+            )]),
+            delay: Duration::from_millis(delay_ms),
+        };
+        RedisTokenBucket::with_clock(connection, "rl:slow", 10, 2.0, MockClock::new(0))
+            .with_failure_mode(mode)
+    }
+
+    /// The command the bucket sends for a decision at `now` for `tokens`.
+    fn evalsha(key: &str, now: u64, tokens: u32) -> Cmd {
+        let mut cmd = redis::cmd("EVALSHA");
+        let _ = cmd
+            .arg(Script::new(TOKEN_BUCKET_SCRIPT).get_hash())
+            .arg(1)
+            .arg(key)
+            .arg(10u32)
+            .arg(2.0f64)
+            .arg(now)
+            .arg(tokens);
+        cmd
+    }
+
+    fn reply(admitted: i64, available: i64, retry_after_ms: i64) -> Result<Value, RedisError> {
+        Ok(Value::Array(vec![
+            Value::Int(admitted),
+            Value::Int(available),
+            Value::Int(retry_after_ms),
+        ]))
+    }
+
+    #[tokio::test]
+    async fn test_decisions_come_from_the_script() {
+        let connection = MockRedisConnection::new([
+            MockCmd::new(evalsha("rl:test", 1000, 4), reply(1, 6, 0)),
+            MockCmd::new(evalsha("rl:test", 1000, 8), reply(0, 6, 1000)),
+            MockCmd::new(evalsha("rl:test", 1500, 0), reply(1, 7, 0)),
+        ]);
+        let clock = MockClock::new(1000);
+        let limiter = RedisTokenBucket::with_clock(connection, "rl:test", 10, 2.0, clock.clone());
+
+        assert!(limiter.try_acquire(4).await.is_ok());
+        match limiter.try_acquire(8).await {
+            Err(RedisLimitError::Limited(err)) => {
+                assert_eq!(err, RateLimitError::rate_limit_exceeded(8, 6, 1000));
+            }
+            other => panic!("expected a rejection, got {other:?}"),
+        }
+
+        clock.set(1500);
+        assert_eq!(limiter.available_tokens().await.unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_missing_script_is_loaded_and_redis_errors_surface() {
+        let script = Script::new(TOKEN_BUCKET_SCRIPT);
+        let mut load = redis::cmd("SCRIPT");
+        let _ = load.arg("LOAD").arg(TOKEN_BUCKET_SCRIPT);
+        let connection = MockRedisConnection::new([
+            MockCmd::new(
+                evalsha("rl:load", 0, 1),
+                Err::<Value, _>(RedisError::from((ErrorKind::NoScriptError, "NOSCRIPT"))),
+            ),
+            MockCmd::new(load, Ok(script.get_hash())),
+            MockCmd::new(evalsha("rl:load", 0, 1), reply(1, 9, 0)),
+            MockCmd::new(
+                evalsha("rl:load", 0, 1),
+                Err::<Value, _>(RedisError::from((ErrorKind::IoError, "connection lost"))),
+            ),
+        ]);
+        let limiter =
+            RedisTokenBucket::with_clock(connection, "rl:load", 10, 2.0, MockClock::new(0));
+
+        assert!(limiter.try_acquire(1).await.is_ok());
+        let err = limiter.try_acquire(1).await.unwrap_err();
+        assert!(matches!(err, RedisLimitError::Redis(_)));
+        assert_eq!(err.retry_after_ms(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_deadline_bounds_the_round_trip() {
+        let deadline = Deadline::after(Duration::from_millis(100));
+
+        // Fails closed with a timeout once the deadline passes, not when Redis answers
+        let start = Instant::now();
+        let limiter = slow_limiter(500, FailureMode::FailClosed);
+        match limiter.acquire_deadline(1, &deadline).await {
+            Err(RedisLimitError::Redis(err)) => assert!(err.is_timeout()),
+            other => panic!("expected a timeout, got {other:?}"),
+        }
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+
+        // The deadline has passed, so later calls reject without asking Redis, reporting
+        // the time 5 tokens take to refill
+        let limiter = slow_limiter(500, FailureMode::FailOpen);
+        match limiter.acquire_deadline(5, &deadline).await {
+            Err(RedisLimitError::Limited(err)) => {
+                assert_eq!(err, RateLimitError::rate_limit_exceeded(5, 0, 2500));
+            }
+            other => panic!("expected a rejection, got {other:?}"),
+        }
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+
+        // A round trip within the deadline decides as usual
+        let deadline = Deadline::after(Duration::from_millis(100));
+        let limiter = slow_limiter(50, FailureMode::FailClosed);
+        assert!(limiter.acquire_deadline(1, &deadline).await.is_ok());
+        assert_eq!(start.elapsed(), Duration::from_millis(150));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_expired_deadline_reports_the_last_rejection() {
+        let connection = SlowConnection {
+            inner: MockRedisConnection::new([MockCmd::new(
+                evalsha("rl:late", 0, 5),
+                reply(0, 3, 400),
+            )]),
+            delay: Duration::from_millis(50),
+        };
+        let limiter =
+            RedisTokenBucket::with_clock(connection, "rl:late", 10, 2.0, MockClock::new(0));
+
+        // The advertised wait runs out with the deadline, and Redis is not asked again
+        let start = Instant::now();
+        let deadline = Deadline::after(Duration::from_millis(450));
+        match limiter.acquire_deadline(5, &deadline).await {
+            Err(RedisLimitError::Limited(err)) => {
+                assert_eq!(err, RateLimitError::rate_limit_exceeded(5, 3, 1));
+            }
+            other => panic!("expected a rejection, got {other:?}"),
+        }
+        assert_eq!(start.elapsed(), Duration::from_millis(450));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timeouts_follow_the_failure_mode() {
+        let start = Instant::now();
+        let deadline = Deadline::after(Duration::from_millis(100));
+        let limiter = slow_limiter(500, FailureMode::FailOpen);
+        assert!(limiter.acquire_deadline(1, &deadline).await.is_ok());
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+
+        let local = TokenBucket::with_clock(1, 1.0, MockClock::new(0));
+        let limiter = slow_limiter(500, FailureMode::Local(Box::new(local)));
+        let deadline = Deadline::after(Duration::from_millis(100));
+        assert!(limiter.acquire_deadline(1, &deadline).await.is_ok());
+        let deadline = Deadline::after(Duration::from_millis(100));
+        let err = limiter.acquire_deadline(1, &deadline).await.unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(1000));
+        assert_eq!(start.elapsed(), Duration::from_millis(300));
+    }
+}
@@ -0,0 +1,354 @@
+//! Rates computed by a user-supplied control law.
+//!
+//! [`RampingReconfigurable`](crate::RampingReconfigurable) moves towards a rate it is
+//! given. [`DynamicRateLimiter`] instead asks a closure for the rate, periodically, so
+//! the limiter can follow a load metric: tightening as a queue fills or CPU usage rises,
+//! and loosening again as it falls. Any control law fits, from a fixed table of load
+//! levels to a feedback loop.
+//!
+//! This module is only available with the `std` feature.
+
+use core::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::Result,
+    traits::{RateLimiter, ReconfigurableRateLimiter, Wrapper},
+    validate,
+};
+
+/// A wrapper whose rate is recomputed by a closure as the limiter is used.
+///
+/// Whenever the limiter is used and at least the configured interval has passed since
+/// the last evaluation, the closure is called with the inner limiter's current
+/// utilization, the fraction of its capacity in use from `0.0` (full of tokens) to
+/// `1.0` (empty). The rate it returns is applied to the inner limiter through
+/// [`set_rate_only`](ReconfigurableRateLimiter::set_rate_only), so tokens accrued so far
+/// are kept. External metrics, such as a queue depth, are read by the closure itself.
+///
+/// A rate that is not positive and finite, or exceeds [`MAX_RATE`](crate::MAX_RATE),
+/// is ignored and the previous rate stays in effect until the next evaluation.
+///
+/// Each evaluation is claimed by one caller with a compare-and-swap on the time of the
+/// last one, and the closure runs without a lock while other callers carry on at the
+/// current rate. It must not use the limiter itself. The wrapper's clock should be the
+/// one the inner limiter uses.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// use bucketboss::{clock::MockClock, DynamicRateLimiter, RateLimiter, TokenBucket};
+///
+/// // Halve the rate for every 100 queued jobs
+/// let queued = Arc::new(AtomicU64::new(0));
+/// let depth = Arc::clone(&queued);
+/// let clock = MockClock::new(0);
+/// let limiter = DynamicRateLimiter::with_clock(
+///     TokenBucket::with_clock(10, 100.0, clock.clone()),
+///     Duration::from_secs(1),
+///     move |_utilization| 100.0 / 2f64.powi((depth.load(Ordering::Relaxed) / 100) as i32),
+///     clock.clone(),
+/// );
+/// assert_eq!(limiter.rate_per_second(), 100.0);
+///
+/// queued.store(250, Ordering::Relaxed);
+/// clock.advance(1000);
+/// assert_eq!(limiter.rate_per_second(), 25.0);
+/// ```
+pub struct DynamicRateLimiter<L, F, C = SystemClock> {
+    inner: L,
+    clock: C,
+    rate_fn: F,
+    interval_ms: u64,
+    /// When the closure was last evaluated, or [`NEVER`] if it has not been.
+    evaluated_at: AtomicU64,
+}
+
+/// The `evaluated_at` of a limiter whose closure has not been evaluated yet.
+const NEVER: u64 = u64::MAX;
+
+impl<L, F> DynamicRateLimiter<L, F, SystemClock>
+where
+    L: ReconfigurableRateLimiter,
+    F: Fn(f64) -> f64 + Send + Sync + 'static,
+{
+    /// Creates a new wrapper that asks `rate_fn` for the rate at most once per
+    /// `interval`.
+    ///
+    /// A zero `interval` evaluates `rate_fn` every time the limiter is used.
+    pub fn new(inner: L, interval: Duration, rate_fn: F) -> Self {
+        Self::with_clock(inner, interval, rate_fn, SystemClock)
+    }
+}
+
+impl<L, F, C> DynamicRateLimiter<L, F, C>
+where
+    L: ReconfigurableRateLimiter,
+    F: Fn(f64) -> f64 + Send + Sync + 'static,
+    C: Clock,
+{
+    /// Creates a new wrapper with the specified clock.
+    pub fn with_clock(inner: L, interval: Duration, rate_fn: F, clock: C) -> Self {
+        Self {
+            inner,
+            clock,
+            rate_fn,
+            interval_ms: interval.as_millis().min(u64::MAX as u128) as u64,
+            evaluated_at: AtomicU64::new(NEVER),
+        }
+    }
+
+    /// Returns the minimum time between evaluations of the closure.
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+
+    /// Returns the fraction of the inner limiter's capacity currently in use.
+    pub fn utilization(&self) -> f64 {
+        let capacity = self.inner.capacity();
+        if capacity == 0 {
+            return 0.0;
+        }
+        1.0 - self.inner.available_tokens().min(capacity) as f64 / capacity as f64
+    }
+
+    /// Evaluates the closure if the interval has passed, and applies its rate.
+    fn touch(&self) {
+        let now = self.clock.now();
+        let at = self.evaluated_at.load(Ordering::Acquire);
+        if at != NEVER && now.saturating_sub(at) < self.interval_ms {
+            return;
+        }
+        // Only the caller that moves the timestamp on evaluates the closure
+        if self
+            .evaluated_at
+            .compare_exchange(at, now, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            return;
+        }
+
+        let rate = (self.rate_fn)(self.utilization());
+        if validate::try_rate(rate, "tokens_per_second must be positive").is_ok()
+            && rate != self.inner.rate_per_second()
+        {
+            // The rate was just validated, which is all the inner limiter checks
+            let _ = self.inner.set_rate_only(rate);
+        }
+    }
+}
+
+impl<L, F, C> RateLimiter for DynamicRateLimiter<L, F, C>
+where
+    L: ReconfigurableRateLimiter,
+    F: Fn(f64) -> f64 + Send + Sync + 'static,
+    C: Clock,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        self.touch();
+        self.inner.try_acquire(tokens)
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        self.touch();
+        self.inner.try_acquire_u64(tokens)
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        self.touch();
+        self.inner.peek_acquire(tokens)
+    }
+
+    fn available_tokens(&self) -> u32 {
+        self.touch();
+        self.inner.available_tokens()
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        self.touch();
+        self.inner.available_tokens_u64()
+    }
+
+    fn capacity(&self) -> u32 {
+        self.inner.capacity()
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.inner.capacity_u64()
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        self.touch();
+        self.inner.rate_per_second()
+    }
+
+    fn effective_rate_per_second(&self) -> f64 {
+        self.touch();
+        self.inner.effective_rate_per_second()
+    }
+
+    fn resolution_warning(&self) -> Option<&'static str> {
+        self.touch();
+        self.inner.resolution_warning()
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        self.touch();
+        self.inner.time_until_next_token_ms()
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.touch();
+        self.inner.time_until_available_ms(tokens)
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        self.touch();
+        self.inner.retry_at(tokens)
+    }
+}
+
+impl<L, F, C> Wrapper for DynamicRateLimiter<L, F, C> {
+    type Inner = L;
+
+    fn get_ref(&self) -> &L {
+        &self.inner
+    }
+
+    fn get_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    fn into_inner(self) -> L {
+        self.inner
+    }
+}
+
+impl<L, F, C> fmt::Debug for DynamicRateLimiter<L, F, C>
+where
+    L: fmt::Debug,
+    C: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynamicRateLimiter")
+            .field("inner", &self.inner)
+            .field("clock", &self.clock)
+            .field("interval_ms", &self.interval_ms)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{clock::MockClock, TokenBucket};
+
+    #[test]
+    fn test_rate_follows_closure_over_time() {
+        let load = Arc::new(AtomicU64::new(0));
+        let metric = Arc::clone(&load);
+        let clock = MockClock::new(0);
+        let limiter = DynamicRateLimiter::with_clock(
+            TokenBucket::with_clock(10, 10.0, clock.clone()),
+            Duration::from_millis(500),
+            move |_| {
+                if metric.load(Ordering::Relaxed) > 80 {
+                    1.0
+                } else {
+                    10.0
+                }
+            },
+            clock.clone(),
+        );
+        assert!(limiter.try_acquire(10).is_ok());
+
+        // Under low load a token refills every 100 ms
+        clock.advance(100);
+        assert!(limiter.try_acquire(1).is_ok());
+
+        // The high load is only seen once the interval has passed
+        load.store(90, Ordering::Relaxed);
+        clock.advance(100);
+        assert!(limiter.try_acquire(1).is_ok());
+        assert_eq!(limiter.rate_per_second(), 10.0);
+        clock.advance(300);
+        assert_eq!(limiter.rate_per_second(), 1.0);
+
+        // Now a token takes a second to refill
+        assert!(limiter.try_acquire(3).is_ok());
+        clock.advance(500);
+        assert!(limiter.try_acquire(1).is_err());
+        clock.advance(500);
+        assert!(limiter.try_acquire(1).is_ok());
+
+        // And the rate recovers with the load
+        load.store(10, Ordering::Relaxed);
+        clock.advance(500);
+        assert_eq!(limiter.rate_per_second(), 10.0);
+    }
+
+    #[test]
+    fn test_closure_sees_utilization_and_invalid_rates_are_ignored() {
+        let clock = MockClock::new(0);
+        let limiter = DynamicRateLimiter::with_clock(
+            TokenBucket::with_clock(10, 10.0, clock.clone()),
+            Duration::ZERO,
+            |utilization| match utilization {
+                u if u >= 1.0 => f64::NAN,
+                u => 10.0 * (1.0 - u),
+            },
+            clock.clone(),
+        );
+        assert_eq!(limiter.utilization(), 0.0);
+        assert_eq!(limiter.rate_per_second(), 10.0);
+
+        assert!(limiter.try_acquire(5).is_ok());
+        assert_eq!(limiter.utilization(), 0.5);
+        assert_eq!(limiter.rate_per_second(), 5.0);
+
+        // An empty bucket asks for NaN, which leaves the rate as it was
+        assert!(limiter.try_acquire(5).is_ok());
+        assert_eq!(limiter.rate_per_second(), 5.0);
+    }
+
+    #[test]
+    fn test_each_interval_is_evaluated_once() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let counted = Arc::clone(&calls);
+        let clock = MockClock::new(0);
+        let limiter = DynamicRateLimiter::with_clock(
+            TokenBucket::with_clock(10, 10.0, clock.clone()),
+            Duration::from_millis(100),
+            move |_| {
+                let _ = counted.fetch_add(1, Ordering::Relaxed);
+                10.0
+            },
+            clock.clone(),
+        );
+
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                let _ = scope.spawn(|| {
+                    for _ in 0..100 {
+                        let _ = limiter.available_tokens();
+                    }
+                });
+            }
+        });
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        clock.advance(100);
+        let _ = limiter.available_tokens();
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,322 @@
+//! Edge-triggered notification when a limiter recovers from exhaustion.
+//!
+//! The buckets refill lazily, so nothing happens at the moment a token becomes
+//! available again. [`EdgeNotifyLimiter`] arms a Tokio timer whenever a request leaves
+//! the inner limiter exhausted, and fires exactly once when availability returns: it
+//! invokes an optional callback and wakes every [`next_edge`](EdgeNotifyLimiter::next_edge)
+//! future. This enables "fire when unblocked" patterns without polling.
+//!
+//! This module is only available with the `async` feature.
+
+use core::fmt;
+use core::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+
+use crate::{error::Result, traits::RateLimiter};
+
+type Callback = Box<dyn Fn() + Send + Sync>;
+
+struct EdgeState {
+    callback: Option<Callback>,
+    armed: AtomicBool,
+    edges: AtomicU64,
+    notify: Notify,
+}
+
+/// A rate limiter wrapper that signals the transition from exhausted to available.
+///
+/// The timer is armed from within `try_acquire`, so that call must happen inside a
+/// Tokio runtime for notifications to fire. Outside a runtime the limiter still
+/// enforces limits but never arms a timer.
+///
+/// The inner limiter is shared with the armed timer task, so instead of implementing
+/// [`Wrapper`](crate::Wrapper) the wrapper has its own accessors:
+/// [`get_mut`](Self::get_mut) only succeeds while no timer is armed, and
+/// [`into_inner`](Self::into_inner) returns the shared `Arc<L>`.
+pub struct EdgeNotifyLimiter<L> {
+    inner: Arc<L>,
+    state: Arc<EdgeState>,
+}
+
+impl<L> EdgeNotifyLimiter<L>
+where
+    L: RateLimiter,
+{
+    /// Creates a new wrapper without a callback.
+    ///
+    /// Use [`next_edge`](Self::next_edge) to await transitions.
+    pub fn new(inner: L) -> Self {
+        Self::build(inner, None)
+    }
+
+    /// Creates a new wrapper that invokes `callback` once per exhausted-to-available
+    /// transition.
+    ///
+    /// The callback runs on a Tokio worker thread and should return quickly.
+    pub fn with_callback<F>(inner: L, callback: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        Self::build(inner, Some(Box::new(callback)))
+    }
+
+    fn build(inner: L, callback: Option<Callback>) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            state: Arc::new(EdgeState {
+                callback,
+                armed: AtomicBool::new(false),
+                edges: AtomicU64::new(0),
+                notify: Notify::new(),
+            }),
+        }
+    }
+
+    /// Waits for the next exhausted-to-available transition.
+    ///
+    /// The future resolves once, on the first transition that happens after it is
+    /// first polled.
+    pub async fn next_edge(&self) {
+        self.state.notify.notified().await;
+    }
+
+    /// Returns the number of transitions observed so far.
+    pub fn edge_count(&self) -> u64 {
+        self.state.edges.load(Ordering::Acquire)
+    }
+
+    /// Returns a reference to the inner limiter.
+    pub fn get_ref(&self) -> &L {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner limiter, or `None` while an armed timer
+    /// still shares it.
+    pub fn get_mut(&mut self) -> Option<&mut L> {
+        Arc::get_mut(&mut self.inner)
+    }
+
+    /// Consumes the wrapper, returning the inner limiter.
+    ///
+    /// An armed timer keeps its own reference until it fires, so the limiter is
+    /// returned as the shared `Arc<L>`.
+    pub fn into_inner(self) -> Arc<L> {
+        self.inner
+    }
+
+    /// Returns whether a timer is currently armed waiting for availability.
+    pub fn is_armed(&self) -> bool {
+        self.state.armed.load(Ordering::Acquire)
+    }
+
+    /// Arms the availability timer if the inner limiter is exhausted.
+    fn arm_if_exhausted(&self) {
+        if self.inner.available_tokens() > 0 {
+            return;
+        }
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        if self
+            .state
+            .armed
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            return;
+        }
+
+        let inner = Arc::clone(&self.inner);
+        let state = Arc::clone(&self.state);
+        drop(handle.spawn(async move {
+            loop {
+                while inner.available_tokens() == 0 {
+                    let wait = inner
+                        .time_until_next_token()
+                        .unwrap_or(Duration::from_millis(1))
+                        .max(Duration::from_millis(1));
+                    tokio::time::sleep(wait).await;
+                }
+
+                // Disarm before the final check: a request that exhausts the limiter
+                // after this point arms a timer of its own, and one that exhausted it
+                // since the loop exited is caught by the check and keeps this one armed
+                if state
+                    .armed
+                    .compare_exchange(true, false, Ordering::AcqRel, Ordering::Acquire)
+                    .is_err()
+                {
+                    return;
+                }
+                if inner.available_tokens() > 0 {
+                    break;
+                }
+                if state
+                    .armed
+                    .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+                    .is_err()
+                {
+                    // Another request already armed a timer for this exhaustion
+                    return;
+                }
+            }
+
+            let _ = state.edges.fetch_add(1, Ordering::AcqRel);
+            if let Some(callback) = &state.callback {
+                callback();
+            }
+            state.notify.notify_waiters();
+        }));
+    }
+}
+
+impl<L> fmt::Debug for EdgeNotifyLimiter<L>
+where
+    L: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EdgeNotifyLimiter")
+            .field("inner", &self.inner)
+            .field("armed", &self.state.armed)
+            .field("edges", &self.state.edges)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<L> RateLimiter for EdgeNotifyLimiter<L>
+where
+    L: RateLimiter,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        let result = self.inner.try_acquire(tokens);
+        self.arm_if_exhausted();
+        result
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        let result = self.inner.try_acquire_u64(tokens);
+        self.arm_if_exhausted();
+        result
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        self.inner.peek_acquire(tokens)
+    }
+
+    fn available_tokens(&self) -> u32 {
+        self.inner.available_tokens()
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        self.inner.available_tokens_u64()
+    }
+
+    fn capacity(&self) -> u32 {
+        self.inner.capacity()
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.inner.capacity_u64()
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        self.inner.rate_per_second()
+    }
+
+    fn effective_rate_per_second(&self) -> f64 {
+        self.inner.effective_rate_per_second()
+    }
+
+    fn resolution_warning(&self) -> Option<&'static str> {
+        self.inner.resolution_warning()
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        self.inner.time_until_next_token_ms()
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.inner.time_until_available_ms(tokens)
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        self.inner.retry_at(tokens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asynchronous::TokioClock, TokenBucket};
+    use std::sync::atomic::AtomicUsize;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_edge_fires_once_per_transition() {
+        let fired = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&fired);
+        let limiter = EdgeNotifyLimiter::with_callback(
+            TokenBucket::with_clock(2, 10.0, TokioClock::new()),
+            move || {
+                let _ = counter.fetch_add(1, Ordering::SeqCst);
+            },
+        );
+
+        for cycle in 1..=3 {
+            // Exhaust the bucket and keep hammering it while exhausted
+            assert!(limiter.try_acquire(2).is_ok());
+            assert!(limiter.is_armed());
+            for _ in 0..5 {
+                assert!(limiter.try_acquire(1).is_err());
+            }
+
+            // Let the refill happen and the timer fire
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            assert_eq!(fired.load(Ordering::SeqCst), cycle);
+            assert_eq!(limiter.edge_count(), cycle as u64);
+            assert!(!limiter.is_armed());
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_next_edge_resolves_when_available() {
+        let limiter = EdgeNotifyLimiter::new(TokenBucket::with_clock(1, 4.0, TokioClock::new()));
+        let start = tokio::time::Instant::now();
+
+        let edge = limiter.next_edge();
+        assert!(limiter.try_acquire(1).is_ok());
+        edge.await;
+
+        assert_eq!(start.elapsed(), Duration::from_millis(250));
+        assert_eq!(limiter.available_tokens(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_get_mut_waits_for_the_timer() {
+        let mut limiter =
+            EdgeNotifyLimiter::new(TokenBucket::with_clock(1, 10.0, TokioClock::new()));
+        assert!(limiter.get_mut().is_some());
+
+        // The armed timer shares the inner limiter until it fires
+        assert!(limiter.try_acquire(1).is_ok());
+        assert!(limiter.get_mut().is_none());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        tokio::task::yield_now().await;
+        assert!(limiter.get_mut().is_some());
+        assert_eq!(limiter.get_ref().available_tokens(), 1);
+    }
+
+    #[test]
+    fn test_no_runtime_never_arms() {
+        let limiter = EdgeNotifyLimiter::new(TokenBucket::with_clock(
+            1,
+            1.0,
+            crate::clock::MockClock::new(0),
+        ));
+        assert!(limiter.try_acquire(1).is_ok());
+        assert!(!limiter.is_armed());
+        assert!(limiter.try_acquire(1).is_err());
+    }
+}
@@ -0,0 +1,274 @@
+//! A [`Clock`] for the `embedded-time` crate's clocks.
+//!
+//! Embedded projects often already expose their hardware timer as an
+//! [`embedded_time::Clock`], which reports instants as a tick count and the length of
+//! one tick as a fraction of a second. [`EmbeddedTimeClock`] adapts such a clock so the
+//! limiters can run on it directly, converting its ticks to the milliseconds and
+//! nanoseconds the limiters keep time in.
+//!
+//! This module is only available with the `embedded-time` feature, and like
+//! `embedded-time` itself it does not need `std` or `alloc`. Like the limiters, the
+//! adapter keeps its state in `AtomicU64`, so it needs a target with 64-bit atomics:
+//! `thumbv6m` and `thumbv7m`, for example, have none.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use embedded_time::{fraction::Fraction, Clock as EmbeddedClock};
+
+use crate::clock::{Clock, NANOS_PER_MS};
+
+/// The number of nanoseconds in a second.
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+/// An adapter implementing [`Clock`] for any [`embedded_time::Clock`].
+///
+/// Readings start at the inner clock's time since its epoch and then advance by the
+/// ticks counted between readings, rounded down to whole nanoseconds, and
+/// [`resolution_ms`](Clock::resolution_ms) is the length of one tick. Counting the
+/// ticks between readings lets time carry on when the counter wraps: a 32-bit
+/// counter at 1 kHz wraps after about 49 days, and the adapter keeps counting as long
+/// as it is read at least once per half of that period.
+///
+/// [`Clock::now`] cannot fail, so if the inner clock reports an error, such as not
+/// running, the adapter repeats its last successful reading. A counter that steps
+/// backwards by less than half its range is treated as stopped until it is back
+/// where it was, so readings never go backwards either.
+///
+/// # Examples
+///
+/// ```
+/// use core::sync::atomic::{AtomicU32, Ordering};
+///
+/// use bucketboss::{EmbeddedTimeClock, RateLimiter, TokenBucket};
+/// use embedded_time::{clock::Error, fraction::Fraction, Clock, Instant};
+///
+/// // A hardware timer ticking every millisecond
+/// static TICKS: AtomicU32 = AtomicU32::new(0);
+///
+/// struct SysTick;
+///
+/// impl Clock for SysTick {
+///     type T = u32;
+///     const SCALING_FACTOR: Fraction = Fraction::new(1, 1_000);
+///
+///     fn try_now(&self) -> Result<Instant<Self>, Error> {
+///         Ok(Instant::new(TICKS.load(Ordering::Relaxed)))
+///     }
+/// }
+///
+/// let bucket = TokenBucket::with_clock(1, 10.0, EmbeddedTimeClock::new(SysTick));
+/// assert!(bucket.try_acquire(1).is_ok());
+/// assert!(bucket.try_acquire(1).is_err());
+///
+/// TICKS.store(100, Ordering::Relaxed);
+/// assert!(bucket.try_acquire(1).is_ok());
+/// ```
+#[derive(Debug)]
+pub struct EmbeddedTimeClock<C> {
+    clock: C,
+    /// The inner clock's tick count at the last reading, or [`UNREAD`] before the first.
+    last_ticks: AtomicU64,
+    /// The ticks counted up to the last reading.
+    ticks: AtomicU64,
+}
+
+/// The `last_ticks` of an adapter that has not read its clock yet.
+const UNREAD: u64 = u64::MAX;
+
+impl<C> EmbeddedTimeClock<C>
+where
+    C: EmbeddedClock,
+    u64: From<C::T>,
+{
+    /// Creates a clock reading time from `clock`.
+    pub fn new(clock: C) -> Self {
+        Self {
+            clock,
+            last_ticks: AtomicU64::new(UNREAD),
+            ticks: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the inner clock.
+    pub fn get_ref(&self) -> &C {
+        &self.clock
+    }
+
+    /// Consumes the adapter, returning the inner clock.
+    pub fn into_inner(self) -> C {
+        self.clock
+    }
+}
+
+impl<C> Clock for EmbeddedTimeClock<C>
+where
+    C: EmbeddedClock + Send + Sync + 'static,
+    u64: From<C::T>,
+{
+    fn now(&self) -> u64 {
+        self.now_nanos() as u64 / NANOS_PER_MS
+    }
+
+    fn now_nanos(&self) -> u128 {
+        if let Ok(instant) = self.clock.try_now() {
+            self.count(u64::from(instant.duration_since_epoch().integer()));
+        }
+        ticks_to_nanos(self.ticks.load(Ordering::Acquire), &C::SCALING_FACTOR)
+    }
+
+    fn resolution_ms(&self) -> f64 {
+        let tick = C::SCALING_FACTOR;
+        *tick.numerator() as f64 * 1000.0 / *tick.denominator() as f64
+    }
+}
+
+impl<C> EmbeddedTimeClock<C>
+where
+    C: EmbeddedClock,
+{
+    /// Adds the ticks from the last reading to `reading` to the count.
+    fn count(&self, reading: u64) {
+        // The counter wraps at the width of the clock's tick type
+        let bits = size_of::<C::T>() * 8;
+        let mask = if bits >= 64 {
+            u64::MAX
+        } else {
+            (1 << bits) - 1
+        };
+
+        let mut last = self.last_ticks.load(Ordering::Acquire);
+        loop {
+            let elapsed = if last == UNREAD {
+                reading
+            } else {
+                reading.wrapping_sub(last) & mask
+            };
+            // A reading behind the last one, possibly taken before it by another caller,
+            // has nothing to add
+            if last != UNREAD && elapsed > mask / 2 {
+                return;
+            }
+            match self.last_ticks.compare_exchange_weak(
+                last,
+                reading,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    let _ = self.ticks.fetch_add(elapsed, Ordering::AcqRel);
+                    return;
+                }
+                Err(actual) => last = actual,
+            }
+        }
+    }
+}
+
+/// Converts `ticks` of `tick` seconds each into nanoseconds, rounding down.
+fn ticks_to_nanos(ticks: u64, tick: &Fraction) -> u128 {
+    ticks as u128 * *tick.numerator() as u128 * NANOS_PER_SECOND / *tick.denominator() as u128
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicBool, AtomicU32};
+    use std::sync::Arc;
+
+    use embedded_time::{clock::Error, Instant};
+
+    use super::*;
+    use crate::{RateLimiter, TokenBucket};
+
+    /// A 32.768 kHz crystal counter that can be stopped.
+    #[derive(Debug, Clone, Default)]
+    struct Rtc {
+        ticks: Arc<AtomicU64>,
+        stopped: Arc<AtomicBool>,
+    }
+
+    impl EmbeddedClock for Rtc {
+        type T = u64;
+        const SCALING_FACTOR: Fraction = Fraction::new(1, 32_768);
+
+        fn try_now(&self) -> Result<Instant<Self>, Error> {
+            if self.stopped.load(Ordering::Relaxed) {
+                return Err(Error::NotRunning);
+            }
+            Ok(Instant::new(self.ticks.load(Ordering::Relaxed)))
+        }
+    }
+
+    #[test]
+    fn test_ticks_convert_to_clock_time() {
+        let rtc = Rtc::default();
+        let clock = EmbeddedTimeClock::new(rtc.clone());
+        assert_eq!(clock.now(), 0);
+
+        rtc.ticks.store(32_768, Ordering::Relaxed);
+        assert_eq!(clock.now(), 1000);
+        assert_eq!(clock.now_nanos(), 1_000_000_000);
+
+        // One tick is 30517.578125 ns, rounded down
+        rtc.ticks.store(32_769, Ordering::Relaxed);
+        assert_eq!(clock.now_nanos(), 1_000_030_517);
+        assert_eq!(clock.resolution_ms(), 1000.0 / 32_768.0);
+    }
+
+    #[test]
+    fn test_stopped_clock_repeats_last_reading() {
+        let rtc = Rtc::default();
+        let clock = EmbeddedTimeClock::new(rtc.clone());
+        rtc.ticks.store(3 * 32_768, Ordering::Relaxed);
+        assert_eq!(clock.now(), 3000);
+
+        rtc.stopped.store(true, Ordering::Relaxed);
+        assert_eq!(clock.now(), 3000);
+
+        // Nor does time go backwards if the counter does
+        rtc.stopped.store(false, Ordering::Relaxed);
+        rtc.ticks.store(0, Ordering::Relaxed);
+        assert_eq!(clock.now(), 3000);
+    }
+
+    /// A 1 kHz 32-bit counter.
+    #[derive(Debug, Clone, Default)]
+    struct SysTick {
+        ticks: Arc<AtomicU32>,
+    }
+
+    impl EmbeddedClock for SysTick {
+        type T = u32;
+        const SCALING_FACTOR: Fraction = Fraction::new(1, 1_000);
+
+        fn try_now(&self) -> Result<Instant<Self>, Error> {
+            Ok(Instant::new(self.ticks.load(Ordering::Relaxed)))
+        }
+    }
+
+    #[test]
+    fn test_time_carries_on_when_counter_wraps() {
+        let systick = SysTick::default();
+        let clock = EmbeddedTimeClock::new(systick.clone());
+        systick.ticks.store(u32::MAX - 99, Ordering::Relaxed);
+        assert_eq!(clock.now(), u32::MAX as u64 - 99);
+
+        // The counter wraps 250 ticks later
+        systick.ticks.store(150, Ordering::Relaxed);
+        assert_eq!(clock.now(), u32::MAX as u64 + 151);
+        systick.ticks.store(1150, Ordering::Relaxed);
+        assert_eq!(clock.now(), u32::MAX as u64 + 1151);
+    }
+
+    #[test]
+    fn test_limiter_on_embedded_time_clock() {
+        let rtc = Rtc::default();
+        let bucket = TokenBucket::with_clock(2, 4.0, EmbeddedTimeClock::new(rtc.clone()));
+        assert!(bucket.try_acquire(2).is_ok());
+        assert!(bucket.try_acquire(1).is_err());
+
+        // A quarter second later, one token has refilled
+        rtc.ticks.store(8192, Ordering::Relaxed);
+        assert!(bucket.try_acquire(1).is_ok());
+        assert!(bucket.try_acquire(1).is_err());
+    }
+}
@@ -2,25 +2,210 @@
 //!
 //! This module defines the error types used throughout the crate, including
 //! rate limit exceeded errors and configuration errors.
+//!
+//! # Propagating with `?`
+//!
+//! [`RateLimitError`] is `Send + Sync + 'static` and, with the `std` feature, implements
+//! [`std::error::Error`]. It therefore converts into `Box<dyn Error + Send + Sync>`,
+//! `anyhow::Error` and `eyre::Report` through their blanket `From` impls, so handlers
+//! can propagate rejections with `?`:
+//!
+//! ```
+//! use bucketboss::{RateLimiter, TokenBucket};
+//!
+//! fn handle(limiter: &TokenBucket) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+//!     limiter.try_acquire(1)?;
+//!     Ok(())
+//! }
+//!
+//! let limiter = TokenBucket::new(1, 1.0);
+//! assert!(handle(&limiter).is_ok());
+//! assert!(handle(&limiter).is_err());
+//! ```
+//!
+//! To map rejections to a response such as HTTP 429, give the application's error type
+//! a variant holding a [`RateLimited`] and a `From` impl for it (with `thiserror`, a
+//! `#[from]` attribute on the variant). The wrapper keeps the rejection as the error's
+//! source and provides the status code and retry delay to render it with.
+//!
+//! # Serialization
+//!
+//! With the `serde` feature, [`RateLimitError`] serializes as an internally tagged
+//! object, so rejections can be returned across a JSON boundary as they are:
+//!
+//! ```json
+//! {"kind":"rate_limit_exceeded","requested":5,"available":2,"retry_after_ms":1000,"rejected_by":null}
+//! {"kind":"invalid_configuration","reason":"capacity must be greater than 0"}
+//! ```
+//!
+//! Deserializing also needs the `std` or `alloc` feature, since the `reason` and the
+//! `rejected_by` label are deserialized as owned strings.
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::{boxed::Box, string::String};
+use core::{fmt, hash, ops::Deref};
 
-use core::fmt;
+/// Text carried by a [`RateLimitError`], such as the reason a configuration is invalid
+/// or the label of the limiter that rejected a request.
+///
+/// The text is either a `&'static str` or, with the `std` or `alloc` feature, a string
+/// built at runtime, for example to name the configuration field that failed
+/// validation. The type is the same with every feature set; only the conversion from
+/// `String` needs an allocator. It dereferences to `str`, and texts compare by content.
+#[derive(Clone)]
+pub struct ErrorText(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Static(&'static str),
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    Owned(Box<str>),
+}
+
+impl ErrorText {
+    /// Creates a text from a string literal.
+    pub const fn new(text: &'static str) -> Self {
+        Self(Repr::Static(text))
+    }
+
+    /// Returns the text as a string slice.
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            Repr::Static(text) => text,
+            #[cfg(any(feature = "std", feature = "alloc"))]
+            Repr::Owned(text) => text,
+        }
+    }
+}
+
+impl Deref for ErrorText {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for ErrorText {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&'static str> for ErrorText {
+    fn from(text: &'static str) -> Self {
+        Self::new(text)
+    }
+}
+
+#[cfg(any(feature = "std", feature = "alloc"))]
+impl From<String> for ErrorText {
+    fn from(text: String) -> Self {
+        Self(Repr::Owned(text.into_boxed_str()))
+    }
+}
+
+impl PartialEq for ErrorText {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for ErrorText {}
+
+impl PartialEq<str> for ErrorText {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for ErrorText {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl hash::Hash for ErrorText {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl fmt::Debug for ErrorText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for ErrorText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ErrorText {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(all(feature = "serde", any(feature = "std", feature = "alloc")))]
+impl<'de> serde::Deserialize<'de> for ErrorText {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
 
 /// The error type for rate limiting operations.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///
+/// The error is `Clone` but not `Copy`, with every feature set, since it can carry
+/// text built at runtime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(
+    all(feature = "serde", any(feature = "std", feature = "alloc")),
+    derive(serde::Deserialize)
+)]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
 pub enum RateLimitError {
     /// The rate limit has been exceeded.
+    ///
+    /// The variant is non-exhaustive so that details can be added without breaking
+    /// downstream matches: construct it with [`rate_limit_exceeded`](Self::rate_limit_exceeded)
+    /// and match it with `..`.
+    #[non_exhaustive]
     RateLimitExceeded {
         /// The number of tokens that were requested.
         requested: u32,
-        /// The number of tokens currently available.
+        /// The number of tokens available when the request was rejected.
+        ///
+        /// The buckets report the count their decision was based on, so under
+        /// contention this may already be out of date, but it is always a state the
+        /// limiter was really in and less than `requested`. The exception is a
+        /// zero-token probe rejected under
+        /// [`ZeroTokenPolicy::ReflectAvailability`](crate::ZeroTokenPolicy::ReflectAvailability),
+        /// which reports 0 available for 0 requested.
         available: u32,
         /// The time in milliseconds until the next token becomes available.
         retry_after_ms: u64,
+        /// The label of the limiter that rejected the request, if known.
+        ///
+        /// Plain limiters leave this as `None`; wrappers such as `LabeledLimiter` fill it
+        /// in so that a rejection from a stack of limiters names the one responsible.
+        #[cfg_attr(feature = "serde", serde(default))]
+        rejected_by: Option<ErrorText>,
     },
     /// The requested configuration is invalid.
     InvalidConfiguration {
         /// A description of what made the configuration invalid.
-        reason: &'static str,
+        reason: ErrorText,
     },
 }
 
@@ -31,12 +216,78 @@ impl RateLimitError {
             requested,
             available,
             retry_after_ms,
+            rejected_by: None,
+        }
+    }
+
+    /// Attributes a `RateLimitExceeded` error to the limiter labelled `label`.
+    ///
+    /// Other errors are returned unchanged.
+    pub fn with_rejected_by(self, label: impl Into<ErrorText>) -> Self {
+        match self {
+            Self::RateLimitExceeded {
+                requested,
+                available,
+                retry_after_ms,
+                ..
+            } => Self::RateLimitExceeded {
+                requested,
+                available,
+                retry_after_ms,
+                rejected_by: Some(label.into()),
+            },
+            other => other,
+        }
+    }
+
+    /// If this is a `RateLimitExceeded` error, returns the label of the limiter that
+    /// rejected the request, if known.
+    ///
+    /// This is unrelated to [`std::error::Error::source`], which stays `None`: a
+    /// rejection has no underlying cause.
+    pub fn rejected_by(&self) -> Option<&str> {
+        match self {
+            Self::RateLimitExceeded { rejected_by, .. } => rejected_by.as_deref(),
+            _ => None,
         }
     }
 
     /// Creates a new `InvalidConfiguration` error.
     pub fn invalid_config(reason: &'static str) -> Self {
-        Self::InvalidConfiguration { reason }
+        Self::InvalidConfiguration {
+            reason: ErrorText::new(reason),
+        }
+    }
+
+    /// Creates a new `InvalidConfiguration` error with a reason built at runtime, such
+    /// as one naming the configuration field that failed validation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bucketboss::RateLimitError;
+    ///
+    /// let field = "limits.api.burst";
+    /// let err = RateLimitError::invalid_config_owned(format!("{field} must be greater than 0"));
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "invalid configuration: limits.api.burst must be greater than 0"
+    /// );
+    /// ```
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn invalid_config_owned(reason: String) -> Self {
+        Self::InvalidConfiguration {
+            reason: reason.into(),
+        }
+    }
+
+    /// If this is an `InvalidConfiguration` error, returns what made the configuration
+    /// invalid.
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            Self::InvalidConfiguration { reason } => Some(reason),
+            _ => None,
+        }
     }
 
     /// Returns whether this error indicates a rate limit was exceeded.
@@ -65,11 +316,18 @@ impl fmt::Display for RateLimitError {
                 requested,
                 available,
                 retry_after_ms,
-            } => write!(
-                f,
-                "rate limit exceeded: requested {} tokens, but only {} available (retry after {}ms)",
-                requested, available, retry_after_ms
-            ),
+                rejected_by,
+            } => {
+                write!(
+                    f,
+                    "rate limit exceeded: requested {} tokens, but only {} available (retry after {}ms)",
+                    requested, available, retry_after_ms
+                )?;
+                if let Some(label) = rejected_by {
+                    write!(f, " by {}", label)?;
+                }
+                Ok(())
+            }
             Self::InvalidConfiguration { reason } => write!(f, "invalid configuration: {}", reason),
         }
     }
@@ -78,6 +336,122 @@ impl fmt::Display for RateLimitError {
 #[cfg(feature = "std")]
 impl std::error::Error for RateLimitError {}
 
+// Error-reporting crates such as `anyhow` require errors to be shareable across threads
+#[cfg(feature = "std")]
+const _: () = {
+    const fn assert_error<E: std::error::Error + Send + Sync + 'static>() {}
+    assert_error::<RateLimitError>();
+};
+
+/// A [`RateLimitError`] wrapped for use as the source of an application error.
+///
+/// `RateLimitError` itself reports no [`source`](std::error::Error::source), so an
+/// application error holding it with `thiserror`'s `#[from]` shows only its own
+/// message. Wrapping it in `RateLimited` instead keeps the limiter's message as the
+/// source, and gives handlers the retry delay and HTTP status to respond with.
+///
+/// # Examples
+///
+/// ```
+/// use std::{error::Error, fmt, time::Duration};
+///
+/// use bucketboss::{RateLimited, RateLimiter, TokenBucket};
+///
+/// #[derive(Debug)]
+/// enum AppError {
+///     // With `thiserror`: `#[error("too many requests")] Limited(#[from] RateLimited)`
+///     Limited(RateLimited),
+/// }
+///
+/// impl From<RateLimited> for AppError {
+///     fn from(err: RateLimited) -> Self {
+///         Self::Limited(err)
+///     }
+/// }
+///
+/// impl fmt::Display for AppError {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         f.write_str("too many requests")
+///     }
+/// }
+///
+/// impl Error for AppError {
+///     fn source(&self) -> Option<&(dyn Error + 'static)> {
+///         match self {
+///             Self::Limited(err) => Some(err),
+///         }
+///     }
+/// }
+///
+/// fn handle(limiter: &TokenBucket) -> Result<(), AppError> {
+///     limiter.try_acquire(1).map_err(RateLimited::from)?;
+///     Ok(())
+/// }
+///
+/// let limiter = TokenBucket::new(1, 1.0);
+/// assert!(handle(&limiter).is_ok());
+///
+/// let AppError::Limited(err) = handle(&limiter).unwrap_err();
+/// assert_eq!(err.status_code(), 429);
+/// assert_eq!(err.retry_after(), Some(Duration::from_secs(1)));
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimited(RateLimitError);
+
+#[cfg(feature = "std")]
+impl RateLimited {
+    /// Returns the wrapped error.
+    pub fn get_ref(&self) -> &RateLimitError {
+        &self.0
+    }
+
+    /// Consumes the wrapper, returning the wrapped error.
+    pub fn into_inner(self) -> RateLimitError {
+        self.0
+    }
+
+    /// Returns how long to wait before retrying, or `None` if the error is not a
+    /// rejection or the request can never succeed.
+    pub fn retry_after(&self) -> Option<std::time::Duration> {
+        self.0
+            .retry_after_ms()
+            .filter(|&ms| ms > 0)
+            .map(std::time::Duration::from_millis)
+    }
+
+    /// Returns the HTTP status code to respond with: 429 (Too Many Requests) for a
+    /// rejection and 500 (Internal Server Error) for an invalid configuration.
+    pub fn status_code(&self) -> u16 {
+        if self.0.is_rate_limit_exceeded() {
+            429
+        } else {
+            500
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<RateLimitError> for RateLimited {
+    fn from(err: RateLimitError) -> Self {
+        Self(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RateLimited {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
 /// A specialized `Result` type for rate limiting operations.
 pub type Result<T> = core::result::Result<T, RateLimitError>;
 
@@ -107,5 +481,137 @@ mod tests {
             err.to_string(),
             "invalid configuration: capacity must be greater than 0"
         );
+        assert_eq!(err.reason(), Some("capacity must be greater than 0"));
+        assert_eq!(RateLimitError::rate_limit_exceeded(1, 0, 1).reason(), None);
+    }
+
+    #[test]
+    fn test_invalid_config_owned() {
+        let field = String::from("limits.api.rate");
+        let err = RateLimitError::invalid_config_owned(format!("{field} must be positive"));
+        assert!(err.is_invalid_config());
+        assert_eq!(err.reason(), Some("limits.api.rate must be positive"));
+        assert_eq!(
+            err.to_string(),
+            "invalid configuration: limits.api.rate must be positive"
+        );
+
+        // Owned and borrowed reasons with the same text are equal
+        assert_eq!(
+            RateLimitError::invalid_config_owned(String::from("bad")),
+            RateLimitError::invalid_config("bad")
+        );
+    }
+
+    #[test]
+    fn test_error_text_compares_by_content() {
+        let text = ErrorText::new("bad");
+        assert_eq!(text, ErrorText::from(String::from("bad")));
+        assert_eq!(text, "bad");
+        assert_eq!(text.len(), 3);
+        assert_eq!(format!("{text} {text:?}"), "bad \"bad\"");
+    }
+
+    #[test]
+    fn test_rejected_by() {
+        let err = RateLimitError::rate_limit_exceeded(5, 2, 1000);
+        assert_eq!(err.rejected_by(), None);
+
+        let err = err.with_rejected_by("per-minute");
+        assert_eq!(err.rejected_by(), Some("per-minute"));
+        assert_eq!(err.retry_after_ms(), Some(1000));
+        assert_eq!(
+            err.to_string(),
+            "rate limit exceeded: requested 5 tokens, but only 2 available (retry after 1000ms) by per-minute"
+        );
+
+        let err = RateLimitError::invalid_config("bad").with_rejected_by("per-minute");
+        assert_eq!(err.rejected_by(), None);
+    }
+
+    #[test]
+    fn test_converts_into_anyhow() {
+        fn handler() -> anyhow::Result<()> {
+            Err(RateLimitError::rate_limit_exceeded(3, 1, 250))?;
+            Ok(())
+        }
+
+        let err = handler().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "rate limit exceeded: requested 3 tokens, but only 1 available (retry after 250ms)"
+        );
+        let original = err.downcast_ref::<RateLimitError>().unwrap();
+        assert_eq!(original.retry_after_ms(), Some(250));
+    }
+
+    #[test]
+    fn test_rate_limited_wraps_error() {
+        fn handler() -> anyhow::Result<()> {
+            Err(RateLimited::from(RateLimitError::rate_limit_exceeded(
+                3, 1, 250,
+            )))?;
+            Ok(())
+        }
+
+        let err = handler().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "rate limit exceeded: requested 3 tokens, but only 1 available (retry after 250ms)"
+        );
+        let limited = err.downcast_ref::<RateLimited>().unwrap();
+        assert_eq!(limited.status_code(), 429);
+        assert_eq!(
+            limited.retry_after(),
+            Some(std::time::Duration::from_millis(250))
+        );
+        let source = std::error::Error::source(limited).unwrap();
+        assert_eq!(
+            source.downcast_ref::<RateLimitError>(),
+            Some(limited.get_ref())
+        );
+
+        let limited = RateLimited::from(RateLimitError::rate_limit_exceeded(3, 1, 0));
+        assert_eq!(limited.retry_after(), None);
+        let limited = RateLimited::from(RateLimitError::invalid_config("bad"));
+        assert_eq!(limited.status_code(), 500);
+        assert_eq!(limited.into_inner(), RateLimitError::invalid_config("bad"));
+    }
+
+    #[test]
+    #[cfg(all(feature = "serde", any(feature = "std", feature = "alloc")))]
+    fn test_serde_round_trip() {
+        let err = RateLimitError::rate_limit_exceeded(5, 2, 1000).with_rejected_by("per-minute");
+        let json = serde_json::to_string(&err).unwrap();
+        assert_eq!(
+            json,
+            r#"{"kind":"rate_limit_exceeded","requested":5,"available":2,"retry_after_ms":1000,"rejected_by":"per-minute"}"#
+        );
+        assert_eq!(serde_json::from_str::<RateLimitError>(&json).unwrap(), err);
+
+        let err = RateLimitError::invalid_config("capacity must be greater than 0");
+        let json = serde_json::to_string(&err).unwrap();
+        assert_eq!(
+            json,
+            r#"{"kind":"invalid_configuration","reason":"capacity must be greater than 0"}"#
+        );
+        let decoded: RateLimitError = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, err);
+
+        // Labels built at runtime round-trip like static ones
+        let label = format!("tier-{}", 3);
+        let err = RateLimitError::rate_limit_exceeded(1, 0, 7).with_rejected_by(label);
+        let json = serde_json::to_string(&err).unwrap();
+        let decoded: RateLimitError = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.rejected_by(), Some("tier-3"));
+        assert_eq!(decoded, err);
+
+        // A missing label deserializes as unattributed
+        let json =
+            r#"{"kind":"rate_limit_exceeded","requested":1,"available":0,"retry_after_ms":7}"#;
+        assert_eq!(
+            serde_json::from_str::<RateLimitError>(json).unwrap(),
+            RateLimitError::rate_limit_exceeded(1, 0, 7)
+        );
     }
 }
@@ -0,0 +1,352 @@
+//! Fair sharing of a global limit between writers.
+//!
+//! Capping each writer of a shared resource separately does not bound the total load,
+//! and a single global cap lets one greedy writer take the whole budget.
+//! [`FairGlobalLimiter`] combines both: every writer has a limiter of its own, and all
+//! writers draw from one global limiter whose budget is shared out by deficit
+//! round-robin accounting, so a writer that keeps the global limiter drained cannot
+//! starve the others.
+//!
+//! This module is only available with the `std` feature.
+
+use core::{fmt, hash::Hash, time::Duration};
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::{RateLimitError, Result},
+    traits::{RateLimiter, Wrapper},
+};
+
+#[derive(Debug)]
+struct Writer<L> {
+    limiter: L,
+    /// The global tokens this writer is owed, its deficit in round-robin terms.
+    deficit: f64,
+    /// When the writer was last used.
+    last_used: u64,
+}
+
+#[derive(Debug)]
+struct State<K, L> {
+    writers: HashMap<K, Writer<L>>,
+    /// When the writers' deficits were last topped up.
+    last_refill: u64,
+}
+
+/// A global limiter shared fairly between writers that each have a limiter of their own.
+///
+/// Each writer's limiter is created by the factory the first time the writer is seen.
+/// A request must be admitted by both the writer's limiter and the global one, and is
+/// charged to both.
+///
+/// The global budget is shared out as in deficit round-robin. The global refill is
+/// credited to the tracked writers in equal parts, as their deficit, which is capped at
+/// an equal share of the global capacity so that an idle writer cannot bank more than
+/// its share of a burst. A writer spends its deficit first. Beyond that, it may only
+/// take global tokens that are not owed to the other writers: the global limiter's
+/// available tokens must cover the request plus the other writers' deficits. A writer
+/// that keeps the global limiter drained therefore gets the tokens nobody else is owed,
+/// while a steady writer still gets its share of the global rate.
+///
+/// Rejections for fairness report the wait until the global refill would cover the
+/// shortfall, assuming nobody else acquires meanwhile.
+///
+/// The writers are kept in a map behind one lock, and every acquire tops up all of
+/// their deficits, so this is meant for a modest number of writers. The global limiter
+/// should only be used through this limiter, or the deficits overstate what it holds.
+///
+/// # Examples
+///
+/// ```
+/// use bucketboss::{clock::MockClock, FairGlobalLimiter, TokenBucket};
+///
+/// // Each writer may burst to 10 tokens, all writers together to 10
+/// let clock = MockClock::new(0);
+/// let limiter = FairGlobalLimiter::with_clock(
+///     TokenBucket::with_clock(10, 10.0, clock.clone()),
+///     || TokenBucket::with_clock(10, 10.0, clock.clone()),
+///     clock.clone(),
+/// );
+///
+/// assert!(limiter.try_acquire(&"alice", 5).is_ok());
+/// assert!(limiter.try_acquire(&"bob", 5).is_ok());
+/// assert!(limiter.try_acquire(&"alice", 1).is_err());
+/// ```
+pub struct FairGlobalLimiter<K, L, F, G, C = SystemClock> {
+    global: G,
+    factory: F,
+    clock: C,
+    idle_ms: Option<u64>,
+    state: Mutex<State<K, L>>,
+}
+
+impl<K, L, F, G> FairGlobalLimiter<K, L, F, G, SystemClock>
+where
+    K: Eq + Hash + Clone,
+    L: RateLimiter,
+    F: Fn() -> L,
+    G: RateLimiter,
+{
+    /// Creates a new limiter sharing `global` between writers whose own limiters are
+    /// built by `factory`.
+    pub fn new(global: G, factory: F) -> Self {
+        Self::with_clock(global, factory, SystemClock)
+    }
+}
+
+impl<K, L, F, G, C> FairGlobalLimiter<K, L, F, G, C>
+where
+    K: Eq + Hash + Clone,
+    L: RateLimiter,
+    F: Fn() -> L,
+    G: RateLimiter,
+    C: Clock,
+{
+    /// Creates a new limiter with the specified clock.
+    ///
+    /// The clock paces the deficit accounting; the limiters keep their own.
+    pub fn with_clock(global: G, factory: F, clock: C) -> Self {
+        let now = clock.now();
+        Self {
+            global,
+            factory,
+            clock,
+            idle_ms: None,
+            state: Mutex::new(State {
+                writers: HashMap::new(),
+                last_refill: now,
+            }),
+        }
+    }
+
+    /// Stops tracking writers that have not acquired for `idle`.
+    ///
+    /// Idle writers are evicted on the next acquire, which frees the deficit they are
+    /// owed for the others. An evicted writer starts over with a fresh limiter and no
+    /// deficit.
+    pub fn with_idle_eviction(mut self, idle: Duration) -> Self {
+        self.idle_ms = Some(idle.as_millis().min(u64::MAX as u128) as u64);
+        self
+    }
+
+    /// Returns the global limiter.
+    pub fn global(&self) -> &G {
+        &self.global
+    }
+
+    /// Returns the global tokens `writer` is owed, or `None` if it is not tracked.
+    pub fn deficit(&self, writer: &K) -> Option<f64> {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+        state.writers.get(writer).map(|writer| writer.deficit)
+    }
+
+    /// Returns the number of writers currently tracked.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().writers.len()
+    }
+
+    /// Returns `true` if no writers are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Attempts to acquire `tokens` for `writer`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the writer's limiter, the global limiter and the fair share all
+    ///   admitted the request
+    /// * `Err(RateLimitError::RateLimitExceeded)` otherwise, from whichever rejected it
+    pub fn try_acquire(&self, writer: &K, tokens: u32) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+
+        let now = self.clock.now();
+        if !state.writers.contains_key(writer) {
+            let _ = state.writers.insert(
+                writer.clone(),
+                Writer {
+                    limiter: (self.factory)(),
+                    deficit: 0.0,
+                    last_used: now,
+                },
+            );
+        }
+
+        let owed_to_others: f64 = state
+            .writers
+            .iter()
+            .filter(|&(key, _)| key != writer)
+            .map(|(_, other)| other.deficit)
+            .sum();
+        let entry = state.writers.get_mut(writer).unwrap();
+        entry.last_used = now;
+
+        // The writer's limiter is only used under the lock, so checking it up front
+        // keeps a rejection from wasting the global tokens
+        match entry.limiter.time_until_available_ms(tokens) {
+            Some(0) => {}
+            wait => {
+                return Err(RateLimitError::rate_limit_exceeded(
+                    tokens,
+                    entry.limiter.available_tokens(),
+                    wait.unwrap_or(0),
+                ))
+            }
+        }
+
+        let tokens_f = tokens as f64;
+        let available = self.global.available_tokens_u64() as f64;
+        let spare = (available - owed_to_others).max(0.0);
+        let shortfall = tokens_f - entry.deficit.max(0.0) - spare;
+        if shortfall > 0.0 {
+            let rate = self.global.effective_rate_per_second();
+            let retry_after_ms = if rate > 0.0 {
+                (shortfall * 1000.0 / rate).ceil() as u64
+            } else {
+                0
+            };
+            return Err(RateLimitError::rate_limit_exceeded(
+                tokens,
+                spare.min(u32::MAX as f64) as u32,
+                retry_after_ms.max(1),
+            ));
+        }
+
+        self.global.try_acquire(tokens)?;
+        entry.limiter.try_acquire(tokens)?;
+        entry.deficit = (entry.deficit - tokens_f).max(0.0);
+        Ok(())
+    }
+
+    /// Credits the global refill since the last call to the writers, evicting idle
+    /// writers first.
+    fn refill(&self, state: &mut State<K, L>) {
+        let now = self.clock.now();
+        if let Some(idle_ms) = self.idle_ms {
+            state
+                .writers
+                .retain(|_, writer| writer.last_used.saturating_add(idle_ms) > now);
+        }
+
+        let elapsed_ms = now.saturating_sub(state.last_refill);
+        state.last_refill = now;
+        if state.writers.is_empty() {
+            return;
+        }
+
+        let writers = state.writers.len() as f64;
+        let share = elapsed_ms as f64 * self.global.effective_rate_per_second() / 1000.0 / writers;
+        let cap = self.global.capacity_u64() as f64 / writers;
+        for writer in state.writers.values_mut() {
+            writer.deficit = (writer.deficit + share).min(cap);
+        }
+    }
+}
+
+impl<K, L, F, G, C> fmt::Debug for FairGlobalLimiter<K, L, F, G, C>
+where
+    G: fmt::Debug,
+    C: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let writers = self.state.lock().map(|state| state.writers.len()).ok();
+        f.debug_struct("FairGlobalLimiter")
+            .field("global", &self.global)
+            .field("clock", &self.clock)
+            .field("idle_ms", &self.idle_ms)
+            .field("writers", &writers)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The wrapped value is the global limiter.
+impl<K, L, F, G, C> Wrapper for FairGlobalLimiter<K, L, F, G, C> {
+    type Inner = G;
+
+    fn get_ref(&self) -> &G {
+        &self.global
+    }
+
+    fn get_mut(&mut self) -> &mut G {
+        &mut self.global
+    }
+
+    fn into_inner(self) -> G {
+        self.global
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, TokenBucket};
+
+    #[test]
+    fn test_greedy_writer_cannot_starve_steady_writer() {
+        let clock = MockClock::new(0);
+        let global_clock = clock.clone();
+        let writer_clock = clock.clone();
+        let limiter = FairGlobalLimiter::with_clock(
+            TokenBucket::with_clock(10, 100.0, global_clock),
+            move || TokenBucket::with_clock(1000, 1000.0, writer_clock.clone()),
+            clock.clone(),
+        );
+
+        // Both writers are seen before the contention starts
+        assert!(limiter.try_acquire(&"steady", 1).is_ok());
+        assert!(limiter.try_acquire(&"greedy", 1).is_ok());
+
+        // The greedy writer takes all it can every millisecond; the steady one asks for
+        // a token every 25 ms, well within its half of the 100 tokens per second
+        let (mut steady, mut greedy) = (0, 0);
+        for ms in 1..=10_000 {
+            clock.advance(1);
+            if ms % 25 == 0 && limiter.try_acquire(&"steady", 1).is_ok() {
+                steady += 1;
+            }
+            while limiter.try_acquire(&"greedy", 1).is_ok() {
+                greedy += 1;
+            }
+        }
+
+        assert_eq!(steady, 400);
+        // The greedy writer gets the rest of the global rate, so nothing is wasted
+        assert!((590..=610).contains(&greedy), "greedy admitted {greedy}");
+    }
+
+    #[test]
+    fn test_per_writer_and_global_caps() {
+        let clock = MockClock::new(0);
+        let global_clock = clock.clone();
+        let writer_clock = clock.clone();
+        let limiter = FairGlobalLimiter::with_clock(
+            TokenBucket::with_clock(10, 1.0, global_clock),
+            move || TokenBucket::with_clock(4, 1.0, writer_clock.clone()),
+            clock.clone(),
+        )
+        .with_idle_eviction(Duration::from_secs(60));
+
+        // The writer's own cap applies first, without spending global tokens
+        assert!(limiter.try_acquire(&"alice", 4).is_ok());
+        let err = limiter.try_acquire(&"alice", 1).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(1000));
+        assert_eq!(limiter.global().available_tokens(), 6);
+
+        // Once the others are owed part of the budget, a writer cannot take it
+        assert!(limiter.try_acquire(&"bob", 4).is_ok());
+        assert!(limiter.try_acquire(&"carol", 2).is_ok());
+        clock.advance(3000);
+        assert!((limiter.deficit(&"alice").unwrap() - 1.0).abs() < 1e-9);
+        assert!(limiter.try_acquire(&"carol", 3).is_err());
+        assert!(limiter.try_acquire(&"carol", 1).is_ok());
+        assert!(limiter.try_acquire(&"alice", 1).is_ok());
+        assert_eq!(limiter.len(), 3);
+
+        clock.advance(60_000);
+        assert_eq!(limiter.deficit(&"alice"), None);
+        assert!(limiter.is_empty());
+    }
+}
@@ -0,0 +1,56 @@
+//! Rounding of `f64` values without `std`.
+//!
+//! `f64::ceil` and `f64::round` are provided by `std`, not `core`, so the arithmetic
+//! shared with `no_std` builds goes through these functions. With `std` they are the
+//! inherent methods; without it they truncate through an integer cast, which is exact
+//! for every value with a fractional part.
+
+/// The smallest magnitude at which every `f64` is a whole number.
+#[cfg(not(feature = "std"))]
+const INTEGRAL: f64 = 4_503_599_627_370_496.0;
+
+/// Returns the smallest whole number not less than `value`.
+#[inline]
+pub(crate) fn ceil(value: f64) -> f64 {
+    #[cfg(feature = "std")]
+    {
+        value.ceil()
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        // Also passes NaN and the infinities through unchanged
+        if value.is_nan() || value.abs() >= INTEGRAL {
+            return value;
+        }
+        let truncated = value as i64 as f64;
+        if truncated < value {
+            truncated + 1.0
+        } else {
+            truncated
+        }
+    }
+}
+
+/// Returns the whole number nearest to `value`, rounding halfway cases away from zero.
+#[inline]
+pub(crate) fn round(value: f64) -> f64 {
+    #[cfg(feature = "std")]
+    {
+        value.round()
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        if value.is_nan() || value.abs() >= INTEGRAL {
+            return value;
+        }
+        let truncated = value as i64 as f64;
+        let fraction = value - truncated;
+        if fraction >= 0.5 {
+            truncated + 1.0
+        } else if fraction <= -0.5 {
+            truncated - 1.0
+        } else {
+            truncated
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! Generic cell rate algorithm (GCRA) implementation.
+//!
+//! GCRA, also known as virtual scheduling, gives the same admissions as a token bucket
+//! but keeps a single timestamp instead of a level and a last-update time: the
+//! theoretical arrival time (TAT) at which the limiter would be idle again. Each
+//! admission pushes the TAT one emission interval per token further out, and a request
+//! is admitted as long as that does not push it more than the burst tolerance past now.
+//! With only one atomic to update, it is the algorithm of choice for many API gateways.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{
+    backoff::Backoff,
+    clock::{now_ns, Clock, SystemClock, NANOS_PER_MS},
+    error::Result,
+    float,
+    outcome::{saturate_u32, Outcome, WideOutcome},
+    traits::RateLimiter,
+    validate,
+};
+
+/// The highest rate, in tokens per second, that a [`Gcra`] accepts.
+///
+/// The emission interval is a whole number of nanoseconds, so faster rates cannot be
+/// represented. Close to this limit the rounding of the interval is significant: at
+/// 3e8 per second it is 3 ns instead of 3.33 ns, and [`rate_per_second`] reports the
+/// 3.33e8 per second actually enforced.
+///
+/// [`rate_per_second`]: RateLimiter::rate_per_second
+pub const MAX_GCRA_RATE: f64 = 1e9;
+
+/// A thread-safe GCRA rate limiter.
+///
+/// Times are kept in nanoseconds internally so that emission intervals that are not a
+/// whole number of milliseconds do not drift. The interval is rounded to the nearest
+/// nanosecond, which is exact to within 0.05% up to a million tokens per second.
+///
+/// # Examples
+///
+/// ```
+/// use bucketboss::{Gcra, RateLimiter};
+///
+/// // 10 requests per second, in bursts of up to 3
+/// let limiter = Gcra::new(10.0, 3);
+///
+/// assert!(limiter.try_acquire(3).is_ok());
+/// assert!(limiter.try_acquire(1).is_err());
+/// ```
+#[derive(Debug)]
+pub struct Gcra<C = SystemClock> {
+    /// The clock used to track time.
+    clock: C,
+    /// The theoretical arrival time, in nanoseconds.
+    tat: AtomicU64,
+    /// The time one token takes to replenish, in nanoseconds.
+    emission_interval_ns: u64,
+    /// The largest number of tokens admitted at once.
+    burst: u64,
+}
+
+#[cfg(feature = "std")]
+impl Gcra<SystemClock> {
+    /// Creates a new `Gcra` admitting `tokens_per_second` in bursts of up to `burst`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tokens_per_second` is not positive or exceeds [`MAX_GCRA_RATE`], or
+    /// if `burst` is 0. With the `no-panic` feature this only happens in debug builds;
+    /// release builds clamp the burst to 1 and the rate into the valid range instead.
+    pub fn new(tokens_per_second: f64, burst: u32) -> Self {
+        Self::with_clock(tokens_per_second, burst, SystemClock)
+    }
+}
+
+impl<C> Gcra<C>
+where
+    C: Clock,
+{
+    /// Creates a new `Gcra` with the specified clock.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`Gcra::new`].
+    pub fn with_clock(tokens_per_second: f64, burst: u32, clock: C) -> Self {
+        let tokens_per_second =
+            validate::rate(tokens_per_second, "tokens_per_second must be positive");
+        let tokens_per_second = validate::rate_at_most(
+            tokens_per_second,
+            MAX_GCRA_RATE,
+            "tokens_per_second must not exceed MAX_GCRA_RATE",
+        );
+        let burst = validate::capacity(burst as u64, "burst must be greater than 0");
+
+        let emission_interval_ns = float::round(1e9 / tokens_per_second).max(1.0) as u64;
+        let now = now_ns(&clock);
+        Self {
+            clock,
+            tat: AtomicU64::new(now),
+            emission_interval_ns,
+            burst,
+        }
+    }
+
+    /// Returns the time one token takes to replenish, in nanoseconds.
+    pub fn emission_interval_ns(&self) -> u64 {
+        self.emission_interval_ns
+    }
+
+    /// Returns how far the TAT may run ahead of now, in nanoseconds.
+    fn tolerance_ns(&self) -> u64 {
+        self.burst.saturating_mul(self.emission_interval_ns)
+    }
+
+    /// Returns the number of tokens that could be admitted at `now` given `tat`.
+    fn available_at(&self, now: u64, tat: u64) -> u64 {
+        let headroom = now
+            .saturating_add(self.tolerance_ns())
+            .saturating_sub(tat.max(now));
+        (headroom / self.emission_interval_ns).min(self.burst)
+    }
+
+    /// Returns the wait, in nanoseconds, until `tokens` could be admitted given `tat`.
+    fn wait_ns_at(&self, now: u64, tat: u64, tokens: u64) -> u64 {
+        let new_tat = tat
+            .max(now)
+            .saturating_add(tokens.saturating_mul(self.emission_interval_ns));
+        new_tat
+            .saturating_sub(self.tolerance_ns())
+            .saturating_sub(now)
+    }
+
+    /// Makes the admission decision for `tokens`, in full-width counts.
+    fn decide_wide(&self, tokens: u64) -> WideOutcome {
+        if tokens == 0 {
+            return WideOutcome::Admitted {
+                remaining: self.available_tokens_u64(),
+            };
+        }
+
+        let now = now_ns(&self.clock);
+        if tokens > self.burst {
+            return WideOutcome::Throttled {
+                retry_after_ms: 0, // No wait time since the request can never succeed
+                available: self.available_at(now, self.tat.load(Ordering::Acquire)),
+            };
+        }
+
+        let mut backoff = Backoff::new();
+        loop {
+            let tat = self.tat.load(Ordering::Acquire);
+            let wait_ns = self.wait_ns_at(now, tat, tokens);
+            if wait_ns > 0 {
+                // Report the headroom of the TAT this decision was made on
+                return WideOutcome::Throttled {
+                    retry_after_ms: wait_ns.div_ceil(NANOS_PER_MS),
+                    available: self.available_at(now, tat),
+                };
+            }
+
+            let new_tat = tat
+                .max(now)
+                .saturating_add(tokens * self.emission_interval_ns);
+            if self
+                .tat
+                .compare_exchange(tat, new_tat, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+            {
+                return WideOutcome::Admitted {
+                    remaining: self.available_at(now, new_tat),
+                };
+            }
+
+            // If we couldn't update atomically, back off and retry
+            backoff.spin();
+        }
+    }
+}
+
+impl<C> RateLimiter for Gcra<C>
+where
+    C: Clock,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        self.decide_wide(tokens as u64).into_result(tokens as u64)
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        self.decide_wide(tokens).into_result(tokens)
+    }
+
+    fn acquire_outcome(&self, tokens: u32) -> Outcome {
+        self.decide_wide(tokens as u64)
+            .narrow(self.capacity_u64(), self.rate_per_second())
+    }
+
+    fn available_tokens(&self) -> u32 {
+        saturate_u32(self.available_tokens_u64())
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        self.available_at(now_ns(&self.clock), self.tat.load(Ordering::Acquire))
+    }
+
+    fn capacity(&self) -> u32 {
+        saturate_u32(self.burst)
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.burst
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        1e9 / self.emission_interval_ns as f64
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        let now = now_ns(&self.clock);
+        match self.wait_ns_at(now, self.tat.load(Ordering::Acquire), 1) {
+            0 => None,
+            wait_ns => Some(wait_ns.div_ceil(NANOS_PER_MS)),
+        }
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        if tokens as u64 > self.burst {
+            return None;
+        }
+        let now = now_ns(&self.clock);
+        let wait_ns = self.wait_ns_at(now, self.tat.load(Ordering::Acquire), tokens as u64);
+        Some(wait_ns.div_ceil(NANOS_PER_MS))
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        let now = self.clock.now();
+        self.time_until_available_ms(tokens)
+            .map(|wait_ms| now.saturating_add(wait_ms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    #[test]
+    fn test_gcra_burst_then_spacing() {
+        let clock = MockClock::new(0);
+        let limiter = Gcra::with_clock(10.0, 3, clock.clone());
+        assert_eq!(limiter.capacity(), 3);
+        assert_eq!(limiter.rate_per_second(), 10.0);
+
+        assert!(limiter.try_acquire(3).is_ok());
+        let err = limiter.try_acquire(1).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(100));
+        assert_eq!(limiter.time_until_next_token_ms(), Some(100));
+
+        clock.advance(99);
+        assert!(limiter.try_acquire(1).is_err());
+        clock.advance(1);
+        assert!(limiter.try_acquire(1).is_ok());
+
+        // Tokens accrue one emission interval apart, up to the burst
+        clock.advance(250);
+        assert_eq!(limiter.available_tokens(), 2);
+        clock.advance(10_000);
+        assert_eq!(limiter.available_tokens(), 3);
+        assert_eq!(limiter.time_until_next_token_ms(), None);
+    }
+
+    #[test]
+    fn test_gcra_rejections() {
+        let clock = MockClock::new(0);
+        let limiter = Gcra::with_clock(3.0, 4, clock.clone());
+        assert!(limiter.try_acquire(3).is_ok());
+
+        // Two more tokens need one more emission interval of 333.3 ms
+        let err = limiter.try_acquire(2).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(334));
+        assert_eq!(limiter.time_until_available_ms(2), Some(334));
+        assert_eq!(limiter.retry_at(2), Some(334));
+        assert_eq!(err, crate::RateLimitError::rate_limit_exceeded(2, 1, 334));
+
+        // More than the burst can never be admitted
+        assert_eq!(
+            limiter.try_acquire(5).unwrap_err().retry_after_ms(),
+            Some(0)
+        );
+        assert_eq!(limiter.time_until_available_ms(5), None);
+        assert!(limiter.try_acquire(0).is_ok());
+    }
+
+    #[test]
+    fn test_gcra_resolves_fast_rates() {
+        let clock = MockClock::new(0);
+        let limiter = Gcra::with_clock(300_000.0, 1000, clock.clone());
+        assert_eq!(limiter.emission_interval_ns(), 3333);
+        assert_eq!(limiter.rate_per_second(), 1e9 / 3333.0);
+
+        // A millisecond replenishes 300 tokens, not the 333 of a 3 µs interval
+        assert!(limiter.try_acquire(1000).is_ok());
+        clock.advance(1);
+        assert_eq!(limiter.available_tokens(), 300);
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-panic"))]
+    #[should_panic(expected = "tokens_per_second must not exceed MAX_GCRA_RATE")]
+    fn test_gcra_panics_on_rate_above_max() {
+        let _ = Gcra::with_clock(2e9, 1, MockClock::new(0));
+    }
+}
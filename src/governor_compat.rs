@@ -0,0 +1,135 @@
+//! Conversions from the `governor` crate's [`Quota`].
+//!
+//! A `governor` quota is a GCRA configuration: a burst size, the number of cells that
+//! can be admitted at once, and a replenish interval, the time one cell takes to come
+//! back. That is the same limit as a token bucket whose capacity is the burst size and
+//! whose rate is one token per replenish interval:
+//!
+//! | `governor`                 | bucketboss                           |
+//! |----------------------------|--------------------------------------|
+//! | `burst_size()`             | capacity (or burst, for [`Gcra`])    |
+//! | `replenish_interval()`     | `1 / tokens_per_second` seconds      |
+//!
+//! So `Quota::per_second(10)` becomes a capacity of 10 at 10 tokens per second, and
+//! `Quota::per_minute(30).allow_burst(5)` a capacity of 5 at 0.5 tokens per second.
+//!
+//! `governor` stores the interval in whole nanoseconds, so a rate that does not divide
+//! a second evenly comes back very slightly off: `Quota::per_second(3)` replenishes
+//! every 333,333,333 ns, which is 3.000000003 tokens per second.
+//!
+//! This module is only available with the `governor-compat` feature.
+//!
+//! [`Gcra`]: crate::Gcra
+
+use core::{num::NonZeroU32, time::Duration};
+
+use governor::Quota;
+
+/// Converts a `governor` quota into a `(capacity, tokens_per_second)` pair.
+///
+/// # Examples
+///
+/// ```
+/// use std::num::NonZeroU32;
+///
+/// use bucketboss::{from_governor_quota, TokenBucket};
+/// use governor::Quota;
+///
+/// let quota = Quota::per_minute(NonZeroU32::new(30).unwrap()).allow_burst(NonZeroU32::new(5).unwrap());
+/// let (capacity, tokens_per_second) = from_governor_quota(quota);
+/// assert_eq!((capacity, tokens_per_second), (5, 0.5));
+///
+/// let bucket = TokenBucket::new(capacity, tokens_per_second);
+/// # let _ = bucket;
+/// ```
+pub fn from_governor_quota(quota: Quota) -> (u32, f64) {
+    let interval = quota.replenish_interval();
+    (quota.burst_size().get(), 1.0 / interval.as_secs_f64())
+}
+
+/// Converts a `(capacity, tokens_per_second)` pair into a `governor` quota, for
+/// migrating back or running both side by side.
+///
+/// Returns `None` if `capacity` is 0, or if `tokens_per_second` is not positive and
+/// finite or so small that one token takes longer than a [`Duration`] can hold.
+pub fn to_governor_quota(capacity: u32, tokens_per_second: f64) -> Option<Quota> {
+    let burst = NonZeroU32::new(capacity)?;
+    if !(tokens_per_second > 0.0 && tokens_per_second.is_finite()) {
+        return None;
+    }
+    let interval = Duration::try_from_secs_f64(1.0 / tokens_per_second).ok()?;
+    Some(Quota::with_period(interval)?.allow_burst(burst))
+}
+
+#[cfg(test)]
+mod tests {
+    use governor::{clock::FakeRelativeClock, RateLimiter as GovernorLimiter};
+
+    use super::*;
+    use crate::{clock::MockClock, RateLimiter, TokenBucket};
+
+    fn nonzero(n: u32) -> NonZeroU32 {
+        NonZeroU32::new(n).unwrap()
+    }
+
+    /// Drives a `governor` limiter and the converted bucket through the same requests,
+    /// one every `step_ms`, and asserts they agree on every one.
+    fn assert_same_admissions(quota: Quota, step_ms: u64, steps: u64) {
+        let (capacity, tokens_per_second) = from_governor_quota(quota);
+        let clock = MockClock::new(0);
+        let bucket = TokenBucket::with_clock(capacity, tokens_per_second, clock.clone());
+        let governor_clock = FakeRelativeClock::default();
+        let governor = GovernorLimiter::direct_with_clock(quota, governor_clock.clone());
+
+        let mut admitted = 0;
+        for step in 0..steps {
+            let expected = governor.check().is_ok();
+            assert_eq!(
+                bucket.try_acquire(1).is_ok(),
+                expected,
+                "disagreed at {} ms",
+                step * step_ms
+            );
+            admitted += expected as u32;
+            clock.advance(step_ms);
+            governor_clock.advance(Duration::from_millis(step_ms));
+        }
+        assert!(admitted > capacity, "the refill was never exercised");
+    }
+
+    #[test]
+    fn test_quotas_convert_to_equivalent_buckets() {
+        let quota = Quota::per_second(nonzero(10));
+        assert_eq!(from_governor_quota(quota), (10, 10.0));
+        assert_same_admissions(quota, 1, 3000);
+
+        let quota = Quota::per_minute(nonzero(30)).allow_burst(nonzero(5));
+        assert_eq!(from_governor_quota(quota), (5, 0.5));
+        assert_same_admissions(quota, 100, 600);
+
+        let quota = Quota::with_period(Duration::from_millis(150))
+            .unwrap()
+            .allow_burst(nonzero(4));
+        assert_eq!(from_governor_quota(quota), (4, 1.0 / 0.15));
+        assert_same_admissions(quota, 1, 3000);
+
+        // A third of a second is rounded to whole nanoseconds on the governor side
+        let (capacity, tokens_per_second) = from_governor_quota(Quota::per_second(nonzero(3)));
+        assert_eq!(capacity, 3);
+        assert!((tokens_per_second - 3.0).abs() < 1e-8);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let quota = to_governor_quota(20, 4.0).unwrap();
+        assert_eq!(quota.burst_size().get(), 20);
+        assert_eq!(quota.replenish_interval(), Duration::from_millis(250));
+        assert_eq!(from_governor_quota(quota), (20, 4.0));
+
+        assert!(to_governor_quota(0, 4.0).is_none());
+        assert!(to_governor_quota(20, 0.0).is_none());
+        assert!(to_governor_quota(20, f64::NAN).is_none());
+        assert!(to_governor_quota(20, f64::INFINITY).is_none());
+        assert!(to_governor_quota(20, 1e-300).is_none());
+    }
+}
@@ -0,0 +1,264 @@
+//! Free allowances ahead of normal rate limiting.
+//!
+//! Freemium products often grant a number of calls that always succeed before the
+//! regular limit applies ("10 free calls, then rate limited"). [`GraceLimiter`] admits
+//! requests unconditionally until its grace count is used up and then delegates to the
+//! inner limiter. The grace can optionally be refilled on a fixed schedule.
+
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::Result,
+    traits::{RateLimiter, Wrapper},
+};
+
+/// A wrapper that admits the first `grace` requests unconditionally.
+///
+/// Each admitted grace request decrements the counter by one, regardless of how many
+/// tokens it asked for, and does not touch the inner limiter. Once the counter reaches
+/// zero, every request is delegated to the inner limiter. The counter belongs to this
+/// instance only.
+///
+/// Status methods such as `available_tokens` always report the inner limiter.
+#[derive(Debug)]
+pub struct GraceLimiter<L, C = SystemClock> {
+    inner: L,
+    clock: C,
+    grace: u32,
+    remaining: AtomicU32,
+    refill_interval_ms: u64,
+    last_refill: AtomicU64,
+}
+
+#[cfg(feature = "std")]
+impl<L> GraceLimiter<L, SystemClock>
+where
+    L: RateLimiter,
+{
+    /// Creates a new wrapper that admits the first `grace` requests unconditionally.
+    pub fn new(inner: L, grace: u32) -> Self {
+        Self::with_clock(inner, grace, SystemClock)
+    }
+}
+
+impl<L, C> GraceLimiter<L, C>
+where
+    L: RateLimiter,
+    C: Clock,
+{
+    /// Creates a new wrapper that uses `clock` to schedule grace refills.
+    pub fn with_clock(inner: L, grace: u32, clock: C) -> Self {
+        let now = clock.now();
+        Self {
+            inner,
+            clock,
+            grace,
+            remaining: AtomicU32::new(grace),
+            refill_interval_ms: 0,
+            last_refill: AtomicU64::new(now),
+        }
+    }
+
+    /// Refills the grace back to its full count every `interval_ms` milliseconds.
+    ///
+    /// Refills are aligned to the time the wrapper was created, and unused grace does
+    /// not carry over. An interval of 0 disables refilling, which is the default.
+    pub fn with_refill_interval(mut self, interval_ms: u64) -> Self {
+        self.refill_interval_ms = interval_ms;
+        self
+    }
+
+    /// Returns the full grace count.
+    pub fn grace(&self) -> u32 {
+        self.grace
+    }
+
+    /// Returns the number of grace requests left before normal limiting applies.
+    pub fn remaining_grace(&self) -> u32 {
+        self.refill(self.clock.now());
+        self.remaining.load(Ordering::Acquire)
+    }
+
+    /// Restores the full grace count if a refill interval has elapsed.
+    fn refill(&self, now: u64) {
+        let interval = self.refill_interval_ms;
+        if interval == 0 {
+            return;
+        }
+
+        let last = self.last_refill.load(Ordering::Acquire);
+        let elapsed = now.saturating_sub(last);
+        if elapsed < interval {
+            return;
+        }
+
+        // Only the thread that advances the schedule performs the refill
+        let next = last + elapsed / interval * interval;
+        if self
+            .last_refill
+            .compare_exchange(last, next, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            self.remaining.store(self.grace, Ordering::Release);
+        }
+    }
+
+    /// Admits a request from the grace count, returning whether any was left.
+    fn take_grace(&self) -> bool {
+        self.refill(self.clock.now());
+        self.remaining
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |remaining| {
+                remaining.checked_sub(1)
+            })
+            .is_ok()
+    }
+}
+
+impl<L, C> RateLimiter for GraceLimiter<L, C>
+where
+    L: RateLimiter,
+    C: Clock,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        if self.take_grace() {
+            return Ok(());
+        }
+        self.inner.try_acquire(tokens)
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        if self.take_grace() {
+            return Ok(());
+        }
+        self.inner.try_acquire_u64(tokens)
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        if self.remaining_grace() > 0 {
+            return Ok(());
+        }
+        self.inner.peek_acquire(tokens)
+    }
+
+    fn available_tokens(&self) -> u32 {
+        self.inner.available_tokens()
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        self.inner.available_tokens_u64()
+    }
+
+    fn capacity(&self) -> u32 {
+        self.inner.capacity()
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.inner.capacity_u64()
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        self.inner.rate_per_second()
+    }
+
+    fn effective_rate_per_second(&self) -> f64 {
+        // Refilled grace is admitted on top of the inner limiter's rate
+        let grace_rate = match self.refill_interval_ms {
+            0 => 0.0,
+            interval_ms => self.grace as f64 * 1000.0 / interval_ms as f64,
+        };
+        self.inner.effective_rate_per_second() + grace_rate
+    }
+
+    fn resolution_warning(&self) -> Option<&'static str> {
+        self.inner.resolution_warning()
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        self.inner.time_until_next_token_ms()
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.inner.time_until_available_ms(tokens)
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        self.inner.retry_at(tokens)
+    }
+}
+
+impl<L, C> Wrapper for GraceLimiter<L, C> {
+    type Inner = L;
+
+    fn get_ref(&self) -> &L {
+        &self.inner
+    }
+
+    fn get_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    fn into_inner(self) -> L {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, TokenBucket};
+
+    #[test]
+    fn test_grace_then_limited() {
+        let clock = MockClock::new(0);
+        let bucket = TokenBucket::with_clock(2, 1.0, clock.clone());
+        let limiter = GraceLimiter::with_clock(bucket, 3, clock);
+
+        // Grace requests succeed regardless of size and leave the bucket untouched
+        assert!(limiter.try_acquire(100).is_ok());
+        assert!(limiter.try_acquire(1).is_ok());
+        assert!(limiter.try_acquire(1).is_ok());
+        assert_eq!(limiter.remaining_grace(), 0);
+        assert_eq!(limiter.available_tokens(), 2);
+
+        // Then the inner limiter takes over
+        assert!(limiter.try_acquire(1).is_ok());
+        assert!(limiter.try_acquire(1).is_ok());
+        assert!(limiter.try_acquire(1).unwrap_err().is_rate_limit_exceeded());
+        assert_eq!(limiter.remaining_grace(), 0);
+    }
+
+    #[test]
+    fn test_grace_refills_on_schedule() {
+        let clock = MockClock::new(0);
+        let bucket = TokenBucket::with_clock(1, 0.001, clock.clone());
+        let limiter = GraceLimiter::with_clock(bucket, 2, clock.clone()).with_refill_interval(1000);
+
+        assert!(limiter.try_acquire(1).is_ok());
+        assert!(limiter.try_acquire(1).is_ok());
+        assert!(limiter.try_acquire(1).is_ok());
+        assert!(limiter.try_acquire(1).is_err());
+
+        clock.advance(999);
+        assert_eq!(limiter.remaining_grace(), 0);
+        assert!(limiter.try_acquire(1).is_err());
+
+        // Refills restore the full grace without carrying over unused grace
+        clock.advance(1);
+        assert_eq!(limiter.remaining_grace(), 2);
+        clock.advance(5000);
+        assert_eq!(limiter.remaining_grace(), 2);
+        assert!(limiter.try_acquire(1).is_ok());
+        assert!(limiter.try_acquire(1).is_ok());
+        assert!(limiter.try_acquire(1).is_err());
+    }
+
+    #[test]
+    fn test_zero_grace_delegates_immediately() {
+        let clock = MockClock::new(0);
+        let limiter =
+            GraceLimiter::with_clock(TokenBucket::with_clock(1, 1.0, clock.clone()), 0, clock);
+        assert!(limiter.try_acquire(1).is_ok());
+        assert!(limiter.try_acquire(1).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+//! Rates configured as a burst per human-readable period.
+//!
+//! Configuration files often write a limit as a burst and the period it refills over,
+//! with the period in `humantime` syntax: `burst = 100, per = "1m30s"`. That is a
+//! capacity of 100 refilled at 100 tokens per 90 seconds. [`from_burst_per`] parses
+//! such a pair into the `(capacity, tokens_per_second)` the constructors take, reporting
+//! malformed periods as errors rather than panicking, since they come from
+//! configuration.
+//!
+//! This module is only available with the `humantime` feature.
+
+use crate::{
+    error::{RateLimitError, Result},
+    validate,
+};
+
+/// Converts a burst and the `humantime` period it refills over into a
+/// `(capacity, tokens_per_second)` pair.
+///
+/// `per` accepts anything [`humantime::parse_duration`] does, such as `"90s"`,
+/// `"1m30s"` or `"1h 15min"`. The capacity is `burst`, and the rate refills all of it
+/// over one period.
+///
+/// # Returns
+///
+/// * `Ok((capacity, tokens_per_second))` if both values are valid
+/// * `Err(RateLimitError::InvalidConfiguration)` if `burst` is 0, `per` does not parse
+///   or is zero, or the resulting rate exceeds [`MAX_RATE`](crate::MAX_RATE)
+///
+/// # Examples
+///
+/// ```
+/// use bucketboss::{from_burst_per, RateLimiter, TokenBucket};
+///
+/// let (capacity, tokens_per_second) = from_burst_per(30, "1m")?;
+/// assert_eq!((capacity, tokens_per_second), (30, 0.5));
+///
+/// let bucket = TokenBucket::new(capacity, tokens_per_second);
+/// assert_eq!(bucket.capacity(), 30);
+/// # Ok::<(), bucketboss::RateLimitError>(())
+/// ```
+pub fn from_burst_per(burst: u32, per: &str) -> Result<(u32, f64)> {
+    if burst == 0 {
+        return Err(RateLimitError::invalid_config(
+            "burst must be greater than 0",
+        ));
+    }
+    let period = humantime::parse_duration(per).map_err(|_| {
+        RateLimitError::invalid_config("period must be a duration such as \"1m30s\"")
+    })?;
+    if period.is_zero() {
+        return Err(RateLimitError::invalid_config("period must be positive"));
+    }
+
+    let tokens_per_second = burst as f64 / period.as_secs_f64();
+    validate::try_rate(tokens_per_second, "tokens_per_second must be positive")?;
+    Ok((burst, tokens_per_second))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_human_periods_map_to_rates() {
+        assert_eq!(from_burst_per(10, "1s").unwrap(), (10, 10.0));
+        assert_eq!(from_burst_per(30, "1m").unwrap(), (30, 0.5));
+        assert_eq!(from_burst_per(100, "1m30s").unwrap(), (100, 100.0 / 90.0));
+        assert_eq!(from_burst_per(5, "500ms").unwrap(), (5, 10.0));
+        assert_eq!(from_burst_per(3600, "1h").unwrap(), (3600, 1.0));
+        assert_eq!(from_burst_per(1, "1day").unwrap(), (1, 1.0 / 86_400.0));
+    }
+
+    #[test]
+    fn test_invalid_input_is_invalid_config() {
+        for (burst, per) in [
+            (0, "1s"),
+            (10, "soon"),
+            (10, ""),
+            (10, "0s"),
+            (u32::MAX, "1ns"),
+        ] {
+            let err = from_burst_per(burst, per).unwrap_err();
+            assert!(err.is_invalid_config(), "{burst} per {per:?}: {err:?}");
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! The time one token takes to refill, as used by the buckets' refill arithmetic.
+//!
+//! By default the interval is an `f64` number of milliseconds, stored as its bits in an
+//! atomic. With the `fixed` feature it is instead a [`U48F16`] number of nanoseconds
+//! stored directly in the atomic, and refills and waits are computed in integer
+//! arithmetic on it, so they are exact in units of 2⁻¹⁶ ns. The fixed-point interval
+//! ranges from 2⁻¹⁶ ns to about 78 hours per token, so under `fixed` the buckets reject
+//! rates outside [`MIN_RATE`] to [`MAX_RATE`]: about one token per 78 hours up to
+//! 6.5·10¹³ tokens per second.
+//!
+//! Either way the configured rate is converted at the boundary, so the public API keeps
+//! taking and reporting `f64` rates.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(feature = "fixed")]
+use fixed::types::U48F16;
+
+use crate::clock::NANOS_PER_MS;
+use crate::error::Result;
+#[cfg(not(feature = "fixed"))]
+use crate::float;
+#[cfg(feature = "fixed")]
+use crate::validate;
+
+/// The slowest rate, in tokens per second, whose interval fits in a [`U48F16`] number of
+/// nanoseconds.
+#[cfg(feature = "fixed")]
+pub(crate) const MIN_RATE: f64 = 1e9 / 281_474_976_710_656.0;
+
+/// The fastest rate, in tokens per second, whose interval is at least the 2⁻¹⁶ ns
+/// resolution of a [`U48F16`].
+#[cfg(feature = "fixed")]
+pub(crate) const MAX_RATE: f64 = 1e9 * 65_536.0;
+
+/// The message reported for rates outside [`MIN_RATE`] to [`MAX_RATE`].
+#[cfg(feature = "fixed")]
+const RATE_OUT_OF_RANGE: &str = "rate must be within the range of the fixed-point refill interval";
+
+/// Checks that a rate validated by [`validate::rate`](crate::validate::rate) has an
+/// interval in range, clamping it into the range under `no-panic`.
+///
+/// Without the `fixed` feature every valid rate is in range.
+#[inline]
+pub(crate) fn rate(value: f64) -> f64 {
+    #[cfg(feature = "fixed")]
+    {
+        let value = validate::rate_at_least(value, MIN_RATE, RATE_OUT_OF_RANGE);
+        validate::rate_at_most(value, MAX_RATE, RATE_OUT_OF_RANGE)
+    }
+    #[cfg(not(feature = "fixed"))]
+    {
+        value
+    }
+}
+
+/// Checks that a rate validated by [`validate::try_rate`](crate::validate::try_rate) has
+/// an interval in range, reporting `InvalidConfiguration` if not.
+#[inline]
+pub(crate) fn try_rate(value: f64) -> Result<()> {
+    #[cfg(feature = "fixed")]
+    {
+        validate::try_rate_at_least(value, MIN_RATE, RATE_OUT_OF_RANGE)?;
+        validate::try_rate_at_most(value, MAX_RATE, RATE_OUT_OF_RANGE)
+    }
+    #[cfg(not(feature = "fixed"))]
+    {
+        let _ = value;
+        Ok(())
+    }
+}
+
+/// The refill interval of a bucket.
+#[derive(Debug)]
+pub(crate) struct RefillInterval(AtomicU64);
+
+#[cfg(not(feature = "fixed"))]
+impl RefillInterval {
+    /// Creates an interval of `ms` milliseconds per token.
+    pub(crate) fn from_ms(ms: f64) -> Self {
+        Self(AtomicU64::new(ms.to_bits()))
+    }
+
+    /// Creates an interval of a whole number of nanoseconds per token.
+    pub(crate) const fn from_whole_ns(ns: u64) -> Self {
+        Self(AtomicU64::new((ns as f64 / NANOS_PER_MS as f64).to_bits()))
+    }
+
+    /// Sets the interval to `ms` milliseconds per token.
+    pub(crate) fn store_ms(&self, ms: f64) {
+        self.0.store(ms.to_bits(), Ordering::Release);
+    }
+
+    /// Returns whether the interval is what [`store_ms`](Self::store_ms) would set for
+    /// `ms`.
+    pub(crate) fn holds_ms(&self, ms: f64) -> bool {
+        self.0.load(Ordering::Acquire) == ms.to_bits()
+    }
+
+    /// Returns the interval in milliseconds.
+    pub(crate) fn ms(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Acquire))
+    }
+
+    /// Returns the number of whole tokens that refill in `elapsed_ns` nanoseconds.
+    pub(crate) fn tokens_in(&self, elapsed_ns: u64) -> u64 {
+        let ns = self.ns();
+        if ns > 0.0 {
+            (elapsed_ns as f64 / ns) as u64
+        } else {
+            0
+        }
+    }
+
+    /// Returns the time `tokens` take to refill, in nanoseconds and rounded up.
+    pub(crate) fn ns_for(&self, tokens: u64) -> u64 {
+        float::ceil(tokens as f64 * self.ns()) as u64
+    }
+
+    /// Returns the time `tokens` take to refill, in nanoseconds and rounded down.
+    pub(crate) fn ns_for_truncated(&self, tokens: u64) -> u64 {
+        (tokens as f64 * self.ns()) as u64
+    }
+
+    /// Returns the time `tokens` take to refill, in milliseconds and rounded up.
+    pub(crate) fn ms_for(&self, tokens: u64) -> u64 {
+        float::ceil(tokens as f64 * self.ms()) as u64
+    }
+}
+
+#[cfg(feature = "fixed")]
+impl RefillInterval {
+    /// Converts `ms` milliseconds to the fixed-point interval, saturating into its
+    /// range.
+    fn to_fixed(ms: f64) -> U48F16 {
+        if ms.is_nan() || ms <= 0.0 {
+            return U48F16::ZERO;
+        }
+        U48F16::saturating_from_num(ms * NANOS_PER_MS as f64).max(U48F16::DELTA)
+    }
+
+    /// Returns the interval in units of 2⁻¹⁶ ns.
+    fn bits(&self) -> u64 {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// Creates an interval of `ms` milliseconds per token.
+    pub(crate) fn from_ms(ms: f64) -> Self {
+        Self(AtomicU64::new(Self::to_fixed(ms).to_bits()))
+    }
+
+    /// Creates an interval of a whole number of nanoseconds per token.
+    pub(crate) const fn from_whole_ns(ns: u64) -> Self {
+        Self(AtomicU64::new(
+            ns.saturating_mul(1u64 << U48F16::FRAC_NBITS),
+        ))
+    }
+
+    /// Sets the interval to `ms` milliseconds per token.
+    pub(crate) fn store_ms(&self, ms: f64) {
+        self.0
+            .store(Self::to_fixed(ms).to_bits(), Ordering::Release);
+    }
+
+    /// Returns whether the interval is what [`store_ms`](Self::store_ms) would set for
+    /// `ms`.
+    pub(crate) fn holds_ms(&self, ms: f64) -> bool {
+        self.bits() == Self::to_fixed(ms).to_bits()
+    }
+
+    /// Returns the interval in milliseconds.
+    pub(crate) fn ms(&self) -> f64 {
+        U48F16::from_bits(self.bits()).to_num::<f64>() / NANOS_PER_MS as f64
+    }
+
+    /// Returns the number of whole tokens that refill in `elapsed_ns` nanoseconds.
+    pub(crate) fn tokens_in(&self, elapsed_ns: u64) -> u64 {
+        match self.bits() {
+            0 => 0,
+            bits => {
+                let tokens = ((elapsed_ns as u128) << U48F16::FRAC_NBITS) / bits as u128;
+                tokens.min(u64::MAX as u128) as u64
+            }
+        }
+    }
+
+    /// Returns the time `tokens` take to refill, in nanoseconds and rounded up.
+    pub(crate) fn ns_for(&self, tokens: u64) -> u64 {
+        let scaled = tokens as u128 * self.bits() as u128;
+        scaled
+            .div_ceil(1u128 << U48F16::FRAC_NBITS)
+            .min(u64::MAX as u128) as u64
+    }
+
+    /// Returns the time `tokens` take to refill, in nanoseconds and rounded down.
+    pub(crate) fn ns_for_truncated(&self, tokens: u64) -> u64 {
+        let scaled = tokens as u128 * self.bits() as u128;
+        (scaled >> U48F16::FRAC_NBITS).min(u64::MAX as u128) as u64
+    }
+
+    /// Returns the time `tokens` take to refill, in milliseconds and rounded up.
+    pub(crate) fn ms_for(&self, tokens: u64) -> u64 {
+        let scaled = tokens as u128 * self.bits() as u128;
+        scaled
+            .div_ceil((NANOS_PER_MS as u128) << U48F16::FRAC_NBITS)
+            .min(u64::MAX as u128) as u64
+    }
+}
+
+impl RefillInterval {
+    /// Returns the interval in nanoseconds.
+    pub(crate) fn ns(&self) -> f64 {
+        self.ms() * NANOS_PER_MS as f64
+    }
+
+    /// Returns whether tokens refill at all.
+    pub(crate) fn is_positive(&self) -> bool {
+        self.ms() > 0.0
+    }
+}
+
+#[cfg(all(test, feature = "fixed"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_point_refill_is_exact() {
+        // A third of a second per token is not a whole number of nanoseconds
+        let interval = RefillInterval::from_ms(1000.0 / 3.0);
+        assert_eq!(interval.tokens_in(1_000_000_000), 3);
+        assert_eq!(interval.tokens_in(999_999_999), 2);
+        assert_eq!(interval.ns_for(3), 1_000_000_000);
+        assert_eq!(interval.ms_for(1), 334);
+        assert!(interval.holds_ms(1000.0 / 3.0));
+
+        // Out-of-range intervals saturate rather than wrap or reach zero
+        let fast = RefillInterval::from_ms(1e-12);
+        assert_eq!(fast.tokens_in(1), 1 << 16);
+        let slow = RefillInterval::from_ms(1e30);
+        assert_eq!(slow.tokens_in(u64::MAX), 65_536);
+    }
+
+    #[test]
+    fn test_rates_outside_fixed_point_range_are_rejected() {
+        use crate::{ReconfigurableRateLimiter, TokenBucket};
+
+        assert!(try_rate(MIN_RATE).is_ok());
+        assert!(try_rate(MAX_RATE).is_ok());
+        assert!(try_rate(MIN_RATE / 2.0).unwrap_err().is_invalid_config());
+        assert!(try_rate(MAX_RATE * 2.0).unwrap_err().is_invalid_config());
+
+        let bucket = TokenBucket::new(10, 10.0);
+        assert!(bucket.update_config(10, 1e14).is_err());
+        assert!(bucket.set_rate_only(1e-6).is_err());
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-panic"))]
+    #[should_panic(expected = "fixed-point refill interval")]
+    fn test_bucket_panics_on_rate_below_fixed_point_range() {
+        let _ = crate::LeakyBucket::new(1e-6, None);
+    }
+}
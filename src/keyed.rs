@@ -0,0 +1,421 @@
+//! Per-key rate limiting.
+//!
+//! Multi-tenant services usually limit each client separately. [`KeyedRateLimiter`]
+//! keeps one limiter per key, created on first use by a factory, and can evict keys
+//! that have been idle for a while so the map does not grow without bound.
+//!
+//! Any `K: Eq + Hash + Clone` works as a key, including tuples such as
+//! `(user, endpoint)`. For keys made of string dimensions, [`CompositeKey`] combines
+//! them without the pitfalls of joining strings by hand.
+//!
+//! This module is only available with the `alloc` and `std` features.
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{fmt, hash::Hash, time::Duration};
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::Result,
+    status::LimiterStatus,
+    traits::{RateLimiter, Wrapper},
+};
+
+/// A key combining several dimensions, such as a user and an endpoint.
+///
+/// Dimensions are kept separately, so `["a:b", "c"]` and `["a", "b:c"]` are distinct
+/// keys, unlike keys built by joining strings with a separator. The order of the
+/// dimensions matters, and equal dimension lists always give equal keys, so the key
+/// is stable across calls and processes.
+///
+/// # Examples
+///
+/// ```
+/// use bucketboss::CompositeKey;
+///
+/// let key = CompositeKey::new().with("alice").with("/upload").with(443);
+/// assert_eq!(key.dims(), ["alice", "/upload", "443"]);
+/// assert_eq!(key, CompositeKey::from_dims(&["alice", "/upload", "443"]));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CompositeKey {
+    dims: Vec<String>,
+}
+
+impl CompositeKey {
+    /// Creates a key with no dimensions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a key from `dims`, in order.
+    pub fn from_dims(dims: &[&str]) -> Self {
+        Self {
+            dims: dims.iter().map(|dim| dim.to_string()).collect(),
+        }
+    }
+
+    /// Appends a dimension, formatted with its `Display` implementation.
+    pub fn with(mut self, dim: impl ToString) -> Self {
+        self.dims.push(dim.to_string());
+        self
+    }
+
+    /// Returns the dimensions, in order.
+    pub fn dims(&self) -> &[String] {
+        &self.dims
+    }
+}
+
+#[derive(Debug)]
+struct Entry<L> {
+    limiter: Arc<L>,
+    /// When the key was last used.
+    last_used: u64,
+}
+
+/// A rate limiter keeping a separate limiter for every key.
+///
+/// The limiter for a key is created by the factory the first time the key is used.
+/// The map lock is only held to look up a key's limiter, never while acquiring from it.
+///
+/// # Examples
+///
+/// ```
+/// use bucketboss::{KeyedRateLimiter, TokenBucket};
+///
+/// let limiter = KeyedRateLimiter::new(|| TokenBucket::new(1, 1.0));
+///
+/// assert!(limiter.try_acquire(&"alice", 1).is_ok());
+/// assert!(limiter.try_acquire(&"alice", 1).is_err());
+/// assert!(limiter.try_acquire(&"bob", 1).is_ok());
+/// ```
+pub struct KeyedRateLimiter<K, L, F, C = SystemClock> {
+    factory: F,
+    clock: C,
+    idle_ms: Option<u64>,
+    entries: Mutex<HashMap<K, Entry<L>>>,
+}
+
+impl<K, L, F> KeyedRateLimiter<K, L, F, SystemClock>
+where
+    K: Eq + Hash + Clone,
+    L: RateLimiter,
+    F: Fn() -> L,
+{
+    /// Creates a new keyed limiter that builds each key's limiter with `factory`.
+    pub fn new(factory: F) -> Self {
+        Self::with_clock(factory, SystemClock)
+    }
+}
+
+impl<K, L, F, C> KeyedRateLimiter<K, L, F, C>
+where
+    K: Eq + Hash + Clone,
+    L: RateLimiter,
+    F: Fn() -> L,
+    C: Clock,
+{
+    /// Creates a new keyed limiter with the specified clock.
+    ///
+    /// The clock only tracks when keys were last used; the limiters keep their own.
+    pub fn with_clock(factory: F, clock: C) -> Self {
+        Self {
+            factory,
+            clock,
+            idle_ms: None,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Evicts keys that have not been used for `idle`.
+    ///
+    /// Eviction happens in [`evict_expired`](Self::evict_expired) and
+    /// [`snapshot_all`](Self::snapshot_all). An evicted key starts over with a fresh
+    /// limiter the next time it is used.
+    pub fn with_idle_eviction(mut self, idle: Duration) -> Self {
+        self.idle_ms = Some(idle.as_millis().min(u64::MAX as u128) as u64);
+        self
+    }
+
+    /// Returns the limiter for `key`, creating it if the key is new.
+    pub fn limiter(&self, key: &K) -> Arc<L> {
+        let now = self.clock.now();
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.get_mut(key) {
+            entry.last_used = now;
+            return Arc::clone(&entry.limiter);
+        }
+
+        let limiter = Arc::new((self.factory)());
+        let _ = entries.insert(
+            key.clone(),
+            Entry {
+                limiter: Arc::clone(&limiter),
+                last_used: now,
+            },
+        );
+        limiter
+    }
+
+    /// Attempts to acquire `tokens` from the limiter for `key`.
+    ///
+    /// See [`RateLimiter::try_acquire`].
+    pub fn try_acquire(&self, key: &K, tokens: u32) -> Result<()> {
+        self.limiter(key).try_acquire(tokens)
+    }
+
+    /// Returns the number of tokens available to `key`.
+    ///
+    /// Querying does not count as using the key. For a key that is not tracked, this is
+    /// what a fresh limiter would report; the key is not added.
+    pub fn available_tokens(&self, key: &K) -> u32 {
+        let limiter = self
+            .entries
+            .lock()
+            .unwrap()
+            .get(key)
+            .map(|entry| Arc::clone(&entry.limiter));
+        match limiter {
+            Some(limiter) => limiter.available_tokens(),
+            None => (self.factory)().available_tokens(),
+        }
+    }
+
+    /// Returns the number of keys currently tracked.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Returns `true` if no keys are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stops tracking `key`, returning whether it was tracked.
+    pub fn remove(&self, key: &K) -> bool {
+        self.entries.lock().unwrap().remove(key).is_some()
+    }
+
+    /// Evicts every key that has not been used for `idle_for` and returns how many were
+    /// evicted, regardless of the configured idle time.
+    pub fn evict_idle(&self, idle_for: Duration) -> usize {
+        let idle_ms = idle_for.as_millis().min(u64::MAX as u128) as u64;
+        self.evict_locked(&mut self.entries.lock().unwrap(), idle_ms)
+    }
+
+    /// Evicts every key idle for longer than the idle time set with
+    /// [`with_idle_eviction`](Self::with_idle_eviction) and returns how many were
+    /// evicted.
+    ///
+    /// Does nothing without an idle time.
+    pub fn evict_expired(&self) -> usize {
+        match self.idle_ms {
+            Some(idle_ms) => self.evict_locked(&mut self.entries.lock().unwrap(), idle_ms),
+            None => 0,
+        }
+    }
+
+    fn evict_locked(&self, entries: &mut HashMap<K, Entry<L>>, idle_ms: u64) -> usize {
+        let now = self.clock.now();
+        let before = entries.len();
+        entries.retain(|_, entry| entry.last_used.saturating_add(idle_ms) > now);
+        before - entries.len()
+    }
+
+    /// Returns the status of every tracked key's limiter, in no particular order.
+    ///
+    /// Idle keys are evicted first, so only live keys are reported. Taking a snapshot
+    /// does not count as using a key.
+    ///
+    /// The snapshot is best-effort: the set of keys is read at one instant, but each
+    /// limiter's status is read afterwards without holding the map lock, so requests
+    /// running concurrently may land between the reads of two keys, and a key added
+    /// meanwhile is missing.
+    pub fn snapshot_all(&self) -> Vec<(K, LimiterStatus)> {
+        let live: Vec<(K, Arc<L>)> = {
+            let mut entries = self.entries.lock().unwrap();
+            if let Some(idle_ms) = self.idle_ms {
+                let _ = self.evict_locked(&mut entries, idle_ms);
+            }
+            entries
+                .iter()
+                .map(|(key, entry)| (key.clone(), Arc::clone(&entry.limiter)))
+                .collect()
+        };
+
+        live.into_iter()
+            .map(|(key, limiter)| (key, limiter.status()))
+            .collect()
+    }
+}
+
+impl<L, F, C> KeyedRateLimiter<CompositeKey, L, F, C>
+where
+    L: RateLimiter,
+    F: Fn() -> L,
+    C: Clock,
+{
+    /// Attempts to acquire `tokens` from the limiter for the key made of `dims`.
+    ///
+    /// This is shorthand for `try_acquire(&CompositeKey::from_dims(dims), tokens)`.
+    pub fn try_acquire_dims(&self, dims: &[&str], tokens: u32) -> Result<()> {
+        self.try_acquire(&CompositeKey::from_dims(dims), tokens)
+    }
+}
+
+impl<K, L, F, C> fmt::Debug for KeyedRateLimiter<K, L, F, C>
+where
+    C: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys = self.entries.lock().map(|entries| entries.len()).ok();
+        f.debug_struct("KeyedRateLimiter")
+            .field("clock", &self.clock)
+            .field("idle_ms", &self.idle_ms)
+            .field("keys", &keys)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The wrapped value is the factory. A factory replaced through
+/// [`get_mut`](Wrapper::get_mut) builds the limiters of keys seen from then on, while
+/// existing keys keep theirs.
+impl<K, L, F, C> Wrapper for KeyedRateLimiter<K, L, F, C> {
+    type Inner = F;
+
+    fn get_ref(&self) -> &F {
+        &self.factory
+    }
+
+    fn get_mut(&mut self) -> &mut F {
+        &mut self.factory
+    }
+
+    fn into_inner(self) -> F {
+        self.factory
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, TokenBucket};
+
+    #[test]
+    fn test_snapshot_all_reports_live_keys() {
+        let clock = MockClock::new(0);
+        let bucket_clock = clock.clone();
+        let limiter = KeyedRateLimiter::with_clock(
+            move || TokenBucket::with_clock(10, 1.0, bucket_clock.clone()),
+            clock.clone(),
+        )
+        .with_idle_eviction(Duration::from_secs(60));
+
+        assert!(limiter.try_acquire(&"alice", 10).is_ok());
+        assert!(limiter.try_acquire(&"bob", 3).is_ok());
+        clock.advance(30_000);
+        assert!(limiter.try_acquire(&"carol", 1).is_ok());
+
+        let mut snapshot = limiter.snapshot_all();
+        snapshot.sort_by_key(|&(key, _)| key);
+        let available: Vec<_> = snapshot
+            .iter()
+            .map(|(key, status)| (*key, status.available, status.capacity))
+            .collect();
+        assert_eq!(
+            available,
+            [("alice", 10, 10), ("bob", 10, 10), ("carol", 9, 10)]
+        );
+
+        // Alice and Bob go idle and are evicted; Carol is still live
+        clock.advance(40_000);
+        let keys: Vec<_> = limiter
+            .snapshot_all()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, ["carol"]);
+        assert_eq!(limiter.len(), 1);
+    }
+
+    #[test]
+    fn test_keys_are_limited_independently() {
+        let limiter = KeyedRateLimiter::with_clock(
+            || TokenBucket::with_clock(1, 1.0, MockClock::new(0)),
+            MockClock::new(0),
+        );
+
+        assert!(limiter.try_acquire(&1, 1).is_ok());
+        assert!(limiter.try_acquire(&1, 1).is_err());
+        assert!(limiter.try_acquire(&2, 1).is_ok());
+        assert_eq!(limiter.evict_expired(), 0);
+
+        assert!(limiter.remove(&1));
+        assert!(limiter.try_acquire(&1, 1).is_ok());
+    }
+
+    #[test]
+    fn test_available_tokens_and_explicit_eviction() {
+        let clock = MockClock::new(0);
+        let bucket_clock = clock.clone();
+        let limiter = KeyedRateLimiter::with_clock(
+            move || TokenBucket::with_clock(5, 1.0, bucket_clock.clone()),
+            clock.clone(),
+        );
+
+        assert!(limiter.try_acquire(&"alice", 4).is_ok());
+        assert_eq!(limiter.available_tokens(&"alice"), 1);
+        // Unknown keys report a fresh limiter without being tracked
+        assert_eq!(limiter.available_tokens(&"bob"), 5);
+        assert_eq!(limiter.len(), 1);
+
+        clock.advance(2000);
+        assert!(limiter.try_acquire(&"carol", 1).is_ok());
+        assert_eq!(limiter.evict_idle(Duration::from_secs(3)), 0);
+        assert_eq!(limiter.evict_idle(Duration::from_secs(1)), 1);
+        assert_eq!(limiter.available_tokens(&"alice"), 5);
+        assert!(!limiter.remove(&"alice"));
+    }
+
+    #[test]
+    fn test_tuple_keys() {
+        let limiter = KeyedRateLimiter::with_clock(
+            || TokenBucket::with_clock(1, 1.0, MockClock::new(0)),
+            MockClock::new(0),
+        );
+
+        assert!(limiter.try_acquire(&("alice", "GET"), 1).is_ok());
+        assert!(limiter.try_acquire(&("alice", "POST"), 1).is_ok());
+        assert!(limiter.try_acquire(&("bob", "GET"), 1).is_ok());
+        assert!(limiter.try_acquire(&("alice", "GET"), 1).is_err());
+        assert_eq!(limiter.len(), 3);
+    }
+
+    #[test]
+    fn test_composite_keys_keep_dimensions_apart() {
+        let limiter = KeyedRateLimiter::with_clock(
+            || TokenBucket::with_clock(1, 1.0, MockClock::new(0)),
+            MockClock::new(0),
+        );
+
+        assert!(limiter.try_acquire_dims(&["alice", "/upload"], 1).is_ok());
+        assert!(limiter.try_acquire_dims(&["alice", "/download"], 1).is_ok());
+        assert!(limiter.try_acquire_dims(&["/upload", "alice"], 1).is_ok());
+        // Would collide with the next key if the dimensions were joined with ':'
+        assert!(limiter.try_acquire_dims(&["a:b", "c"], 1).is_ok());
+        assert!(limiter.try_acquire_dims(&["a", "b:c"], 1).is_ok());
+        assert_eq!(limiter.len(), 5);
+
+        // The builder and the slice form give the same key
+        let key = CompositeKey::new().with("alice").with("/upload");
+        assert!(limiter.try_acquire(&key, 1).is_err());
+        assert!(limiter
+            .try_acquire_dims(&["alice", "/download"], 1)
+            .is_err());
+    }
+}
@@ -0,0 +1,227 @@
+//! Attributing rejections to a named limiter.
+//!
+//! When several limiters are combined, a bare rejection does not say which one ran out.
+//! [`LabeledLimiter`] gives a limiter a name and records it as the `rejected_by` label
+//! of every `RateLimitExceeded` error it produces, turning "rejected" into "rejected by
+//! the per-minute tier".
+
+use core::time::Duration;
+
+use crate::{
+    error::{ErrorText, Result},
+    status::BucketState,
+    traits::{
+        AdjustableRateLimiter, RateLimiter, ReconfigurableRateLimiter, Resettable,
+        StatefulRateLimiter, Wrapper,
+    },
+};
+
+/// A wrapper that attributes the inner limiter's rejections to a label.
+///
+/// If a rejection already carries a label, for example because the inner limiter is
+/// itself labelled, the innermost label is kept.
+#[derive(Debug)]
+pub struct LabeledLimiter<L> {
+    inner: L,
+    label: ErrorText,
+}
+
+impl<L> LabeledLimiter<L>
+where
+    L: RateLimiter,
+{
+    /// Creates a new wrapper that attributes rejections to `label`.
+    ///
+    /// The label is a string literal or, with the `std` or `alloc` feature, a `String`
+    /// such as one read from configuration.
+    pub fn new(inner: L, label: impl Into<ErrorText>) -> Self {
+        Self {
+            inner,
+            label: label.into(),
+        }
+    }
+
+    /// Returns the label attached to rejections.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Attaches the label to a rejection that has no label yet.
+    fn attribute(&self, result: Result<()>) -> Result<()> {
+        result.map_err(|err| match err.rejected_by() {
+            Some(_) => err,
+            None => err.with_rejected_by(self.label.clone()),
+        })
+    }
+}
+
+impl<L> RateLimiter for LabeledLimiter<L>
+where
+    L: RateLimiter,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        self.attribute(self.inner.try_acquire(tokens))
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        self.attribute(self.inner.try_acquire_u64(tokens))
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        self.attribute(self.inner.peek_acquire(tokens))
+    }
+
+    fn available_tokens(&self) -> u32 {
+        self.inner.available_tokens()
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        self.inner.available_tokens_u64()
+    }
+
+    fn capacity(&self) -> u32 {
+        self.inner.capacity()
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.inner.capacity_u64()
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        self.inner.rate_per_second()
+    }
+
+    fn effective_rate_per_second(&self) -> f64 {
+        self.inner.effective_rate_per_second()
+    }
+
+    fn resolution_warning(&self) -> Option<&'static str> {
+        self.inner.resolution_warning()
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        self.inner.time_until_next_token_ms()
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.inner.time_until_available_ms(tokens)
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        self.inner.retry_at(tokens)
+    }
+}
+
+impl<L> ReconfigurableRateLimiter for LabeledLimiter<L>
+where
+    L: ReconfigurableRateLimiter,
+{
+    fn update_config(&self, capacity: u32, tokens_per_second: f64) -> Result<()> {
+        self.inner.update_config(capacity, tokens_per_second)
+    }
+
+    fn set_rate_only(&self, tokens_per_second: f64) -> Result<()> {
+        self.inner.set_rate_only(tokens_per_second)
+    }
+}
+
+impl<L> AdjustableRateLimiter for LabeledLimiter<L>
+where
+    L: AdjustableRateLimiter,
+{
+    fn refund(&self, tokens: u32) {
+        self.inner.refund(tokens);
+    }
+
+    fn penalize(&self, tokens: u32) {
+        self.inner.penalize(tokens);
+    }
+}
+
+impl<L> StatefulRateLimiter for LabeledLimiter<L>
+where
+    L: StatefulRateLimiter,
+{
+    fn snapshot(&self) -> BucketState {
+        self.inner.snapshot()
+    }
+
+    fn restore(&self, state: &BucketState, elapsed: Duration) {
+        self.inner.restore(state, elapsed);
+    }
+}
+
+impl<L> Resettable for LabeledLimiter<L>
+where
+    L: Resettable,
+{
+    fn reset(&self) {
+        self.inner.reset();
+    }
+
+    fn drain(&self) {
+        self.inner.drain();
+    }
+}
+
+impl<L> Wrapper for LabeledLimiter<L> {
+    type Inner = L;
+
+    fn get_ref(&self) -> &L {
+        &self.inner
+    }
+
+    fn get_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    fn into_inner(self) -> L {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, TokenBucket};
+
+    #[test]
+    fn test_rejection_names_the_tier() {
+        let clock = MockClock::new(0);
+        let per_second =
+            LabeledLimiter::new(TokenBucket::with_clock(2, 2.0, clock.clone()), "per-second");
+        let per_minute = LabeledLimiter::new(
+            TokenBucket::with_clock(3, 0.05, clock.clone()),
+            "per-minute",
+        );
+        let acquire = |tokens| {
+            per_second.try_acquire(tokens)?;
+            per_minute.try_acquire(tokens)
+        };
+
+        assert!(acquire(2).is_ok());
+        assert_eq!(acquire(1).unwrap_err().rejected_by(), Some("per-second"));
+
+        clock.advance(1000);
+        assert!(acquire(1).is_ok());
+        clock.advance(1000);
+        assert_eq!(acquire(1).unwrap_err().rejected_by(), Some("per-minute"));
+    }
+
+    #[test]
+    fn test_innermost_label_wins() {
+        let bucket = TokenBucket::with_clock(1, 1.0, MockClock::new(0));
+        let limiter = LabeledLimiter::new(LabeledLimiter::new(bucket, "inner"), "outer");
+        assert_eq!(limiter.label(), "outer");
+
+        assert!(limiter.try_acquire(1).is_ok());
+        assert_eq!(
+            limiter.try_acquire(1).unwrap_err().rejected_by(),
+            Some("inner")
+        );
+        assert_eq!(
+            limiter.try_acquire_u64(1).unwrap_err().rejected_by(),
+            Some("inner")
+        );
+    }
+}
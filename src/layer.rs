@@ -0,0 +1,371 @@
+//! Tower middleware for rate limiting HTTP services.
+//!
+//! [`RateLimitLayer`] puts a limiter in front of any tower `Service` handling `http`
+//! requests, such as an Axum router. Each request acquires one token; a rejected
+//! request never reaches the service and is answered with `429 Too Many Requests`
+//! instead, with a `Retry-After` header if waiting would help.
+//!
+//! The limiters are thread-safe, so the layer shares one behind an `Arc` without a
+//! lock:
+//!
+//! ```
+//! use bucketboss::{RateLimitLayer, TokenBucket};
+//!
+//! let layer = RateLimitLayer::new(TokenBucket::new(100, 10.0));
+//! # let _ = layer;
+//! ```
+//!
+//! For per-client limits, [`RateLimitLayer::per_key`] routes each request to a
+//! [`KeyedRateLimiter`] by a key extracted from the request, such as the client's IP
+//! address.
+//!
+//! This module is only available with the `tower` feature.
+
+use alloc::sync::Arc;
+use core::{
+    fmt,
+    future::Future,
+    hash::Hash,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use http::{header, HeaderValue, Request, Response, StatusCode};
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{clock::Clock, error::Result, keyed::KeyedRateLimiter, traits::RateLimiter};
+
+/// A limiter that decides whether an HTTP request is admitted.
+///
+/// This is implemented for every [`RateLimiter`], which charges each request one token,
+/// and for [`PerKey`], which charges the limiter of the request's key.
+pub trait RequestLimiter<B> {
+    /// Attempts to acquire a token for `request`.
+    fn try_acquire_request(&self, request: &Request<B>) -> Result<()>;
+}
+
+impl<L, B> RequestLimiter<B> for L
+where
+    L: RateLimiter,
+{
+    fn try_acquire_request(&self, _request: &Request<B>) -> Result<()> {
+        self.try_acquire(1)
+    }
+}
+
+/// A [`KeyedRateLimiter`] paired with a function extracting the key from a request.
+///
+/// Created by [`RateLimitLayer::per_key`].
+pub struct PerKey<K, L, F, C, E> {
+    limiter: KeyedRateLimiter<K, L, F, C>,
+    key: E,
+}
+
+impl<K, L, F, C, E> PerKey<K, L, F, C, E> {
+    /// Returns the keyed limiter.
+    pub fn limiter(&self) -> &KeyedRateLimiter<K, L, F, C> {
+        &self.limiter
+    }
+}
+
+impl<K, L, F, C, E, B> RequestLimiter<B> for PerKey<K, L, F, C, E>
+where
+    K: Eq + Hash + Clone,
+    L: RateLimiter,
+    F: Fn() -> L,
+    C: Clock,
+    E: Fn(&Request<B>) -> K,
+{
+    fn try_acquire_request(&self, request: &Request<B>) -> Result<()> {
+        self.limiter.try_acquire(&(self.key)(request), 1)
+    }
+}
+
+impl<K, L, F, C, E> fmt::Debug for PerKey<K, L, F, C, E>
+where
+    C: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PerKey")
+            .field("limiter", &self.limiter)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A tower [`Layer`] that rate limits the requests reaching a service.
+///
+/// See the [module documentation](self) for an overview.
+#[derive(Debug)]
+pub struct RateLimitLayer<R> {
+    limiter: Arc<R>,
+}
+
+impl<R> RateLimitLayer<R> {
+    /// Creates a layer admitting the requests that `limiter` admits.
+    pub fn new(limiter: R) -> Self {
+        Self::from_arc(Arc::new(limiter))
+    }
+
+    /// Creates a layer sharing `limiter` with its other users.
+    pub fn from_arc(limiter: Arc<R>) -> Self {
+        Self { limiter }
+    }
+
+    /// Returns the limiter.
+    pub fn limiter(&self) -> &Arc<R> {
+        &self.limiter
+    }
+}
+
+impl<K, L, F, C, E> RateLimitLayer<PerKey<K, L, F, C, E>> {
+    /// Creates a layer charging each request to the limiter for the key `key` extracts
+    /// from it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bucketboss::{KeyedRateLimiter, RateLimitLayer, TokenBucket};
+    /// use http::Request;
+    ///
+    /// // 10 requests per second for each client, keyed by a header set by the proxy
+    /// let layer = RateLimitLayer::per_key(
+    ///     KeyedRateLimiter::<String, _, _>::new(|| TokenBucket::new(10, 10.0)),
+    ///     |request: &Request<()>| {
+    ///         request
+    ///             .headers()
+    ///             .get("x-forwarded-for")
+    ///             .and_then(|value| value.to_str().ok())
+    ///             .unwrap_or("unknown")
+    ///             .to_owned()
+    ///     },
+    /// );
+    /// # let _ = layer;
+    /// ```
+    pub fn per_key(limiter: KeyedRateLimiter<K, L, F, C>, key: E) -> Self {
+        Self::new(PerKey { limiter, key })
+    }
+}
+
+impl<R> Clone for RateLimitLayer<R> {
+    fn clone(&self) -> Self {
+        Self {
+            limiter: Arc::clone(&self.limiter),
+        }
+    }
+}
+
+impl<S, R> Layer<S> for RateLimitLayer<R> {
+    type Service = RateLimitService<S, R>;
+
+    fn layer(&self, inner: S) -> RateLimitService<S, R> {
+        RateLimitService {
+            inner,
+            limiter: Arc::clone(&self.limiter),
+        }
+    }
+}
+
+/// A tower [`Service`] that rate limits the requests reaching `S`.
+///
+/// Created by [`RateLimitLayer`]. A rejected request is answered with
+/// `429 Too Many Requests` and an empty body. When the limiter reports how long to
+/// wait, the response carries it as a `Retry-After` header in whole seconds, rounded up
+/// and at least 1, as the header cannot express shorter waits. A request the limiter
+/// can never admit, which it reports with a wait of zero, gets no `Retry-After`, so
+/// clients do not retry it in vain.
+#[derive(Debug)]
+pub struct RateLimitService<S, R> {
+    inner: S,
+    limiter: Arc<R>,
+}
+
+impl<S, R> RateLimitService<S, R> {
+    /// Returns a reference to the wrapped service.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S, R> Clone for RateLimitService<S, R>
+where
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            limiter: Arc::clone(&self.limiter),
+        }
+    }
+}
+
+impl<S, R, ReqBody, ResBody> Service<Request<ReqBody>> for RateLimitService<S, R>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    R: RequestLimiter<ReqBody>,
+    ResBody: Default,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = RateLimitFuture<S::Future, ResBody>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<core::result::Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        match self.limiter.try_acquire_request(&request) {
+            Ok(()) => RateLimitFuture {
+                state: State::Admitted {
+                    future: self.inner.call(request),
+                },
+            },
+            Err(err) => {
+                let mut response = Response::new(ResBody::default());
+                *response.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+                if let Some(retry_after_ms) = err.retry_after_ms().filter(|&ms| ms > 0) {
+                    let seconds = retry_after_ms.div_ceil(1000).max(1);
+                    let _ = response
+                        .headers_mut()
+                        .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+                }
+                RateLimitFuture {
+                    state: State::Rejected {
+                        response: Some(response),
+                    },
+                }
+            }
+        }
+    }
+}
+
+pin_project! {
+    /// The future returned by [`RateLimitService`].
+    ///
+    /// An admitted request's future is polled in place rather than boxed, so the
+    /// layer adds no allocation per request.
+    pub struct RateLimitFuture<F, B> {
+        #[pin]
+        state: State<F, B>,
+    }
+}
+
+pin_project! {
+    /// Whether a [`RateLimitFuture`]'s request was admitted.
+    #[project = StateProj]
+    enum State<F, B> {
+        /// The request was admitted and is handled by the inner service.
+        Admitted {
+            #[pin]
+            future: F,
+        },
+        /// The request was rejected; the `429` response is returned on the first poll.
+        Rejected {
+            response: Option<Response<B>>,
+        },
+    }
+}
+
+impl<F, B, E> Future for RateLimitFuture<F, B>
+where
+    F: Future<Output = core::result::Result<Response<B>, E>>,
+{
+    type Output = core::result::Result<Response<B>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project().state.project() {
+            StateProj::Admitted { future } => future.poll(cx),
+            StateProj::Rejected { response } => Poll::Ready(Ok(response
+                .take()
+                .expect("RateLimitFuture polled after completion"))),
+        }
+    }
+}
+
+impl<F, B> fmt::Debug for RateLimitFuture<F, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.state {
+            State::Admitted { .. } => f.write_str("RateLimitFuture::Admitted"),
+            State::Rejected { .. } => f.write_str("RateLimitFuture::Rejected"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use tower::{service_fn, ServiceExt};
+
+    use super::*;
+    use crate::{clock::MockClock, RateLimitError, TokenBucket};
+
+    async fn ok(_request: Request<()>) -> core::result::Result<Response<String>, Infallible> {
+        Ok(Response::new("ok".to_owned()))
+    }
+
+    #[tokio::test]
+    async fn test_rejected_requests_get_429_with_retry_after() {
+        let layer = RateLimitLayer::new(TokenBucket::with_clock(1, 0.4, MockClock::new(0)));
+        let service = layer.layer(service_fn(ok));
+
+        let response = service.clone().oneshot(Request::new(())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.into_body(), "ok");
+
+        // 2.5 s until the next token, rounded up to whole seconds
+        let response = service.oneshot(Request::new(())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "3");
+        assert_eq!(response.into_body(), "");
+        assert_eq!(layer.limiter().available_tokens(), 0);
+    }
+
+    /// A limiter whose requests never fit, as reported by the limiters for requests
+    /// above their capacity.
+    #[derive(Debug)]
+    struct ImpossibleLimiter;
+
+    impl<B> RequestLimiter<B> for ImpossibleLimiter {
+        fn try_acquire_request(&self, _request: &Request<B>) -> Result<()> {
+            Err(RateLimitError::rate_limit_exceeded(1, 0, 0))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_impossible_requests_get_no_retry_after() {
+        let layer = RateLimitLayer::new(ImpossibleLimiter);
+        let response = layer
+            .layer(service_fn(ok))
+            .oneshot(Request::new(()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(!response.headers().contains_key(header::RETRY_AFTER));
+    }
+
+    #[tokio::test]
+    async fn test_per_key_limits() {
+        let layer = RateLimitLayer::per_key(
+            KeyedRateLimiter::with_clock(
+                || TokenBucket::with_clock(1, 1.0, MockClock::new(0)),
+                MockClock::new(0),
+            ),
+            |request: &Request<()>| request.uri().path().to_owned(),
+        );
+        let service = layer.layer(service_fn(ok));
+        let status = |path: &'static str| {
+            let service = service.clone();
+            async move {
+                let request = Request::builder().uri(path).body(()).unwrap();
+                service.oneshot(request).await.unwrap().status()
+            }
+        };
+
+        assert_eq!(status("/alice").await, StatusCode::OK);
+        assert_eq!(status("/bob").await, StatusCode::OK);
+        assert_eq!(status("/alice").await, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(layer.limiter().limiter().len(), 2);
+    }
+}
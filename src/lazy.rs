@@ -0,0 +1,158 @@
+//! Process-wide limiters initialized on first use.
+//!
+//! Web handlers often want one limiter per endpoint, stored in a `static`. Limiters
+//! cannot be built in a constant context, so [`LazyLimiter`] builds the limiter on first
+//! access and shares it from then on. The [`rate_limit!`](crate::rate_limit) macro
+//! wraps the common case of a per-call-site token bucket.
+//!
+//! This module is only available with the `std` feature.
+
+use core::fmt;
+use core::ops::Deref;
+use std::sync::{Arc, OnceLock};
+
+use crate::traits::RateLimiter;
+
+/// A shared limiter created the first time it is used.
+///
+/// Initialization runs exactly once, even when several threads race for the first
+/// access; the others wait for it and then see the same limiter.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use bucketboss::{LazyLimiter, RateLimiter, TokenBucket};
+///
+/// static SEARCH: LazyLimiter = LazyLimiter::new(|| Arc::new(TokenBucket::new(10, 5.0)));
+///
+/// assert!(SEARCH.try_acquire(1).is_ok());
+/// ```
+pub struct LazyLimiter {
+    cell: OnceLock<Arc<dyn RateLimiter>>,
+    init: fn() -> Arc<dyn RateLimiter>,
+}
+
+impl LazyLimiter {
+    /// Creates a limiter that `init` builds on first access.
+    pub const fn new(init: fn() -> Arc<dyn RateLimiter>) -> Self {
+        Self {
+            cell: OnceLock::new(),
+            init,
+        }
+    }
+
+    /// Returns the limiter, building it if this is the first access.
+    pub fn get(&self) -> &Arc<dyn RateLimiter> {
+        self.cell.get_or_init(self.init)
+    }
+
+    /// Returns a new handle to the limiter, building it if this is the first access.
+    pub fn shared(&self) -> Arc<dyn RateLimiter> {
+        Arc::clone(self.get())
+    }
+}
+
+impl Deref for LazyLimiter {
+    type Target = dyn RateLimiter;
+
+    fn deref(&self) -> &Self::Target {
+        &**self.get()
+    }
+}
+
+impl fmt::Debug for LazyLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyLimiter")
+            .field("initialized", &self.cell.get().is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns a process-wide [`TokenBucket`](crate::TokenBucket) for this call site.
+///
+/// Each invocation of the macro owns one bucket with the given capacity and rate in
+/// tokens per second, created on first use and shared by every later evaluation of the
+/// same invocation, from any thread. The result is an `Arc<dyn RateLimiter>`.
+///
+/// The arguments are evaluated once, at initialization, and must not refer to local
+/// variables.
+///
+/// # Panics
+///
+/// Panics on first use under the same conditions as
+/// [`TokenBucket::new`](crate::TokenBucket::new).
+///
+/// # Examples
+///
+/// ```
+/// use bucketboss::{rate_limit, RateLimiter};
+///
+/// fn handle_search() -> bool {
+///     rate_limit!(2, 1.0).try_acquire(1).is_ok()
+/// }
+///
+/// assert!(handle_search());
+/// assert!(handle_search());
+/// assert!(!handle_search());
+/// ```
+#[macro_export]
+macro_rules! rate_limit {
+    ($capacity:expr, $rate:expr $(,)?) => {{
+        static LIMITER: $crate::LazyLimiter = $crate::LazyLimiter::new(|| {
+            ::std::sync::Arc::new($crate::TokenBucket::new($capacity, $rate))
+        });
+        LIMITER.shared()
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Barrier;
+    use std::thread;
+
+    use super::*;
+    use crate::{clock::MockClock, TokenBucket};
+
+    static INITS: AtomicUsize = AtomicUsize::new(0);
+    static SHARED: LazyLimiter = LazyLimiter::new(|| {
+        let _ = INITS.fetch_add(1, Ordering::SeqCst);
+        Arc::new(TokenBucket::with_clock(100, 1.0, MockClock::new(0)))
+    });
+
+    #[test]
+    fn test_initializes_once_under_concurrent_first_access() {
+        let threads = 8;
+        let barrier = Arc::new(Barrier::new(threads));
+
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                let barrier = Arc::clone(&barrier);
+                thread::spawn(move || {
+                    let _ = barrier.wait();
+                    let limiter = SHARED.shared();
+                    assert!(limiter.try_acquire(1).is_ok());
+                    limiter
+                })
+            })
+            .collect();
+        let limiters: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        assert_eq!(INITS.load(Ordering::SeqCst), 1);
+        assert!(limiters.iter().all(|l| Arc::ptr_eq(l, SHARED.get())));
+        assert_eq!(SHARED.available_tokens(), 92);
+    }
+
+    #[test]
+    fn test_macro_shares_one_bucket_per_call_site() {
+        let acquire = || crate::rate_limit!(3, 0.001).try_acquire(1).is_ok();
+        assert!(acquire());
+        assert!(acquire());
+        assert!(acquire());
+        assert!(!acquire());
+
+        // A different call site has its own bucket
+        assert!(crate::rate_limit!(1, 0.001).try_acquire(1).is_ok());
+    }
+}
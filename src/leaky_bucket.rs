@@ -4,40 +4,110 @@
 //! where requests are added to the bucket and processed at a constant rate. This provides
 //! a smoother traffic pattern compared to Token Bucket.
 
+#[cfg(feature = "peak-burst")]
+use crate::peak::PeakTracker;
+#[cfg(feature = "saturation")]
+use crate::saturation::SaturationTracker;
 use crate::{
-    clock::{Clock, SystemClock},
+    backoff::Backoff,
+    clock::{now_ns, resolution_warning, Clock, SystemClock, NANOS_PER_MS},
     error::{RateLimitError, Result},
-    traits::{RateLimiter, ReconfigurableRateLimiter, WithClock},
+    float,
+    interval::{self, RefillInterval},
+    observer::Observer,
+    outcome::{saturate_u32, Outcome, WideOutcome},
+    policy::ZeroTokenPolicy,
+    schedule::Schedule,
+    status::BucketState,
+    traits::{
+        AdjustableRateLimiter, RateLimiter, RateLimiterBuilder, ReconfigurableRateLimiter,
+        Resettable, StatefulRateLimiter, WithClock,
+    },
+    validate,
 };
-use std::sync::atomic::{AtomicU64, Ordering};
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
 
-// Helper functions for atomic float operations
-fn f64_to_u64(value: f64) -> u64 {
-    value.to_bits()
+/// A guaranteed admission at a future time, returned by [`LeakyBucket::reserve_next`].
+///
+/// The slot is taken from the bucket when the reservation is made, so redeeming it
+/// with [`LeakyBucket::redeem`] only waits for [`ready_at`](Reservation::ready_at) and
+/// never competes with other requests. A reservation stands for exactly one slot of
+/// the bucket that made it, so it can neither be copied nor made up by hand, redeeming
+/// it consumes it, and other buckets reject it.
+#[allow(missing_copy_implementations)]
+#[derive(Debug, PartialEq, Eq)]
+pub struct Reservation {
+    /// The time, in milliseconds by the bucket's clock, from which the reservation can
+    /// be redeemed.
+    ready_at: u64,
+    /// The id of the bucket that made the reservation.
+    bucket: u64,
+}
+
+impl Reservation {
+    /// Returns the time, in milliseconds by the bucket's clock, from which the
+    /// reservation can be redeemed.
+    pub fn ready_at(&self) -> u64 {
+        self.ready_at
+    }
 }
 
-fn u64_to_f64(value: u64) -> f64 {
-    f64::from_bits(value)
+/// Returns the highest level a bucket of `capacity` reaches with reservations, which
+/// may queue at most one bucket's worth of slots past the capacity.
+fn reservation_limit(capacity: u64) -> u64 {
+    capacity.saturating_mul(2)
 }
 
+/// Returns the slots of `level` reserved past `capacity`.
+fn reserved_over(level: u64, capacity: u64) -> u64 {
+    level.saturating_sub(capacity)
+}
+
+/// Numbers the buckets within the process, so that each accepts only its own
+/// reservations.
+static BUCKET_IDS: AtomicU64 = AtomicU64::new(0);
+
 /// A thread-safe leaky bucket rate limiter.
 ///
 /// This implementation uses atomic operations to ensure thread safety without requiring
 /// external synchronization. It's designed for high throughput and low latency.
+///
+/// # Fairness
+///
+/// Concurrent callers race for the queue's free slots the same way they race for a
+/// token bucket's tokens: see [`TokenBucket`'s fairness notes](crate::TokenBucket#fairness).
 #[derive(Debug)]
-pub struct LeakyBucket<C = SystemClock> {
+pub struct LeakyBucket<C = SystemClock, O = ()> {
     /// The clock used to track time.
     clock: C,
+    /// Identifies the bucket to the reservations it makes.
+    id: u64,
     /// The capacity of the bucket (maximum burst size).
     capacity: AtomicU64,
-    /// The time in milliseconds between processing each request (stored as bits of f64).
-    ms_per_request: AtomicU64,
-    /// The time in milliseconds when the next request is allowed.
+    /// The time between processing each request.
+    interval: RefillInterval,
+    /// The time in nanoseconds when the next request drains.
     next_allowed_time: AtomicU64,
     /// The current number of requests in the bucket.
     current_level: AtomicU64,
+    /// How requests for zero tokens are treated.
+    zero_token_policy: ZeroTokenPolicy,
+    /// Milliseconds added to the `retry_after` advertised with rejections.
+    retry_slack_ms: u64,
+    /// Receives the admission decisions.
+    observer: O,
+    /// The clock movement, in nanoseconds, below which the drain is not recomputed.
+    drain_interval_ns: u64,
+    /// The largest number of requests admitted within one second.
+    #[cfg(feature = "peak-burst")]
+    peak: PeakTracker,
+    /// When the bucket last became exhausted.
+    #[cfg(feature = "saturation")]
+    saturation: SaturationTracker,
 }
 
+#[cfg(feature = "std")]
 impl LeakyBucket<SystemClock> {
     /// Creates a new `LeakyBucket` with the specified rate and optional burst size.
     ///
@@ -49,25 +119,55 @@ impl LeakyBucket<SystemClock> {
     ///
     /// # Panics
     ///
-    /// Panics if `requests_per_second` is zero or if `burst_size` is zero.
+    /// Panics if `requests_per_second` is not positive or exceeds
+    /// [`MAX_RATE`](crate::MAX_RATE), or if `burst_size` is zero. With the `fixed`
+    /// feature it also panics if the rate is below one request per 78 hours or above
+    /// 6.5·10¹³ requests per second, the range of the fixed-point refill interval. With
+    /// the `no-panic` feature this only happens in debug builds; release builds clamp
+    /// the burst size to 1 and the rate into the valid range instead.
     pub fn new(requests_per_second: f64, burst_size: Option<u32>) -> Self {
-        assert!(
-            requests_per_second > 0.0,
-            "requests_per_second must be positive"
-        );
-        let burst_size = burst_size.unwrap_or(1);
-        assert!(burst_size > 0, "burst_size must be greater than 0");
+        Self::with_clock(requests_per_second, burst_size, SystemClock)
+    }
+
+    /// Creates a new `LeakyBucket` whose burst size may exceed `u32::MAX`.
+    ///
+    /// Use this for large-magnitude budgets such as byte counts, together with
+    /// [`RateLimiter::try_acquire_u64`] and [`RateLimiter::available_tokens_u64`].
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`LeakyBucket::new`].
+    pub fn new_u64(requests_per_second: f64, burst_size: Option<u64>) -> Self {
+        Self::with_clock_u64(requests_per_second, burst_size, SystemClock)
+    }
 
-        let now = SystemClock.now();
-        let ms_per_request = 1000.0 / requests_per_second;
+    /// Creates a new `LeakyBucket`, reporting invalid input as an error instead of
+    /// panicking.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Self)` if the configuration is valid
+    /// * `Err(RateLimitError::InvalidConfiguration)` if `requests_per_second` is not
+    ///   positive, is NaN or infinite, or exceeds [`MAX_RATE`](crate::MAX_RATE), or if
+    ///   `burst_size` is zero; with the `fixed` feature, also if the rate is outside the
+    ///   range [`LeakyBucket::new`] accepts
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bucketboss::LeakyBucket;
+    ///
+    /// // A rate parsed from runtime configuration
+    /// let rate: f64 = "NaN".parse().unwrap();
+    /// assert!(LeakyBucket::try_new(rate, Some(10)).unwrap_err().is_invalid_config());
+    /// ```
+    pub fn try_new(requests_per_second: f64, burst_size: Option<u32>) -> Result<Self> {
+        Self::try_with_clock(requests_per_second, burst_size, SystemClock)
+    }
 
-        Self {
-            capacity: AtomicU64::new(burst_size as u64),
-            ms_per_request: AtomicU64::new(f64_to_u64(ms_per_request)),
-            next_allowed_time: AtomicU64::new(now),
-            current_level: AtomicU64::new(0),
-            clock: SystemClock,
-        }
+    /// Returns a builder for configuring a `LeakyBucket` step by step.
+    pub fn builder() -> LeakyBucketBuilder {
+        LeakyBucketBuilder::new()
     }
 
     /// Creates a new `LeakyBucket` that allows one request per second.
@@ -85,31 +185,202 @@ where
     /// Creates a new `LeakyBucket` with the specified clock.
     ///
     /// This is useful for testing or for environments where you need to control time.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`LeakyBucket::new`].
     pub fn with_clock(requests_per_second: f64, burst_size: Option<u32>, clock: C) -> Self {
-        assert!(
-            requests_per_second > 0.0,
-            "requests_per_second must be positive"
-        );
-        let burst_size = burst_size.unwrap_or(1);
-        assert!(burst_size > 0, "burst_size must be greater than 0");
+        Self::with_clock_u64(requests_per_second, burst_size.map(u64::from), clock)
+    }
+
+    /// Creates a new `LeakyBucket` with the specified clock and a burst size that may
+    /// exceed `u32::MAX`.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`LeakyBucket::new`].
+    pub fn with_clock_u64(requests_per_second: f64, burst_size: Option<u64>, clock: C) -> Self {
+        let requests_per_second = interval::rate(validate::rate(
+            requests_per_second,
+            "requests_per_second must be positive",
+        ));
+        let burst_size =
+            validate::capacity(burst_size.unwrap_or(1), "burst_size must be greater than 0");
 
-        let now = clock.now();
-        let ms_per_request = 1000.0 / requests_per_second;
+        let now = now_ns(&clock);
 
         Self {
-            capacity: AtomicU64::new(burst_size as u64),
-            ms_per_request: AtomicU64::new(f64_to_u64(ms_per_request)),
+            id: BUCKET_IDS.fetch_add(1, Ordering::Relaxed),
+            capacity: AtomicU64::new(burst_size),
+            interval: RefillInterval::from_ms(1000.0 / requests_per_second),
             next_allowed_time: AtomicU64::new(now),
             current_level: AtomicU64::new(0),
             clock,
+            zero_token_policy: ZeroTokenPolicy::AlwaysAdmit,
+            retry_slack_ms: 0,
+            observer: (),
+            drain_interval_ns: 0,
+            #[cfg(feature = "peak-burst")]
+            peak: PeakTracker::new(),
+            #[cfg(feature = "saturation")]
+            saturation: SaturationTracker::new(),
+        }
+    }
+
+    /// Creates a new `LeakyBucket` with the specified clock, reporting invalid input as
+    /// an error instead of panicking.
+    ///
+    /// See [`LeakyBucket::try_new`] for the conditions that are rejected.
+    pub fn try_with_clock(
+        requests_per_second: f64,
+        burst_size: Option<u32>,
+        clock: C,
+    ) -> Result<Self> {
+        validate::try_rate(requests_per_second, "requests_per_second must be positive")?;
+        interval::try_rate(requests_per_second)?;
+        if burst_size == Some(0) {
+            return Err(RateLimitError::invalid_config(
+                "burst_size must be greater than 0",
+            ));
+        }
+
+        Ok(Self::with_clock(requests_per_second, burst_size, clock))
+    }
+
+    /// Rebuilds a bucket from a [`BucketState`], for example one persisted by the
+    /// previous process in a rolling deploy, so a restart does not forget the queued
+    /// requests.
+    ///
+    /// The bucket continues to drain from the state's `last_update_ns`, which is
+    /// clamped to the clock's current time if it lies in the future. The queued level
+    /// keeps the slots reserved with [`reserve_next`](Self::reserve_next) past the
+    /// capacity, up to the limit on reservations, and is clamped to that limit.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Self)` if the snapshot's configuration is valid
+    /// * `Err(RateLimitError::InvalidConfiguration)` if its capacity is 0 or its rate
+    ///   is not positive and finite
+    pub fn from_state(state: BucketState, clock: C) -> Result<Self> {
+        validate::try_rate(
+            state.tokens_per_second,
+            "requests_per_second must be positive",
+        )?;
+        interval::try_rate(state.tokens_per_second)?;
+        if state.capacity == 0 {
+            return Err(RateLimitError::invalid_config(
+                "burst_size must be greater than 0",
+            ));
+        }
+
+        let bucket = Self::with_clock_u64(state.tokens_per_second, Some(state.capacity), clock);
+        let now = now_ns(&bucket.clock);
+        bucket.current_level.store(
+            state.tokens.min(reservation_limit(state.capacity)),
+            Ordering::Release,
+        );
+        bucket
+            .next_allowed_time
+            .store(state.last_update_ns.min(now), Ordering::Release);
+        Ok(bucket)
+    }
+}
+
+impl<C, O> LeakyBucket<C, O>
+where
+    C: Clock,
+    O: Observer,
+{
+    /// Sets how requests for zero tokens are treated.
+    ///
+    /// See [`ZeroTokenPolicy`] for the available behaviors.
+    pub fn with_zero_token_policy(mut self, policy: ZeroTokenPolicy) -> Self {
+        self.zero_token_policy = policy;
+        self
+    }
+
+    /// Adds `slack` to the `retry_after` advertised with every rejection.
+    ///
+    /// Clients that retry exactly when told often arrive a moment too early because of
+    /// network jitter or clock skew, and are rejected again. The slack makes them wait
+    /// slightly longer so the first retry succeeds. Only the advertised wait changes:
+    /// admission decisions and [`time_until_available_ms`](RateLimiter::time_until_available_ms)
+    /// are unaffected. Unlike jitter, the slack is the same for every rejection.
+    pub fn with_retry_slack(mut self, slack: Duration) -> Self {
+        self.retry_slack_ms = slack.as_millis().min(u64::MAX as u128) as u64;
+        self
+    }
+
+    /// Only recomputes the drain once the clock has moved at least `interval` since the
+    /// last drain.
+    ///
+    /// By default every call that sees the clock move drains the bucket with a
+    /// compare-and-swap, which under saturated load means atomic traffic on every
+    /// acquire. With an interval, calls in between reuse the last drained level, so the
+    /// writes happen at most once per interval however many calls arrive.
+    ///
+    /// The trade-off is bounded under-draining, never over-admission: the level seen
+    /// can be stale by up to `interval`, so up to `interval × rate` slots, rounded up,
+    /// that have already drained may be reported as occupied and waits may be
+    /// overstated by up to `interval`. Nothing drained is lost; it is credited at the
+    /// next recomputation. Keep the interval well below the time one slot takes to
+    /// drain if that inaccuracy matters.
+    pub fn with_drain_interval(mut self, interval: Duration) -> Self {
+        self.drain_interval_ns = interval.as_nanos().min(u64::MAX as u128) as u64;
+        self
+    }
+
+    /// Calls `observer` with every admission decision, from within `try_acquire` and
+    /// the other acquiring methods.
+    ///
+    /// See [`Observer`] for the requirements on its methods, which run concurrently on
+    /// the admission path.
+    pub fn with_observer<P>(self, observer: P) -> LeakyBucket<C, P>
+    where
+        P: Observer,
+    {
+        LeakyBucket {
+            clock: self.clock,
+            id: self.id,
+            capacity: self.capacity,
+            interval: self.interval,
+            next_allowed_time: self.next_allowed_time,
+            current_level: self.current_level,
+            zero_token_policy: self.zero_token_policy,
+            retry_slack_ms: self.retry_slack_ms,
+            observer,
+            drain_interval_ns: self.drain_interval_ns,
+            #[cfg(feature = "peak-burst")]
+            peak: self.peak,
+            #[cfg(feature = "saturation")]
+            saturation: self.saturation,
+        }
+    }
+
+    /// Returns the observer of the bucket's admission decisions.
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    /// Sets whether the bucket starts full instead of empty.
+    ///
+    /// A new bucket is empty, so its whole burst is available immediately. A primed
+    /// bucket starts at capacity, as if it had just been
+    /// [`drain`](Resettable::drain)ed: nothing is admitted until requests drain, and
+    /// then only at the steady rate. This suits limiters created at process start,
+    /// where a fleet restarting at once should not each let through a full burst.
+    pub fn with_start_primed(self, primed: bool) -> Self {
+        if primed {
+            self.drain();
         }
+        self
     }
 
-    /// Updates the internal state of the leaky bucket based on the current time.
+    /// Updates the internal state of the leaky bucket based on the current time, in
+    /// nanoseconds.
     fn update_state(&self, now: u64) -> (u64, u64) {
         let mut current_level = self.current_level.load(Ordering::Relaxed);
         let mut next_allowed = self.next_allowed_time.load(Ordering::Acquire);
-        let ms_per_request = u64_to_f64(self.ms_per_request.load(Ordering::Acquire));
         // Explicitly ignore the capacity load to prevent rate limit violations
         let _ = self.capacity.load(Ordering::Acquire);
 
@@ -125,12 +396,16 @@ where
                 // No time has passed, state is up to date
                 return (current_level, next_allowed);
             }
+            if elapsed < self.drain_interval_ns {
+                // Amortized: report the last drained level, which can only overstate it
+                return (current_level, next_allowed);
+            }
 
             // Calculate how many requests could have been processed in the elapsed time
-            let processed = if ms_per_request > 0.0 {
-                (elapsed as f64 / ms_per_request) as u64
+            let processed = if self.interval.is_positive() {
+                self.interval.tokens_in(elapsed)
             } else {
-                current_level // If ms_per_request is 0, process all requests
+                current_level // If the interval is 0, process all requests
             };
 
             if processed >= current_level {
@@ -141,14 +416,16 @@ where
                     .is_ok()
                 {
                     // Update the next_allowed_time to be now
-                    let new_next = now + ms_per_request as u64;
+                    let new_next = now + self.interval.ns_for_truncated(1);
                     self.next_allowed_time.store(new_next, Ordering::Release);
+                    #[cfg(feature = "saturation")]
+                    self.saturation.update(now, false);
                     return (0, new_next);
                 }
             } else {
                 // Some requests remain in the bucket
                 let new_level = current_level - processed;
-                let new_next = next_allowed + (processed as f64 * ms_per_request) as u64;
+                let new_next = next_allowed + self.interval.ns_for_truncated(processed);
 
                 // Try to update the state atomically
                 if self
@@ -162,6 +439,9 @@ where
                     .is_ok()
                 {
                     self.next_allowed_time.store(new_next, Ordering::Release);
+                    #[cfg(feature = "saturation")]
+                    self.saturation
+                        .update(now, new_level >= self.capacity.load(Ordering::Acquire));
                     return (new_level, new_next);
                 }
             }
@@ -172,6 +452,377 @@ where
         }
     }
 
+    /// Brings the saturation tracker up to date after the level was changed at `now`, in
+    /// nanoseconds, other than by a leak or an admission.
+    #[cfg(feature = "saturation")]
+    fn track_saturation(&self, now: u64) {
+        let full =
+            self.current_level.load(Ordering::Acquire) >= self.capacity.load(Ordering::Acquire);
+        self.saturation.update(now, full);
+    }
+
+    /// Returns the queued level as of `now`, in nanoseconds, without storing the drain.
+    fn level_at(&self, now: u64) -> u64 {
+        let current_level = self.current_level.load(Ordering::Acquire);
+        let elapsed = now.saturating_sub(self.next_allowed_time.load(Ordering::Acquire));
+        if current_level == 0 || elapsed == 0 || elapsed < self.drain_interval_ns {
+            return current_level;
+        }
+        if !self.interval.is_positive() {
+            return 0;
+        }
+        current_level.saturating_sub(self.interval.tokens_in(elapsed))
+    }
+
+    /// Returns the number of slots currently available, including the fraction of the
+    /// next slot that has drained so far.
+    ///
+    /// `available_tokens` truncates this value, so at low rates it can report 0 while
+    /// the next slot has almost drained. Admission decisions still use whole slots;
+    /// this is intended for monitoring and progress reporting.
+    pub fn available_tokens_f64(&self) -> f64 {
+        let now = now_ns(&self.clock);
+        let (current_level, next_allowed) = self.update_state(now);
+        let capacity = self.capacity.load(Ordering::Acquire);
+        let available = capacity.saturating_sub(current_level) as f64;
+        let ns_per_request = self.interval.ns();
+        if current_level == 0 || ns_per_request <= 0.0 {
+            return available;
+        }
+
+        let drained = now.saturating_sub(next_allowed) as f64 / ns_per_request;
+        (available + drained.min(current_level as f64)).min(capacity as f64)
+    }
+
+    /// Returns the largest number of requests admitted within any one-second window so
+    /// far.
+    ///
+    /// This is the observed counterpart of the configured burst size, for audits such
+    /// as "never more than X per second". Admissions are counted in 100 ms slots, so
+    /// the window covers between 0.9 and 1 s. The count saturates at `u32::MAX`.
+    #[cfg(feature = "peak-burst")]
+    pub fn peak_burst(&self) -> u32 {
+        saturate_u32(self.peak.peak())
+    }
+
+    /// Returns how long the bucket has been continuously full, or `None` if a slot is
+    /// available.
+    ///
+    /// The full stretch starts at the admission that took the last slot, so this is a
+    /// signal for alerting on a limiter that stays exhausted, as opposed to one that is
+    /// briefly filled between leaks.
+    #[cfg(feature = "saturation")]
+    pub fn saturated_since(&self) -> Option<Duration> {
+        let now = now_ns(&self.clock);
+        let (current_level, _) = self.update_state(now);
+        let capacity = self.capacity.load(Ordering::Acquire);
+        self.saturation.since(now, current_level >= capacity)
+    }
+
+    /// Raises the number of available slots to at least `min`.
+    ///
+    /// This grants extra budget ahead of a planned spike by draining the bucket's level.
+    /// The target is clamped to the bucket's capacity, and the available count is only
+    /// ever raised, never lowered.
+    ///
+    /// Slots reserved with [`reserve_next`](Self::reserve_next) past the capacity stay
+    /// reserved on top of the drained level, so while reservations are outstanding
+    /// fewer than `min` slots may be available.
+    pub fn ensure_available(&self, min: u32) {
+        let now = now_ns(&self.clock);
+        let _ = self.update_state(now);
+
+        let capacity = self.capacity.load(Ordering::Acquire);
+        let max_level = capacity.saturating_sub(min as u64);
+        let _ = self
+            .current_level
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |level| {
+                Some(level.min(max_level) + reserved_over(level, capacity))
+            });
+        #[cfg(feature = "saturation")]
+        self.track_saturation(now);
+    }
+
+    /// Reserves the next free slot, which may lie in the future, and returns when it
+    /// can be used.
+    ///
+    /// Unlike [`try_acquire`](RateLimiter::try_acquire), this succeeds while the bucket
+    /// is full: the slot is queued behind those already taken, raising the level past
+    /// the capacity until it drains. Consecutive reservations on a full bucket are
+    /// therefore ready one interval apart, and later requests wait for every slot
+    /// reserved ahead of them. This is the pacing model of schedulers that plan work
+    /// ahead instead of retrying on rejection.
+    ///
+    /// At most one bucket's worth of slots is reserved past the capacity, so the wait
+    /// for a reservation stays below the time the bucket takes to drain twice over.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Reservation)` with the time the slot can be used
+    /// * `Err(RateLimitError::RateLimitExceeded)` if the capacity is already reserved
+    ///   over, with the wait until the next slot can be reserved
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bucketboss::{clock::MockClock, LeakyBucket};
+    ///
+    /// let clock = MockClock::new(0);
+    /// let bucket = LeakyBucket::with_clock(10.0, Some(1), clock.clone());
+    /// let first = bucket.reserve_next().unwrap();
+    /// let second = bucket.reserve_next().unwrap();
+    /// assert_eq!((first.ready_at(), second.ready_at()), (0, 100));
+    /// assert!(bucket.reserve_next().is_err());
+    ///
+    /// let (second, err) = bucket.redeem(second).unwrap_err();
+    /// assert_eq!(err.retry_after_ms(), Some(100));
+    /// clock.set(100);
+    /// assert!(bucket.redeem(second).is_ok());
+    /// ```
+    pub fn reserve_next(&self) -> Result<Reservation> {
+        let mut backoff = Backoff::new();
+        loop {
+            let now = now_ns(&self.clock);
+            let (current_level, _) = self.update_state(now);
+            let capacity = self.capacity.load(Ordering::Acquire);
+
+            let limit = reservation_limit(capacity);
+            if current_level >= limit {
+                return Err(RateLimitError::rate_limit_exceeded(
+                    1,
+                    0,
+                    self.interval.ms_for(current_level + 1 - limit),
+                ));
+            }
+
+            let new_level = current_level.saturating_add(1);
+            if self
+                .current_level
+                .compare_exchange(
+                    current_level,
+                    new_level,
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                #[cfg(feature = "saturation")]
+                if new_level == capacity {
+                    self.saturation.record_exhausted(now);
+                }
+                let wait_ns = self.interval.ns_for(new_level.saturating_sub(capacity));
+                return Ok(Reservation {
+                    ready_at: now.saturating_add(wait_ns).div_ceil(NANOS_PER_MS),
+                    bucket: self.id,
+                });
+            }
+
+            backoff.spin();
+        }
+    }
+
+    /// Redeems a reservation made by [`reserve_next`](Self::reserve_next) on this
+    /// bucket.
+    ///
+    /// Redeeming consumes the reservation, so its slot cannot be used twice:
+    ///
+    /// ```compile_fail
+    /// use bucketboss::LeakyBucket;
+    ///
+    /// let bucket = LeakyBucket::new(10.0, Some(1));
+    /// let reservation = bucket.reserve_next().unwrap();
+    /// let _ = bucket.redeem(reservation);
+    /// let _ = bucket.redeem(reservation);
+    /// ```
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` once the clock has reached the reservation's `ready_at`, regardless
+    ///   of how full the bucket is, since its slot was already taken
+    /// * `Err((reservation, RateLimitError::RateLimitExceeded))` before then, handing
+    ///   the reservation back with the wait until `ready_at`
+    /// * `Err((reservation, RateLimitError::InvalidConfiguration))` if another bucket
+    ///   made the reservation
+    pub fn redeem(
+        &self,
+        reservation: Reservation,
+    ) -> core::result::Result<(), (Reservation, RateLimitError)> {
+        if reservation.bucket != self.id {
+            return Err((
+                reservation,
+                RateLimitError::invalid_config("reservation was made by another bucket"),
+            ));
+        }
+        let now = self.clock.now();
+        if now >= reservation.ready_at {
+            return Ok(());
+        }
+        let wait_ms = reservation.ready_at - now;
+        Err((
+            reservation,
+            RateLimitError::rate_limit_exceeded(1, self.available_tokens(), wait_ms),
+        ))
+    }
+
+    /// Returns the wait, as of `now` in nanoseconds, until `tokens` could be acquired.
+    fn wait_ms_at(&self, now: u64, tokens: u32) -> Option<u64> {
+        let capacity = self.capacity.load(Ordering::Acquire);
+        if tokens as u64 > capacity {
+            return None;
+        }
+
+        let (current_level, _) = self.update_state(now);
+        if current_level + tokens as u64 <= capacity {
+            return Some(0);
+        }
+
+        if !self.interval.is_positive() {
+            return None;
+        }
+        Some(
+            self.interval
+                .ms_for(current_level + tokens as u64 - capacity),
+        )
+    }
+
+    /// Admits if at least one slot is available, without consuming anything.
+    fn probe_availability(&self) -> WideOutcome {
+        let now = now_ns(&self.clock);
+        let (current_level, _) = self.update_state(now);
+        let capacity = self.capacity.load(Ordering::Acquire);
+        if current_level < capacity {
+            return WideOutcome::Admitted {
+                remaining: capacity - current_level,
+            };
+        }
+
+        let wait_ms = self.interval.ms_for(current_level + 1 - capacity);
+        WideOutcome::Throttled {
+            retry_after_ms: wait_ms,
+            available: 0,
+        }
+    }
+
+    /// Makes the admission decision for `tokens`, in full-width counts, as advertised
+    /// to the caller.
+    fn acquire_wide(&self, tokens: u64) -> WideOutcome {
+        let outcome = self
+            .decide_wide(tokens)
+            .with_retry_slack(self.retry_slack_ms);
+        match outcome {
+            WideOutcome::Admitted { .. } => self.observer.on_acquire(tokens, true),
+            WideOutcome::Throttled { retry_after_ms, .. } => {
+                self.observer.on_acquire(tokens, false);
+                self.observer.on_reject(tokens, retry_after_ms);
+            }
+        }
+        outcome
+    }
+
+    /// Makes the admission decision for `tokens`, in full-width counts.
+    fn decide_wide(&self, tokens: u64) -> WideOutcome {
+        if tokens == 0 {
+            return match self.zero_token_policy {
+                ZeroTokenPolicy::AlwaysAdmit => WideOutcome::Admitted {
+                    remaining: self.available_tokens_u64(),
+                },
+                ZeroTokenPolicy::ReflectAvailability => self.probe_availability(),
+            };
+        }
+
+        let capacity = self.capacity.load(Ordering::Acquire);
+
+        // Check if the request exceeds the bucket capacity
+        if tokens > capacity {
+            return WideOutcome::Throttled {
+                retry_after_ms: 0, // No wait time since the request is immediately rejected
+                available: capacity,
+            };
+        }
+
+        let mut backoff = Backoff::new();
+        loop {
+            let now = now_ns(&self.clock);
+            // We don't need the next_allowed value here, so we can ignore it
+            let (current_level, _) = self.update_state(now);
+
+            // Check if we have enough capacity
+            if current_level.saturating_add(tokens) > capacity {
+                // Calculate wait time based on the current rate
+                let wait_ms = self.interval.ms_for(current_level + tokens - capacity);
+
+                // Report the room this decision was made on rather than re-reading it,
+                // which a concurrent leak could already have raised to `tokens`
+                return WideOutcome::Throttled {
+                    retry_after_ms: wait_ms,
+                    available: capacity.saturating_sub(current_level),
+                };
+            }
+
+            // Try to acquire the tokens
+            let new_level = current_level + tokens;
+            if self
+                .current_level
+                .compare_exchange(
+                    current_level,
+                    new_level,
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                #[cfg(feature = "peak-burst")]
+                self.peak.record(now / NANOS_PER_MS, tokens);
+                #[cfg(feature = "saturation")]
+                if new_level == capacity {
+                    self.saturation.record_exhausted(now);
+                }
+                return WideOutcome::Admitted {
+                    remaining: capacity - new_level,
+                };
+            }
+
+            // If we couldn't update atomically, back off and retry the whole operation
+            backoff.spin();
+        }
+    }
+
+    /// Makes the admission decision `decide_wide` would make for `tokens`, without
+    /// draining or filling anything.
+    fn peek_wide(&self, tokens: u64) -> WideOutcome {
+        let current_level = self.level_at(now_ns(&self.clock));
+        let capacity = self.capacity.load(Ordering::Acquire);
+        if tokens == 0 {
+            return match self.zero_token_policy {
+                ZeroTokenPolicy::ReflectAvailability if current_level >= capacity => {
+                    WideOutcome::Throttled {
+                        retry_after_ms: self.interval.ms_for(current_level + 1 - capacity),
+                        available: 0,
+                    }
+                }
+                _ => WideOutcome::Admitted {
+                    remaining: capacity.saturating_sub(current_level),
+                },
+            };
+        }
+
+        if tokens > capacity {
+            return WideOutcome::Throttled {
+                retry_after_ms: 0,
+                available: capacity,
+            };
+        }
+        if current_level.saturating_add(tokens) > capacity {
+            return WideOutcome::Throttled {
+                retry_after_ms: self.interval.ms_for(current_level + tokens - capacity),
+                available: capacity.saturating_sub(current_level),
+            };
+        }
+        WideOutcome::Admitted {
+            remaining: capacity - current_level - tokens,
+        }
+    }
+
     /// Updates the rate and capacity of the leaky bucket.
     ///
     /// # Arguments
@@ -179,20 +830,12 @@ where
     /// * `capacity` - The new capacity of the bucket (maximum burst size).
     /// * `requests_per_second` - The new rate of requests allowed, in requests per second.
     fn set_rate(&self, capacity: u64, requests_per_second: f64) {
-        // Calculate the new ms_per_request value
-        let ms_per_request = if requests_per_second > 0.0 {
-            1000.0 / requests_per_second
-        } else {
-            0.0
-        };
-
         // Store the new values atomically
         self.capacity.store(capacity, Ordering::Release);
-        self.ms_per_request
-            .store(f64_to_u64(ms_per_request), Ordering::Release);
+        self.store_rate(requests_per_second);
 
         // Update the next_allowed_time to prevent rate limit violations
-        let now = self.clock.now();
+        let now = now_ns(&self.clock);
         // We don't need the next_allowed value here, so we can ignore it
         let (current_level, _) = self.update_state(now);
 
@@ -207,145 +850,346 @@ where
             }
         }
     }
+
+    /// Updates the processing rate of the leaky bucket.
+    fn store_rate(&self, requests_per_second: f64) {
+        let ms_per_request = if requests_per_second > 0.0 {
+            1000.0 / requests_per_second
+        } else {
+            0.0
+        };
+        self.interval.store_ms(ms_per_request);
+    }
 }
 
-impl<C> RateLimiter for LeakyBucket<C>
+impl<C, O> RateLimiter for LeakyBucket<C, O>
 where
     C: Clock,
+    O: Observer,
 {
     fn try_acquire(&self, tokens: u32) -> Result<()> {
-        if tokens == 0 {
-            return Ok(());
-        }
+        self.acquire_wide(tokens as u64).into_result(tokens as u64)
+    }
 
-        let capacity = self.capacity.load(Ordering::Acquire);
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        self.acquire_wide(tokens).into_result(tokens)
+    }
 
-        // Check if the request exceeds the bucket capacity
-        if tokens > capacity as u32 {
-            return Err(RateLimitError::rate_limit_exceeded(
-                tokens,
-                capacity as u32,
-                0, // No wait time since the request is immediately rejected
-            ));
+    fn try_acquire_up_to(&self, max_tokens: u32) -> u32 {
+        let mut tokens = self.available_tokens_u64().min(max_tokens as u64);
+        while tokens > 0 {
+            match self.acquire_wide(tokens) {
+                WideOutcome::Admitted { .. } => break,
+                // A concurrent acquisition took some of the tokens first
+                WideOutcome::Throttled { available, .. } => tokens = available.min(tokens - 1),
+            }
         }
+        tokens as u32
+    }
 
-        let now = self.clock.now();
-        // We don't need the next_allowed value here, so we can ignore it
-        let (current_level, _) = self.update_state(now);
-
-        // Check if we have enough capacity
-        if current_level + (tokens as u64) > capacity {
-            // Calculate wait time based on the current rate
-            let ms_per_request = u64_to_f64(self.ms_per_request.load(Ordering::Acquire));
-            let wait_ms = if ms_per_request > 0.0 {
-                ((current_level + tokens as u64 - capacity) as f64 * ms_per_request).ceil() as u64
-            } else {
-                0
-            };
-
-            return Err(RateLimitError::rate_limit_exceeded(
-                tokens,
-                capacity.saturating_sub(current_level) as u32,
-                wait_ms,
-            ));
-        }
+    fn acquire_outcome(&self, tokens: u32) -> Outcome {
+        self.acquire_wide(tokens as u64)
+            .narrow(self.capacity_u64(), self.rate_per_second())
+    }
 
-        // Try to acquire the tokens
-        let new_level = current_level + tokens as u64;
-        if self
-            .current_level
-            .compare_exchange(
-                current_level,
-                new_level,
-                Ordering::AcqRel,
-                Ordering::Relaxed,
-            )
-            .is_ok()
-        {
-            Ok(())
-        } else {
-            // If we couldn't update atomically, retry the whole operation
-            self.try_acquire(tokens)
-        }
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        self.peek_wide(tokens as u64)
+            .with_retry_slack(self.retry_slack_ms)
+            .into_result(tokens as u64)
     }
 
     fn available_tokens(&self) -> u32 {
-        let now = self.clock.now();
+        saturate_u32(self.available_tokens_u64())
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        let now = now_ns(&self.clock);
         let (current_level, _) = self.update_state(now);
         self.capacity
             .load(Ordering::Acquire)
-            .saturating_sub(current_level) as u32
+            .saturating_sub(current_level)
     }
 
     fn capacity(&self) -> u32 {
-        self.capacity.load(Ordering::Acquire) as u32
+        saturate_u32(self.capacity_u64())
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.capacity.load(Ordering::Acquire)
     }
 
+    /// Returns the drain rate in requests per second.
+    ///
+    /// A zero or vanishingly small interval between requests, which no valid
+    /// configuration produces, drains every queued request at once and is reported as
+    /// `f64::INFINITY`.
     fn rate_per_second(&self) -> f64 {
-        let ms_per_request = u64_to_f64(self.ms_per_request.load(Ordering::Acquire));
-        if ms_per_request > 0.0 {
-            let rate = 1000.0 / ms_per_request;
-            // Round to 6 decimal places to handle floating-point precision issues
-            (rate * 1_000_000.0).round() / 1_000_000.0
+        let ms_per_request = self.interval.ms();
+        let rate = 1000.0 / ms_per_request;
+        if !(ms_per_request > 0.0 && rate.is_finite()) {
+            return f64::INFINITY;
+        }
+
+        // Round to 6 decimal places to handle floating-point precision issues, where
+        // the scaled rate is still representable
+        let scaled = rate * 1_000_000.0;
+        if scaled.is_finite() {
+            float::round(scaled) / 1_000_000.0
         } else {
-            0.0
+            rate
         }
     }
 
+    fn resolution_warning(&self) -> Option<&'static str> {
+        resolution_warning(&self.clock, self.rate_per_second())
+    }
+
+    fn acquire_probe(&self) -> (bool, Duration) {
+        // Pace the next attempt to the next drain, even if there is room left, so the
+        // caller converges on the rate instead of filling the burst
+        let admitted = matches!(self.acquire_wide(1), WideOutcome::Admitted { .. });
+        let now = now_ns(&self.clock);
+        let (_, next_allowed) = self.update_state(now);
+        let interval_ns = self.interval.ns_for(1);
+        let wait_ns = next_allowed
+            .saturating_add(interval_ns)
+            .saturating_sub(now)
+            .min(interval_ns);
+        (
+            admitted,
+            Duration::from_millis(wait_ns.div_ceil(NANOS_PER_MS)),
+        )
+    }
+
     fn time_until_next_token_ms(&self) -> Option<u64> {
-        let now = self.clock.now();
+        let now = now_ns(&self.clock);
         let next_allowed = self.next_allowed_time.load(Ordering::Acquire);
 
         if next_allowed > now {
-            Some(next_allowed - now)
+            Some((next_allowed - now).div_ceil(NANOS_PER_MS))
         } else {
             None
         }
     }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.wait_ms_at(now_ns(&self.clock), tokens)
+    }
+
+    /// Returns the timeline of the next `n` slots: the free room, then one slot per
+    /// drain interval counted from the last drain.
+    fn schedule(&self, n: u32) -> Schedule {
+        let now = now_ns(&self.clock);
+        let (current_level, next_allowed) = self.update_state(now);
+        let room = self
+            .capacity
+            .load(Ordering::Acquire)
+            .saturating_sub(current_level);
+        let ms_per_request = self.interval.ms();
+        Schedule::new(
+            now / NANOS_PER_MS,
+            n,
+            saturate_u32(room),
+            next_allowed as f64 / NANOS_PER_MS as f64 + ms_per_request,
+            ms_per_request,
+        )
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        let now = now_ns(&self.clock);
+        self.wait_ms_at(now, tokens)
+            .map(|wait_ms| (now / NANOS_PER_MS).saturating_add(wait_ms))
+    }
 }
 
-impl<C> ReconfigurableRateLimiter for LeakyBucket<C>
+impl<C, O> AdjustableRateLimiter for LeakyBucket<C, O>
 where
     C: Clock,
+    O: Observer,
 {
+    fn refund(&self, tokens: u32) {
+        let now = now_ns(&self.clock);
+        let _ = self.update_state(now);
+        let _ = self
+            .current_level
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |level| {
+                Some(level.saturating_sub(tokens as u64))
+            });
+        #[cfg(feature = "saturation")]
+        self.track_saturation(now);
+    }
+
+    fn penalize(&self, tokens: u32) {
+        let now = now_ns(&self.clock);
+        let _ = self.update_state(now);
+        let capacity = self.capacity.load(Ordering::Acquire);
+        let _ = self
+            .current_level
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |level| {
+                // Fill up to the capacity, but keep any slots reserved past it
+                Some(level.max(level.saturating_add(tokens as u64).min(capacity)))
+            });
+        #[cfg(feature = "saturation")]
+        self.track_saturation(now);
+    }
+}
+
+impl<C, O> Resettable for LeakyBucket<C, O>
+where
+    C: Clock,
+    O: Observer,
+{
+    /// Empties the bucket.
+    ///
+    /// This also releases the slots held by outstanding reservations from
+    /// [`reserve_next`](LeakyBucket::reserve_next): they still redeem at their
+    /// `ready_at`, but new requests are admitted alongside them as if they had never
+    /// been made.
+    fn reset(&self) {
+        let now = now_ns(&self.clock);
+        self.next_allowed_time.store(now, Ordering::Release);
+        self.current_level.store(0, Ordering::Release);
+        #[cfg(feature = "saturation")]
+        self.track_saturation(now);
+    }
+
+    /// Fills the bucket to its capacity, keeping any slots reserved past it.
+    fn drain(&self) {
+        let now = now_ns(&self.clock);
+        self.next_allowed_time.store(now, Ordering::Release);
+        let _ = self
+            .current_level
+            .fetch_max(self.capacity.load(Ordering::Acquire), Ordering::AcqRel);
+        #[cfg(feature = "saturation")]
+        self.saturation.record_exhausted(now);
+    }
+}
+
+impl<C, O> StatefulRateLimiter for LeakyBucket<C, O>
+where
+    C: Clock,
+    O: Observer,
+{
+    fn snapshot(&self) -> BucketState {
+        let now = now_ns(&self.clock);
+        let (current_level, next_allowed) = self.update_state(now);
+        BucketState {
+            capacity: self.capacity.load(Ordering::Acquire),
+            tokens_per_second: 1000.0 / self.interval.ms(),
+            tokens: current_level,
+            last_update_ns: next_allowed,
+            taken_at_ns: now,
+        }
+    }
+
+    /// Replaces the queued level with that of `state`, drained for the time since the
+    /// state's `last_update_ns`.
+    ///
+    /// Slots reserved with [`reserve_next`](LeakyBucket::reserve_next) past the capacity
+    /// are kept, up to the limit on reservations.
+    fn restore(&self, state: &BucketState, elapsed: Duration) {
+        // Drain here rather than leaving it to `update_state`, as the drain may reach
+        // back past this clock's epoch
+        let elapsed = state
+            .taken_at_ns
+            .saturating_sub(state.last_update_ns)
+            .saturating_add(elapsed.as_nanos().min(u64::MAX as u128) as u64);
+        let drained = if self.interval.is_positive() {
+            self.interval.tokens_in(elapsed)
+        } else {
+            state.tokens
+        };
+        let level = state
+            .tokens
+            .saturating_sub(drained)
+            .min(reservation_limit(self.capacity.load(Ordering::Acquire)));
+        let remainder = if level > 0 {
+            elapsed.saturating_sub(self.interval.ns_for(drained))
+        } else {
+            0
+        };
+
+        let now = now_ns(&self.clock);
+        self.current_level.store(level, Ordering::Release);
+        self.next_allowed_time
+            .store(now.saturating_sub(remainder), Ordering::Release);
+        #[cfg(feature = "saturation")]
+        self.track_saturation(now);
+    }
+}
+
+impl<C, O> ReconfigurableRateLimiter for LeakyBucket<C, O>
+where
+    C: Clock,
+    O: Observer,
+{
+    fn set_rate_only(&self, requests_per_second: f64) -> Result<()> {
+        validate::try_rate(requests_per_second, "requests_per_second must be positive")?;
+        interval::try_rate(requests_per_second)?;
+
+        // Drain the requests processed at the old rate before switching
+        let _ = self.update_state(now_ns(&self.clock));
+        self.store_rate(requests_per_second);
+        Ok(())
+    }
+
     fn update_config(&self, capacity: u32, requests_per_second: f64) -> Result<()> {
         if capacity == 0 {
             return Err(RateLimitError::invalid_config(
                 "capacity must be greater than 0",
             ));
         }
-        if requests_per_second <= 0.0 {
-            return Err(RateLimitError::invalid_config(
-                "requests_per_second must be positive",
-            ));
+        validate::try_rate(requests_per_second, "requests_per_second must be positive")?;
+        interval::try_rate(requests_per_second)?;
+
+        // Reapplying the current configuration is a no-op
+        if self.capacity.load(Ordering::Acquire) == capacity as u64
+            && self.interval.holds_ms(1000.0 / requests_per_second)
+        {
+            return Ok(());
         }
 
-        let now = self.clock.now();
+        let now = now_ns(&self.clock);
 
         // Update the state first to process any pending requests
         let _ = self.update_state(now);
 
         // Update the rate and capacity
+        let old_capacity = self.capacity.load(Ordering::Acquire);
         self.set_rate(capacity as u64, requests_per_second);
 
-        // Cap the current level to the new capacity
-        let current_level = self.current_level.load(Ordering::Relaxed);
-        if current_level > capacity as u64 {
-            self.current_level.store(capacity as u64, Ordering::Release);
-        }
+        // Cap the current level to the new capacity, keeping the slots reserved past
+        // the old one
+        let _ = self
+            .current_level
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |level| {
+                let reserved = reserved_over(level, old_capacity);
+                Some((level - reserved).min(capacity as u64) + reserved)
+            });
+        #[cfg(feature = "saturation")]
+        self.track_saturation(now);
 
         Ok(())
     }
 }
 
-impl<C> WithClock<C> for LeakyBucket<C> {
+impl<C, O> WithClock<C> for LeakyBucket<C, O> {
     fn with_clock(self, clock: C) -> Self {
         LeakyBucket {
             clock,
+            id: self.id,
             capacity: self.capacity,
-            ms_per_request: self.ms_per_request,
+            interval: self.interval,
             next_allowed_time: self.next_allowed_time,
             current_level: self.current_level,
+            zero_token_policy: self.zero_token_policy,
+            retry_slack_ms: self.retry_slack_ms,
+            observer: self.observer,
+            drain_interval_ns: self.drain_interval_ns,
+            #[cfg(feature = "peak-burst")]
+            peak: self.peak,
+            #[cfg(feature = "saturation")]
+            saturation: self.saturation,
         }
     }
 }
@@ -359,6 +1203,121 @@ where
     }
 }
 
+/// A builder for [`LeakyBucket`].
+///
+/// Unlike [`LeakyBucket::new`], building never panics: invalid settings are reported
+/// by [`build`](RateLimiterBuilder::build) as `RateLimitError::InvalidConfiguration`.
+/// The rate defaults to one request per second and the burst size to 1.
+#[derive(Debug, Clone)]
+pub struct LeakyBucketBuilder<C = SystemClock> {
+    requests_per_second: f64,
+    burst_size: Option<u32>,
+    zero_token_policy: ZeroTokenPolicy,
+    retry_slack: Duration,
+    drain_interval: Duration,
+    start_primed: bool,
+    clock: C,
+}
+
+#[cfg(feature = "std")]
+impl LeakyBucketBuilder<SystemClock> {
+    /// Creates a builder with the default settings, using the system clock.
+    pub fn new() -> Self {
+        Self {
+            requests_per_second: 1.0,
+            burst_size: None,
+            zero_token_policy: ZeroTokenPolicy::AlwaysAdmit,
+            retry_slack: Duration::ZERO,
+            drain_interval: Duration::ZERO,
+            start_primed: false,
+            clock: SystemClock,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for LeakyBucketBuilder<SystemClock> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> LeakyBucketBuilder<C> {
+    /// Sets the clock the bucket will use.
+    pub fn clock<D>(self, clock: D) -> LeakyBucketBuilder<D> {
+        LeakyBucketBuilder {
+            requests_per_second: self.requests_per_second,
+            burst_size: self.burst_size,
+            zero_token_policy: self.zero_token_policy,
+            retry_slack: self.retry_slack,
+            drain_interval: self.drain_interval,
+            start_primed: self.start_primed,
+            clock,
+        }
+    }
+
+    /// Sets how requests for zero tokens are treated.
+    pub fn zero_token_policy(mut self, policy: ZeroTokenPolicy) -> Self {
+        self.zero_token_policy = policy;
+        self
+    }
+
+    /// Sets the slack added to advertised retry times.
+    ///
+    /// See [`LeakyBucket::with_retry_slack`].
+    pub fn retry_slack(mut self, slack: Duration) -> Self {
+        self.retry_slack = slack;
+        self
+    }
+
+    /// Sets the clock movement below which the drain is not recomputed.
+    ///
+    /// See [`LeakyBucket::with_drain_interval`].
+    pub fn drain_interval(mut self, interval: Duration) -> Self {
+        self.drain_interval = interval;
+        self
+    }
+
+    /// Sets whether the bucket starts full instead of empty.
+    ///
+    /// See [`LeakyBucket::with_start_primed`].
+    pub fn start_primed(mut self, primed: bool) -> Self {
+        self.start_primed = primed;
+        self
+    }
+}
+
+impl<C> RateLimiterBuilder for LeakyBucketBuilder<C>
+where
+    C: Clock,
+{
+    type Limiter = LeakyBucket<C>;
+
+    /// Sets the burst size of the bucket.
+    fn capacity(mut self, capacity: u32) -> Self {
+        self.burst_size = Some(capacity);
+        self
+    }
+
+    /// Sets the rate at which requests are processed, in requests per second.
+    fn tokens_per_second(mut self, tokens_per_second: f64) -> Self {
+        self.requests_per_second = tokens_per_second;
+        self
+    }
+
+    fn build(self) -> Result<LeakyBucket<C>> {
+        LeakyBucket::try_with_clock(self.requests_per_second, self.burst_size, self.clock).map(
+            |bucket| {
+                bucket
+                    .with_zero_token_policy(self.zero_token_policy)
+                    .with_retry_slack(self.retry_slack)
+                    .with_drain_interval(self.drain_interval)
+                    .with_start_primed(self.start_primed)
+            },
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -393,4 +1352,738 @@ mod tests {
         std::thread::sleep(Duration::from_millis(1100));
         assert!(bucket.try_acquire(2).is_ok());
     }
+
+    #[test]
+    fn test_leaky_bucket_ensure_available() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(1.0, Some(10), clock);
+
+        // Already above the minimum: nothing changes
+        assert!(bucket.try_acquire(2).is_ok());
+        bucket.ensure_available(5);
+        assert_eq!(bucket.available_tokens(), 8);
+
+        // Below the minimum: topped up to exactly `min`
+        assert!(bucket.try_acquire(7).is_ok());
+        bucket.ensure_available(5);
+        assert_eq!(bucket.available_tokens(), 5);
+
+        // Above capacity: clamped to capacity
+        bucket.ensure_available(50);
+        assert_eq!(bucket.available_tokens(), 10);
+    }
+
+    #[test]
+    fn test_leaky_bucket_zero_token_policy() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(1.0, Some(1), clock.clone());
+        assert!(bucket.try_acquire(1).is_ok());
+
+        // By default a zero-token request is admitted even when exhausted
+        assert!(bucket.try_acquire(0).is_ok());
+
+        let bucket = bucket.with_zero_token_policy(ZeroTokenPolicy::ReflectAvailability);
+        let err = bucket.try_acquire(0).unwrap_err();
+        assert!(err.is_rate_limit_exceeded());
+        assert!(err.retry_after_ms().unwrap() > 0);
+
+        // Once the request drains the probe succeeds without consuming a slot
+        clock.advance(1000);
+        assert!(bucket.try_acquire(0).is_ok());
+        assert_eq!(bucket.available_tokens(), 1);
+    }
+
+    #[test]
+    fn test_leaky_bucket_try_acquire_up_to() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(4.0, Some(10), clock.clone());
+
+        assert_eq!(bucket.try_acquire_up_to(3), 3);
+        assert_eq!(bucket.try_acquire_up_to(50), 7);
+        assert_eq!(bucket.try_acquire_up_to(50), 0);
+
+        clock.advance(750);
+        assert_eq!(bucket.try_acquire_up_to(50), 3);
+        assert_eq!(bucket.available_tokens(), 0);
+    }
+
+    #[test]
+    fn test_leaky_bucket_acquire_outcome() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(2.0, Some(5), clock.clone());
+
+        assert_eq!(
+            bucket.acquire_outcome(3),
+            Outcome::Admitted {
+                remaining: 2,
+                used_burst: true
+            }
+        );
+        assert_eq!(
+            bucket.acquire_outcome(4),
+            Outcome::Throttled {
+                retry_after: Duration::from_millis(1000),
+                available: 2,
+            }
+        );
+        assert_eq!(
+            bucket.try_acquire(4),
+            Err(RateLimitError::rate_limit_exceeded(4, 2, 1000))
+        );
+
+        // Requests above capacity are throttled without a wait time
+        assert_eq!(
+            bucket.acquire_outcome(6),
+            Outcome::Throttled {
+                retry_after: Duration::ZERO,
+                available: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn test_leaky_bucket_peek_acquire_leaves_state_untouched() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(10.0, Some(5), clock.clone());
+        assert!(bucket.try_acquire(5).is_ok());
+
+        // One and a half intervals later one slot is free, but nothing is stored
+        clock.advance(150);
+        assert!(bucket.peek_acquire(1).is_ok());
+        let err = bucket.peek_acquire(3).unwrap_err();
+        assert_eq!(err, RateLimitError::rate_limit_exceeded(3, 1, 200));
+        assert_eq!(bucket.current_level.load(Ordering::SeqCst), 5);
+
+        // The real attempt makes the same decision
+        assert_eq!(bucket.try_acquire(3).unwrap_err(), err);
+        assert!(bucket.try_acquire(1).is_ok());
+        assert!(bucket.peek_acquire(1).is_err());
+    }
+
+    #[test]
+    fn test_leaky_bucket_retry_at() {
+        let clock = crate::clock::MockClock::new(10_000);
+        let bucket = LeakyBucket::with_clock(2.0, Some(5), clock.clone());
+        assert!(bucket.try_acquire(5).is_ok());
+
+        for tokens in 1..=5 {
+            let wait_ms = bucket.time_until_available_ms(tokens).unwrap();
+            assert!(wait_ms > 0);
+            assert_eq!(bucket.retry_at(tokens), Some(clock.now() + wait_ms));
+        }
+        assert_eq!(bucket.time_until_available_ms(6), None);
+        assert_eq!(bucket.retry_at(6), None);
+
+        // Once the clock reaches the timestamp the tokens can be acquired
+        let retry_at = bucket.retry_at(3).unwrap();
+        clock.set(retry_at);
+        assert_eq!(bucket.time_until_available_ms(3), Some(0));
+        assert!(bucket.try_acquire(3).is_ok());
+    }
+
+    #[test]
+    fn test_leaky_bucket_acquire_probe() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(10.0, Some(3), clock.clone());
+        assert!(bucket.try_acquire(3).is_ok());
+
+        // A client that starts out of phase and then waits as suggested settles into
+        // one admission per 100 ms drain interval
+        clock.advance(30);
+        let mut probes = Vec::new();
+        for _ in 0..5 {
+            let (admitted, wait) = bucket.acquire_probe();
+            probes.push((admitted, wait.as_millis()));
+            clock.advance(wait.as_millis() as u64);
+        }
+        assert_eq!(
+            probes,
+            [
+                (false, 70),
+                (true, 100),
+                (true, 100),
+                (true, 100),
+                (true, 100)
+            ]
+        );
+
+        // Admitted into an empty bucket partway through an interval
+        let bucket = LeakyBucket::with_clock(10.0, Some(3), clock.clone());
+        clock.advance(40);
+        assert_eq!(bucket.acquire_probe(), (true, Duration::from_millis(60)));
+    }
+
+    #[test]
+    fn test_leaky_bucket_reservations_are_evenly_spaced() {
+        let clock = crate::clock::MockClock::new(1000);
+        let bucket = LeakyBucket::with_clock(10.0, Some(2), clock.clone());
+
+        // The burst is ready at once, then one slot every 100 ms
+        let mut reservations: Vec<_> = (0..4).map(|_| bucket.reserve_next().unwrap()).collect();
+        let ready: Vec<_> = reservations.iter().map(Reservation::ready_at).collect();
+        assert_eq!(ready, [1000, 1000, 1100, 1200]);
+
+        // No more than the capacity is reserved past the capacity
+        let err = bucket.reserve_next().unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(100));
+        clock.set(1100);
+        reservations.push(bucket.reserve_next().unwrap());
+        assert_eq!(reservations[4].ready_at(), 1300);
+
+        // Ordinary requests queue behind every reserved slot
+        let err = bucket.try_acquire(1).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(300));
+
+        clock.set(1250);
+        let last = reservations.pop().unwrap();
+        assert!(bucket.redeem(reservations.pop().unwrap()).is_ok());
+        let (last, err) = bucket.redeem(last).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(50));
+
+        // A reservation stays valid however full the bucket is when it is redeemed
+        clock.set(1300);
+        assert!(bucket.try_acquire(1).is_err());
+        assert!(bucket.redeem(last).is_ok());
+        clock.set(1400);
+        assert!(bucket.try_acquire(1).is_ok());
+    }
+
+    #[test]
+    fn test_leaky_bucket_redeem_checks_issuer() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(10.0, Some(1), clock.clone());
+        let other = LeakyBucket::with_clock(10.0, Some(1), clock.clone());
+        let reservation = bucket.reserve_next().unwrap();
+
+        // Another bucket hands the reservation back instead of honouring it
+        let (reservation, err) = other.redeem(reservation).unwrap_err();
+        assert!(err.is_invalid_config());
+        assert!(bucket.redeem(reservation).is_ok());
+    }
+
+    #[test]
+    fn test_leaky_bucket_operations_keep_reservations() {
+        let clock = crate::clock::MockClock::new(0);
+        // Two slots in the bucket and two reserved past it, ready at 100 and 200 ms
+        let reserved = || {
+            let bucket = LeakyBucket::with_clock(10.0, Some(2), clock.clone());
+            let ready: Vec<_> = (0..4)
+                .map(|_| bucket.reserve_next().unwrap().ready_at())
+                .collect();
+            assert_eq!(ready, [0, 0, 100, 200]);
+            bucket
+        };
+        let retry = |bucket: &LeakyBucket<_>| bucket.try_acquire(1).unwrap_err().retry_after_ms();
+
+        let bucket = reserved();
+        bucket.penalize(1);
+        assert_eq!(retry(&bucket), Some(300));
+
+        let bucket = reserved();
+        bucket.drain();
+        assert_eq!(retry(&bucket), Some(300));
+
+        // Only the slots in the bucket are released
+        let bucket = reserved();
+        bucket.ensure_available(2);
+        assert_eq!(bucket.available_tokens(), 0);
+        assert_eq!(retry(&bucket), Some(100));
+
+        let bucket = reserved();
+        bucket.update_config(1, 10.0).unwrap();
+        assert_eq!(retry(&bucket), Some(300));
+        let bucket = reserved();
+        bucket.update_config(4, 10.0).unwrap();
+        assert_eq!(retry(&bucket), Some(100));
+
+        // Resetting releases the reserved slots too
+        let bucket = reserved();
+        bucket.reset();
+        assert!(bucket.try_acquire(2).is_ok());
+    }
+
+    #[test]
+    fn test_leaky_bucket_snapshot_keeps_reservations() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(10.0, Some(2), clock.clone());
+        for _ in 0..4 {
+            let _ = bucket.reserve_next().unwrap();
+        }
+
+        // The reserved slots past the capacity are still owed after a restore
+        let restored = LeakyBucket::from_state(bucket.snapshot(), clock.clone()).unwrap();
+        let err = restored.try_acquire(1).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(300));
+        assert!(restored.reserve_next().is_err());
+    }
+
+    #[test]
+    fn test_leaky_bucket_restore_across_epochs() {
+        let clock = crate::clock::MockClock::new(1000);
+        let bucket = LeakyBucket::with_clock(10.0, Some(5), clock.clone());
+        assert!(bucket.try_acquire(4).is_ok());
+        clock.advance(150);
+        let state = bucket.snapshot();
+        assert_eq!(state.tokens, 3);
+
+        // Stored for 100 ms, one more request has drained and the next is 50 ms away
+        let clock = crate::clock::MockClock::new(50_000);
+        let restarted = LeakyBucket::with_clock(10.0, Some(5), clock.clone());
+        restarted.restore(&state, Duration::from_millis(100));
+        assert_eq!(restarted.available_tokens(), 3);
+        clock.advance(49);
+        assert_eq!(restarted.available_tokens(), 3);
+        clock.advance(1);
+        assert_eq!(restarted.available_tokens(), 4);
+    }
+
+    #[test]
+    fn test_leaky_bucket_available_tokens_f64() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(1.0, Some(2), clock.clone());
+        assert_eq!(bucket.available_tokens_f64(), 2.0);
+        assert!(bucket.try_acquire(2).is_ok());
+
+        clock.advance(990);
+        assert_eq!(bucket.available_tokens(), 0);
+        assert!((bucket.available_tokens_f64() - 0.99).abs() < 1e-9);
+
+        clock.advance(260);
+        assert_eq!(bucket.available_tokens(), 1);
+        assert!((bucket.available_tokens_f64() - 1.25).abs() < 1e-9);
+
+        clock.advance(10_000);
+        assert_eq!(bucket.available_tokens_f64(), 2.0);
+    }
+
+    #[test]
+    fn test_leaky_bucket_byte_scale_u64() {
+        const GIB: u64 = 1 << 30;
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock_u64(GIB as f64, Some(GIB * 8), clock.clone());
+        assert_eq!(bucket.available_tokens_u64(), 8 * GIB);
+        assert_eq!(bucket.available_tokens(), u32::MAX);
+
+        // The capacity saturates instead of wrapping to 0
+        assert_eq!(bucket.capacity_u64(), 8 * GIB);
+        assert_eq!(bucket.capacity(), u32::MAX);
+
+        // A 5 GiB request does not fit in a u32
+        assert!(bucket.try_acquire_u64(5 * GIB).is_ok());
+        assert_eq!(bucket.available_tokens_u64(), 3 * GIB);
+        assert_eq!(bucket.available_tokens(), (3 * GIB) as u32);
+
+        // Rejections saturate the reported counts
+        let err = bucket.try_acquire_u64(5 * GIB).unwrap_err();
+        assert_eq!(
+            err,
+            RateLimitError::rate_limit_exceeded(u32::MAX, (3 * GIB) as u32, 2000)
+        );
+
+        clock.advance(2000);
+        assert!(bucket.try_acquire_u64(4 * GIB).is_ok());
+
+        // The u32 entry point shares the same state
+        assert!(bucket.try_acquire(1024).is_ok());
+        #[cfg(not(feature = "fixed"))]
+        assert_eq!(bucket.available_tokens_u64(), GIB - 1024);
+        // A GiB per second is within 2⁻¹⁷ ns of the fixed-point interval, not exact
+        #[cfg(feature = "fixed")]
+        assert!(bucket.available_tokens_u64().abs_diff(GIB - 1024) < GIB / 100_000);
+    }
+
+    #[test]
+    fn test_leaky_bucket_update_config_noop() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(1.0, Some(10), clock.clone());
+        assert!(bucket.try_acquire(8).is_ok());
+
+        // Reapplying the current configuration leaves the state untouched
+        assert!(bucket.update_config(10, 1.0).is_ok());
+        assert_eq!(bucket.available_tokens(), 2);
+        assert_eq!(bucket.rate_per_second(), 1.0);
+
+        // A real change still applies
+        assert!(bucket.update_config(5, 1.0).is_ok());
+        assert_eq!(bucket.capacity(), 5);
+        assert_eq!(bucket.available_tokens(), 0);
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_config() {
+        let clock = crate::clock::MockClock::new(0);
+        let builder = || LeakyBucket::builder().clock(clock.clone());
+
+        let err = builder().tokens_per_second(0.0).build().unwrap_err();
+        assert!(err.is_invalid_config());
+        let err = builder().tokens_per_second(f64::NAN).build().unwrap_err();
+        assert!(err.is_invalid_config());
+        let err = builder().capacity(0).build().unwrap_err();
+        assert!(err.is_invalid_config());
+        assert!(LeakyBucket::try_new(-1.0, Some(5)).is_err());
+
+        let bucket = builder()
+            .capacity(5)
+            .tokens_per_second(10.0)
+            .zero_token_policy(ZeroTokenPolicy::ReflectAvailability)
+            .retry_slack(Duration::from_millis(20))
+            .clock(crate::clock::MockClock::new(0))
+            .build()
+            .unwrap();
+        assert_eq!(bucket.capacity(), 5);
+        assert_eq!(bucket.rate_per_second(), 10.0);
+        assert!(bucket.try_acquire(5).is_ok());
+        assert!(bucket.try_acquire(0).is_err());
+        let true_wait = bucket.time_until_available_ms(1).unwrap();
+        let err = bucket.try_acquire(1).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(true_wait + 20));
+    }
+
+    #[test]
+    fn test_leaky_bucket_take_stats() {
+        use crate::{observer::AdmissionCounters, status::LimiterStats};
+
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(10.0, Some(2), clock.clone())
+            .with_observer(AdmissionCounters::new());
+        assert!(bucket.try_acquire(2).is_ok());
+        assert!(bucket.try_acquire(1).is_err());
+        assert_eq!(
+            bucket.observer().stats(),
+            LimiterStats {
+                admitted: 1,
+                rejected: 1
+            }
+        );
+        assert_eq!(bucket.observer().take_stats().admitted, 1);
+
+        // Each scrape only sees the decisions since the previous one
+        clock.advance(100);
+        assert!(bucket.try_acquire(1).is_ok());
+        assert_eq!(
+            bucket.observer().take_stats(),
+            LimiterStats {
+                admitted: 1,
+                rejected: 0
+            }
+        );
+        assert_eq!(bucket.observer().take_stats(), LimiterStats::default());
+    }
+
+    #[test]
+    fn test_leaky_bucket_reset_and_drain() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(2.0, Some(10), clock.clone());
+        assert!(bucket.try_acquire(3).is_ok());
+        clock.advance(200);
+
+        bucket.drain();
+        assert_eq!(bucket.available_tokens(), 0);
+        assert!(bucket.try_acquire(1).is_err());
+        clock.advance(500);
+        assert_eq!(bucket.available_tokens(), 1);
+
+        bucket.reset();
+        assert_eq!(bucket.available_tokens(), 10);
+        assert!(bucket.try_acquire(10).is_ok());
+        assert!(bucket.try_acquire(1).is_err());
+    }
+
+    #[test]
+    fn test_leaky_bucket_start_primed() {
+        let clock = crate::clock::MockClock::new(0);
+
+        // An empty bucket admits its whole burst at once
+        let bucket = LeakyBucket::with_clock(2.0, Some(4), clock.clone()).with_start_primed(false);
+        assert_eq!(bucket.available_tokens(), 4);
+        assert!(bucket.try_acquire(4).is_ok());
+
+        // A primed bucket admits nothing until a request drains
+        let bucket = LeakyBucket::builder()
+            .capacity(4)
+            .tokens_per_second(2.0)
+            .start_primed(true)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        assert_eq!(bucket.available_tokens(), 0);
+        let err = bucket.try_acquire(1).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(500));
+
+        clock.advance(500);
+        assert_eq!(bucket.available_tokens(), 1);
+        assert!(bucket.try_acquire(1).is_ok());
+        assert!(bucket.try_acquire(1).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "peak-burst")]
+    fn test_leaky_bucket_peak_burst() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(10.0, Some(5), clock.clone());
+
+        assert!(bucket.try_acquire(5).is_ok());
+        clock.advance(500);
+        assert!(bucket.try_acquire(5).is_ok());
+        assert_eq!(bucket.peak_burst(), 10);
+    }
+
+    #[test]
+    #[cfg(feature = "saturation")]
+    fn test_leaky_bucket_saturated_since() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(1.0, Some(2), clock.clone());
+        assert_eq!(bucket.saturated_since(), None);
+
+        assert!(bucket.try_acquire(2).is_ok());
+        clock.advance(900);
+        assert_eq!(bucket.saturated_since(), Some(Duration::from_millis(900)));
+
+        // A leaked slot ends the stretch, and refilling it starts a new one
+        clock.advance(100);
+        assert_eq!(bucket.saturated_since(), None);
+        clock.advance(200);
+        assert!(bucket.try_acquire(1).is_ok());
+        clock.advance(300);
+        assert_eq!(bucket.saturated_since(), Some(Duration::from_millis(300)));
+
+        // A leak seen only by an admission check ends the stretch too, so shrinking
+        // the capacity to the level later starts a new one
+        clock.advance(700);
+        assert_eq!(bucket.available_tokens(), 1);
+        clock.advance(200);
+        assert!(bucket.update_config(1, 1.0).is_ok());
+        clock.advance(500);
+        assert_eq!(bucket.saturated_since(), Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn test_leaky_bucket_penalize_and_refund() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(1.0, Some(5), clock);
+
+        bucket.penalize(3);
+        assert_eq!(bucket.available_tokens(), 2);
+        assert!(bucket.try_acquire(2).is_ok());
+        assert!(bucket.try_acquire(1).is_err());
+
+        // The level is capped at capacity and floored at empty
+        bucket.penalize(10);
+        assert_eq!(bucket.available_tokens(), 0);
+        bucket.refund(2);
+        assert_eq!(bucket.available_tokens(), 2);
+        bucket.refund(10);
+        assert_eq!(bucket.available_tokens(), 5);
+    }
+
+    #[test]
+    fn test_leaky_bucket_set_rate_only() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(1.0, Some(10), clock.clone());
+        assert!(bucket.try_acquire(10).is_ok());
+
+        assert!(bucket.set_rate_only(10.0).is_ok());
+        assert_eq!(bucket.available_tokens(), 0);
+        assert_eq!(bucket.capacity(), 10);
+        assert_eq!(bucket.rate_per_second(), 10.0);
+    }
+
+    #[test]
+    fn test_leaky_bucket_schedule() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(10.0, Some(3), clock.clone());
+        assert!(bucket.try_acquire(2).is_ok());
+        clock.advance(150);
+
+        let schedule = bucket.schedule(5);
+        assert_eq!(schedule.collect::<Vec<_>>(), [150, 150, 200, 300, 400]);
+        assert_schedule_is_kept(&bucket, &clock, schedule);
+    }
+
+    /// Acquires one token at each scheduled time, checking none was available earlier.
+    fn assert_schedule_is_kept<L: RateLimiter>(
+        limiter: &L,
+        clock: &crate::clock::MockClock,
+        schedule: Schedule,
+    ) {
+        let mut previous = clock.now();
+        for at in schedule {
+            if at > previous {
+                clock.set(at - 1);
+                assert!(limiter.try_acquire(1).is_err(), "admitted before {at}");
+            }
+            clock.set(at);
+            assert!(limiter.try_acquire(1).is_ok(), "rejected at {at}");
+            previous = at;
+        }
+    }
+
+    #[test]
+    fn test_leaky_bucket_drain_interval_under_drains() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::builder()
+            .capacity(10)
+            .tokens_per_second(100.0)
+            .drain_interval(Duration::from_millis(50))
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        let exact = LeakyBucket::with_clock(100.0, Some(10), clock.clone());
+        assert!(bucket.try_acquire(10).is_ok());
+        assert!(exact.try_acquire(10).is_ok());
+
+        // Three slots have drained, but the drain is not recomputed yet
+        clock.advance(30);
+        assert_eq!(exact.available_tokens(), 3);
+        assert_eq!(bucket.available_tokens(), 0);
+        clock.advance(20);
+        assert_eq!(bucket.available_tokens(), 5);
+
+        // Saturated traffic: the amortized bucket never admits more than the exact one
+        let (mut admitted, mut admitted_exact) = (0, 0);
+        for _ in 0..1000 {
+            clock.advance(3);
+            admitted += u32::from(bucket.try_acquire(1).is_ok());
+            admitted_exact += u32::from(exact.try_acquire(1).is_ok());
+            assert!(admitted <= admitted_exact);
+        }
+        // Nothing drained is lost, only delayed
+        assert!(admitted_exact - admitted <= 5);
+    }
+
+    #[test]
+    #[cfg(feature = "no-panic")]
+    #[cfg_attr(debug_assertions, should_panic(expected = "must be"))]
+    fn test_leaky_bucket_no_panic_clamps_input() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(0.0, Some(0), clock.clone());
+        assert_eq!(bucket.capacity(), 1);
+
+        // The rate is clamped to one too slow to drain the request within an hour
+        assert!(bucket.try_acquire(1).is_ok());
+        clock.advance(3_600_000);
+        assert!(bucket.try_acquire(1).is_err());
+
+        let bucket = LeakyBucket::with_clock(1e18, Some(1), clock);
+        let max = interval::rate(crate::MAX_RATE);
+        assert!((bucket.rate_per_second() - max).abs() <= max * 1e-9);
+    }
+
+    #[test]
+    fn test_leaky_bucket_try_new_rejects_non_finite_rates() {
+        let clock = crate::clock::MockClock::new(0);
+        for rate in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, -0.0, 1e18] {
+            let err = LeakyBucket::try_with_clock(rate, Some(5), clock.clone()).unwrap_err();
+            assert!(err.is_invalid_config(), "accepted {rate}");
+        }
+        assert!(LeakyBucket::try_new(10.0, Some(0))
+            .unwrap_err()
+            .is_invalid_config());
+        assert!(LeakyBucket::try_new(10.0, None).is_ok());
+    }
+
+    #[test]
+    fn test_leaky_bucket_reconfigure_rejects_non_finite_rates() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(10.0, Some(5), clock.clone());
+        let not_finite = RateLimitError::invalid_config("rate must be finite");
+        for rate in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert_eq!(bucket.update_config(5, rate).unwrap_err(), not_finite);
+            assert_eq!(bucket.set_rate_only(rate).unwrap_err(), not_finite);
+            let err = LeakyBucket::builder()
+                .tokens_per_second(rate)
+                .build()
+                .unwrap_err();
+            assert_eq!(err, not_finite);
+        }
+        assert_eq!(bucket.rate_per_second(), 10.0);
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-panic"))]
+    #[should_panic(expected = "rate must be finite")]
+    fn test_leaky_bucket_panics_on_nan_rate() {
+        let _ = LeakyBucket::new(f64::NAN, Some(5));
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-panic"))]
+    #[should_panic(expected = "rate must be finite")]
+    fn test_leaky_bucket_panics_on_infinite_rate() {
+        let _ = LeakyBucket::with_clock(f64::INFINITY, Some(5), crate::clock::MockClock::new(0));
+    }
+
+    #[test]
+    fn test_leaky_bucket_resolution_warning() {
+        // A millisecond clock paces up to 1000 requests per second
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(1000.0, Some(10), clock.clone());
+        assert_eq!(bucket.resolution_warning(), None);
+        let bucket = LeakyBucket::with_clock(5000.0, Some(10), clock);
+        assert!(bucket.resolution_warning().is_some());
+    }
+
+    #[test]
+    fn test_leaky_bucket_rate_with_degenerate_interval() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = LeakyBucket::with_clock(10.0, Some(5), clock.clone());
+
+        // A zero interval drains everything as soon as the clock moves, which is an
+        // unbounded rate
+        bucket.interval.store_ms(0.0);
+        assert_eq!(bucket.rate_per_second(), f64::INFINITY);
+        for _ in 0..10 {
+            assert!(bucket.try_acquire(5).is_ok());
+            clock.advance(1);
+        }
+
+        // A denormal interval overflows the division rather than producing NaN
+        bucket.interval.store_ms(f64::from_bits(1));
+        let rate = bucket.rate_per_second();
+        assert!(rate > 0.0 && !rate.is_nan());
+        #[cfg(not(feature = "fixed"))]
+        assert_eq!(rate, f64::INFINITY);
+
+        // A tiny but normal interval keeps its rate instead of overflowing the rounding
+        bucket.interval.store_ms(1e-300);
+        let rate = bucket.rate_per_second();
+        assert!(rate.is_finite() && rate > 0.0);
+        #[cfg(not(feature = "fixed"))]
+        assert!(rate > crate::MAX_RATE);
+    }
+
+    #[test]
+    fn test_leaky_bucket_snapshot_restore() {
+        let clock = crate::clock::MockClock::new(1000);
+        let bucket = LeakyBucket::with_clock(10.0, Some(5), clock.clone());
+        assert!(bucket.try_acquire(4).is_ok());
+        clock.advance(150);
+
+        let state = bucket.snapshot();
+        assert_eq!(state.capacity, 5);
+        assert_eq!(state.tokens_per_second, 10.0);
+        assert_eq!(state.tokens, 3);
+
+        // The new process picks up the queued requests where the old one left off
+        let restored = LeakyBucket::from_state(state, clock.clone()).unwrap();
+        assert_eq!(restored.available_tokens(), bucket.available_tokens());
+        clock.advance(50);
+        assert_eq!(restored.available_tokens(), 3);
+
+        // A timestamp from the future drains from now instead
+        let future = BucketState {
+            last_update_ns: 1_000_000_000_000,
+            ..state
+        };
+        let restored = LeakyBucket::from_state(future, clock.clone()).unwrap();
+        assert_eq!(restored.available_tokens(), 2);
+        clock.advance(100);
+        assert_eq!(restored.available_tokens(), 3);
+
+        let invalid = BucketState {
+            tokens_per_second: f64::NAN,
+            ..state
+        };
+        assert!(LeakyBucket::from_state(invalid, clock).is_err());
+    }
 }
@@ -8,6 +8,16 @@
 //! - **No-std support** - Works in `no_std` environments with `alloc`
 //! - **Async ready** - Optional async support via feature flags
 //! - **Distributed** - Optional Redis backend for distributed rate limiting
+//!
+//! ## Panic-free construction
+//!
+//! The `new`/`with_clock` constructors panic on invalid input such as a zero capacity.
+//! Enabling the `no-panic` feature turns those checks into `debug_assert!`s: release
+//! builds clamp invalid values instead (a zero capacity becomes 1, a non-positive rate
+//! becomes a tiny positive rate that effectively never refills), so the constructor
+//! checks add no panic path to release builds. Whether a binary is panic-free overall
+//! also depends on the rest of the code linked into it, so measure the target's build,
+//! for example with `cargo bloat`, if that matters.
 
 #![cfg_attr(not(feature = "std"), no_std)]
 #![warn(missing_docs)]
@@ -26,25 +36,167 @@
 #[cfg(feature = "alloc")]
 extern crate alloc;
 
+#[cfg(feature = "async")]
+pub mod asynchronous;
+mod backoff;
+pub mod bounded;
+#[cfg(feature = "std")]
+pub mod circuit;
 pub mod clock;
+pub mod coalescing;
+#[cfg(feature = "std")]
+pub mod composite;
+pub mod concurrency;
+#[cfg(all(feature = "serde", feature = "std"))]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod decorated;
+#[cfg(feature = "redis")]
+pub mod distributed;
+#[cfg(feature = "std")]
+pub mod dynamic;
+#[cfg(feature = "async")]
+pub mod edge;
+#[cfg(feature = "embedded-time")]
+pub mod embedded_time_compat;
 pub mod error;
+#[cfg(feature = "std")]
+pub mod fair;
+mod float;
+pub mod gcra;
+#[cfg(feature = "governor-compat")]
+pub mod governor_compat;
+pub mod grace;
+#[cfg(feature = "humantime")]
+pub mod humantime_compat;
+mod interval;
+#[cfg(all(feature = "alloc", feature = "std"))]
+pub mod keyed;
+pub mod labeled;
+#[cfg(feature = "tower")]
+pub mod layer;
+#[cfg(feature = "std")]
+pub mod lazy;
 pub mod leaky_bucket;
+#[cfg(feature = "alloc")]
+pub mod multi;
+pub mod observer;
+pub mod outcome;
+#[cfg(feature = "peak-burst")]
+mod peak;
+#[cfg(feature = "std")]
+pub mod persistent;
+pub mod policy;
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+mod quantile;
+#[cfg(feature = "std")]
+pub mod ramping;
+#[cfg(feature = "std")]
+pub mod retry_observer;
+pub mod ring_trace;
+#[cfg(feature = "saturation")]
+mod saturation;
+pub mod scaled;
+pub mod schedule;
+#[cfg(feature = "std")]
+pub mod shadow;
+#[cfg(feature = "std")]
+pub mod sharded;
+#[cfg(all(feature = "alloc", feature = "std"))]
+pub mod sliding_cost;
+#[cfg(feature = "std")]
+pub mod sliding_counter;
+#[cfg(all(feature = "alloc", feature = "std"))]
+pub mod sliding_log;
+pub mod sliding_window;
+pub mod status;
+#[cfg(feature = "std")]
+pub mod testing;
+pub mod tiered;
 pub mod token_bucket;
+#[cfg(feature = "std")]
+pub mod trace;
 pub mod traits;
+mod validate;
+#[cfg(feature = "async")]
+pub mod wait_queue;
 
+#[cfg(feature = "async")]
+pub use asynchronous::*;
+pub use bounded::*;
+#[cfg(feature = "std")]
+pub use circuit::*;
 pub use clock::*;
+pub use coalescing::*;
+#[cfg(feature = "std")]
+pub use composite::*;
+pub use concurrency::*;
+#[cfg(all(feature = "serde", feature = "std"))]
+pub use config::*;
+#[cfg(feature = "std")]
+pub use decorated::*;
+#[cfg(feature = "redis")]
+pub use distributed::*;
+#[cfg(feature = "std")]
+pub use dynamic::*;
+#[cfg(feature = "async")]
+pub use edge::*;
+#[cfg(feature = "embedded-time")]
+pub use embedded_time_compat::*;
 pub use error::*;
+#[cfg(feature = "std")]
+pub use fair::*;
+pub use gcra::*;
+#[cfg(feature = "governor-compat")]
+pub use governor_compat::*;
+pub use grace::*;
+#[cfg(feature = "humantime")]
+pub use humantime_compat::*;
+#[cfg(all(feature = "alloc", feature = "std"))]
+pub use keyed::*;
+pub use labeled::*;
+#[cfg(feature = "tower")]
+pub use layer::*;
+#[cfg(feature = "std")]
+pub use lazy::*;
 pub use leaky_bucket::*;
+#[cfg(feature = "alloc")]
+pub use multi::*;
+pub use observer::*;
+pub use outcome::*;
+#[cfg(feature = "std")]
+pub use persistent::*;
+pub use policy::*;
+#[cfg(feature = "std")]
+pub use ramping::*;
+#[cfg(feature = "std")]
+pub use retry_observer::*;
+pub use ring_trace::*;
+pub use scaled::*;
+pub use schedule::*;
+#[cfg(feature = "std")]
+pub use shadow::*;
+#[cfg(feature = "std")]
+pub use sharded::*;
+#[cfg(all(feature = "alloc", feature = "std"))]
+pub use sliding_cost::*;
+#[cfg(feature = "std")]
+pub use sliding_counter::*;
+#[cfg(all(feature = "alloc", feature = "std"))]
+pub use sliding_log::*;
+pub use sliding_window::*;
+pub use status::*;
+pub use tiered::*;
 pub use token_bucket::*;
-pub use traits::*;
-
-/// Re-export for use in tests and examples
 #[cfg(feature = "std")]
-pub mod testing {
-    pub use super::clock::MockClock;
-}
+pub use trace::*;
+pub use traits::*;
+pub use validate::MAX_RATE;
+#[cfg(feature = "async")]
+pub use wait_queue::*;
 
 #[cfg(test)]
+#[allow(dead_code)]
 mod tests {
     //! Integration tests for the bucketboss crate.
     //!
@@ -82,7 +234,7 @@ mod tests {
     /// Helper function to run a test with a test clock
     pub fn with_test_clock<F>(test: F)
     where
-        F: FnOnce(TestClock) -> (),
+        F: FnOnce(TestClock),
     {
         let clock = TestClock::new(0);
         test(clock.clone());
@@ -90,6 +242,7 @@ mod tests {
 }
 
 #[cfg(test)]
+#[allow(dead_code)]
 mod property_tests {
     //! Property-based tests for the bucketboss crate.
     //!
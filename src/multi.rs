@@ -0,0 +1,385 @@
+//! Enforcing several limits together.
+//!
+//! APIs often publish more than one limit at once, such as "10 per second and 1000 per
+//! hour": the short window bounds bursts, the long one the sustained volume.
+//! [`MultiLimiter`] admits a request only if every one of its limiters does.
+//!
+//! This module is only available with the `alloc` feature.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt;
+
+use crate::{
+    error::{ErrorText, RateLimitError, Result},
+    outcome::saturate_u32,
+    traits::{AdjustableRateLimiter, RateLimiter},
+};
+
+/// A limiter that admits a request only if all of its limiters admit it.
+///
+/// # Rollback
+///
+/// [`try_acquire`](RateLimiter::try_acquire) charges the limiters in order. If one
+/// rejects, the tokens already taken from the limiters before it are returned with
+/// [`refund`](AdjustableRateLimiter::refund), which is why the limiters must be
+/// [`AdjustableRateLimiter`]s, and the limiters after it are only checked with
+/// [`peek_acquire`](RateLimiter::peek_acquire) to find the longest wait. A rejected
+/// request therefore leaves every limiter with the tokens it had, and an admitted one
+/// is charged against all of them.
+///
+/// The rollback is not atomic with respect to other callers. Between a charge and its
+/// refund, a concurrent request can see the charged limiter short of the rolled-back
+/// tokens and be rejected itself. A refund is also capped at the limiter's capacity,
+/// so if the limiter refilled to capacity in the meantime, the refunded tokens are
+/// lost rather than pushed over it. Both err on the side of rejecting: the combined
+/// limit is never exceeded. Put the limiter most likely to reject first, usually the
+/// strictest, so that rejections roll back as little as possible.
+///
+/// As a [`RateLimiter`], the available tokens, capacity and rate are the minimums over
+/// all limiters, and the wait for tokens is the longest wait of any limiter. A
+/// rejection keeps the `rejected_by` label of the limiter with that wait, so wrapping
+/// the tiers in [`LabeledLimiter`](crate::LabeledLimiter) names the one to wait for.
+///
+/// # Examples
+///
+/// ```
+/// use bucketboss::{AdjustableRateLimiter, MultiLimiter, RateLimiter, TokenBucket};
+///
+/// // 10 per second and 1000 per hour
+/// let limiters: Vec<Box<dyn AdjustableRateLimiter>> = vec![
+///     Box::new(TokenBucket::new(10, 10.0)),
+///     Box::new(TokenBucket::new(1000, 1000.0 / 3600.0)),
+/// ];
+/// let limiter = MultiLimiter::new(limiters)?;
+/// assert!(limiter.try_acquire(10).is_ok());
+/// assert!(limiter.try_acquire(1).is_err());
+/// assert_eq!(limiter.available_tokens(), 0);
+/// # Ok::<(), bucketboss::RateLimitError>(())
+/// ```
+pub struct MultiLimiter {
+    limiters: Vec<Box<dyn AdjustableRateLimiter>>,
+}
+
+impl MultiLimiter {
+    /// Creates a limiter admitting requests that all of `limiters` admit.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Self)` if there is at least one limiter
+    /// * `Err(RateLimitError::InvalidConfiguration)` if `limiters` is empty
+    pub fn new(limiters: Vec<Box<dyn AdjustableRateLimiter>>) -> Result<Self> {
+        if limiters.is_empty() {
+            return Err(RateLimitError::invalid_config(
+                "at least one limiter is required",
+            ));
+        }
+
+        Ok(Self { limiters })
+    }
+
+    /// Returns the limiters, in the order they are charged.
+    pub fn limiters(&self) -> &[Box<dyn AdjustableRateLimiter>] {
+        &self.limiters
+    }
+
+    /// Refunds `tokens` to the first `charged` limiters.
+    ///
+    /// Counts above `u32::MAX` are refunded in `u32::MAX` steps.
+    fn roll_back(&self, charged: usize, tokens: u64) {
+        for limiter in &self.limiters[..charged] {
+            let mut left = tokens;
+            while left > 0 {
+                let step = saturate_u32(left);
+                limiter.refund(step);
+                left -= u64::from(step);
+            }
+        }
+    }
+
+    /// Charges `tokens` to every limiter with `charge`, rolling back on a rejection.
+    fn acquire_with(
+        &self,
+        tokens: u64,
+        charge: impl Fn(&dyn AdjustableRateLimiter) -> Result<()>,
+    ) -> Result<()> {
+        let requested = saturate_u32(tokens);
+        for (index, limiter) in self.limiters.iter().enumerate() {
+            let (mut available, mut retry_after_ms, mut rejected_by) =
+                match charge(limiter.as_ref()) {
+                    Ok(()) => continue,
+                    Err(RateLimitError::RateLimitExceeded {
+                        available,
+                        retry_after_ms,
+                        rejected_by,
+                        ..
+                    }) => (available, retry_after_ms, rejected_by),
+                    Err(err) => {
+                        self.roll_back(index, tokens);
+                        return Err(err);
+                    }
+                };
+            self.roll_back(index, tokens);
+
+            // The request waits for the slowest of the limiters that would reject it
+            for limiter in &self.limiters[index + 1..] {
+                if let Err(RateLimitError::RateLimitExceeded {
+                    available: limiter_available,
+                    retry_after_ms: limiter_retry,
+                    rejected_by: limiter_rejected_by,
+                    ..
+                }) = limiter.peek_acquire(requested)
+                {
+                    available = available.min(limiter_available);
+                    if limiter_retry > retry_after_ms {
+                        retry_after_ms = limiter_retry;
+                        rejected_by = limiter_rejected_by;
+                    }
+                }
+            }
+            return Err(RateLimitError::RateLimitExceeded {
+                requested,
+                available,
+                retry_after_ms,
+                rejected_by,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl RateLimiter for MultiLimiter {
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        self.acquire_with(u64::from(tokens), |limiter| limiter.try_acquire(tokens))
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        self.acquire_with(tokens, |limiter| limiter.try_acquire_u64(tokens))
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        let mut rejection: Option<(u32, u64, Option<ErrorText>)> = None;
+        for limiter in &self.limiters {
+            if let Err(RateLimitError::RateLimitExceeded {
+                available,
+                retry_after_ms,
+                rejected_by,
+                ..
+            }) = limiter.peek_acquire(tokens)
+            {
+                rejection = Some(match rejection {
+                    Some((least, longest, slowest)) if longest >= retry_after_ms => {
+                        (least.min(available), longest, slowest)
+                    }
+                    Some((least, ..)) => (least.min(available), retry_after_ms, rejected_by),
+                    None => (available, retry_after_ms, rejected_by),
+                });
+            }
+        }
+        match rejection {
+            Some((available, retry_after_ms, rejected_by)) => {
+                Err(RateLimitError::RateLimitExceeded {
+                    requested: tokens,
+                    available,
+                    retry_after_ms,
+                    rejected_by,
+                })
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn available_tokens(&self) -> u32 {
+        self.limiters
+            .iter()
+            .map(|limiter| limiter.available_tokens())
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        self.limiters
+            .iter()
+            .map(|limiter| limiter.available_tokens_u64())
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn capacity(&self) -> u32 {
+        self.limiters
+            .iter()
+            .map(|limiter| limiter.capacity())
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.limiters
+            .iter()
+            .map(|limiter| limiter.capacity_u64())
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        self.limiters
+            .iter()
+            .map(|limiter| limiter.rate_per_second())
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    fn effective_rate_per_second(&self) -> f64 {
+        self.limiters
+            .iter()
+            .map(|limiter| limiter.effective_rate_per_second())
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    fn resolution_warning(&self) -> Option<&'static str> {
+        self.limiters
+            .iter()
+            .find_map(|limiter| limiter.resolution_warning())
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        match self.time_until_available_ms(1)? {
+            0 => None,
+            wait_ms => Some(wait_ms),
+        }
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.limiters
+            .iter()
+            .map(|limiter| limiter.time_until_available_ms(tokens))
+            .try_fold(0, |longest, wait_ms| Some(longest.max(wait_ms?)))
+    }
+}
+
+impl AdjustableRateLimiter for MultiLimiter {
+    fn refund(&self, tokens: u32) {
+        self.roll_back(self.limiters.len(), u64::from(tokens));
+    }
+
+    fn penalize(&self, tokens: u32) {
+        for limiter in &self.limiters {
+            limiter.penalize(tokens);
+        }
+    }
+}
+
+impl fmt::Debug for MultiLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultiLimiter")
+            .field("limiters", &self.limiters.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::{clock::MockClock, LabeledLimiter, TokenBucket};
+
+    /// 5 per second and 8 per 10 seconds, the long limit charged first.
+    fn limiter(clock: &MockClock) -> MultiLimiter {
+        MultiLimiter::new(vec![
+            Box::new(TokenBucket::with_clock(8, 0.8, clock.clone())),
+            Box::new(TokenBucket::with_clock(5, 5.0, clock.clone())),
+        ])
+        .unwrap()
+    }
+
+    fn available(limiter: &MultiLimiter) -> Vec<u32> {
+        limiter
+            .limiters()
+            .iter()
+            .map(|limiter| limiter.available_tokens())
+            .collect()
+    }
+
+    #[test]
+    fn test_all_limits_apply() {
+        let clock = MockClock::new(0);
+        let limiter = limiter(&clock);
+        assert_eq!(limiter.capacity(), 5);
+        assert_eq!(limiter.rate_per_second(), 0.8);
+
+        assert!(limiter.try_acquire(5).is_ok());
+        assert_eq!(available(&limiter), [3, 0]);
+        assert_eq!(limiter.available_tokens(), 0);
+
+        // The per-second limit recovers, but the long one only has 3 left
+        clock.advance(1000);
+        assert_eq!(available(&limiter), [3, 5]);
+        assert!(limiter.try_acquire(3).is_ok());
+        assert!(limiter.try_acquire(1).is_err());
+        assert_eq!(limiter.time_until_available_ms(1), Some(1250));
+    }
+
+    #[test]
+    fn test_rejection_rolls_back_earlier_limiters() {
+        let clock = MockClock::new(0);
+        let limiter = limiter(&clock);
+        assert!(limiter.try_acquire(5).is_ok());
+
+        // The long limit admits and is charged, then the per-second limit rejects
+        let err = limiter.try_acquire(1).unwrap_err();
+        assert_eq!(available(&limiter), [3, 0]);
+        assert_eq!(err.retry_after_ms(), Some(200));
+    }
+
+    #[test]
+    fn test_retry_after_is_longest_wait() {
+        let clock = MockClock::new(0);
+        let limiter = limiter(&clock);
+        assert!(limiter.try_acquire(5).is_ok());
+
+        // The long limit needs 1 more token (1250 ms), the short one 4 (800 ms)
+        let err = limiter.try_acquire(4).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(1250));
+        assert_eq!(limiter.time_until_available_ms(4), Some(1250));
+        assert!(matches!(
+            err,
+            RateLimitError::RateLimitExceeded { available: 0, .. }
+        ));
+        assert_eq!(available(&limiter), [3, 0]);
+    }
+
+    #[test]
+    fn test_rejection_names_the_slowest_tier() {
+        let clock = MockClock::new(0);
+        let limiter = MultiLimiter::new(vec![
+            Box::new(LabeledLimiter::new(
+                TokenBucket::with_clock(8, 0.8, clock.clone()),
+                "per-10s",
+            )),
+            Box::new(LabeledLimiter::new(
+                TokenBucket::with_clock(5, 5.0, clock.clone()),
+                "per-second",
+            )),
+        ])
+        .unwrap();
+        assert!(limiter.try_acquire(5).is_ok());
+
+        let err = limiter.try_acquire(1).unwrap_err();
+        assert_eq!(err.rejected_by(), Some("per-second"));
+
+        // Both reject 4 tokens, and the long limit has the longer wait
+        let err = limiter.try_acquire(4).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(1250));
+        assert_eq!(err.rejected_by(), Some("per-10s"));
+        let err = limiter.peek_acquire(4).unwrap_err();
+        assert_eq!(err.rejected_by(), Some("per-10s"));
+        assert_eq!(
+            limiter.peek_acquire(1).unwrap_err().rejected_by(),
+            Some("per-second")
+        );
+    }
+
+    #[test]
+    fn test_requires_a_limiter() {
+        assert!(MultiLimiter::new(Vec::new()).is_err());
+    }
+}
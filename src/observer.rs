@@ -0,0 +1,144 @@
+//! Hooks into a limiter's admission decisions, for metrics.
+//!
+//! Counting admitted and rejected requests, or recording how long rejected clients are
+//! told to wait, usually means wrapping every call site. An [`Observer`] attached with
+//! [`TokenBucket::with_observer`](crate::TokenBucket::with_observer) or
+//! [`LeakyBucket::with_observer`](crate::LeakyBucket::with_observer) is instead called
+//! by the bucket itself, from within `try_acquire`, and one attached to a builder with
+//! [`RateLimiterBuilder::observer`](crate::RateLimiterBuilder::observer) sees the
+//! results of the decorated limiter. The observer is a type parameter, and the default
+//! `()` does nothing, so a limiter without one compiles to the same admission path as
+//! before.
+//!
+//! [`AdmissionCounters`] is a ready-made observer counting the decisions for a metrics
+//! scraper.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::status::LimiterStats;
+
+/// Receives the admission decisions of a limiter.
+///
+/// Both methods do nothing by default, so an implementation only overrides the ones it
+/// needs.
+///
+/// # Thread safety
+///
+/// The methods are called on the thread making the decision, concurrently with other
+/// decisions and without any lock held, so they take `&self` and an implementation must
+/// be `Send + Sync`. Keep them cheap and non-blocking, such as an atomic increment or a
+/// metrics library call: they run on the admission path of every request. Calls for
+/// concurrent decisions may arrive in any order.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::atomic::{AtomicU64, Ordering};
+///
+/// use bucketboss::{clock::MockClock, Observer, RateLimiter, TokenBucket};
+///
+/// /// Records the longest wait advertised to a rejected client.
+/// #[derive(Debug, Default)]
+/// struct LongestRetry(AtomicU64);
+///
+/// impl Observer for LongestRetry {
+///     fn on_reject(&self, _tokens: u64, retry_after_ms: u64) {
+///         self.0.fetch_max(retry_after_ms, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let bucket = TokenBucket::with_clock(2, 1.0, MockClock::new(0))
+///     .with_observer(LongestRetry::default());
+/// assert!(bucket.try_acquire(2).is_ok());
+/// assert!(bucket.try_acquire(1).is_err());
+/// assert!(bucket.try_acquire(2).is_err());
+/// assert_eq!(bucket.observer().0.load(Ordering::Relaxed), 2000);
+/// ```
+pub trait Observer: Send + Sync + 'static {
+    /// Called for every admission decision on a request for `tokens`, with whether it
+    /// was granted.
+    #[inline]
+    fn on_acquire(&self, tokens: u64, granted: bool) {
+        let _ = (tokens, granted);
+    }
+
+    /// Called after [`on_acquire`](Self::on_acquire) when a request for `tokens` is
+    /// rejected, with the `retry_after_ms` advertised to the caller.
+    #[inline]
+    fn on_reject(&self, tokens: u64, retry_after_ms: u64) {
+        let _ = (tokens, retry_after_ms);
+    }
+}
+
+/// The observer of a limiter without one, which ignores every decision.
+impl Observer for () {}
+
+/// An [`Observer`] counting admitted and rejected requests, for metrics scraping.
+///
+/// A scraper polling every few seconds wants the decisions made since its last poll.
+/// [`take_stats`](Self::take_stats) hands the counts out with a `swap` to zero, so each
+/// decision is reported by exactly one scrape without the scraper keeping the previous
+/// totals.
+///
+/// # Examples
+///
+/// ```
+/// use bucketboss::{clock::MockClock, AdmissionCounters, LimiterStats, RateLimiter, TokenBucket};
+///
+/// let bucket =
+///     TokenBucket::with_clock(2, 1.0, MockClock::new(0)).with_observer(AdmissionCounters::new());
+/// for _ in 0..3 {
+///     let _ = bucket.try_acquire(1);
+/// }
+/// assert_eq!(bucket.observer().take_stats(), LimiterStats { admitted: 2, rejected: 1 });
+/// assert_eq!(bucket.observer().take_stats(), LimiterStats::default());
+/// ```
+#[derive(Debug, Default)]
+pub struct AdmissionCounters {
+    admitted: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl AdmissionCounters {
+    /// Creates counters starting at zero.
+    pub const fn new() -> Self {
+        Self {
+            admitted: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the counts accumulated since they were last taken, without resetting
+    /// them.
+    pub fn stats(&self) -> LimiterStats {
+        LimiterStats {
+            admitted: self.admitted.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the counts accumulated since they were last taken and resets them to 0.
+    ///
+    /// Each counter is swapped to 0 atomically, so every decision is reported by
+    /// exactly one call however calls and admissions interleave. The two counters are
+    /// swapped one after the other, so a decision made in between can be reported with
+    /// the next call's counts instead.
+    pub fn take_stats(&self) -> LimiterStats {
+        LimiterStats {
+            admitted: self.admitted.swap(0, Ordering::Relaxed),
+            rejected: self.rejected.swap(0, Ordering::Relaxed),
+        }
+    }
+}
+
+impl Observer for AdmissionCounters {
+    #[inline]
+    fn on_acquire(&self, _tokens: u64, granted: bool) {
+        let counter = if granted {
+            &self.admitted
+        } else {
+            &self.rejected
+        };
+        let _ = counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
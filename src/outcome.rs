@@ -0,0 +1,217 @@
+//! Structured admission outcomes.
+//!
+//! [`Outcome`] describes the result of an acquisition attempt as a plain value rather
+//! than a `Result`, for callers who treat throttling as a normal part of operation
+//! instead of an error.
+
+use core::time::Duration;
+
+use crate::error::{RateLimitError, Result};
+
+/// The outcome of an acquisition attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The tokens were acquired.
+    ///
+    /// The variant may gain fields, so patterns matching it need a `..` rest pattern,
+    /// and details such as [`used_burst`](Outcome::used_burst) are best read through
+    /// the accessors.
+    #[non_exhaustive]
+    Admitted {
+        /// The number of tokens left after the acquisition.
+        remaining: u32,
+        /// Whether the acquisition drew on the burst reserve; see
+        /// [`Outcome::used_burst`].
+        used_burst: bool,
+    },
+    /// The tokens could not be acquired.
+    Throttled {
+        /// How long to wait before the request could succeed.
+        retry_after: Duration,
+        /// The number of tokens available at the time of the attempt.
+        available: u32,
+    },
+}
+
+impl Outcome {
+    /// Returns whether the tokens were acquired.
+    pub fn is_admitted(&self) -> bool {
+        matches!(self, Self::Admitted { .. })
+    }
+
+    /// Returns whether the tokens could not be acquired.
+    pub fn is_throttled(&self) -> bool {
+        matches!(self, Self::Throttled { .. })
+    }
+
+    /// Returns whether the tokens were acquired from the burst reserve rather than the
+    /// steady refill.
+    ///
+    /// The steady refill is what the limiter replenishes in one second, its rate; the
+    /// burst reserve is the rest of its capacity. An admission uses the burst reserve
+    /// when it leaves fewer than `capacity - rate` tokens, that is, when more than one
+    /// second's worth of refill is outstanding. A client acquiring at or below the rate
+    /// never does, while one that bursts after an idle period does once its burst
+    /// exceeds the rate. A limiter whose capacity is at most its rate holds no burst
+    /// reserve.
+    pub fn used_burst(&self) -> bool {
+        matches!(
+            self,
+            Self::Admitted {
+                used_burst: true,
+                ..
+            }
+        )
+    }
+
+    /// If admitted, returns the number of tokens left after the acquisition.
+    pub fn remaining(&self) -> Option<u32> {
+        match self {
+            Self::Admitted { remaining, .. } => Some(*remaining),
+            Self::Throttled { .. } => None,
+        }
+    }
+
+    /// If throttled, returns how long to wait before the request could succeed.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Throttled { retry_after, .. } => Some(*retry_after),
+            Self::Admitted { .. } => None,
+        }
+    }
+
+    /// Converts this outcome into the `Result` returned by `try_acquire`.
+    ///
+    /// # Arguments
+    ///
+    /// * `requested` - The number of tokens that were requested, reported in the error.
+    pub fn into_result(self, requested: u32) -> Result<()> {
+        match self {
+            Self::Admitted { .. } => Ok(()),
+            Self::Throttled {
+                retry_after,
+                available,
+            } => Err(RateLimitError::rate_limit_exceeded(
+                requested,
+                available,
+                u64::try_from(retry_after.as_millis()).unwrap_or(u64::MAX),
+            )),
+        }
+    }
+}
+
+/// Converts a full-width count to `u32`, saturating at `u32::MAX`.
+#[inline]
+pub(crate) fn saturate_u32(value: u64) -> u32 {
+    u32::try_from(value).unwrap_or(u32::MAX)
+}
+
+/// Returns whether `remaining` tokens after an admission are below the steady level of
+/// a limiter, `capacity - tokens_per_second`.
+#[inline]
+pub(crate) fn used_burst(remaining: u64, capacity: u64, tokens_per_second: f64) -> bool {
+    (remaining as f64) < capacity as f64 - tokens_per_second
+}
+
+/// The full-width counterpart of [`Outcome`] used by the bucket implementations.
+///
+/// The buckets decide in `u64` so that `try_acquire_u64` shares the same code path as
+/// `try_acquire`; the result is narrowed only at the public boundary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum WideOutcome {
+    /// The tokens were acquired.
+    Admitted {
+        /// The number of tokens left after the acquisition.
+        remaining: u64,
+    },
+    /// The tokens could not be acquired.
+    Throttled {
+        /// How long to wait, in milliseconds, before the request could succeed.
+        retry_after_ms: u64,
+        /// The number of tokens available at the time of the attempt.
+        available: u64,
+    },
+}
+
+impl WideOutcome {
+    /// Narrows this outcome to an [`Outcome`], saturating counts at `u32::MAX`.
+    ///
+    /// An admission leaving fewer than `capacity - tokens_per_second` tokens is flagged
+    /// as having used the burst reserve.
+    pub(crate) fn narrow(self, capacity: u64, tokens_per_second: f64) -> Outcome {
+        match self {
+            Self::Admitted { remaining } => Outcome::Admitted {
+                remaining: saturate_u32(remaining),
+                used_burst: used_burst(remaining, capacity, tokens_per_second),
+            },
+            Self::Throttled {
+                retry_after_ms,
+                available,
+            } => Outcome::Throttled {
+                retry_after: Duration::from_millis(retry_after_ms),
+                available: saturate_u32(available),
+            },
+        }
+    }
+
+    /// Adds `slack_ms` to the advertised wait of a throttled outcome.
+    ///
+    /// A zero wait is left as is, since it means no wait could be computed.
+    pub(crate) fn with_retry_slack(self, slack_ms: u64) -> Self {
+        match self {
+            Self::Throttled {
+                retry_after_ms,
+                available,
+            } if retry_after_ms > 0 => Self::Throttled {
+                retry_after_ms: retry_after_ms.saturating_add(slack_ms),
+                available,
+            },
+            outcome => outcome,
+        }
+    }
+
+    /// Converts this outcome into a `Result`, saturating counts at `u32::MAX`.
+    pub(crate) fn into_result(self, requested: u64) -> Result<()> {
+        match self {
+            Self::Admitted { .. } => Ok(()),
+            Self::Throttled {
+                retry_after_ms,
+                available,
+            } => Err(RateLimitError::rate_limit_exceeded(
+                saturate_u32(requested),
+                saturate_u32(available),
+                retry_after_ms,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_into_result() {
+        let admitted = Outcome::Admitted {
+            remaining: 3,
+            used_burst: false,
+        };
+        assert!(admitted.is_admitted());
+        assert!(!admitted.used_burst());
+        assert_eq!(admitted.remaining(), Some(3));
+        assert_eq!(admitted.retry_after(), None);
+        assert_eq!(admitted.into_result(2), Ok(()));
+
+        let throttled = Outcome::Throttled {
+            retry_after: Duration::from_millis(250),
+            available: 1,
+        };
+        assert!(throttled.is_throttled());
+        assert_eq!(throttled.remaining(), None);
+        assert_eq!(throttled.retry_after(), Some(Duration::from_millis(250)));
+        assert_eq!(
+            throttled.into_result(4),
+            Err(RateLimitError::rate_limit_exceeded(4, 1, 250))
+        );
+    }
+}
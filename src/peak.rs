@@ -0,0 +1,101 @@
+//! High-water mark of admissions within a rolling one-second window.
+//!
+//! Auditing often needs the *observed* maximum burst rather than the configured
+//! capacity. [`PeakTracker`] counts admitted tokens in ten coarse 100 ms slots and
+//! remembers the largest sum over the slots covering the last second.
+//!
+//! This module is only available with the `peak-burst` feature.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// The width of one slot, in milliseconds.
+const SLOT_MS: u64 = 100;
+
+/// The number of slots making up the one-second window.
+const SLOTS: usize = 10;
+
+/// Packs a slot's tick and count into one atomic word: the count in the high half,
+/// the tick (truncated to 32 bits) in the low half.
+fn pack(tick: u64, count: u64) -> u64 {
+    (count.min(u32::MAX as u64) << 32) | (tick & u32::MAX as u64)
+}
+
+/// Tracks the largest number of tokens admitted within any one-second window.
+///
+/// The window is measured in 100 ms slots, so the recorded peak covers between 0.9 and
+/// 1 s of admissions and may slightly undercount bursts straddling a slot boundary.
+#[derive(Debug)]
+pub(crate) struct PeakTracker {
+    slots: [AtomicU64; SLOTS],
+    peak: AtomicU64,
+}
+
+impl PeakTracker {
+    /// Creates a tracker that has seen no admissions.
+    pub(crate) const fn new() -> Self {
+        Self {
+            slots: [const { AtomicU64::new(0) }; SLOTS],
+            peak: AtomicU64::new(0),
+        }
+    }
+
+    /// Records `tokens` admitted at `now` and updates the peak.
+    pub(crate) fn record(&self, now: u64, tokens: u64) {
+        let tick = now / SLOT_MS;
+        let slot = &self.slots[tick as usize % SLOTS];
+        let _ = slot.fetch_update(Ordering::AcqRel, Ordering::Acquire, |packed| {
+            let count = if packed & u32::MAX as u64 == tick & u32::MAX as u64 {
+                packed >> 32
+            } else {
+                0
+            };
+            Some(pack(tick, count.saturating_add(tokens)))
+        });
+
+        let _ = self
+            .peak
+            .fetch_max(self.window_total(tick), Ordering::AcqRel);
+    }
+
+    /// Returns the tokens admitted in the slots of the window ending at `tick`.
+    fn window_total(&self, tick: u64) -> u64 {
+        (0..SLOTS as u64)
+            .filter_map(|back| tick.checked_sub(back))
+            .map(|slot_tick| {
+                let packed = self.slots[slot_tick as usize % SLOTS].load(Ordering::Acquire);
+                if packed != 0 && packed & u32::MAX as u64 == slot_tick & u32::MAX as u64 {
+                    packed >> 32
+                } else {
+                    0
+                }
+            })
+            .sum()
+    }
+
+    /// Returns the largest one-second total observed so far.
+    pub(crate) fn peak(&self) -> u64 {
+        self.peak.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peak_over_rolling_window() {
+        let tracker = PeakTracker::new();
+        tracker.record(0, 3);
+        tracker.record(450, 4);
+        tracker.record(950, 2);
+        assert_eq!(tracker.peak(), 9);
+
+        // The admissions at t=0 and t=450 have left the window by t=1500
+        tracker.record(1500, 5);
+        assert_eq!(tracker.peak(), 9);
+
+        // The one at t=950 is still inside it at t=1600: 2 + 5 + 6
+        tracker.record(1600, 6);
+        assert_eq!(tracker.peak(), 13);
+    }
+}
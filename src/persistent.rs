@@ -0,0 +1,472 @@
+//! Limiter state that survives restarts.
+//!
+//! A freshly created bucket is full, so restarting a service grants every client a full
+//! burst. [`PersistentLimiter`] saves the [`BucketState`] of a [`StatefulRateLimiter`]
+//! to a file and restores it on construction, crediting the refill for the time the service was
+//! down, without needing an external store such as Redis.
+//!
+//! This module is only available with the `std` feature.
+
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, PoisonError,
+    },
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::{
+    clock::{SystemClock, WallClock},
+    error::Result,
+    status::BucketState,
+    traits::{RateLimiter, StatefulRateLimiter, Wrapper},
+};
+
+/// The first field of a state file, identifying its format.
+const FORMAT: &str = "bucketboss-state-v1";
+
+/// Numbers the temporary files of concurrent saves within the process.
+static TEMP_FILES: AtomicU64 = AtomicU64::new(0);
+
+/// A wrapper that persists a limiter's state to a file.
+///
+/// The state is written when the wrapper is dropped, on [`save`](Self::save), and on
+/// [`save_if_due`](Self::save_if_due) once the interval set with
+/// [`with_save_interval`](Self::with_save_interval) has passed. Acquiring tokens never
+/// touches the file, so the admission path does no blocking I/O; call `save_if_due`
+/// from a background thread or timer instead.
+///
+/// Each save writes a temporary file of its own next to the target, flushes it to disk
+/// and then renames it over the target, so neither a crash nor a concurrent save ever
+/// leaves a truncated or interleaved state file behind. Saves through the same wrapper
+/// take turns, so a save that snapshotted an older state never replaces a newer one.
+///
+/// On construction a saved state is loaded if present. A missing or unreadable file is
+/// not an error: the limiter simply starts fresh. The wall-clock time between the save
+/// and the load is credited as refill, so the limiter resumes as if it had kept running.
+#[derive(Debug)]
+pub struct PersistentLimiter<L, C = SystemClock>
+where
+    L: StatefulRateLimiter,
+    C: WallClock,
+{
+    /// Only `None` once `into_inner` has taken the limiter.
+    inner: Option<L>,
+    path: PathBuf,
+    clock: C,
+    restored: bool,
+    save_interval_ms: Option<u64>,
+    /// When the state was last saved, in milliseconds since the Unix epoch.
+    last_saved: AtomicU64,
+    /// Held from snapshot to rename so that saves land in the order they snapshotted.
+    saving: Mutex<()>,
+}
+
+impl<L> PersistentLimiter<L, SystemClock>
+where
+    L: StatefulRateLimiter,
+{
+    /// Wraps `inner`, restoring the state saved at `path` if there is one.
+    pub fn open(inner: L, path: impl AsRef<Path>) -> Self {
+        Self::with_clock(inner, path, SystemClock)
+    }
+}
+
+impl<L, C> PersistentLimiter<L, C>
+where
+    L: StatefulRateLimiter,
+    C: WallClock,
+{
+    /// Wraps `inner` with the specified clock, restoring the state saved at `path` if
+    /// there is one.
+    ///
+    /// The clock is only used to measure how long the state was stored.
+    pub fn with_clock(inner: L, path: impl AsRef<Path>, clock: C) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let now = unix_ms(&clock);
+
+        let restored = match load(&path) {
+            Some((saved_at, state)) => {
+                let stored = Duration::from_millis(now.saturating_sub(saved_at));
+                inner.restore(&state, stored);
+                true
+            }
+            None => false,
+        };
+
+        Self {
+            inner: Some(inner),
+            path,
+            clock,
+            restored,
+            save_interval_ms: None,
+            last_saved: AtomicU64::new(now),
+            saving: Mutex::new(()),
+        }
+    }
+
+    /// Sets the interval after which [`save_if_due`](Self::save_if_due) saves the state
+    /// again.
+    pub fn with_save_interval(mut self, interval: Duration) -> Self {
+        self.save_interval_ms = Some(interval.as_millis().min(u64::MAX as u128) as u64);
+        self
+    }
+
+    /// Returns the path of the state file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns `true` if the state was restored from the file on construction.
+    pub fn restored(&self) -> bool {
+        self.restored
+    }
+
+    /// Writes the current state to the file, atomically replacing any previous one.
+    ///
+    /// The write is flushed to disk before this returns. Concurrent calls wait for each
+    /// other, so the file always ends up with the most recent snapshot.
+    pub fn save(&self) -> io::Result<()> {
+        // A panicked save left nothing behind that the next one relies on
+        let _saving = self.saving.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = unix_ms(&self.clock);
+        let state = self.limiter().snapshot();
+        let contents = format!(
+            "{FORMAT} {now} {} {} {} {} {}\n",
+            state.capacity,
+            state.tokens_per_second,
+            state.tokens,
+            state.last_update_ns,
+            state.taken_at_ns
+        );
+        write_atomically(&self.path, contents.as_bytes())?;
+        self.last_saved.store(now, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Saves the state if the interval set with
+    /// [`with_save_interval`](Self::with_save_interval) has passed since the last save.
+    ///
+    /// Returns whether the state was saved. Of concurrent callers only one saves for
+    /// each interval; if its write fails, the next call retries.
+    pub fn save_if_due(&self) -> io::Result<bool> {
+        let Some(interval_ms) = self.save_interval_ms else {
+            return Ok(false);
+        };
+        let now = unix_ms(&self.clock);
+        let last = self.last_saved.load(Ordering::Relaxed);
+        // Only the caller that claims this interval writes the file
+        if now.saturating_sub(last) < interval_ms
+            || self
+                .last_saved
+                .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_err()
+        {
+            return Ok(false);
+        }
+
+        if let Err(err) = self.save() {
+            // Let the next call retry instead of waiting for another interval
+            let _ =
+                self.last_saved
+                    .compare_exchange(now, last, Ordering::Relaxed, Ordering::Relaxed);
+            return Err(err);
+        }
+        Ok(true)
+    }
+
+    fn limiter(&self) -> &L {
+        self.inner.as_ref().expect("limiter taken by into_inner")
+    }
+}
+
+/// Returns the clock's calendar time in milliseconds since the Unix epoch.
+fn unix_ms<C: WallClock>(clock: &C) -> u64 {
+    clock
+        .system_time()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
+
+/// Replaces the file at `path` with `contents`, through a temporary file that is
+/// flushed to disk and renamed over it.
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut temp = OsString::from(path.as_os_str());
+    temp.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temp = PathBuf::from(temp);
+
+    let written = File::create(&temp).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    if let Err(err) = written.and_then(|()| fs::rename(&temp, path)) {
+        let _ = fs::remove_file(&temp);
+        return Err(err);
+    }
+
+    // Persist the rename itself, which lives in the directory
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Reads a state file, returning when it was saved and the state it holds.
+fn load(path: &Path) -> Option<(u64, BucketState)> {
+    let contents = fs::read_to_string(path).ok()?;
+    let mut fields = contents.split_whitespace();
+    if fields.next()? != FORMAT {
+        return None;
+    }
+    let saved_at = fields.next()?.parse().ok()?;
+    let state = BucketState {
+        capacity: fields.next()?.parse().ok()?,
+        tokens_per_second: fields.next()?.parse().ok()?,
+        tokens: fields.next()?.parse().ok()?,
+        last_update_ns: fields.next()?.parse().ok()?,
+        taken_at_ns: fields.next()?.parse().ok()?,
+    };
+    if fields.next().is_some() {
+        return None;
+    }
+
+    Some((saved_at, state))
+}
+
+impl<L, C> RateLimiter for PersistentLimiter<L, C>
+where
+    L: StatefulRateLimiter,
+    C: WallClock,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        self.limiter().try_acquire(tokens)
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        self.limiter().try_acquire_u64(tokens)
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        self.limiter().peek_acquire(tokens)
+    }
+
+    fn available_tokens(&self) -> u32 {
+        self.limiter().available_tokens()
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        self.limiter().available_tokens_u64()
+    }
+
+    fn capacity(&self) -> u32 {
+        self.limiter().capacity()
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.limiter().capacity_u64()
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        self.limiter().rate_per_second()
+    }
+
+    fn effective_rate_per_second(&self) -> f64 {
+        self.limiter().effective_rate_per_second()
+    }
+
+    fn resolution_warning(&self) -> Option<&'static str> {
+        self.limiter().resolution_warning()
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        self.limiter().time_until_next_token_ms()
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.limiter().time_until_available_ms(tokens)
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        self.limiter().retry_at(tokens)
+    }
+}
+
+impl<L, C> Wrapper for PersistentLimiter<L, C>
+where
+    L: StatefulRateLimiter,
+    C: WallClock,
+{
+    type Inner = L;
+
+    fn get_ref(&self) -> &L {
+        self.limiter()
+    }
+
+    fn get_mut(&mut self) -> &mut L {
+        self.inner.as_mut().expect("limiter taken by into_inner")
+    }
+
+    /// Saves the state, ignoring write errors, and returns the wrapped limiter.
+    fn into_inner(mut self) -> L {
+        let _ = self.save();
+        self.inner.take().expect("limiter taken by into_inner")
+    }
+}
+
+impl<L, C> Drop for PersistentLimiter<L, C>
+where
+    L: StatefulRateLimiter,
+    C: WallClock,
+{
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.save();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, TokenBucket};
+
+    fn state_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("bucketboss-{}-{name}.state", std::process::id()))
+    }
+
+    #[test]
+    fn test_state_survives_restart() {
+        let path = state_path("restart");
+        let _ = fs::remove_file(&path);
+
+        let clock = MockClock::new(0);
+        let bucket = TokenBucket::with_clock(10, 1.0, clock.clone());
+        let limiter = PersistentLimiter::with_clock(bucket, &path, clock.clone());
+        assert!(!limiter.restored());
+        assert!(limiter.try_acquire(10).is_ok());
+        clock.advance(2500);
+        drop(limiter);
+
+        // The restarted process has a new clock epoch; 1.5s of wall time passed
+        let clock = MockClock::new(10_000);
+        clock.set_system_time(UNIX_EPOCH + Duration::from_millis(4000));
+        let bucket = TokenBucket::with_clock(10, 1.0, clock.clone());
+        let limiter = PersistentLimiter::with_clock(bucket, &path, clock.clone());
+        assert!(limiter.restored());
+        assert_eq!(limiter.available_tokens(), 4);
+
+        // The half token accrued before the save is not lost
+        clock.advance(999);
+        assert_eq!(limiter.available_tokens(), 4);
+        clock.advance(1);
+        assert_eq!(limiter.available_tokens(), 5);
+
+        let _ = limiter.into_inner();
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_missing_or_corrupt_file_starts_fresh() {
+        let path = state_path("corrupt");
+        fs::write(&path, "bucketboss-state-v1 12 10 1 not-a-number 0 0\n").unwrap();
+
+        let clock = MockClock::new(0);
+        let bucket = TokenBucket::with_clock(10, 1.0, clock.clone());
+        let limiter = PersistentLimiter::with_clock(bucket, &path, clock.clone())
+            .with_save_interval(Duration::from_secs(1));
+        assert!(!limiter.restored());
+        assert_eq!(limiter.available_tokens(), 10);
+
+        // Acquiring never writes the file; a due periodic save replaces the corrupt file
+        // with a valid one
+        assert!(limiter.try_acquire(3).is_ok());
+        assert!(!limiter.save_if_due().unwrap());
+        clock.advance(1000);
+        assert!(limiter.try_acquire(3).is_ok());
+        assert_eq!(load(&path), None);
+        assert!(limiter.save_if_due().unwrap());
+        assert!(!limiter.save_if_due().unwrap());
+        assert_eq!(
+            load(&path),
+            Some((
+                1000,
+                BucketState {
+                    capacity: 10,
+                    tokens_per_second: 1.0,
+                    tokens: 5,
+                    last_update_ns: 1_000_000_000,
+                    taken_at_ns: 1_000_000_000,
+                }
+            ))
+        );
+
+        let _ = limiter.into_inner();
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_concurrent_saves_use_their_own_temp_files() {
+        let path = state_path("concurrent");
+        let clock = MockClock::new(0);
+        let bucket = TokenBucket::with_clock(10, 1.0, clock.clone());
+        let limiter = PersistentLimiter::with_clock(bucket, &path, clock.clone());
+
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                let _ = scope.spawn(|| {
+                    for _ in 0..20 {
+                        limiter.save().unwrap();
+                    }
+                });
+            }
+        });
+        assert!(load(&path).is_some());
+
+        // No temporary file is left behind
+        let dir = path.parent().unwrap();
+        let name = path.file_name().unwrap().to_str().unwrap();
+        let leftovers = fs::read_dir(dir)
+            .unwrap()
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|file| file.starts_with(name) && file.ends_with(".tmp"))
+            .count();
+        assert_eq!(leftovers, 0);
+
+        let _ = limiter.into_inner();
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_concurrent_saves_keep_the_latest_state() {
+        let path = state_path("latest");
+        let clock = MockClock::new(0);
+        let bucket = TokenBucket::with_clock(200, 1.0, clock.clone());
+        let limiter = PersistentLimiter::with_clock(bucket, &path, clock.clone());
+
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                let _ = scope.spawn(|| {
+                    for _ in 0..20 {
+                        assert!(limiter.try_acquire(1).is_ok());
+                        limiter.save().unwrap();
+                    }
+                });
+            }
+        });
+
+        // The last save to finish wrote the state after every acquisition
+        assert_eq!(load(&path).map(|(_, state)| state.tokens), Some(40));
+
+        let _ = limiter.into_inner();
+        let _ = fs::remove_file(&path);
+    }
+}
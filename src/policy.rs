@@ -0,0 +1,22 @@
+//! Policies that tune how rate limiters treat edge-case requests.
+//!
+//! Policies are small configuration enums attached to a limiter at construction time.
+//! They never change the core admission algorithm, only how boundary cases are reported.
+
+/// How a limiter responds to a request for zero tokens.
+///
+/// By default a zero-token request is always admitted, which keeps `try_acquire(0)`
+/// a cheap no-op for existing callers. With [`ZeroTokenPolicy::ReflectAvailability`]
+/// a zero-token request becomes a pure readiness probe: it succeeds only while at least
+/// one token is available, and it never consumes anything.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ZeroTokenPolicy {
+    /// `try_acquire(0)` always succeeds.
+    #[default]
+    AlwaysAdmit,
+    /// `try_acquire(0)` succeeds only if at least one token is currently available.
+    ///
+    /// When the limiter is exhausted the returned `RateLimitExceeded` error reports
+    /// `requested: 0` and the time until the next token becomes available.
+    ReflectAvailability,
+}
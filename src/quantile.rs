@@ -0,0 +1,195 @@
+//! Streaming quantile estimation in constant memory.
+//!
+//! Implements the P² algorithm of Jain and Chlamtac ("The P² algorithm for dynamic
+//! calculation of quantiles and histograms without storing observations", CACM 1985).
+//! Five markers track the minimum, the maximum, the target quantile and the quantiles
+//! halfway to either side; each observation nudges the markers towards their ideal
+//! positions, adjusting their heights by piecewise-parabolic interpolation.
+
+/// A running estimate of one quantile of the observed values.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct P2Quantile {
+    /// The quantile being estimated, in `[0, 1]`.
+    p: f64,
+    /// The number of values observed so far.
+    count: u64,
+    /// The marker heights; until five values are seen, the values themselves.
+    heights: [f64; 5],
+    /// The actual marker positions, counted from 0.
+    positions: [f64; 5],
+    /// The ideal marker positions.
+    desired: [f64; 5],
+    /// How far each ideal position moves per observation.
+    increments: [f64; 5],
+}
+
+impl P2Quantile {
+    /// Creates an estimator for the quantile `p`, clamped to `[0, 1]`.
+    pub(crate) fn new(p: f64) -> Self {
+        let p = if p.is_nan() { 0.5 } else { p.clamp(0.0, 1.0) };
+        Self {
+            p,
+            count: 0,
+            heights: [0.0; 5],
+            positions: [0.0, 1.0, 2.0, 3.0, 4.0],
+            desired: [0.0, 2.0 * p, 4.0 * p, 2.0 + 2.0 * p, 4.0],
+            increments: [0.0, p / 2.0, p, (1.0 + p) / 2.0, 1.0],
+        }
+    }
+
+    /// Returns the number of values observed.
+    pub(crate) fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Adds a value to the estimate.
+    pub(crate) fn observe(&mut self, value: f64) {
+        if self.count < 5 {
+            self.heights[self.count as usize] = value;
+            self.count += 1;
+            if self.count == 5 {
+                self.heights.sort_unstable_by(f64::total_cmp);
+            }
+            return;
+        }
+        self.count += 1;
+
+        // Find the cell the value falls in, widening the extremes if needed
+        let cell = if value < self.heights[0] {
+            self.heights[0] = value;
+            0
+        } else if value >= self.heights[4] {
+            self.heights[4] = value;
+            3
+        } else {
+            (1..5).find(|&i| value < self.heights[i]).unwrap_or(4) - 1
+        };
+
+        for position in &mut self.positions[cell + 1..] {
+            *position += 1.0;
+        }
+        for (desired, increment) in self.desired.iter_mut().zip(self.increments) {
+            *desired += increment;
+        }
+
+        // Move the middle markers that drifted a whole position from their ideal
+        for i in 1..4 {
+            let drift = self.desired[i] - self.positions[i];
+            let room_right = self.positions[i + 1] - self.positions[i];
+            let room_left = self.positions[i - 1] - self.positions[i];
+            if (drift >= 1.0 && room_right > 1.0) || (drift <= -1.0 && room_left < -1.0) {
+                let step = if drift > 0.0 { 1.0 } else { -1.0 };
+                let parabolic = self.parabolic(i, step);
+                self.heights[i] =
+                    if self.heights[i - 1] < parabolic && parabolic < self.heights[i + 1] {
+                        parabolic
+                    } else {
+                        self.linear(i, step)
+                    };
+                self.positions[i] += step;
+            }
+        }
+    }
+
+    /// Returns the current estimate, or `None` if nothing was observed.
+    ///
+    /// Until five values are seen, this is the nearest-rank quantile of those values.
+    pub(crate) fn estimate(&self) -> Option<f64> {
+        match self.count {
+            0 => None,
+            count @ 1..=4 => {
+                let mut seen = [0.0; 4];
+                let seen = &mut seen[..count as usize];
+                seen.copy_from_slice(&self.heights[..count as usize]);
+                seen.sort_unstable_by(f64::total_cmp);
+                let rank = (self.p * (count - 1) as f64 + 0.5) as usize;
+                Some(seen[rank])
+            }
+            _ => Some(self.heights[2]),
+        }
+    }
+
+    /// The height of marker `i` moved by `step` on the parabola through its neighbours.
+    fn parabolic(&self, i: usize, step: f64) -> f64 {
+        let (q, n) = (&self.heights, &self.positions);
+        q[i] + step / (n[i + 1] - n[i - 1])
+            * ((n[i] - n[i - 1] + step) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                + (n[i + 1] - n[i] - step) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]))
+    }
+
+    /// The height of marker `i` moved by `step` on the line to the neighbour it moves
+    /// towards.
+    fn linear(&self, i: usize, step: f64) -> f64 {
+        let (q, n) = (&self.heights, &self.positions);
+        let j = if step > 0.0 { i + 1 } else { i - 1 };
+        q[i] + step * (q[j] - q[i]) / (n[j] - n[i])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fixed pseudo-random sequence in `[0, 1)`.
+    fn uniform(len: usize) -> impl Iterator<Item = f64> {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        (0..len).map(move |_| {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (state >> 11) as f64 / (1u64 << 53) as f64
+        })
+    }
+
+    #[test]
+    fn test_estimates_uniform_quantiles() {
+        let mut estimators = [0.5, 0.9, 0.99].map(P2Quantile::new);
+        for value in uniform(100_000) {
+            for estimator in &mut estimators {
+                estimator.observe(value * 1000.0);
+            }
+        }
+
+        for (estimator, expected) in estimators.iter().zip([500.0, 900.0, 990.0]) {
+            let estimate = estimator.estimate().unwrap();
+            assert!(
+                (estimate - expected).abs() < 5.0,
+                "estimated {estimate}, expected {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_estimates_exponential_tail() {
+        // Exponential with mean 100: the p99 is 100 * ln(100)
+        let mut p99 = P2Quantile::new(0.99);
+        for value in uniform(200_000) {
+            p99.observe(-100.0 * (1.0 - value).ln());
+        }
+
+        let expected = 100.0 * 100f64.ln();
+        let estimate = p99.estimate().unwrap();
+        assert!(
+            (estimate - expected).abs() / expected < 0.03,
+            "estimated {estimate}, expected {expected}"
+        );
+        assert_eq!(p99.count(), 200_000);
+    }
+
+    #[test]
+    fn test_few_observations_use_nearest_rank() {
+        let mut median = P2Quantile::new(0.5);
+        assert_eq!(median.estimate(), None);
+        for value in [30.0, 10.0, 20.0] {
+            median.observe(value);
+        }
+        assert_eq!(median.estimate(), Some(20.0));
+
+        // Constant input stays exact
+        let mut p90 = P2Quantile::new(0.9);
+        for _ in 0..50 {
+            p90.observe(7.0);
+        }
+        assert_eq!(p90.estimate(), Some(7.0));
+    }
+}
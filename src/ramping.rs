@@ -0,0 +1,297 @@
+//! Gradual rate changes.
+//!
+//! `update_config` switches a limiter to its new rate at once, which can shock a
+//! downstream sized for the old rate. [`RampingReconfigurable`] instead treats a new
+//! rate as a target and moves towards it linearly over a fixed ramp duration, for gentle
+//! transitions when autoscaling.
+//!
+//! This module is only available with the `std` feature.
+
+use core::time::Duration;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::Result,
+    traits::{RateLimiter, ReconfigurableRateLimiter, Wrapper},
+    validate,
+};
+
+#[derive(Debug)]
+struct Ramp {
+    /// The rate when the current ramp started.
+    from: f64,
+    /// The rate the current ramp ends at.
+    to: f64,
+    /// When the current ramp started.
+    started_at: u64,
+    /// The rate last applied to the inner limiter.
+    applied: f64,
+}
+
+/// A wrapper that ramps rate changes in linearly instead of applying them at once.
+///
+/// `update_config` records the new rate as a target. Whenever the limiter is used
+/// afterwards, the rate of the inner limiter is moved to the point on the line from the
+/// old rate to the target that the clock has reached, through
+/// [`set_rate_only`](ReconfigurableRateLimiter::set_rate_only), so tokens accrued so far
+/// are kept. Capacity changes still apply immediately.
+///
+/// The wrapper's clock should be the one the inner limiter uses.
+#[derive(Debug)]
+pub struct RampingReconfigurable<L, C = SystemClock> {
+    inner: L,
+    clock: C,
+    ramp_ms: u64,
+    ramp: Mutex<Ramp>,
+    /// Whether a ramp is in progress, so the limiter skips the lock once it is done.
+    ramping: AtomicBool,
+}
+
+impl<L> RampingReconfigurable<L, SystemClock>
+where
+    L: ReconfigurableRateLimiter,
+{
+    /// Creates a new wrapper that ramps rate changes over `ramp`.
+    ///
+    /// A zero `ramp` applies rate changes immediately.
+    pub fn new(inner: L, ramp: Duration) -> Self {
+        Self::with_clock(inner, ramp, SystemClock)
+    }
+}
+
+impl<L, C> RampingReconfigurable<L, C>
+where
+    L: ReconfigurableRateLimiter,
+    C: Clock,
+{
+    /// Creates a new wrapper with the specified clock.
+    pub fn with_clock(inner: L, ramp: Duration, clock: C) -> Self {
+        let rate = inner.rate_per_second();
+        let now = clock.now();
+        Self {
+            inner,
+            clock,
+            ramp_ms: ramp.as_millis().min(u64::MAX as u128) as u64,
+            ramp: Mutex::new(Ramp {
+                from: rate,
+                to: rate,
+                started_at: now,
+                applied: rate,
+            }),
+            ramping: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the duration over which rate changes are ramped in.
+    pub fn ramp(&self) -> Duration {
+        Duration::from_millis(self.ramp_ms)
+    }
+
+    /// Returns the rate the limiter is ramping towards, in tokens per second.
+    pub fn target_rate(&self) -> f64 {
+        self.ramp.lock().unwrap().to
+    }
+
+    /// Moves the inner limiter's rate to where the ramp stands at `now`.
+    fn advance(&self, ramp: &mut Ramp, now: u64) -> Result<()> {
+        if ramp.applied == ramp.to {
+            self.ramping.store(false, Ordering::Release);
+            return Ok(());
+        }
+
+        let elapsed = now.saturating_sub(ramp.started_at);
+        let rate = if elapsed >= self.ramp_ms {
+            ramp.to
+        } else {
+            ramp.from + (ramp.to - ramp.from) * (elapsed as f64 / self.ramp_ms as f64)
+        };
+        if rate != ramp.applied {
+            self.inner.set_rate_only(rate)?;
+            ramp.applied = rate;
+        }
+        if rate == ramp.to {
+            self.ramping.store(false, Ordering::Release);
+        }
+        Ok(())
+    }
+
+    /// Brings the inner limiter's rate up to date before it is used.
+    fn touch(&self) {
+        if !self.ramping.load(Ordering::Acquire) {
+            return;
+        }
+        let mut ramp = self.ramp.lock().unwrap();
+        // The ramp only passes through positive rates, which the inner limiter accepts
+        let _ = self.advance(&mut ramp, self.clock.now());
+    }
+}
+
+impl<L, C> RateLimiter for RampingReconfigurable<L, C>
+where
+    L: ReconfigurableRateLimiter,
+    C: Clock,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        self.touch();
+        self.inner.try_acquire(tokens)
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        self.touch();
+        self.inner.try_acquire_u64(tokens)
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        self.touch();
+        self.inner.peek_acquire(tokens)
+    }
+
+    fn available_tokens(&self) -> u32 {
+        self.touch();
+        self.inner.available_tokens()
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        self.touch();
+        self.inner.available_tokens_u64()
+    }
+
+    fn capacity(&self) -> u32 {
+        self.inner.capacity()
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.inner.capacity_u64()
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        self.touch();
+        self.inner.rate_per_second()
+    }
+
+    fn effective_rate_per_second(&self) -> f64 {
+        self.touch();
+        self.inner.effective_rate_per_second()
+    }
+
+    fn resolution_warning(&self) -> Option<&'static str> {
+        self.touch();
+        self.inner.resolution_warning()
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        self.touch();
+        self.inner.time_until_next_token_ms()
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.touch();
+        self.inner.time_until_available_ms(tokens)
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        self.touch();
+        self.inner.retry_at(tokens)
+    }
+}
+
+impl<L, C> ReconfigurableRateLimiter for RampingReconfigurable<L, C>
+where
+    L: ReconfigurableRateLimiter,
+    C: Clock,
+{
+    /// Applies a capacity change immediately and starts ramping towards the new rate
+    /// from the current one.
+    fn update_config(&self, capacity: u32, tokens_per_second: f64) -> Result<()> {
+        validate::try_rate(tokens_per_second, "tokens_per_second must be positive")?;
+
+        let now = self.clock.now();
+        let mut ramp = self.ramp.lock().unwrap();
+        self.advance(&mut ramp, now)?;
+        if capacity != self.inner.capacity() {
+            self.inner.update_config(capacity, ramp.applied)?;
+        }
+
+        ramp.from = ramp.applied;
+        ramp.to = tokens_per_second;
+        ramp.started_at = now;
+        self.ramping.store(true, Ordering::Release);
+        self.advance(&mut ramp, now)
+    }
+
+    fn set_rate_only(&self, tokens_per_second: f64) -> Result<()> {
+        self.update_config(self.inner.capacity(), tokens_per_second)
+    }
+}
+
+impl<L, C> Wrapper for RampingReconfigurable<L, C> {
+    type Inner = L;
+
+    fn get_ref(&self) -> &L {
+        &self.inner
+    }
+
+    fn get_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    fn into_inner(self) -> L {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, TokenBucket};
+
+    #[test]
+    fn test_rate_ramps_instead_of_jumping() {
+        let clock = MockClock::new(0);
+        let bucket = TokenBucket::with_clock(1000, 10.0, clock.clone());
+        let limiter =
+            RampingReconfigurable::with_clock(bucket, Duration::from_secs(1), clock.clone());
+        assert!(limiter.try_acquire(1000).is_ok());
+
+        assert!(!limiter.ramping.load(Ordering::Acquire));
+        assert!(limiter.update_config(1000, 20.0).is_ok());
+        assert!(limiter.ramping.load(Ordering::Acquire));
+        assert_eq!(limiter.target_rate(), 20.0);
+        assert_eq!(limiter.rate_per_second(), 10.0);
+
+        clock.advance(250);
+        assert_eq!(limiter.rate_per_second(), 12.5);
+        clock.advance(250);
+        assert_eq!(limiter.rate_per_second(), 15.0);
+        clock.advance(500);
+        assert_eq!(limiter.rate_per_second(), 20.0);
+        clock.advance(5000);
+        assert_eq!(limiter.rate_per_second(), 20.0);
+        // Once the ramp is done, using the limiter no longer takes the lock
+        assert!(!limiter.ramping.load(Ordering::Acquire));
+
+        // The ramp never refilled the bucket: only the accrued tokens are available
+        assert!(limiter.available_tokens() < 200);
+
+        // Ramping down starts from the current rate
+        assert!(limiter.update_config(1000, 10.0).is_ok());
+        clock.advance(500);
+        assert_eq!(limiter.rate_per_second(), 15.0);
+    }
+
+    #[test]
+    fn test_zero_ramp_applies_immediately() {
+        let clock = MockClock::new(0);
+        let bucket = TokenBucket::with_clock(10, 1.0, clock.clone());
+        let limiter = RampingReconfigurable::with_clock(bucket, Duration::ZERO, clock);
+
+        assert!(limiter.update_config(20, 4.0).is_ok());
+        assert_eq!(limiter.rate_per_second(), 4.0);
+        assert_eq!(limiter.capacity(), 20);
+        assert!(limiter.update_config(20, 0.0).is_err());
+    }
+}
@@ -0,0 +1,176 @@
+//! Tail statistics of advertised retry waits.
+//!
+//! How long rejected clients are told to wait is a good signal of how overloaded a
+//! limiter is, but the interesting part is the tail. [`RetryAfterObserver`] keeps
+//! running p50, p90 and p99 estimates of the `retry_after_ms` of every rejection, in
+//! constant memory and without the coarse resolution of fixed histogram buckets.
+//!
+//! This module is only available with the `std` feature.
+
+use std::sync::Mutex;
+
+use crate::{
+    error::{RateLimitError, Result},
+    quantile::P2Quantile,
+    traits::{RateLimiter, Wrapper},
+};
+
+/// Estimated percentiles of the advertised retry waits, in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryAfterPercentiles {
+    /// The number of rejections observed.
+    pub samples: u64,
+    /// The estimated median wait.
+    pub p50: f64,
+    /// The estimated 90th percentile wait.
+    pub p90: f64,
+    /// The estimated 99th percentile wait.
+    pub p99: f64,
+}
+
+/// A wrapper that estimates percentiles of the `retry_after_ms` of rejections.
+///
+/// The estimates use the P² algorithm, which keeps five markers per percentile instead
+/// of the samples themselves. They are exact for the first five rejections and
+/// converge on the true percentiles as more arrive; the error shrinks with the number
+/// of samples but is not bounded for adversarial orderings.
+///
+/// Only rejections reported as `RateLimitExceeded` are observed; admitted requests and
+/// other errors are passed through untouched.
+#[derive(Debug)]
+pub struct RetryAfterObserver<L> {
+    inner: L,
+    estimators: Mutex<[P2Quantile; 3]>,
+}
+
+impl<L> RetryAfterObserver<L>
+where
+    L: RateLimiter,
+{
+    /// Wraps `inner`, observing the waits it advertises.
+    pub fn new(inner: L) -> Self {
+        Self {
+            inner,
+            estimators: Mutex::new([0.5, 0.9, 0.99].map(P2Quantile::new)),
+        }
+    }
+
+    /// Returns the estimated percentiles, or `None` if nothing was rejected yet.
+    pub fn percentiles(&self) -> Option<RetryAfterPercentiles> {
+        let [p50, p90, p99] = *self.estimators.lock().unwrap();
+        Some(RetryAfterPercentiles {
+            samples: p50.count(),
+            p50: p50.estimate()?,
+            p90: p90.estimate()?,
+            p99: p99.estimate()?,
+        })
+    }
+
+    fn observe(&self, result: Result<()>) -> Result<()> {
+        if let Err(RateLimitError::RateLimitExceeded { retry_after_ms, .. }) = result {
+            let mut estimators = self.estimators.lock().unwrap();
+            for estimator in estimators.iter_mut() {
+                estimator.observe(retry_after_ms as f64);
+            }
+        }
+        result
+    }
+}
+
+impl<L> RateLimiter for RetryAfterObserver<L>
+where
+    L: RateLimiter,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        self.observe(self.inner.try_acquire(tokens))
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        self.observe(self.inner.try_acquire_u64(tokens))
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        self.inner.peek_acquire(tokens)
+    }
+
+    fn available_tokens(&self) -> u32 {
+        self.inner.available_tokens()
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        self.inner.available_tokens_u64()
+    }
+
+    fn capacity(&self) -> u32 {
+        self.inner.capacity()
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.inner.capacity_u64()
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        self.inner.rate_per_second()
+    }
+
+    fn effective_rate_per_second(&self) -> f64 {
+        self.inner.effective_rate_per_second()
+    }
+
+    fn resolution_warning(&self) -> Option<&'static str> {
+        self.inner.resolution_warning()
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        self.inner.time_until_next_token_ms()
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.inner.time_until_available_ms(tokens)
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        self.inner.retry_at(tokens)
+    }
+}
+
+impl<L> Wrapper for RetryAfterObserver<L> {
+    type Inner = L;
+
+    fn get_ref(&self) -> &L {
+        &self.inner
+    }
+
+    fn get_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    fn into_inner(self) -> L {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, TokenBucket};
+
+    #[test]
+    fn test_observes_rejection_waits() {
+        let clock = MockClock::new(0);
+        let limiter = RetryAfterObserver::new(TokenBucket::with_clock(100, 1.0, clock.clone()));
+        assert!(limiter.try_acquire(100).is_ok());
+        assert_eq!(limiter.percentiles(), None);
+
+        // Rejections for 1 to 100 tokens advertise waits of 1 to 100 seconds
+        for tokens in 1..=100 {
+            assert!(limiter.try_acquire(tokens).is_err());
+        }
+
+        let percentiles = limiter.percentiles().unwrap();
+        assert_eq!(percentiles.samples, 100);
+        assert!((percentiles.p50 - 50_000.0).abs() <= 2_000.0);
+        assert!((percentiles.p90 - 90_000.0).abs() <= 2_000.0);
+        assert!(percentiles.p99 >= 97_000.0 && percentiles.p99 <= 100_000.0);
+    }
+}
@@ -0,0 +1,240 @@
+//! A fixed-size log of recent decisions for debugging without an allocator.
+//!
+//! [`TraceLimiter`](crate::TraceLimiter) keeps every decision in a `Vec`, which is not
+//! an option on firmware without a heap. [`EmbeddedTraceLimiter`] instead keeps only
+//! the last `N` decisions, in an array of atomics sized at compile time, so a debug
+//! console can dump recent activity over a serial line. It uses `core` only.
+
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use crate::{
+    clock::Clock,
+    error::Result,
+    traits::{RateLimiter, Wrapper},
+};
+
+/// A single admission decision kept by an [`EmbeddedTraceLimiter`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecisionRecord {
+    /// The clock time, in milliseconds, at which the decision was made.
+    pub timestamp: u64,
+    /// Whether the request was admitted.
+    pub allowed: bool,
+}
+
+impl DecisionRecord {
+    /// Packs the record into one word: the timestamp above the `allowed` bit.
+    fn pack(self) -> u64 {
+        (self.timestamp.min(u64::MAX >> 1) << 1) | u64::from(self.allowed)
+    }
+
+    fn unpack(packed: u64) -> Self {
+        Self {
+            timestamp: packed >> 1,
+            allowed: packed & 1 == 1,
+        }
+    }
+}
+
+/// A wrapper that keeps the last `N` decisions of the inner limiter in a ring buffer.
+///
+/// Each decision is stored in a single atomic word, so recording never blocks and a
+/// record is never torn. A [`dump`](Self::dump) running concurrently with acquires is
+/// best-effort, however: a slot overwritten while it is being read shows the newer
+/// decision, and a decision still being recorded may be missing. Timestamps are
+/// capped at `u64::MAX / 2` milliseconds to make room for the `allowed` bit.
+///
+/// # Examples
+///
+/// ```
+/// use bucketboss::{clock::MockClock, DecisionRecord, EmbeddedTraceLimiter, RateLimiter, TokenBucket};
+///
+/// let clock = MockClock::new(0);
+/// let limiter: EmbeddedTraceLimiter<_, _, 4> =
+///     EmbeddedTraceLimiter::new(TokenBucket::with_clock(1, 1.0, clock.clone()), clock);
+/// assert!(limiter.try_acquire(1).is_ok());
+/// assert!(limiter.try_acquire(1).is_err());
+///
+/// let mut records = [DecisionRecord::default(); 4];
+/// let len = limiter.dump(&mut records);
+/// assert_eq!(len, 2);
+/// assert!(records[0].allowed && !records[1].allowed);
+/// ```
+#[derive(Debug)]
+pub struct EmbeddedTraceLimiter<L, C, const N: usize> {
+    inner: L,
+    clock: C,
+    /// The packed records, with record `i` in slot `i % N`.
+    slots: [AtomicU64; N],
+    /// The number of records written so far.
+    written: AtomicUsize,
+}
+
+impl<L, C, const N: usize> EmbeddedTraceLimiter<L, C, N>
+where
+    L: RateLimiter,
+    C: Clock,
+{
+    /// Wraps `inner`, timestamping its decisions with `clock`.
+    pub fn new(inner: L, clock: C) -> Self {
+        Self {
+            inner,
+            clock,
+            slots: [const { AtomicU64::new(0) }; N],
+            written: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of decisions recorded since the limiter was created,
+    /// including those already overwritten.
+    pub fn recorded(&self) -> usize {
+        self.written.load(Ordering::Acquire)
+    }
+
+    /// Copies the most recent decisions into `out`, oldest first, and returns how many
+    /// were copied.
+    ///
+    /// At most `N` decisions are kept; if `out` is shorter than that, only the most
+    /// recent ones that fit are copied.
+    pub fn dump(&self, out: &mut [DecisionRecord]) -> usize {
+        let written = self.written.load(Ordering::Acquire);
+        let len = written.min(N).min(out.len());
+        for (offset, record) in out[..len].iter_mut().enumerate() {
+            let index = written - len + offset;
+            *record = DecisionRecord::unpack(self.slots[index % N].load(Ordering::Acquire));
+        }
+        len
+    }
+
+    fn record(&self, result: Result<()>) -> Result<()> {
+        if N > 0 {
+            let record = DecisionRecord {
+                timestamp: self.clock.now(),
+                allowed: result.is_ok(),
+            };
+            let index = self.written.fetch_add(1, Ordering::AcqRel);
+            self.slots[index % N].store(record.pack(), Ordering::Release);
+        }
+        result
+    }
+}
+
+impl<L, C, const N: usize> RateLimiter for EmbeddedTraceLimiter<L, C, N>
+where
+    L: RateLimiter,
+    C: Clock,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        self.record(self.inner.try_acquire(tokens))
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        self.record(self.inner.try_acquire_u64(tokens))
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        self.inner.peek_acquire(tokens)
+    }
+
+    fn available_tokens(&self) -> u32 {
+        self.inner.available_tokens()
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        self.inner.available_tokens_u64()
+    }
+
+    fn capacity(&self) -> u32 {
+        self.inner.capacity()
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.inner.capacity_u64()
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        self.inner.rate_per_second()
+    }
+
+    fn effective_rate_per_second(&self) -> f64 {
+        self.inner.effective_rate_per_second()
+    }
+
+    fn resolution_warning(&self) -> Option<&'static str> {
+        self.inner.resolution_warning()
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        self.inner.time_until_next_token_ms()
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.inner.time_until_available_ms(tokens)
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        self.inner.retry_at(tokens)
+    }
+}
+
+impl<L, C, const N: usize> Wrapper for EmbeddedTraceLimiter<L, C, N> {
+    type Inner = L;
+
+    fn get_ref(&self) -> &L {
+        &self.inner
+    }
+
+    fn get_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    fn into_inner(self) -> L {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, TokenBucket};
+
+    #[test]
+    fn test_ring_wraps_and_dumps_oldest_first() {
+        let clock = MockClock::new(0);
+        let limiter: EmbeddedTraceLimiter<_, _, 3> = EmbeddedTraceLimiter::new(
+            TokenBucket::with_clock(2, 1.0, clock.clone()),
+            clock.clone(),
+        );
+
+        // Two admissions, then rejections every 100 ms
+        for _ in 0..5 {
+            let _ = limiter.try_acquire(1);
+            clock.advance(100);
+        }
+        assert_eq!(limiter.recorded(), 5);
+
+        let mut records = [DecisionRecord::default(); 8];
+        assert_eq!(limiter.dump(&mut records), 3);
+        let expected = [(200, false), (300, false), (400, false)]
+            .map(|(timestamp, allowed)| DecisionRecord { timestamp, allowed });
+        assert_eq!(records[..3], expected);
+
+        // A short buffer gets the most recent decisions
+        let mut records = [DecisionRecord::default(); 2];
+        assert_eq!(limiter.dump(&mut records), 2);
+        assert_eq!(records, expected[1..]);
+
+        clock.advance(1000);
+        assert!(limiter.try_acquire(1).is_ok());
+        let mut records = [DecisionRecord::default(); 3];
+        assert_eq!(limiter.dump(&mut records), 3);
+        assert_eq!(
+            records[2],
+            DecisionRecord {
+                timestamp: 1500,
+                allowed: true
+            }
+        );
+        assert_eq!(records[0], expected[1]);
+    }
+}
@@ -0,0 +1,77 @@
+//! How long a bucket has been continuously exhausted.
+//!
+//! Instantaneous utilization says a bucket is empty right now; alerting usually wants
+//! to know whether it has *stayed* empty, as in "at 0 available for over 30 s".
+//! [`SaturationTracker`] remembers when the bucket last became exhausted, which is when
+//! the current exhausted stretch began, and forgets it whenever the level changes to
+//! leave something available.
+//!
+//! This module is only available with the `saturation` feature.
+
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Tracks when a bucket last became exhausted.
+#[derive(Debug)]
+pub(crate) struct SaturationTracker {
+    /// When the bucket last became exhausted, in nanoseconds plus one; 0 if unknown.
+    since: AtomicU64,
+}
+
+impl SaturationTracker {
+    /// Creates a tracker that has not seen the bucket exhausted.
+    pub(crate) const fn new() -> Self {
+        Self {
+            since: AtomicU64::new(0),
+        }
+    }
+
+    /// Records that an admission at `now` nanoseconds left nothing available.
+    ///
+    /// The admission needed available tokens, so the exhausted stretch starts here.
+    pub(crate) fn record_exhausted(&self, now: u64) {
+        self.since.store(now.saturating_add(1), Ordering::Release);
+    }
+
+    /// Records a change of the bucket's level at `now` nanoseconds, given whether it
+    /// left the bucket exhausted.
+    ///
+    /// An exhausted bucket keeps the start of its current stretch, or starts one now;
+    /// a bucket with something available ends it.
+    pub(crate) fn update(&self, now: u64, exhausted: bool) {
+        if exhausted {
+            let _ = self.since.compare_exchange(
+                0,
+                now.saturating_add(1),
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            );
+        } else if self.since.load(Ordering::Relaxed) != 0 {
+            self.since.store(0, Ordering::Release);
+        }
+    }
+
+    /// Returns how long the bucket has been exhausted as of `now` nanoseconds, given
+    /// whether it is exhausted now.
+    ///
+    /// A bucket found exhausted without a recorded start, such as one restored from a
+    /// snapshot, is treated as exhausted from `now`.
+    pub(crate) fn since(&self, now: u64, exhausted: bool) -> Option<Duration> {
+        if !exhausted {
+            self.since.store(0, Ordering::Release);
+            return None;
+        }
+        let since = match self.since.compare_exchange(
+            0,
+            now.saturating_add(1),
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => now,
+            Err(recorded) => recorded - 1,
+        };
+        Some(Duration::from_nanos(now.saturating_sub(since)))
+    }
+}
@@ -0,0 +1,230 @@
+//! Unit conversion between callers and the limiter.
+//!
+//! When callers measure usage in one unit (for example bytes) but the limit is
+//! expressed in a coarser one (for example kilobytes), [`ScaledLimiter`] performs the
+//! conversion in one place instead of at every call site.
+
+use crate::{
+    error::{RateLimitError, Result},
+    outcome::saturate_u32,
+    traits::{RateLimiter, ReconfigurableRateLimiter, Wrapper},
+};
+
+/// A wrapper that converts token counts between the caller's unit and the limiter's.
+///
+/// Requests are divided by `scale`, rounding up, before reaching the inner limiter, so
+/// a partial unit always costs a whole one. Everything reported back (available tokens,
+/// capacity, rate and rejection errors) is multiplied by `scale` into the caller's unit,
+/// saturating at `u32::MAX`.
+#[derive(Debug)]
+pub struct ScaledLimiter<L> {
+    inner: L,
+    scale: u32,
+}
+
+impl<L> ScaledLimiter<L>
+where
+    L: RateLimiter,
+{
+    /// Creates a new wrapper where one inner token represents `scale` caller tokens.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Self)` if `scale` is at least 1
+    /// * `Err(RateLimitError::InvalidConfiguration)` if `scale` is 0
+    pub fn new(inner: L, scale: u32) -> Result<Self> {
+        if scale == 0 {
+            return Err(RateLimitError::invalid_config(
+                "scale must be greater than 0",
+            ));
+        }
+
+        Ok(Self { inner, scale })
+    }
+
+    /// Returns the number of caller tokens represented by one inner token.
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// Converts a caller token count into inner tokens, rounding up.
+    pub fn to_inner(&self, tokens: u32) -> u32 {
+        tokens.div_ceil(self.scale)
+    }
+
+    /// Converts an inner token count into caller tokens, saturating at `u32::MAX`.
+    pub fn to_outer(&self, tokens: u32) -> u32 {
+        tokens.saturating_mul(self.scale)
+    }
+
+    /// Reports a rejection of the inner tokens for `tokens` in the caller's unit.
+    fn rescale(&self, tokens: u32, result: Result<()>) -> Result<()> {
+        match result {
+            Err(RateLimitError::RateLimitExceeded {
+                available,
+                retry_after_ms,
+                rejected_by,
+                ..
+            }) => Err(RateLimitError::RateLimitExceeded {
+                requested: tokens,
+                available: self.to_outer(available),
+                retry_after_ms,
+                rejected_by,
+            }),
+            result => result,
+        }
+    }
+}
+
+impl<L> RateLimiter for ScaledLimiter<L>
+where
+    L: RateLimiter,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        self.rescale(tokens, self.inner.try_acquire(self.to_inner(tokens)))
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        let inner_tokens = tokens.div_ceil(u64::from(self.scale));
+        self.rescale(
+            saturate_u32(tokens),
+            self.inner.try_acquire_u64(inner_tokens),
+        )
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        self.rescale(tokens, self.inner.peek_acquire(self.to_inner(tokens)))
+    }
+
+    fn available_tokens(&self) -> u32 {
+        self.to_outer(self.inner.available_tokens())
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        self.inner
+            .available_tokens_u64()
+            .saturating_mul(u64::from(self.scale))
+    }
+
+    fn capacity(&self) -> u32 {
+        self.to_outer(self.inner.capacity())
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.inner
+            .capacity_u64()
+            .saturating_mul(u64::from(self.scale))
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        self.inner.rate_per_second() * self.scale as f64
+    }
+
+    fn effective_rate_per_second(&self) -> f64 {
+        self.inner.effective_rate_per_second() * self.scale as f64
+    }
+
+    fn resolution_warning(&self) -> Option<&'static str> {
+        self.inner.resolution_warning()
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        self.inner.time_until_next_token_ms()
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.inner.time_until_available_ms(self.to_inner(tokens))
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        self.inner.retry_at(self.to_inner(tokens))
+    }
+}
+
+impl<L> ReconfigurableRateLimiter for ScaledLimiter<L>
+where
+    L: ReconfigurableRateLimiter,
+{
+    /// Reconfigures the inner limiter using values expressed in the caller's unit.
+    ///
+    /// The capacity is rounded up to a whole number of inner tokens.
+    fn update_config(&self, capacity: u32, tokens_per_second: f64) -> Result<()> {
+        self.inner.update_config(
+            self.to_inner(capacity),
+            tokens_per_second / self.scale as f64,
+        )
+    }
+
+    fn set_rate_only(&self, tokens_per_second: f64) -> Result<()> {
+        self.inner
+            .set_rate_only(tokens_per_second / self.scale as f64)
+    }
+}
+
+impl<L> Wrapper for ScaledLimiter<L> {
+    type Inner = L;
+
+    fn get_ref(&self) -> &L {
+        &self.inner
+    }
+
+    fn get_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    fn into_inner(self) -> L {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, TokenBucket};
+
+    #[test]
+    fn test_rounds_up_at_scale_boundaries() {
+        // 10 KB of capacity, requests in bytes
+        let bucket = TokenBucket::with_clock(10, 1.0, MockClock::new(0));
+        let limiter = ScaledLimiter::new(bucket, 1024).unwrap();
+
+        assert_eq!(limiter.capacity(), 10 * 1024);
+        assert_eq!(limiter.to_inner(0), 0);
+        assert_eq!(limiter.to_inner(1), 1);
+        assert_eq!(limiter.to_inner(1024), 1);
+        assert_eq!(limiter.to_inner(1025), 2);
+
+        // Exactly one KB costs one inner token, one more byte costs a second
+        assert!(limiter.try_acquire(1024).is_ok());
+        assert_eq!(limiter.available_tokens(), 9 * 1024);
+        assert!(limiter.try_acquire(1025).is_ok());
+        assert_eq!(limiter.available_tokens(), 7 * 1024);
+
+        // Rejections are reported in the caller's unit
+        let err = limiter.try_acquire(8 * 1024).unwrap_err();
+        assert_eq!(
+            err,
+            RateLimitError::rate_limit_exceeded(8 * 1024, 7 * 1024, 1000)
+        );
+    }
+
+    #[test]
+    fn test_rate_and_reconfigure_in_caller_units() {
+        let bucket = TokenBucket::with_clock(10, 2.0, MockClock::new(0));
+        let limiter = ScaledLimiter::new(bucket, 100).unwrap();
+        assert_eq!(limiter.rate_per_second(), 200.0);
+
+        assert!(limiter.update_config(250, 500.0).is_ok());
+        assert_eq!(limiter.get_ref().capacity(), 3);
+        assert_eq!(limiter.get_ref().rate_per_second(), 5.0);
+        assert_eq!(limiter.capacity(), 300);
+    }
+
+    #[test]
+    fn test_zero_scale_is_rejected() {
+        let bucket = TokenBucket::with_clock(10, 1.0, MockClock::new(0));
+        assert!(ScaledLimiter::new(bucket, 0)
+            .unwrap_err()
+            .is_invalid_config());
+    }
+}
@@ -0,0 +1,91 @@
+//! Planned admission timelines.
+//!
+//! A scheduler laying out a batch of tasks up front wants to know when each could be
+//! admitted, not just when the next one could. [`Schedule`] is the timeline returned by
+//! [`RateLimiter::schedule`](crate::RateLimiter::schedule): the tokens available now
+//! come first, then one token per refill interval.
+
+/// The clock timestamps at which successive tokens become available.
+///
+/// The first `immediate` timestamps are the time the schedule was taken; after that,
+/// token `k` (counting from 0 after the immediate ones) is due at
+/// `first_refill + k * interval`, rounded up to the next millisecond and never earlier
+/// than the time the schedule was taken.
+///
+/// The timeline assumes each token is acquired when it becomes available and that
+/// nobody else acquires from the limiter meanwhile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Schedule {
+    now: u64,
+    immediate: u32,
+    first_refill: f64,
+    interval: f64,
+    next: u32,
+    len: u32,
+}
+
+impl Schedule {
+    /// Creates the timeline of `len` tokens.
+    ///
+    /// * `now` - the clock time the schedule is taken at
+    /// * `immediate` - the number of tokens available at `now`
+    /// * `first_refill` - when the first token after those becomes available
+    /// * `interval` - the time between later tokens, in milliseconds
+    pub fn new(now: u64, len: u32, immediate: u32, first_refill: f64, interval: f64) -> Self {
+        Self {
+            now,
+            immediate,
+            first_refill,
+            interval,
+            next: 0,
+            len,
+        }
+    }
+
+    /// Creates a timeline with no tokens, for limiters that cannot anchor one to a
+    /// clock.
+    pub fn empty() -> Self {
+        Self::new(0, 0, 0, 0.0, 0.0)
+    }
+}
+
+impl Iterator for Schedule {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.next >= self.len {
+            return None;
+        }
+        let index = self.next;
+        self.next += 1;
+
+        if index < self.immediate {
+            return Some(self.now);
+        }
+        let due = self.first_refill + (index - self.immediate) as f64 * self.interval;
+        let whole = due as u64;
+        let due = whole + u64::from((whole as f64) < due);
+        Some(due.max(self.now))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.len - self.next) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Schedule {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_immediate_tokens_then_interval() {
+        let schedule = Schedule::new(100, 5, 2, 150.0, 33.4);
+        assert_eq!(schedule.len(), 5);
+        let timeline: [u64; 5] = [100, 100, 150, 184, 217];
+        assert!(schedule.eq(timeline));
+        assert_eq!(Schedule::empty().next(), None);
+    }
+}
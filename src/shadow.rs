@@ -0,0 +1,280 @@
+//! Evaluating a limiter on live traffic without enforcing it.
+//!
+//! Before switching algorithms, it helps to know how the candidate would have treated
+//! real traffic. [`ShadowLimiter`] enforces one limiter's decisions while feeding every
+//! request to a second limiter in shadow, recording where the two disagree.
+//!
+//! This module is only available with the `std` feature.
+
+use core::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{
+    error::Result,
+    outcome::saturate_u32,
+    traits::{RateLimiter, Wrapper},
+};
+
+/// The decisions both limiters made for one request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShadowDecision {
+    /// The number of tokens requested.
+    pub tokens: u32,
+    /// Whether the enforcing limiter admitted the request.
+    pub primary_admitted: bool,
+    /// Whether the shadow limiter would have admitted the request.
+    pub shadow_admitted: bool,
+}
+
+impl ShadowDecision {
+    /// Returns `true` if the two limiters decided differently.
+    pub fn disagrees(&self) -> bool {
+        self.primary_admitted != self.shadow_admitted
+    }
+}
+
+/// Counts of the decisions made by a [`ShadowLimiter`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShadowStats {
+    /// The number of requests fed to both limiters.
+    pub requests: u64,
+    /// Requests rejected by the enforcing limiter.
+    pub primary_rejected: u64,
+    /// Requests the shadow limiter would have rejected.
+    pub shadow_rejected: u64,
+    /// Requests the shadow limiter would have decided differently.
+    pub disagreements: u64,
+}
+
+type Observer = Box<dyn Fn(&ShadowDecision) + Send + Sync>;
+
+/// A limiter that enforces `A` while running `B` in shadow.
+///
+/// Every `try_acquire` is fed to both limiters, primary first, so the shadow's state
+/// evolves exactly as it would if it were enforcing. Only the primary's result is
+/// returned. The shadow's decisions are counted in [`stats`](Self::stats) and, with
+/// [`with_observer`](Self::with_observer), reported request by request.
+///
+/// Queries such as `available_tokens` describe the primary.
+pub struct ShadowLimiter<A, B> {
+    primary: A,
+    shadow: B,
+    observer: Option<Observer>,
+    requests: AtomicU64,
+    primary_rejected: AtomicU64,
+    shadow_rejected: AtomicU64,
+    disagreements: AtomicU64,
+}
+
+impl<A, B> ShadowLimiter<A, B>
+where
+    A: RateLimiter,
+    B: RateLimiter,
+{
+    /// Creates a limiter enforcing `primary` with `shadow` running alongside it.
+    pub fn new(primary: A, shadow: B) -> Self {
+        Self {
+            primary,
+            shadow,
+            observer: None,
+            requests: AtomicU64::new(0),
+            primary_rejected: AtomicU64::new(0),
+            shadow_rejected: AtomicU64::new(0),
+            disagreements: AtomicU64::new(0),
+        }
+    }
+
+    /// Calls `observer` with the decisions made for every request.
+    ///
+    /// The observer runs on the requesting thread, after both limiters have decided,
+    /// so it should be cheap.
+    pub fn with_observer<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&ShadowDecision) + Send + Sync + 'static,
+    {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+}
+
+impl<A, B> ShadowLimiter<A, B> {
+    /// Returns the limiter running in shadow.
+    pub fn shadow(&self) -> &B {
+        &self.shadow
+    }
+
+    /// Returns the decision counts collected so far.
+    pub fn stats(&self) -> ShadowStats {
+        ShadowStats {
+            requests: self.requests.load(Ordering::Relaxed),
+            primary_rejected: self.primary_rejected.load(Ordering::Relaxed),
+            shadow_rejected: self.shadow_rejected.load(Ordering::Relaxed),
+            disagreements: self.disagreements.load(Ordering::Relaxed),
+        }
+    }
+
+    fn record(&self, decision: &ShadowDecision) {
+        let _ = self.requests.fetch_add(1, Ordering::Relaxed);
+        if !decision.primary_admitted {
+            let _ = self.primary_rejected.fetch_add(1, Ordering::Relaxed);
+        }
+        if !decision.shadow_admitted {
+            let _ = self.shadow_rejected.fetch_add(1, Ordering::Relaxed);
+        }
+        if decision.disagrees() {
+            let _ = self.disagreements.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(observer) = &self.observer {
+            observer(decision);
+        }
+    }
+}
+
+impl<A, B> RateLimiter for ShadowLimiter<A, B>
+where
+    A: RateLimiter,
+    B: RateLimiter,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        let result = self.primary.try_acquire(tokens);
+        let decision = ShadowDecision {
+            tokens,
+            primary_admitted: result.is_ok(),
+            shadow_admitted: self.shadow.try_acquire(tokens).is_ok(),
+        };
+        self.record(&decision);
+        result
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        let result = self.primary.try_acquire_u64(tokens);
+        let decision = ShadowDecision {
+            tokens: saturate_u32(tokens),
+            primary_admitted: result.is_ok(),
+            shadow_admitted: self.shadow.try_acquire_u64(tokens).is_ok(),
+        };
+        self.record(&decision);
+        result
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        self.primary.peek_acquire(tokens)
+    }
+
+    fn available_tokens(&self) -> u32 {
+        self.primary.available_tokens()
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        self.primary.available_tokens_u64()
+    }
+
+    fn capacity(&self) -> u32 {
+        self.primary.capacity()
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.primary.capacity_u64()
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        self.primary.rate_per_second()
+    }
+
+    fn effective_rate_per_second(&self) -> f64 {
+        self.primary.effective_rate_per_second()
+    }
+
+    fn resolution_warning(&self) -> Option<&'static str> {
+        self.primary.resolution_warning()
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        self.primary.time_until_next_token_ms()
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.primary.time_until_available_ms(tokens)
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        self.primary.retry_at(tokens)
+    }
+}
+
+impl<A, B> Wrapper for ShadowLimiter<A, B> {
+    type Inner = A;
+
+    fn get_ref(&self) -> &A {
+        &self.primary
+    }
+
+    fn get_mut(&mut self) -> &mut A {
+        &mut self.primary
+    }
+
+    fn into_inner(self) -> A {
+        self.primary
+    }
+}
+
+impl<A, B> fmt::Debug for ShadowLimiter<A, B>
+where
+    A: fmt::Debug,
+    B: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShadowLimiter")
+            .field("primary", &self.primary)
+            .field("shadow", &self.shadow)
+            .field("stats", &self.stats())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{clock::MockClock, LeakyBucket, TokenBucket};
+
+    #[test]
+    fn test_shadow_decisions_are_recorded_not_enforced() {
+        let clock = MockClock::new(0);
+        let decisions = Arc::new(Mutex::new(Vec::new()));
+        let observed = Arc::clone(&decisions);
+        let limiter = ShadowLimiter::new(
+            TokenBucket::with_clock(4, 1.0, clock.clone()),
+            LeakyBucket::with_clock(1.0, Some(2), clock.clone()),
+        )
+        .with_observer(move |decision| observed.lock().unwrap().push(*decision));
+
+        // The token bucket admits a burst of 4; the leaky bucket would have stopped at 2
+        for _ in 0..4 {
+            assert!(limiter.try_acquire(1).is_ok());
+        }
+        assert!(limiter.try_acquire(1).is_err());
+
+        let shadow_admitted: Vec<_> = decisions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|decision| decision.shadow_admitted)
+            .collect();
+        assert_eq!(shadow_admitted, [true, true, false, false, false]);
+        assert_eq!(
+            limiter.stats(),
+            ShadowStats {
+                requests: 5,
+                primary_rejected: 1,
+                shadow_rejected: 3,
+                disagreements: 2,
+            }
+        );
+
+        // The shadow was driven by every request, so its state reflects them all
+        assert_eq!(limiter.shadow().available_tokens(), 0);
+        assert_eq!(limiter.available_tokens(), 0);
+    }
+}
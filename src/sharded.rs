@@ -0,0 +1,312 @@
+//! Spreading one budget over several limiters to avoid contention.
+//!
+//! On many-core machines every acquire from a single bucket lands on the same atomic,
+//! whose cache line then bounces between cores and sockets. [`ShardedLimiter`] splits
+//! the budget over several shards and sends each acquire to the shard picked by a
+//! pluggable selector, so threads pinned to a core or NUMA node keep hitting a
+//! core-local atomic.
+//!
+//! This module is only available with the `std` feature.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{
+    composite::AnyOf,
+    error::{RateLimitError, Result},
+    outcome::saturate_u32,
+    traits::{RateLimiter, Wrapper},
+};
+
+/// Returns a shard index that is fixed for the calling thread.
+///
+/// Threads are numbered round-robin the first time they call this, so consecutive
+/// threads land on consecutive shards. This is the default selector of
+/// [`ShardedLimiter`]; it needs no platform support but knows nothing about where the
+/// thread runs.
+pub fn thread_shard() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    std::thread_local! {
+        static SHARD: usize = NEXT.fetch_add(1, Ordering::Relaxed);
+    }
+    SHARD.with(|shard| *shard)
+}
+
+/// A limiter whose budget is split over several shards.
+///
+/// Each acquire goes to the shard returned by the selector, modulo the number of shards.
+/// Only if that shard rejects are the others tried, in order, so budget left on idle
+/// shards is not stranded. A single request must fit in one shard: it is never split
+/// across shards.
+///
+/// The default selector is [`thread_shard`]. For NUMA-aware placement, inject one
+/// with [`with_shard_selector`](Self::with_shard_selector) that returns the executing
+/// CPU or node, for example from `sched_getcpu` on Linux.
+///
+/// # Accounting across shards
+///
+/// As a [`RateLimiter`], the capacity, available tokens and rate are the sums over all
+/// shards, and the wait for tokens is the shortest wait of any shard. The shards are
+/// read one after another, not atomically, so under concurrent use the sum of
+/// `available_tokens` is a best-effort figure that may never have held at any single
+/// instant. Since requests are not split, a request for up to that many tokens can
+/// still be rejected when the tokens are spread over several shards.
+///
+/// For the same reason, the capacity is not the largest request the limiter admits.
+/// A request for more tokens than any one shard holds is rejected with a wait of 0,
+/// as it can never succeed, and
+/// [`time_until_available_ms`](RateLimiter::time_until_available_ms) returns `None`
+/// for it.
+///
+/// # Examples
+///
+/// ```
+/// use bucketboss::{RateLimiter, ShardedLimiter, TokenBucket};
+///
+/// // 1000 tokens per second in total, split over 4 shards
+/// let limiter = ShardedLimiter::new(4, |_| TokenBucket::new(250, 250.0)).unwrap();
+///
+/// assert_eq!(limiter.capacity(), 1000);
+/// assert!(limiter.try_acquire(10).is_ok());
+/// ```
+#[derive(Debug)]
+pub struct ShardedLimiter<L> {
+    shards: Vec<L>,
+    selector: fn() -> usize,
+}
+
+impl<L> ShardedLimiter<L>
+where
+    L: RateLimiter,
+{
+    /// Creates a limiter with `shards` shards, building shard `i` with `factory(i)`.
+    ///
+    /// The factory decides each shard's share of the budget; usually every shard gets
+    /// the total capacity and rate divided by the number of shards.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Self)` if there is at least one shard
+    /// * `Err(RateLimitError::InvalidConfiguration)` if `shards` is zero
+    pub fn new(shards: usize, factory: impl FnMut(usize) -> L) -> Result<Self> {
+        if shards == 0 {
+            return Err(RateLimitError::invalid_config(
+                "at least one shard is required",
+            ));
+        }
+
+        Ok(Self {
+            shards: (0..shards).map(factory).collect(),
+            selector: thread_shard,
+        })
+    }
+
+    /// Picks shards with `selector` instead of [`thread_shard`].
+    ///
+    /// The selector is called on every acquire, so it should be cheap. Its result may
+    /// exceed the number of shards; it is reduced modulo that number.
+    pub fn with_shard_selector(mut self, selector: fn() -> usize) -> Self {
+        self.selector = selector;
+        self
+    }
+
+    /// Returns the shards.
+    pub fn shards(&self) -> &[L] {
+        &self.shards
+    }
+
+    /// Returns the index of the shard the calling thread acquires from first.
+    pub fn current_shard(&self) -> usize {
+        (self.selector)()
+            .checked_rem(self.shards.len())
+            .unwrap_or(0)
+    }
+
+    /// Returns the first admission by `acquire`, or the combined rejection of a
+    /// request for `tokens` if every shard rejects it.
+    fn acquire_with(&self, tokens: u32, acquire: impl Fn(&L) -> Result<()>) -> Result<()> {
+        // Start at the local shard and only then move on to the others
+        let (others, local) = self.shards.split_at(self.current_shard());
+        AnyOf::acquire_first(local.iter().chain(others), tokens, acquire)
+    }
+}
+
+impl<L> RateLimiter for ShardedLimiter<L>
+where
+    L: RateLimiter,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        self.acquire_with(tokens, |shard| shard.try_acquire(tokens))
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        self.acquire_with(saturate_u32(tokens), |shard| shard.try_acquire_u64(tokens))
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        self.acquire_with(tokens, |shard| shard.peek_acquire(tokens))
+    }
+
+    fn available_tokens(&self) -> u32 {
+        AnyOf(&self.shards).available_tokens()
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        AnyOf(&self.shards).available_tokens_u64()
+    }
+
+    fn capacity(&self) -> u32 {
+        AnyOf(&self.shards).capacity()
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        AnyOf(&self.shards).capacity_u64()
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        AnyOf(&self.shards).rate_per_second()
+    }
+
+    fn effective_rate_per_second(&self) -> f64 {
+        AnyOf(&self.shards).effective_rate_per_second()
+    }
+
+    fn resolution_warning(&self) -> Option<&'static str> {
+        AnyOf(&self.shards).resolution_warning()
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        AnyOf(&self.shards).time_until_next_token_ms()
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        AnyOf(&self.shards).time_until_available_ms(tokens)
+    }
+}
+
+/// The wrapped value is the set of shards; an emptied set admits nothing.
+impl<L> Wrapper for ShardedLimiter<L> {
+    type Inner = Vec<L>;
+
+    fn get_ref(&self) -> &Vec<L> {
+        &self.shards
+    }
+
+    fn get_mut(&mut self) -> &mut Vec<L> {
+        &mut self.shards
+    }
+
+    fn into_inner(self) -> Vec<L> {
+        self.shards
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, LabeledLimiter, TokenBucket};
+
+    fn always_shard_two() -> usize {
+        2
+    }
+
+    #[test]
+    fn test_acquires_from_the_selected_shard_first() {
+        let clock = MockClock::new(0);
+        let limiter = ShardedLimiter::new(3, |_| TokenBucket::with_clock(5, 1.0, clock.clone()))
+            .unwrap()
+            .with_shard_selector(always_shard_two);
+        assert_eq!(limiter.current_shard(), 2);
+        assert_eq!(limiter.capacity(), 15);
+        assert_eq!(limiter.rate_per_second(), 3.0);
+
+        assert!(limiter.try_acquire(4).is_ok());
+        let available: Vec<_> = limiter
+            .shards()
+            .iter()
+            .map(|shard| shard.available_tokens())
+            .collect();
+        assert_eq!(available, [5, 5, 1]);
+
+        // The local shard cannot serve this one, so it spills over to shard 0
+        assert!(limiter.try_acquire(3).is_ok());
+        assert_eq!(limiter.shards()[0].available_tokens(), 2);
+        assert_eq!(limiter.available_tokens(), 8);
+    }
+
+    #[test]
+    fn test_requests_are_not_split_across_shards() {
+        let clock = MockClock::new(0);
+        let limiter =
+            ShardedLimiter::new(2, |_| TokenBucket::with_clock(4, 1.0, clock.clone())).unwrap();
+        assert!(limiter.try_acquire(3).is_ok());
+        assert!(limiter.try_acquire(3).is_ok());
+
+        // Two tokens are available in total, but only one on each shard
+        assert_eq!(limiter.available_tokens(), 2);
+        let err = limiter.try_acquire(2).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(1000));
+        assert_eq!(limiter.time_until_available_ms(2), Some(1000));
+        assert!(limiter.try_acquire(1).is_ok());
+    }
+
+    #[test]
+    fn test_request_larger_than_a_shard_never_succeeds() {
+        let clock = MockClock::new(0);
+        let limiter =
+            ShardedLimiter::new(2, |_| TokenBucket::with_clock(4, 1.0, clock.clone())).unwrap();
+        assert_eq!(limiter.capacity(), 8);
+
+        let err = limiter.try_acquire(5).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(0));
+        assert_eq!(limiter.time_until_available_ms(5), None);
+    }
+
+    #[test]
+    fn test_rejection_keeps_the_shard_label() {
+        let clock = MockClock::new(0);
+        let limiter = ShardedLimiter::new(2, |_| {
+            LabeledLimiter::new(TokenBucket::with_clock(1, 1.0, clock.clone()), "shard")
+        })
+        .unwrap();
+        assert!(limiter.try_acquire(1).is_ok());
+        assert!(limiter.try_acquire(1).is_ok());
+        assert_eq!(
+            limiter.try_acquire(1).unwrap_err().rejected_by(),
+            Some("shard")
+        );
+    }
+
+    #[test]
+    fn test_unwrap_shards() {
+        let clock = MockClock::new(0);
+        let mut limiter = ShardedLimiter::new(2, |shard| {
+            let label = if shard == 0 { "first" } else { "second" };
+            LabeledLimiter::new(TokenBucket::with_clock(2, 1.0, clock.clone()), label)
+        })
+        .unwrap()
+        .with_shard_selector(|| 1);
+        assert!(limiter.try_acquire(2).is_ok());
+        assert_eq!(limiter.get_ref()[1].get_ref().available_tokens(), 0);
+
+        // An emptied set admits nothing
+        let shards = core::mem::take(limiter.get_mut());
+        assert_eq!(limiter.current_shard(), 0);
+        assert_eq!(
+            limiter.try_acquire(1).unwrap_err().retry_after_ms(),
+            Some(0)
+        );
+
+        let labels: Vec<_> = shards.iter().map(|shard| shard.label()).collect();
+        assert_eq!(labels, ["first", "second"]);
+        assert_eq!(shards[0].available_tokens(), 2);
+    }
+
+    #[test]
+    fn test_thread_shard_is_stable_per_thread() {
+        let shard = thread_shard();
+        assert_eq!(thread_shard(), shard);
+        let other = std::thread::spawn(thread_shard).join().unwrap();
+        assert_ne!(other, shard);
+        assert!(ShardedLimiter::<TokenBucket>::new(0, |_| TokenBucket::new(1, 1.0)).is_err());
+    }
+}
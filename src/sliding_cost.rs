@@ -0,0 +1,242 @@
+//! Sliding window limiting by summed request cost.
+//!
+//! Cost-based API budgets ("1000 points per minute") charge each request a variable
+//! number of points. [`SlidingCostLimiter`] keeps a log of `(timestamp, cost)` entries
+//! and admits a request only if the cost of the entries still inside the window plus
+//! the new cost stays within the budget.
+//!
+//! This module is only available with the `alloc` and `std` features.
+
+use alloc::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::{RateLimitError, Result},
+    traits::RateLimiter,
+    validate,
+};
+
+#[derive(Debug, Default)]
+struct CostLog {
+    /// Admitted requests as `(timestamp, cost)`, oldest first.
+    entries: VecDeque<(u64, u32)>,
+    /// The summed cost of `entries`.
+    total: u64,
+}
+
+impl CostLog {
+    /// Drops entries that have left the window as of `now`.
+    fn evict(&mut self, now: u64, window_ms: u64) {
+        while let Some(&(timestamp, cost)) = self.entries.front() {
+            if timestamp.saturating_add(window_ms) > now {
+                break;
+            }
+            let _ = self.entries.pop_front();
+            self.total -= cost as u64;
+        }
+    }
+
+    /// Returns how long until `cost` more fits within `budget`, as of `now`.
+    fn wait_ms(&self, now: u64, window_ms: u64, budget: u64, cost: u64) -> u64 {
+        let mut total = self.total;
+        for &(timestamp, entry_cost) in &self.entries {
+            if total + cost <= budget {
+                break;
+            }
+            total -= entry_cost as u64;
+            if total + cost <= budget {
+                return timestamp.saturating_add(window_ms).saturating_sub(now);
+            }
+        }
+        0
+    }
+}
+
+/// A sliding window limiter that budgets the summed cost of requests.
+///
+/// The `tokens` passed to `try_acquire` are the request's cost. A request is admitted
+/// if the cost of all requests admitted within the last `window_ms` milliseconds, plus
+/// its own cost, is at most the budget. `available_tokens` reports the remaining
+/// budget and `capacity` reports the full budget.
+///
+/// Every admitted request is logged until it leaves the window, so memory use grows
+/// with the number of requests per window.
+#[derive(Debug)]
+pub struct SlidingCostLimiter<C = SystemClock> {
+    clock: C,
+    budget: u32,
+    window_ms: u64,
+    log: Mutex<CostLog>,
+}
+
+impl SlidingCostLimiter<SystemClock> {
+    /// Creates a new limiter allowing `budget` cost per sliding `window_ms` milliseconds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `budget` or `window_ms` is 0. With the `no-panic` feature this only
+    /// happens in debug builds; release builds clamp both to 1 instead.
+    pub fn new(budget: u32, window_ms: u64) -> Self {
+        Self::with_clock(budget, window_ms, SystemClock)
+    }
+}
+
+impl<C> SlidingCostLimiter<C>
+where
+    C: Clock,
+{
+    /// Creates a new limiter with the specified clock.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`SlidingCostLimiter::new`].
+    pub fn with_clock(budget: u32, window_ms: u64, clock: C) -> Self {
+        let budget = validate::capacity(budget as u64, "budget must be greater than 0") as u32;
+        let window_ms = validate::capacity(window_ms, "window_ms must be greater than 0");
+
+        Self {
+            clock,
+            budget,
+            window_ms,
+            log: Mutex::new(CostLog::default()),
+        }
+    }
+
+    /// Returns the length of the sliding window in milliseconds.
+    pub fn window_ms(&self) -> u64 {
+        self.window_ms
+    }
+
+    /// Returns the summed cost of the requests currently inside the window.
+    pub fn used(&self) -> u64 {
+        let mut log = self.log.lock().unwrap();
+        log.evict(self.clock.now(), self.window_ms);
+        log.total
+    }
+}
+
+impl<C> RateLimiter for SlidingCostLimiter<C>
+where
+    C: Clock,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        if tokens > self.budget {
+            return Err(RateLimitError::rate_limit_exceeded(
+                tokens,
+                self.available_tokens(),
+                0, // The cost can never fit in the budget
+            ));
+        }
+
+        let now = self.clock.now();
+        let mut log = self.log.lock().unwrap();
+        log.evict(now, self.window_ms);
+
+        let budget = self.budget as u64;
+        if log.total + tokens as u64 > budget {
+            return Err(RateLimitError::rate_limit_exceeded(
+                tokens,
+                (budget - log.total) as u32,
+                log.wait_ms(now, self.window_ms, budget, tokens as u64),
+            ));
+        }
+
+        if tokens > 0 {
+            log.entries.push_back((now, tokens));
+            log.total += tokens as u64;
+        }
+        Ok(())
+    }
+
+    fn available_tokens(&self) -> u32 {
+        self.budget - self.used() as u32
+    }
+
+    fn capacity(&self) -> u32 {
+        self.budget
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        self.budget as f64 * 1000.0 / self.window_ms as f64
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        let now = self.clock.now();
+        let mut log = self.log.lock().unwrap();
+        log.evict(now, self.window_ms);
+        if log.total < self.budget as u64 {
+            return None;
+        }
+        log.entries
+            .front()
+            .map(|&(timestamp, _)| timestamp.saturating_add(self.window_ms) - now)
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        if tokens > self.budget {
+            return None;
+        }
+
+        let now = self.clock.now();
+        let mut log = self.log.lock().unwrap();
+        log.evict(now, self.window_ms);
+        Some(log.wait_ms(now, self.window_ms, self.budget as u64, tokens as u64))
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        let now = self.clock.now();
+        self.time_until_available_ms(tokens)
+            .map(|wait_ms| now.saturating_add(wait_ms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    #[test]
+    fn test_mixed_costs_expire_out_of_window() {
+        let clock = MockClock::new(0);
+        let limiter = SlidingCostLimiter::with_clock(1000, 60_000, clock.clone());
+
+        assert!(limiter.try_acquire(400).is_ok());
+        clock.advance(10_000);
+        assert!(limiter.try_acquire(100).is_ok());
+        clock.advance(10_000);
+        assert!(limiter.try_acquire(450).is_ok());
+        assert_eq!(limiter.available_tokens(), 50);
+
+        // 300 points only fit once the first 400-point request expires at t=60s
+        let err = limiter.try_acquire(300).unwrap_err();
+        assert_eq!(err, RateLimitError::rate_limit_exceeded(300, 50, 40_000));
+        assert_eq!(limiter.time_until_available_ms(300), Some(40_000));
+        assert_eq!(limiter.retry_at(300), Some(60_000));
+
+        // Small requests still fit in the remaining budget
+        assert!(limiter.try_acquire(50).is_ok());
+        assert_eq!(limiter.available_tokens(), 0);
+        assert_eq!(limiter.time_until_next_token_ms(), Some(40_000));
+
+        clock.set(60_000);
+        assert_eq!(limiter.available_tokens(), 400);
+        assert!(limiter.try_acquire(300).is_ok());
+
+        // Expiring the 100-point request frees exactly enough for 200 more
+        assert_eq!(limiter.time_until_available_ms(200), Some(10_000));
+        clock.set(70_000);
+        assert!(limiter.try_acquire(200).is_ok());
+        assert_eq!(limiter.used(), 1000);
+    }
+
+    #[test]
+    fn test_cost_above_budget_never_fits() {
+        let clock = MockClock::new(0);
+        let limiter = SlidingCostLimiter::with_clock(100, 1000, clock);
+        let err = limiter.try_acquire(101).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(0));
+        assert_eq!(limiter.time_until_available_ms(101), None);
+        assert_eq!(limiter.rate_per_second(), 100.0);
+    }
+}
@@ -0,0 +1,329 @@
+//! Approximate sliding window limiting from two fixed-window counters.
+//!
+//! `SlidingWindowLog`, available with the `alloc` feature, is exact but remembers
+//! every token in the window. [`SlidingWindowCounter`] only counts the tokens admitted
+//! in the current and the previous fixed window, and estimates the sliding window's
+//! count by weighting the previous window by how much of it the sliding window still
+//! overlaps:
+//!
+//! ```text
+//! estimated = previous_count * overlap_fraction + current_count
+//! ```
+//!
+//! This assumes the previous window's tokens were spread evenly over it. The estimate
+//! is never more than the limit, but an uneven previous window can let up to twice the
+//! limit through within one window length in the worst case.
+//!
+//! This module is only available with the `std` feature.
+
+use std::sync::Mutex;
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::{RateLimitError, Result},
+    traits::{RateLimiter, ReconfigurableRateLimiter},
+    validate,
+};
+
+#[derive(Debug)]
+struct Windows {
+    limit: u32,
+    window_ms: u64,
+    /// When the current fixed window started.
+    start: u64,
+    /// Tokens admitted in the previous fixed window.
+    previous: u64,
+    /// Tokens admitted in the current fixed window.
+    current: u64,
+}
+
+impl Windows {
+    /// Rolls the fixed windows forward to the one containing `now`.
+    fn advance(&mut self, now: u64) {
+        let start = now - now % self.window_ms;
+        if start > self.start {
+            self.previous = if start - self.start == self.window_ms {
+                self.current
+            } else {
+                0
+            };
+            self.current = 0;
+            self.start = start;
+        }
+    }
+
+    /// Returns the estimated count of the sliding window ending at `now`.
+    fn estimate(&self, now: u64) -> f64 {
+        let overlap = self
+            .window_ms
+            .saturating_sub(now.saturating_sub(self.start));
+        self.previous as f64 * overlap as f64 / self.window_ms as f64 + self.current as f64
+    }
+
+    /// Returns how long until the estimate leaves room for `tokens`, as of `now`.
+    ///
+    /// `tokens` must be at most the limit.
+    fn wait_ms(&self, now: u64, tokens: u64) -> u64 {
+        let limit = self.limit as u64;
+        let window = self.window_ms as f64;
+        let elapsed = now.saturating_sub(self.start).min(self.window_ms);
+
+        if self.current + tokens <= limit {
+            // The previous window's weight falls linearly until the request fits
+            let room = (limit - self.current - tokens) as f64;
+            let overlap_needed = room * window / self.previous as f64;
+            let wait = (window - elapsed as f64 - overlap_needed).max(0.0);
+            return wait.ceil() as u64;
+        }
+
+        // The request only fits in the next window, once enough of this one's weight
+        // has slid out
+        let until_next = self.window_ms - elapsed;
+        let slide = window * (1.0 - (limit - tokens) as f64 / self.current as f64);
+        until_next + slide.ceil() as u64
+    }
+}
+
+/// A sliding window limiter estimating the window's count from two fixed windows.
+///
+/// The tokens admitted within each fixed window of `window_ms` milliseconds, aligned
+/// to multiples of `window_ms`, are counted. A request is admitted if the estimated
+/// count of the trailing window plus the request stays within the limit; see the
+/// [module documentation](self) for the estimate. State is two counters regardless of
+/// the limit or traffic.
+///
+/// Rejections report the wait until the estimate, as the previous window's weight
+/// slides out, leaves room for the request, assuming no other requests are admitted
+/// meanwhile. Requests for more than the limit are rejected outright.
+///
+/// `update_config` takes the limit as the capacity and derives the window from the
+/// rate, `capacity / tokens_per_second` seconds, keeping the current counts.
+///
+/// # Examples
+///
+/// ```
+/// use bucketboss::{clock::MockClock, RateLimiter, SlidingWindowCounter};
+///
+/// let clock = MockClock::new(0);
+/// let limiter = SlidingWindowCounter::with_clock(10, 1000, clock.clone());
+/// assert!(limiter.try_acquire(10).is_ok());
+///
+/// // Halfway into the next window, half of the previous one still counts
+/// clock.set(1500);
+/// assert_eq!(limiter.available_tokens(), 5);
+/// ```
+#[derive(Debug)]
+pub struct SlidingWindowCounter<C = SystemClock> {
+    clock: C,
+    windows: Mutex<Windows>,
+}
+
+impl SlidingWindowCounter<SystemClock> {
+    /// Creates a new limiter admitting about `limit` tokens per sliding `window_ms`
+    /// milliseconds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` or `window_ms` is 0. With the `no-panic` feature this only
+    /// happens in debug builds; release builds clamp both to 1 instead.
+    pub fn new(limit: u32, window_ms: u64) -> Self {
+        Self::with_clock(limit, window_ms, SystemClock)
+    }
+}
+
+impl<C> SlidingWindowCounter<C>
+where
+    C: Clock,
+{
+    /// Creates a new limiter with the specified clock.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`SlidingWindowCounter::new`].
+    pub fn with_clock(limit: u32, window_ms: u64, clock: C) -> Self {
+        let limit = validate::capacity(limit as u64, "limit must be greater than 0") as u32;
+        let window_ms = validate::capacity(window_ms, "window_ms must be greater than 0");
+        let now = clock.now();
+
+        Self {
+            clock,
+            windows: Mutex::new(Windows {
+                limit,
+                window_ms,
+                start: now - now % window_ms,
+                previous: 0,
+                current: 0,
+            }),
+        }
+    }
+
+    /// Returns the length of the window in milliseconds.
+    pub fn window_ms(&self) -> u64 {
+        self.windows.lock().unwrap().window_ms
+    }
+
+    /// Returns the estimated number of tokens admitted within the trailing window.
+    pub fn estimated(&self) -> f64 {
+        let now = self.clock.now();
+        let mut windows = self.windows.lock().unwrap();
+        windows.advance(now);
+        windows.estimate(now)
+    }
+}
+
+impl<C> RateLimiter for SlidingWindowCounter<C>
+where
+    C: Clock,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        let now = self.clock.now();
+        let mut windows = self.windows.lock().unwrap();
+        windows.advance(now);
+
+        let estimated = windows.estimate(now);
+        let limit = windows.limit;
+        if estimated + tokens as f64 > limit as f64 {
+            let available = (limit as f64 - estimated).max(0.0) as u32;
+            let retry_after_ms = if tokens > limit {
+                0 // The request can never fit in the window
+            } else {
+                windows.wait_ms(now, tokens as u64)
+            };
+            return Err(RateLimitError::rate_limit_exceeded(
+                tokens,
+                available,
+                retry_after_ms,
+            ));
+        }
+
+        windows.current += tokens as u64;
+        Ok(())
+    }
+
+    fn available_tokens(&self) -> u32 {
+        let now = self.clock.now();
+        let mut windows = self.windows.lock().unwrap();
+        windows.advance(now);
+        (windows.limit as f64 - windows.estimate(now)).max(0.0) as u32
+    }
+
+    fn capacity(&self) -> u32 {
+        self.windows.lock().unwrap().limit
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        let windows = self.windows.lock().unwrap();
+        windows.limit as f64 * 1000.0 / windows.window_ms as f64
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        match self.time_until_available_ms(1) {
+            Some(0) | None => None,
+            wait => wait,
+        }
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        let now = self.clock.now();
+        let mut windows = self.windows.lock().unwrap();
+        if tokens > windows.limit {
+            return None;
+        }
+        windows.advance(now);
+        if windows.estimate(now) + tokens as f64 <= windows.limit as f64 {
+            return Some(0);
+        }
+        Some(windows.wait_ms(now, tokens as u64))
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        let now = self.clock.now();
+        self.time_until_available_ms(tokens)
+            .map(|wait_ms| now.saturating_add(wait_ms))
+    }
+}
+
+impl<C> ReconfigurableRateLimiter for SlidingWindowCounter<C>
+where
+    C: Clock,
+{
+    fn update_config(&self, capacity: u32, tokens_per_second: f64) -> Result<()> {
+        if capacity == 0 {
+            return Err(RateLimitError::invalid_config(
+                "capacity must be greater than 0",
+            ));
+        }
+        validate::try_rate(tokens_per_second, "tokens_per_second must be positive")?;
+
+        let window_ms = (capacity as f64 * 1000.0 / tokens_per_second).round() as u64;
+        let now = self.clock.now();
+        let mut windows = self.windows.lock().unwrap();
+        windows.advance(now);
+        windows.limit = capacity;
+        if window_ms.max(1) != windows.window_ms {
+            // The counts stay with the window they were admitted in, realigned to the
+            // new length
+            windows.window_ms = window_ms.max(1);
+            windows.start = now - now % windows.window_ms;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    #[test]
+    fn test_previous_window_is_weighted_by_overlap() {
+        let clock = MockClock::new(0);
+        let limiter = SlidingWindowCounter::with_clock(10, 1000, clock.clone());
+        assert!(limiter.try_acquire(8).is_ok());
+        clock.set(900);
+        assert!(limiter.try_acquire(2).is_ok());
+        // The full window has to wait for the next one, until 1 of its 10 tokens has
+        // slid out at t=1100
+        let err = limiter.try_acquire(1).unwrap_err();
+        assert_eq!(err, RateLimitError::rate_limit_exceeded(1, 0, 200));
+
+        // At t=1250 three quarters of the previous 10 tokens still count
+        clock.set(1250);
+        assert_eq!(limiter.estimated(), 7.5);
+        assert!(limiter.try_acquire(2).is_ok());
+        assert_eq!(limiter.available_tokens(), 0);
+
+        // Two more fit once the previous window's weight drops to 6, at t=1400
+        let err = limiter.try_acquire(2).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(150));
+        assert_eq!(limiter.retry_at(2), Some(1400));
+        clock.set(1400);
+        assert!(limiter.try_acquire(2).is_ok());
+        assert_eq!(limiter.estimated(), 10.0);
+    }
+
+    #[test]
+    fn test_idle_window_resets_and_reconfigure() {
+        let clock = MockClock::new(0);
+        let limiter = SlidingWindowCounter::with_clock(5, 100, clock.clone());
+        assert!(limiter.try_acquire(5).is_ok());
+        assert_eq!(limiter.time_until_available_ms(6), None);
+        assert_eq!(
+            limiter.try_acquire(6).unwrap_err().retry_after_ms(),
+            Some(0)
+        );
+
+        // A whole window without traffic leaves nothing to carry over
+        clock.set(250);
+        assert_eq!(limiter.estimated(), 0.0);
+
+        // 20 tokens per 2 seconds
+        limiter.update_config(20, 10.0).unwrap();
+        assert_eq!(limiter.window_ms(), 2000);
+        assert_eq!(limiter.capacity(), 20);
+        assert_eq!(limiter.rate_per_second(), 10.0);
+        assert!(limiter.try_acquire(20).is_ok());
+        assert!(limiter.update_config(0, 10.0).is_err());
+        assert!(limiter.update_config(5, f64::NAN).is_err());
+    }
+}
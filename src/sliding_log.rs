@@ -0,0 +1,237 @@
+//! Exact sliding window limiting with a log of admission timestamps.
+//!
+//! A fixed window lets a client spend its limit at the end of one window and again at
+//! the start of the next, so up to twice the limit can pass in a short span.
+//! [`SlidingWindowLog`] instead remembers when each of the last `limit` tokens was
+//! admitted and only admits a request if fewer than `limit` tokens fall within the
+//! trailing window.
+//!
+//! This module is only available with the `alloc` and `std` features.
+
+use alloc::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::{RateLimitError, Result},
+    traits::RateLimiter,
+    validate,
+};
+
+/// A sliding window limiter admitting at most `limit` tokens per window.
+///
+/// Every admitted token is logged with its timestamp until it leaves the window, in a
+/// ring buffer that never holds more than `limit` entries. Memory therefore grows with
+/// the limit rather than with traffic, but at 8 bytes per token: a limit of a million
+/// tokens per window can take 8 MB. For large limits, [`SlidingCostLimiter`] logs one
+/// entry per request instead, and a token bucket keeps constant state.
+///
+/// Rejections report the wait until enough of the oldest tokens leave the window for
+/// the request to fit. Requests for more than `limit` tokens are rejected outright.
+///
+/// [`SlidingCostLimiter`]: crate::SlidingCostLimiter
+///
+/// # Examples
+///
+/// ```
+/// use bucketboss::{clock::MockClock, RateLimiter, SlidingWindowLog};
+///
+/// let clock = MockClock::new(0);
+/// let limiter = SlidingWindowLog::with_clock(2, 1000, clock.clone());
+/// assert!(limiter.try_acquire(1).is_ok());
+/// clock.advance(600);
+/// assert!(limiter.try_acquire(1).is_ok());
+///
+/// // The first token leaves the window at t=1000
+/// let err = limiter.try_acquire(1).unwrap_err();
+/// assert_eq!(err.retry_after_ms(), Some(400));
+/// ```
+#[derive(Debug)]
+pub struct SlidingWindowLog<C = SystemClock> {
+    clock: C,
+    limit: u32,
+    window_ms: u64,
+    /// Admission timestamps, one per token, oldest first.
+    log: Mutex<VecDeque<u64>>,
+}
+
+impl SlidingWindowLog<SystemClock> {
+    /// Creates a new limiter admitting `limit` tokens per sliding `window_ms`
+    /// milliseconds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` or `window_ms` is 0. With the `no-panic` feature this only
+    /// happens in debug builds; release builds clamp both to 1 instead.
+    pub fn new(limit: u32, window_ms: u64) -> Self {
+        Self::with_clock(limit, window_ms, SystemClock)
+    }
+}
+
+impl<C> SlidingWindowLog<C>
+where
+    C: Clock,
+{
+    /// Creates a new limiter with the specified clock.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`SlidingWindowLog::new`].
+    pub fn with_clock(limit: u32, window_ms: u64, clock: C) -> Self {
+        let limit = validate::capacity(limit as u64, "limit must be greater than 0") as u32;
+        let window_ms = validate::capacity(window_ms, "window_ms must be greater than 0");
+
+        Self {
+            clock,
+            limit,
+            window_ms,
+            log: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Returns the length of the sliding window in milliseconds.
+    pub fn window_ms(&self) -> u64 {
+        self.window_ms
+    }
+
+    /// Returns the number of tokens admitted within the current window.
+    pub fn used(&self) -> u32 {
+        let mut log = self.log.lock().unwrap();
+        self.evict(&mut log, self.clock.now());
+        log.len() as u32
+    }
+
+    /// Drops the timestamps that have left the window as of `now`.
+    fn evict(&self, log: &mut VecDeque<u64>, now: u64) {
+        while let Some(&timestamp) = log.front() {
+            if timestamp.saturating_add(self.window_ms) > now {
+                break;
+            }
+            let _ = log.pop_front();
+        }
+    }
+
+    /// Returns how long until `tokens` more fit in the window, as of `now`.
+    ///
+    /// `log` must already be evicted and `tokens` at most the limit.
+    fn wait_ms(&self, log: &VecDeque<u64>, now: u64, tokens: u32) -> u64 {
+        let excess = (log.len() + tokens as usize).saturating_sub(self.limit as usize);
+        match excess {
+            0 => 0,
+            // The request fits once the `excess` oldest tokens have left the window
+            excess => log[excess - 1]
+                .saturating_add(self.window_ms)
+                .saturating_sub(now),
+        }
+    }
+}
+
+impl<C> RateLimiter for SlidingWindowLog<C>
+where
+    C: Clock,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        if tokens > self.limit {
+            return Err(RateLimitError::rate_limit_exceeded(
+                tokens,
+                self.available_tokens(),
+                0, // The request can never fit in the window
+            ));
+        }
+
+        let now = self.clock.now();
+        let mut log = self.log.lock().unwrap();
+        self.evict(&mut log, now);
+
+        let used = log.len() as u32;
+        if used + tokens > self.limit {
+            return Err(RateLimitError::rate_limit_exceeded(
+                tokens,
+                self.limit - used,
+                self.wait_ms(&log, now, tokens),
+            ));
+        }
+
+        log.extend((0..tokens).map(|_| now));
+        Ok(())
+    }
+
+    fn available_tokens(&self) -> u32 {
+        self.limit - self.used()
+    }
+
+    fn capacity(&self) -> u32 {
+        self.limit
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        self.limit as f64 * 1000.0 / self.window_ms as f64
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        match self.time_until_available_ms(1) {
+            Some(0) | None => None,
+            wait => wait,
+        }
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        if tokens > self.limit {
+            return None;
+        }
+
+        let now = self.clock.now();
+        let mut log = self.log.lock().unwrap();
+        self.evict(&mut log, now);
+        Some(self.wait_ms(&log, now, tokens))
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        let now = self.clock.now();
+        self.time_until_available_ms(tokens)
+            .map(|wait_ms| now.saturating_add(wait_ms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    #[test]
+    fn test_no_double_burst_at_window_boundary() {
+        let clock = MockClock::new(0);
+        let limiter = SlidingWindowLog::with_clock(10, 1000, clock.clone());
+
+        // The whole limit is spent just before a fixed window would roll over
+        clock.set(900);
+        assert!(limiter.try_acquire(10).is_ok());
+        clock.set(1000);
+        let err = limiter.try_acquire(1).unwrap_err();
+        assert_eq!(err, RateLimitError::rate_limit_exceeded(1, 0, 900));
+
+        // Tokens are logged individually, so a batch can be followed by smaller requests
+        clock.set(1900);
+        assert_eq!(limiter.available_tokens(), 10);
+        assert!(limiter.try_acquire(4).is_ok());
+        clock.set(2500);
+        assert!(limiter.try_acquire(6).is_ok());
+        assert_eq!(limiter.used(), 10);
+
+        // Three more fit once the batch of four from t=1900 leaves the window
+        assert_eq!(limiter.time_until_available_ms(3), Some(400));
+        assert_eq!(limiter.retry_at(3), Some(2900));
+        assert_eq!(limiter.time_until_next_token_ms(), Some(400));
+        assert!(limiter.log.lock().unwrap().len() <= 10);
+    }
+
+    #[test]
+    fn test_requests_above_limit_never_fit() {
+        let clock = MockClock::new(0);
+        let limiter = SlidingWindowLog::with_clock(5, 500, clock);
+        let err = limiter.try_acquire(6).unwrap_err();
+        assert_eq!(err, RateLimitError::rate_limit_exceeded(6, 5, 0));
+        assert_eq!(limiter.time_until_available_ms(6), None);
+        assert_eq!(limiter.rate_per_second(), 10.0);
+    }
+}
@@ -0,0 +1,250 @@
+//! Exact sliding window limiting without heap allocation.
+//!
+//! [`SlidingWindowConst`] remembers the timestamps of the last `N` admissions in a
+//! fixed-size ring buffer and admits a request only if fewer than `N` admissions fall
+//! within the trailing window. It uses nothing beyond `core`, which makes it suitable
+//! for microcontrollers without an allocator where a small, known number of requests
+//! per window must be enforced exactly.
+
+use core::array;
+use core::hint;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::{RateLimitError, Result},
+    traits::RateLimiter,
+    validate,
+};
+
+/// Releases the ring buffer lock when dropped.
+struct SpinGuard<'a>(&'a AtomicBool);
+
+impl Drop for SpinGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// A sliding window limiter admitting at most `N` tokens per window, without allocating.
+///
+/// Each admitted token occupies one slot of a `[u64; N]` ring buffer until it leaves
+/// the window. Requests for more than one token need that many expired slots and are
+/// rejected outright if they exceed `N`. `N` must be greater than 0.
+///
+/// Updates to the ring buffer are serialized by a spin lock, so the limiter is `Sync`
+/// without requiring `std`.
+#[derive(Debug)]
+pub struct SlidingWindowConst<const N: usize, C = SystemClock> {
+    clock: C,
+    window_ms: u64,
+    /// Admission timestamps plus one, oldest first from `head`; 0 marks an unused slot.
+    slots: [AtomicU64; N],
+    head: AtomicUsize,
+    locked: AtomicBool,
+}
+
+#[cfg(feature = "std")]
+impl<const N: usize> SlidingWindowConst<N, SystemClock> {
+    /// Creates a new limiter admitting `N` tokens per sliding `window_ms` milliseconds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window_ms` is 0. With the `no-panic` feature this only happens in
+    /// debug builds; release builds clamp the window to 1 ms instead.
+    pub fn new(window_ms: u64) -> Self {
+        Self::with_clock(window_ms, SystemClock)
+    }
+}
+
+impl<const N: usize, C> SlidingWindowConst<N, C>
+where
+    C: Clock,
+{
+    const NON_EMPTY: () = assert!(N > 0, "N must be greater than 0");
+
+    /// Creates a new limiter with the specified clock.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`SlidingWindowConst::new`].
+    pub fn with_clock(window_ms: u64, clock: C) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::NON_EMPTY;
+        let window_ms = validate::capacity(window_ms, "window_ms must be greater than 0");
+
+        Self {
+            clock,
+            window_ms,
+            slots: array::from_fn(|_| AtomicU64::new(0)),
+            head: AtomicUsize::new(0),
+            locked: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the length of the sliding window in milliseconds.
+    pub fn window_ms(&self) -> u64 {
+        self.window_ms
+    }
+
+    fn lock(&self) -> SpinGuard<'_> {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            hint::spin_loop();
+        }
+        SpinGuard(&self.locked)
+    }
+
+    /// Returns the time at which the `index`-th oldest slot becomes free, or 0 if it is
+    /// unused.
+    fn expiry(&self, head: usize, index: usize) -> u64 {
+        match self.slots[(head + index) % N].load(Ordering::Relaxed) {
+            0 => 0,
+            stamp => (stamp - 1).saturating_add(self.window_ms),
+        }
+    }
+
+    /// Returns the wait until `tokens` slots are free, or `None` if `tokens > N`.
+    fn wait_ms(&self, now: u64, tokens: usize) -> Option<u64> {
+        if tokens > N {
+            return None;
+        }
+        if tokens == 0 {
+            return Some(0);
+        }
+
+        let _guard = self.lock();
+        let head = self.head.load(Ordering::Relaxed);
+        Some(self.expiry(head, tokens - 1).saturating_sub(now))
+    }
+}
+
+impl<const N: usize, C> RateLimiter for SlidingWindowConst<N, C>
+where
+    C: Clock,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        let count = tokens as usize;
+        if count > N {
+            return Err(RateLimitError::rate_limit_exceeded(
+                tokens,
+                self.available_tokens(),
+                0, // The request can never fit in the window
+            ));
+        }
+        if count == 0 {
+            return Ok(());
+        }
+
+        let now = self.clock.now();
+        let _guard = self.lock();
+        let head = self.head.load(Ordering::Relaxed);
+
+        // Slots are ordered oldest first, so the request fits if its last slot is free
+        let expiry = self.expiry(head, count - 1);
+        if expiry > now {
+            let available = (0..N)
+                .take_while(|&index| self.expiry(head, index) <= now)
+                .count();
+            return Err(RateLimitError::rate_limit_exceeded(
+                tokens,
+                available as u32,
+                expiry - now,
+            ));
+        }
+
+        for index in 0..count {
+            self.slots[(head + index) % N].store(now.saturating_add(1), Ordering::Relaxed);
+        }
+        self.head.store((head + count) % N, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn available_tokens(&self) -> u32 {
+        let now = self.clock.now();
+        let _guard = self.lock();
+        let head = self.head.load(Ordering::Relaxed);
+        (0..N)
+            .take_while(|&index| self.expiry(head, index) <= now)
+            .count() as u32
+    }
+
+    fn capacity(&self) -> u32 {
+        N as u32
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        N as f64 * 1000.0 / self.window_ms as f64
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        match self.wait_ms(self.clock.now(), 1) {
+            Some(0) | None => None,
+            wait => wait,
+        }
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.wait_ms(self.clock.now(), tokens as usize)
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        let now = self.clock.now();
+        self.wait_ms(now, tokens as usize)
+            .map(|wait_ms| now.saturating_add(wait_ms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    #[test]
+    fn test_exact_sliding_window() {
+        let clock = MockClock::new(0);
+        let limiter = SlidingWindowConst::<3, _>::with_clock(1000, clock.clone());
+        assert_eq!(limiter.capacity(), 3);
+
+        assert!(limiter.try_acquire(1).is_ok());
+        clock.advance(400);
+        assert!(limiter.try_acquire(1).is_ok());
+        clock.advance(400);
+        assert!(limiter.try_acquire(1).is_ok());
+        assert_eq!(limiter.available_tokens(), 0);
+
+        // The first admission leaves the window at t=1000
+        let err = limiter.try_acquire(1).unwrap_err();
+        assert_eq!(err, RateLimitError::rate_limit_exceeded(1, 0, 200));
+        assert_eq!(limiter.time_until_next_token_ms(), Some(200));
+
+        clock.set(1000);
+        assert_eq!(limiter.available_tokens(), 1);
+        assert!(limiter.try_acquire(1).is_ok());
+        assert!(limiter.try_acquire(1).is_err());
+
+        // Two slots are free once the admissions from t=400 and t=800 expire
+        assert_eq!(limiter.time_until_available_ms(2), Some(800));
+        assert_eq!(limiter.retry_at(2), Some(1800));
+        clock.set(1800);
+        assert!(limiter.try_acquire(2).is_ok());
+        assert_eq!(limiter.available_tokens(), 0);
+    }
+
+    #[test]
+    fn test_batches_larger_than_n_reject() {
+        let clock = MockClock::new(0);
+        let limiter = SlidingWindowConst::<4, _>::with_clock(100, clock);
+
+        let err = limiter.try_acquire(5).unwrap_err();
+        assert_eq!(err, RateLimitError::rate_limit_exceeded(5, 4, 0));
+        assert_eq!(limiter.time_until_available_ms(5), None);
+
+        assert!(limiter.try_acquire(4).is_ok());
+        assert!(limiter.try_acquire(1).is_err());
+        assert_eq!(limiter.rate_per_second(), 40.0);
+    }
+}
@@ -0,0 +1,114 @@
+//! Point-in-time snapshots of a limiter's state.
+//!
+//! [`LimiterStatus`] gathers the figures a dashboard or test usually wants from a
+//! limiter into one value, so they can be compared, logged or collected in bulk.
+//! [`BucketState`] captures a bucket's configuration and fill level, so another process
+//! can rebuild or restore the bucket from it alone. [`LimiterStats`] counts the
+//! decisions made since the last metrics scrape.
+
+use crate::traits::RateLimiter;
+
+/// A snapshot of a limiter's state, as returned by [`status`](RateLimiter::status).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LimiterStatus {
+    /// The number of tokens available when the snapshot was taken.
+    pub available: u32,
+    /// The limiter's capacity.
+    pub capacity: u32,
+    /// The rate at which tokens are replenished, in tokens per second.
+    pub rate_per_second: f64,
+    /// The time until the next token becomes available, in milliseconds, or `None`
+    /// if tokens were available.
+    pub time_until_next_token_ms: Option<u64>,
+}
+
+impl LimiterStatus {
+    /// Takes a snapshot of `limiter`.
+    pub fn of<L>(limiter: &L) -> Self
+    where
+        L: RateLimiter + ?Sized,
+    {
+        Self {
+            available: limiter.available_tokens(),
+            capacity: limiter.capacity(),
+            rate_per_second: limiter.rate_per_second(),
+            time_until_next_token_ms: limiter.time_until_next_token_ms(),
+        }
+    }
+}
+
+/// A bucket's configuration and fill level, as returned by
+/// [`StatefulRateLimiter::snapshot`](crate::StatefulRateLimiter::snapshot).
+///
+/// The state can be restored in two ways. The buckets' `from_state` constructors, such
+/// as [`TokenBucket::from_state`](crate::TokenBucket::from_state), rebuild a bucket
+/// that continues from `last_update_ns`, which needs a clock with the same epoch, such
+/// as the [`SystemClock`](crate::clock::SystemClock) of another process.
+/// [`StatefulRateLimiter::restore`](crate::StatefulRateLimiter::restore) only uses the
+/// time between `last_update_ns` and `taken_at_ns`, so it works across clock epochs.
+/// Settings such as the zero-token policy are not included.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BucketState {
+    /// The bucket's capacity.
+    pub capacity: u64,
+    /// The rate at which tokens are replenished, or requests drained, per second.
+    pub tokens_per_second: f64,
+    /// For a token bucket, the tokens available as of `last_update_ns`; for a leaky
+    /// bucket, the requests still queued as of `last_update_ns`.
+    pub tokens: u64,
+    /// The clock time, in nanoseconds, from which the bucket continues to refill or
+    /// drain.
+    ///
+    /// The time since then that is too short to refill or drain a whole token is the
+    /// progress towards the next one, which nanoseconds keep exactly.
+    pub last_update_ns: u64,
+    /// The clock time, in nanoseconds, at which the state was taken.
+    pub taken_at_ns: u64,
+}
+
+/// Admission counts collected by an observer, as returned by
+/// [`AdmissionCounters::take_stats`](crate::AdmissionCounters::take_stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LimiterStats {
+    /// The number of requests admitted.
+    pub admitted: u64,
+    /// The number of requests rejected.
+    pub rejected: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{clock::MockClock, traits::RateLimiter, TokenBucket};
+
+    #[test]
+    fn test_status_snapshot() {
+        let bucket = TokenBucket::with_clock(4, 2.0, MockClock::new(0));
+        assert!(bucket.try_acquire(4).is_ok());
+
+        let status = bucket.status();
+        assert_eq!(status.available, 0);
+        assert_eq!(status.capacity, 4);
+        assert_eq!(status.rate_per_second, 2.0);
+        assert_eq!(status.time_until_next_token_ms, Some(500));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_bucket_state_serde_round_trip() {
+        use crate::traits::StatefulRateLimiter;
+
+        let bucket = TokenBucket::with_clock(4, 2.0, MockClock::new(0));
+        assert!(bucket.try_acquire(3).is_ok());
+
+        let json = serde_json::to_string(&bucket.snapshot()).unwrap();
+        assert_eq!(
+            json,
+            r#"{"capacity":4,"tokens_per_second":2.0,"tokens":1,"last_update_ns":0,"taken_at_ns":0}"#
+        );
+        let state = serde_json::from_str(&json).unwrap();
+        let restored = TokenBucket::from_state(state, MockClock::new(0)).unwrap();
+        assert_eq!(restored.available_tokens(), 1);
+    }
+}
@@ -0,0 +1,283 @@
+//! Utilities for testing code that uses rate limiters.
+//!
+//! Besides re-exporting [`MockClock`], this module provides [`Simulation`], which runs
+//! a scripted timeline of operations against a limiter and records every decision.
+//! Multi-step timing tests become a declarative list of events instead of interleaved
+//! clock advances and assertions. [`ClockDriver`] steps many limiters sharing one clock
+//! and reports the status of each after every step.
+
+pub use crate::clock::MockClock;
+
+use core::fmt;
+use std::sync::Arc;
+
+use crate::{clock::Clock, outcome::Outcome, status::LimiterStatus, traits::RateLimiter};
+
+/// An operation performed at a point of a simulated timeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Attempts to acquire the given number of tokens.
+    Acquire(u32),
+    /// Reads the number of available tokens without consuming any.
+    Available,
+}
+
+/// What the limiter decided for an [`Op`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// The outcome of an [`Op::Acquire`].
+    Acquired(Outcome),
+    /// The number of tokens reported for an [`Op::Available`].
+    Available(u32),
+}
+
+/// A record of one operation of a simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    /// The clock time, in milliseconds, at which the operation ran.
+    pub at_ms: u64,
+    /// The operation that ran.
+    pub op: Op,
+    /// The limiter's decision.
+    pub decision: Decision,
+}
+
+/// A deterministic scenario run against a limiter driven by a [`MockClock`].
+///
+/// Events are given as absolute clock times and run in time order; events at the same
+/// time run in the order they were added. The clock is set to each event's time before
+/// it runs, so the limiter must read its time from the same `MockClock`.
+///
+/// # Examples
+///
+/// ```
+/// use bucketboss::testing::{Decision, MockClock, Op, Simulation};
+/// use bucketboss::TokenBucket;
+///
+/// let clock = MockClock::new(0);
+/// let bucket = TokenBucket::with_clock(1, 10.0, clock.clone());
+///
+/// let steps = Simulation::new(clock, &bucket)
+///     .at(0, Op::Acquire(1))
+///     .at(0, Op::Acquire(1))
+///     .at(100, Op::Acquire(1))
+///     .run();
+///
+/// let admitted: Vec<bool> = steps
+///     .iter()
+///     .map(|step| matches!(step.decision, Decision::Acquired(o) if o.is_admitted()))
+///     .collect();
+/// assert_eq!(admitted, [true, false, true]);
+/// ```
+#[derive(Debug)]
+pub struct Simulation<'a, L: ?Sized> {
+    clock: MockClock,
+    limiter: &'a L,
+    events: Vec<(u64, Op)>,
+}
+
+impl<'a, L> Simulation<'a, L>
+where
+    L: RateLimiter + ?Sized,
+{
+    /// Creates an empty scenario for `limiter`, which reads its time from `clock`.
+    pub fn new(clock: MockClock, limiter: &'a L) -> Self {
+        Self {
+            clock,
+            limiter,
+            events: Vec::new(),
+        }
+    }
+
+    /// Schedules `op` to run when the clock reads `at_ms`.
+    pub fn at(mut self, at_ms: u64, op: Op) -> Self {
+        self.events.push((at_ms, op));
+        self
+    }
+
+    /// Runs every event in time order and returns the record of each decision.
+    ///
+    /// The clock never moves backwards: an event scheduled before the clock's current
+    /// time runs at the current time.
+    pub fn run(mut self) -> Vec<Step> {
+        self.events.sort_by_key(|&(at_ms, _)| at_ms);
+
+        self.events
+            .iter()
+            .map(|&(at_ms, op)| {
+                if at_ms > self.clock.now() {
+                    self.clock.set(at_ms);
+                }
+                let decision = match op {
+                    Op::Acquire(tokens) => Decision::Acquired(self.limiter.acquire_outcome(tokens)),
+                    Op::Available => Decision::Available(self.limiter.available_tokens()),
+                };
+                Step {
+                    at_ms: self.clock.now(),
+                    op,
+                    decision,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Advances a [`MockClock`] shared by several limiters and snapshots each of them.
+///
+/// Register the limiters driven by the clock, then call [`advance`](Self::advance) once
+/// per time step. Snapshots are returned in registration order.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use bucketboss::testing::{ClockDriver, MockClock};
+/// use bucketboss::{LeakyBucket, RateLimiter, TokenBucket};
+///
+/// let clock = MockClock::new(0);
+/// let mut driver = ClockDriver::new(clock.clone());
+/// let bucket = driver.register(Arc::new(TokenBucket::with_clock(2, 1.0, clock.clone())));
+/// let leaky = driver.register(Arc::new(LeakyBucket::with_clock(1.0, Some(2), clock)));
+/// assert!(driver.limiter(bucket).try_acquire(2).is_ok());
+/// assert!(driver.limiter(leaky).try_acquire(2).is_ok());
+///
+/// let available: Vec<u32> = driver.advance(1000).iter().map(|s| s.available).collect();
+/// assert_eq!(available, [1, 1]);
+/// ```
+pub struct ClockDriver {
+    clock: MockClock,
+    limiters: Vec<Arc<dyn RateLimiter>>,
+}
+
+impl ClockDriver {
+    /// Creates a driver for limiters that read their time from `clock`.
+    pub fn new(clock: MockClock) -> Self {
+        Self {
+            clock,
+            limiters: Vec::new(),
+        }
+    }
+
+    /// Registers a limiter and returns its index in the snapshots.
+    pub fn register(&mut self, limiter: Arc<dyn RateLimiter>) -> usize {
+        self.limiters.push(limiter);
+        self.limiters.len() - 1
+    }
+
+    /// Returns the limiter registered at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no limiter was registered at `index`.
+    pub fn limiter(&self, index: usize) -> &Arc<dyn RateLimiter> {
+        &self.limiters[index]
+    }
+
+    /// Advances the clock by `ms` milliseconds and returns a snapshot of every
+    /// registered limiter.
+    pub fn advance(&self, ms: u64) -> Vec<LimiterStatus> {
+        self.clock.advance(ms);
+        self.snapshot()
+    }
+
+    /// Returns a snapshot of every registered limiter without moving the clock.
+    pub fn snapshot(&self) -> Vec<LimiterStatus> {
+        self.limiters
+            .iter()
+            .map(|limiter| limiter.status())
+            .collect()
+    }
+}
+
+impl fmt::Debug for ClockDriver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClockDriver")
+            .field("clock", &self.clock)
+            .field("limiters", &self.limiters.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::*;
+    use crate::TokenBucket;
+
+    #[test]
+    fn test_scenario_records_every_decision() {
+        let clock = MockClock::new(0);
+        let bucket = TokenBucket::with_clock(2, 10.0, clock.clone());
+
+        // Events are added out of order; the simulation sorts them by time
+        let steps = Simulation::new(clock, &bucket)
+            .at(250, Op::Available)
+            .at(0, Op::Acquire(2))
+            .at(0, Op::Acquire(1))
+            .at(100, Op::Acquire(1))
+            .run();
+
+        let decisions: Vec<_> = steps
+            .iter()
+            .map(|step| (step.at_ms, step.decision))
+            .collect();
+        assert_eq!(
+            decisions,
+            [
+                (
+                    0,
+                    Decision::Acquired(Outcome::Admitted {
+                        remaining: 0,
+                        used_burst: false
+                    })
+                ),
+                (
+                    0,
+                    Decision::Acquired(Outcome::Throttled {
+                        retry_after: Duration::from_millis(100),
+                        available: 0,
+                    })
+                ),
+                (
+                    100,
+                    Decision::Acquired(Outcome::Admitted {
+                        remaining: 0,
+                        used_burst: false
+                    })
+                ),
+                (250, Decision::Available(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clock_driver_steps_every_limiter() {
+        let clock = MockClock::new(0);
+        let mut driver = ClockDriver::new(clock.clone());
+        let fast = driver.register(Arc::new(TokenBucket::with_clock(10, 10.0, clock.clone())));
+        let slow = driver.register(Arc::new(TokenBucket::with_clock(10, 1.0, clock.clone())));
+        assert!(driver.limiter(fast).try_acquire(10).is_ok());
+        assert!(driver.limiter(slow).try_acquire(10).is_ok());
+
+        let available = |statuses: Vec<LimiterStatus>| -> Vec<u32> {
+            statuses.iter().map(|status| status.available).collect()
+        };
+        assert_eq!(available(driver.snapshot()), [0, 0]);
+        assert_eq!(available(driver.advance(500)), [5, 0]);
+        assert_eq!(available(driver.advance(500)), [10, 1]);
+        assert_eq!(clock.now(), 1000);
+    }
+
+    #[test]
+    fn test_clock_never_moves_backwards() {
+        let clock = MockClock::new(1000);
+        let bucket = TokenBucket::with_clock(1, 1.0, clock.clone());
+
+        let steps = Simulation::new(clock.clone(), &bucket)
+            .at(0, Op::Available)
+            .run();
+        assert_eq!(steps[0].at_ms, 1000);
+        assert_eq!(clock.now(), 1000);
+    }
+}
@@ -0,0 +1,164 @@
+//! Cost-banded rate limiting.
+//!
+//! Some APIs let cheap operations through at the normal rate but throttle expensive ones
+//! harder. [`TieredCostLimiter`] routes each request by its cost to one of several
+//! limiters, so heavy requests drain a stricter limit of their own. Unlike weighting,
+//! where every request draws from one budget at a different charge, each tier here has
+//! its own capacity and rate.
+
+use crate::{
+    error::{RateLimitError, Result},
+    traits::{RateLimiter, Wrapper},
+};
+
+/// A limiter that routes each request to a tier chosen by the request's cost.
+///
+/// Each tier is a `(min_cost, limiter)` pair, and a request of cost `c` is charged `c`
+/// tokens against the tier with the largest `min_cost` not above `c`. Costs below the
+/// first tier's bound go to the first tier. For example, tiers `[(1, fast), (10, slow)]`
+/// send costs 1 to 9 to `fast` and costs from 10 upwards to `slow`.
+///
+/// The tiers are held in a fixed-size array, so no allocation is needed.
+#[derive(Debug)]
+pub struct TieredCostLimiter<L, const N: usize> {
+    tiers: [(u32, L); N],
+}
+
+impl<L, const N: usize> TieredCostLimiter<L, N>
+where
+    L: RateLimiter,
+{
+    /// Creates a new limiter from `(min_cost, limiter)` tiers.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Self)` if the configuration is valid
+    /// * `Err(RateLimitError::InvalidConfiguration)` if there are no tiers or the
+    ///   `min_cost` bounds are not strictly increasing
+    pub fn new(tiers: [(u32, L); N]) -> Result<Self> {
+        if N == 0 {
+            return Err(RateLimitError::invalid_config(
+                "at least one tier is required",
+            ));
+        }
+        if tiers.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(RateLimitError::invalid_config(
+                "tier min_cost bounds must be strictly increasing",
+            ));
+        }
+
+        Ok(Self { tiers })
+    }
+
+    /// Returns the index of the tier that requests of `cost` are routed to.
+    pub fn tier_index(&self, cost: u32) -> usize {
+        self.tiers
+            .iter()
+            .rposition(|&(min_cost, _)| min_cost <= cost)
+            .unwrap_or(0)
+    }
+
+    /// Returns the limiter that requests of `cost` are routed to.
+    pub fn tier_for(&self, cost: u32) -> &L {
+        &self.tiers[self.tier_index(cost)].1
+    }
+
+    /// Returns each tier's `min_cost` bound and limiter, cheapest first.
+    ///
+    /// The limiters can be queried for per-tier status such as
+    /// [`available_tokens`](RateLimiter::available_tokens).
+    pub fn tiers(&self) -> impl Iterator<Item = (u32, &L)> + '_ {
+        self.tiers
+            .iter()
+            .map(|(min_cost, limiter)| (*min_cost, limiter))
+    }
+
+    /// Attempts to acquire `cost` tokens from the tier for `cost`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the tier admitted the request
+    /// * `Err(RateLimitError::RateLimitExceeded)` if the tier's limit would be
+    ///   exceeded; other tiers are unaffected
+    pub fn try_acquire(&self, cost: u32) -> Result<()> {
+        self.tier_for(cost).try_acquire(cost)
+    }
+
+    /// Returns the time until a request of `cost` could be admitted, in milliseconds.
+    ///
+    /// See [`RateLimiter::time_until_available_ms`].
+    pub fn time_until_available_ms(&self, cost: u32) -> Option<u64> {
+        self.tier_for(cost).time_until_available_ms(cost)
+    }
+}
+
+/// The wrapped value is the array of `(min_cost, limiter)` tiers. Changes through
+/// [`get_mut`](Wrapper::get_mut) should keep the `min_cost` bounds strictly
+/// increasing, or requests are routed to the last tier whose bound they reach.
+impl<L, const N: usize> Wrapper for TieredCostLimiter<L, N> {
+    type Inner = [(u32, L); N];
+
+    fn get_ref(&self) -> &[(u32, L); N] {
+        &self.tiers
+    }
+
+    fn get_mut(&mut self) -> &mut [(u32, L); N] {
+        &mut self.tiers
+    }
+
+    fn into_inner(self) -> [(u32, L); N] {
+        self.tiers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, TokenBucket};
+
+    #[test]
+    fn test_cheap_and_expensive_requests_hit_their_own_tier() {
+        let clock = MockClock::new(0);
+        let limiter = TieredCostLimiter::new([
+            (1, TokenBucket::with_clock(20, 20.0, clock.clone())),
+            (10, TokenBucket::with_clock(25, 1.0, clock.clone())),
+        ])
+        .unwrap();
+        assert_eq!(limiter.tier_index(0), 0);
+        assert_eq!(limiter.tier_index(9), 0);
+        assert_eq!(limiter.tier_index(10), 1);
+
+        // Two expensive requests exhaust the slow tier
+        assert!(limiter.try_acquire(10).is_ok());
+        assert!(limiter.try_acquire(12).is_ok());
+        let err = limiter.try_acquire(10).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(7000));
+
+        // Cheap requests still draw from the fast tier
+        for _ in 0..4 {
+            assert!(limiter.try_acquire(5).is_ok());
+        }
+        assert!(limiter.try_acquire(1).is_err());
+
+        let available: Vec<_> = limiter
+            .tiers()
+            .map(|(min_cost, tier)| (min_cost, tier.available_tokens()))
+            .collect();
+        assert_eq!(available, [(1, 0), (10, 3)]);
+
+        // The fast tier recovers long before the slow one
+        clock.advance(1000);
+        assert!(limiter.try_acquire(9).is_ok());
+        assert!(limiter.try_acquire(10).is_err());
+        assert_eq!(limiter.time_until_available_ms(10), Some(6000));
+    }
+
+    #[test]
+    fn test_invalid_tiers() {
+        let bucket = || TokenBucket::with_clock(1, 1.0, MockClock::new(0));
+        let empty: [(u32, TokenBucket<MockClock>); 0] = [];
+        assert!(TieredCostLimiter::new(empty).is_err());
+        assert!(TieredCostLimiter::new([(5, bucket()), (5, bucket())]).is_err());
+        assert!(TieredCostLimiter::new([(5, bucket()), (1, bucket())]).is_err());
+    }
+}
@@ -7,43 +7,90 @@
 use core::{
     f64,
     sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
 };
 
+#[cfg(feature = "peak-burst")]
+use crate::peak::PeakTracker;
+#[cfg(feature = "saturation")]
+use crate::saturation::SaturationTracker;
 use crate::{
-    clock::{Clock, SystemClock},
+    backoff::Backoff,
+    clock::{now_ns, resolution_warning, Clock, SystemClock, NANOS_PER_MS},
     error::{RateLimitError, Result},
-    traits::{RateLimiter, ReconfigurableRateLimiter, WithClock},
+    float,
+    interval::{self, RefillInterval},
+    observer::Observer,
+    outcome::{saturate_u32, Outcome, WideOutcome},
+    policy::ZeroTokenPolicy,
+    schedule::Schedule,
+    status::BucketState,
+    traits::{
+        AdjustableRateLimiter, RateLimiter, RateLimiterBuilder, ReconfigurableRateLimiter,
+        Resettable, StatefulRateLimiter, WithClock,
+    },
+    validate,
 };
 
-// Helper functions for atomic float operations
-fn f64_to_u64(value: f64) -> u64 {
-    value.to_bits()
-}
-
-fn u64_to_f64(value: u64) -> f64 {
-    f64::from_bits(value)
-}
-
 /// A thread-safe token bucket rate limiter.
 ///
 /// This implementation uses atomic operations to ensure thread safety without requiring
 /// external synchronization. It's designed for high throughput and low latency.
+///
+/// # Fairness
+///
+/// Admission is lock-free, not fair: concurrent callers are not served in arrival
+/// order. A call that loses a race for the same tokens backs off briefly and retries,
+/// so contention alone never turns into a spurious rejection, and the backoff keeps one
+/// thread from being shut out by others that retry immediately. There is no bound on
+/// how often a single caller can lose, however; put a queue in front of the limiter if
+/// first-come-first-served ordering is required.
 #[derive(Debug)]
-pub struct TokenBucket<C = SystemClock> {
+pub struct TokenBucket<C = SystemClock, O = ()> {
     /// The clock used to track time.
     clock: C,
     /// The maximum number of tokens the bucket can hold.
     capacity: AtomicU64,
     /// The number of tokens added per second (stored as bits of f64).
     tokens_per_second: AtomicU64,
-    /// The time in milliseconds between adding each token (stored as bits of f64).
-    ms_per_token: AtomicU64,
+    /// The time between adding each token.
+    interval: RefillInterval,
     /// The current number of tokens in the bucket.
     tokens: AtomicU64,
-    /// The last time the token count was updated.
+    /// The time, in nanoseconds, from which the next token accrues.
     last_update: AtomicU64,
+    /// How requests for zero tokens are treated.
+    zero_token_policy: ZeroTokenPolicy,
+    /// Whether refills discarded at capacity are counted in `wasted_tokens`.
+    track_waste: bool,
+    /// Tokens discarded because the bucket was full when they were refilled.
+    wasted_tokens: AtomicU64,
+    /// Whether the refill restarts when a full bucket admits a request.
+    strict: bool,
+    /// Milliseconds added to the `retry_after` advertised with rejections.
+    retry_slack_ms: u64,
+    /// Receives the admission decisions.
+    observer: O,
+    /// The largest number of tokens admitted within one second.
+    #[cfg(feature = "peak-burst")]
+    peak: PeakTracker,
+    /// When the bucket last became exhausted.
+    #[cfg(feature = "saturation")]
+    saturation: SaturationTracker,
+}
+
+/// Usage statistics collected by a [`TokenBucket`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenBucketStats {
+    /// Tokens discarded because the bucket was already full when they were refilled.
+    ///
+    /// Steady growth means the capacity is never used in full, or the rate exceeds
+    /// demand. Only counted when enabled with [`TokenBucket::with_waste_tracking`];
+    /// otherwise 0.
+    pub wasted_tokens: u64,
 }
 
+#[cfg(feature = "std")]
 impl TokenBucket<SystemClock> {
     /// Creates a new `TokenBucket` with the specified capacity and rate.
     ///
@@ -58,25 +105,55 @@ impl TokenBucket<SystemClock> {
     ///
     /// # Panics
     ///
-    /// Panics if `capacity` is 0 or if `tokens_per_second` is not positive.
+    /// Panics if `capacity` is 0 or if `tokens_per_second` is not positive or exceeds
+    /// [`MAX_RATE`](crate::MAX_RATE). With the `fixed` feature it also panics if the
+    /// rate is below one token per 78 hours or above 6.5·10¹³ tokens per second, the
+    /// range of the fixed-point refill interval. With the `no-panic` feature this only
+    /// happens in debug builds; release builds clamp the capacity to 1 and the rate into
+    /// the valid range instead.
     pub fn new(capacity: u32, tokens_per_second: f64) -> Self {
-        assert!(capacity > 0, "capacity must be greater than 0");
-        assert!(
-            tokens_per_second > 0.0,
-            "tokens_per_second must be positive"
-        );
+        Self::with_clock(capacity, tokens_per_second, SystemClock)
+    }
+
+    /// Creates a new `TokenBucket` whose capacity may exceed `u32::MAX`.
+    ///
+    /// Use this for large-magnitude budgets such as byte counts, together with
+    /// [`RateLimiter::try_acquire_u64`] and [`RateLimiter::available_tokens_u64`].
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`TokenBucket::new`].
+    pub fn new_u64(capacity: u64, tokens_per_second: f64) -> Self {
+        Self::with_clock_u64(capacity, tokens_per_second, SystemClock)
+    }
 
-        let now = SystemClock.now();
-        let ms_per_token = 1000.0 / tokens_per_second;
+    /// Creates a new `TokenBucket` in a `const` context, refilling one token every
+    /// `ns_per_token` nanoseconds.
+    ///
+    /// See [`TokenBucket::const_with_clock`].
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`TokenBucket::const_with_clock`].
+    pub const fn const_new(capacity: u64, ns_per_token: u64) -> Self {
+        Self::const_with_clock(capacity, ns_per_token, SystemClock)
+    }
 
-        Self {
-            capacity: AtomicU64::new(capacity as u64),
-            tokens_per_second: AtomicU64::new(f64_to_u64(tokens_per_second)),
-            ms_per_token: AtomicU64::new(f64_to_u64(ms_per_token)),
-            clock: SystemClock,
-            tokens: AtomicU64::new(capacity as u64),
-            last_update: AtomicU64::new(now),
-        }
+    /// Creates a new `TokenBucket` that admits exactly `tokens_per_second`, with no
+    /// burst at all.
+    ///
+    /// See [`TokenBucket::strict_with_clock`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tokens_per_second` is not positive, as for [`TokenBucket::new`].
+    pub fn strict(tokens_per_second: f64) -> Self {
+        Self::strict_with_clock(tokens_per_second, SystemClock)
+    }
+
+    /// Returns a builder for configuring a `TokenBucket` step by step.
+    pub fn builder() -> TokenBucketBuilder {
+        TokenBucketBuilder::new()
     }
 }
 
@@ -87,65 +164,505 @@ where
     /// Creates a new `TokenBucket` with the specified clock.
     ///
     /// This is useful for testing or for environments where you need to control time.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`TokenBucket::new`].
     pub fn with_clock(capacity: u32, tokens_per_second: f64, clock: C) -> Self {
-        assert!(capacity > 0, "capacity must be greater than 0");
-        assert!(
-            tokens_per_second > 0.0,
-            "tokens_per_second must be positive"
-        );
+        Self::with_clock_u64(capacity as u64, tokens_per_second, clock)
+    }
+
+    /// Creates a new `TokenBucket` with the specified clock and a capacity that may
+    /// exceed `u32::MAX`.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`TokenBucket::new`].
+    pub fn with_clock_u64(capacity: u64, tokens_per_second: f64, clock: C) -> Self {
+        let capacity = validate::capacity(capacity, "capacity must be greater than 0");
+        let tokens_per_second = interval::rate(validate::rate(
+            tokens_per_second,
+            "tokens_per_second must be positive",
+        ));
 
-        let now = clock.now();
-        let ms_per_token = 1000.0 / tokens_per_second;
+        let now = now_ns(&clock);
 
         Self {
-            capacity: AtomicU64::new(capacity as u64),
-            tokens_per_second: AtomicU64::new(f64_to_u64(tokens_per_second)),
-            ms_per_token: AtomicU64::new(f64_to_u64(ms_per_token)),
+            capacity: AtomicU64::new(capacity),
+            tokens_per_second: AtomicU64::new(tokens_per_second.to_bits()),
+            interval: RefillInterval::from_ms(1000.0 / tokens_per_second),
             clock,
-            tokens: AtomicU64::new(capacity as u64),
+            tokens: AtomicU64::new(capacity),
             last_update: AtomicU64::new(now),
+            zero_token_policy: ZeroTokenPolicy::AlwaysAdmit,
+            track_waste: false,
+            wasted_tokens: AtomicU64::new(0),
+            strict: false,
+            retry_slack_ms: 0,
+            observer: (),
+            #[cfg(feature = "peak-burst")]
+            peak: PeakTracker::new(),
+            #[cfg(feature = "saturation")]
+            saturation: SaturationTracker::new(),
+        }
+    }
+
+    /// Creates a new `TokenBucket` with no burst, using the specified clock.
+    ///
+    /// A bucket of capacity 1 still bunches requests: if it sat full for part of an
+    /// emission interval, the next token arrives less than a full interval after the
+    /// request that emptied it. A strict bucket has capacity 1 and restarts its refill
+    /// whenever it admits a request while full, so any two admitted requests are at
+    /// least `1 / tokens_per_second` seconds apart.
+    ///
+    /// This is the pacing of a [`LeakyBucket`](crate::LeakyBucket) without burst, such
+    /// as [`LeakyBucket::one_per_second`](crate::LeakyBucket::one_per_second), offered
+    /// through the token bucket type. Raising the capacity with
+    /// [`update_config`](ReconfigurableRateLimiter::update_config) reintroduces bursts.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`TokenBucket::strict`].
+    pub fn strict_with_clock(tokens_per_second: f64, clock: C) -> Self {
+        let mut bucket = Self::with_clock(1, tokens_per_second, clock);
+        bucket.strict = true;
+        bucket
+    }
+
+    /// Creates a new `TokenBucket` in a `const` context, so it can be the initializer
+    /// of a `static` on targets where lazy initialization is costly.
+    ///
+    /// A `const fn` can neither read the clock nor validate a float rate, so the rate
+    /// is given as the whole number of nanoseconds between tokens, precomputed as
+    /// `1_000_000_000 / tokens_per_second`: `100_000_000` is 10 tokens per second. The
+    /// bucket starts full with its refill anchored at clock time 0, which is harmless
+    /// since a full bucket has nothing to catch up on. `clock` must itself be
+    /// constructible in a `const` context, like [`SystemClock`] or a unit struct
+    /// reading a hardware timer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bucketboss::{RateLimiter, TokenBucket};
+    ///
+    /// // 5 tokens, refilled at 10 per second
+    /// static LIMITER: TokenBucket = TokenBucket::const_new(5, 100_000_000);
+    ///
+    /// assert!(LIMITER.try_acquire(5).is_ok());
+    /// assert!(LIMITER.try_acquire(1).is_err());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` or `ns_per_token` is 0, which fails the build when the
+    /// bucket initializes a `static`. With the `no-panic` feature this only happens in
+    /// debug builds; release builds clamp both to 1 instead.
+    pub const fn const_with_clock(capacity: u64, ns_per_token: u64, clock: C) -> Self {
+        let capacity = validate::capacity(capacity, "capacity must be greater than 0");
+        let ns_per_token = validate::capacity(ns_per_token, "ns_per_token must be greater than 0");
+        let tokens_per_second = NANOS_PER_MS as f64 * 1000.0 / ns_per_token as f64;
+
+        Self {
+            capacity: AtomicU64::new(capacity),
+            tokens_per_second: AtomicU64::new(tokens_per_second.to_bits()),
+            interval: RefillInterval::from_whole_ns(ns_per_token),
+            clock,
+            tokens: AtomicU64::new(capacity),
+            last_update: AtomicU64::new(0),
+            zero_token_policy: ZeroTokenPolicy::AlwaysAdmit,
+            track_waste: false,
+            wasted_tokens: AtomicU64::new(0),
+            strict: false,
+            retry_slack_ms: 0,
+            observer: (),
+            #[cfg(feature = "peak-burst")]
+            peak: PeakTracker::new(),
+            #[cfg(feature = "saturation")]
+            saturation: SaturationTracker::new(),
+        }
+    }
+
+    /// Rebuilds a bucket from a [`BucketState`], for example one persisted by the
+    /// previous process in a rolling deploy, so a restart does not hand out a fresh
+    /// burst.
+    ///
+    /// The bucket continues to refill from the state's `last_update_ns`, which is
+    /// clamped to the clock's current time if it lies in the future, so clock skew
+    /// between processes never delays the refill. The token count is clamped to the
+    /// capacity.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Self)` if the snapshot's configuration is valid
+    /// * `Err(RateLimitError::InvalidConfiguration)` if its capacity is 0 or its rate
+    ///   is not positive and finite
+    pub fn from_state(state: BucketState, clock: C) -> Result<Self> {
+        if state.capacity == 0 {
+            return Err(RateLimitError::invalid_config(
+                "capacity must be greater than 0",
+            ));
+        }
+        validate::try_rate(
+            state.tokens_per_second,
+            "tokens_per_second must be positive",
+        )?;
+        interval::try_rate(state.tokens_per_second)?;
+
+        let bucket = Self::with_clock_u64(state.capacity, state.tokens_per_second, clock);
+        let now = now_ns(&bucket.clock);
+        bucket
+            .tokens
+            .store(state.tokens.min(state.capacity), Ordering::Release);
+        bucket
+            .last_update
+            .store(state.last_update_ns.min(now), Ordering::Release);
+        Ok(bucket)
+    }
+}
+
+impl<C, O> TokenBucket<C, O>
+where
+    C: Clock,
+    O: Observer,
+{
+    /// Sets how requests for zero tokens are treated.
+    ///
+    /// See [`ZeroTokenPolicy`] for the available behaviors.
+    pub fn with_zero_token_policy(mut self, policy: ZeroTokenPolicy) -> Self {
+        self.zero_token_policy = policy;
+        self
+    }
+
+    /// Adds `slack` to the `retry_after` advertised with every rejection.
+    ///
+    /// Clients that retry exactly when told often arrive a moment too early because of
+    /// network jitter or clock skew, and are rejected again. The slack makes them wait
+    /// slightly longer so the first retry succeeds. Only the advertised wait changes:
+    /// admission decisions and [`time_until_available_ms`](RateLimiter::time_until_available_ms)
+    /// are unaffected. Unlike jitter, the slack is the same for every rejection.
+    pub fn with_retry_slack(mut self, slack: Duration) -> Self {
+        self.retry_slack_ms = slack.as_millis().min(u64::MAX as u128) as u64;
+        self
+    }
+
+    /// Enables counting of tokens discarded at capacity, reported by
+    /// [`stats`](TokenBucket::stats).
+    ///
+    /// This is off by default to keep the refill path free of the extra atomic update.
+    pub fn with_waste_tracking(mut self) -> Self {
+        self.track_waste = true;
+        self
+    }
+
+    /// Calls `observer` with every admission decision, from within `try_acquire` and
+    /// the other acquiring methods.
+    ///
+    /// See [`Observer`] for the requirements on its methods, which run concurrently on
+    /// the admission path.
+    pub fn with_observer<P>(self, observer: P) -> TokenBucket<C, P>
+    where
+        P: Observer,
+    {
+        TokenBucket {
+            capacity: self.capacity,
+            tokens_per_second: self.tokens_per_second,
+            interval: self.interval,
+            clock: self.clock,
+            tokens: self.tokens,
+            last_update: self.last_update,
+            zero_token_policy: self.zero_token_policy,
+            track_waste: self.track_waste,
+            wasted_tokens: self.wasted_tokens,
+            strict: self.strict,
+            retry_slack_ms: self.retry_slack_ms,
+            observer,
+            #[cfg(feature = "peak-burst")]
+            peak: self.peak,
+            #[cfg(feature = "saturation")]
+            saturation: self.saturation,
         }
     }
 
-    /// Updates the internal state of the token bucket based on the current time.
+    /// Returns the observer of the bucket's admission decisions.
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    /// Returns the usage statistics collected so far.
+    pub fn stats(&self) -> TokenBucketStats {
+        TokenBucketStats {
+            wasted_tokens: self.wasted_tokens.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the largest number of tokens admitted within any one-second window so
+    /// far.
+    ///
+    /// Unlike [`capacity`](RateLimiter::capacity), which is the burst the bucket
+    /// *allows*, this is the burst it has actually *admitted*, for audits such as
+    /// "never more than X per second". Admissions are counted in 100 ms slots, so the
+    /// window covers between 0.9 and 1 s. The count saturates at `u32::MAX`.
+    #[cfg(feature = "peak-burst")]
+    pub fn peak_burst(&self) -> u32 {
+        saturate_u32(self.peak.peak())
+    }
+
+    /// Returns how long the bucket has been continuously empty, or `None` if a token
+    /// is available.
+    ///
+    /// The empty stretch starts at the admission that took the last token, so this is
+    /// a signal for alerting on a limiter that stays exhausted, as opposed to one that
+    /// is briefly emptied between refills.
+    #[cfg(feature = "saturation")]
+    pub fn saturated_since(&self) -> Option<Duration> {
+        let now = now_ns(&self.clock);
+        let available = self.update_state(now);
+        self.saturation.since(now, available == 0)
+    }
+
+    /// Updates the internal state of the token bucket based on the current time, in
+    /// nanoseconds.
     ///
     /// This method is called internally by `try_acquire` and `available_tokens`
     /// to ensure the token count is up to date.
-    fn update_state(&self, now: u64) -> u32 {
+    fn update_state(&self, now: u64) -> u64 {
         let last = self.last_update.load(Ordering::Acquire);
         let elapsed = now.saturating_sub(last);
 
         if elapsed == 0 {
-            return self.tokens.load(Ordering::Relaxed) as u32;
+            return self.tokens.load(Ordering::Relaxed);
         }
 
-        // Get the current ms_per_token as f64
-        let ms_per_token = u64_to_f64(self.ms_per_token.load(Ordering::Acquire));
-
         // Calculate how many tokens to add based on elapsed time
-        let tokens_to_add = if ms_per_token > 0.0 {
-            (elapsed as f64 / ms_per_token) as u64
-        } else {
-            0
-        };
+        let tokens_to_add = self.interval.tokens_in(elapsed);
 
         if tokens_to_add == 0 {
-            return self.tokens.load(Ordering::Relaxed) as u32;
+            return self.tokens.load(Ordering::Relaxed);
         }
 
-        // Update the last update time
-        self.last_update.store(now, Ordering::Release);
-
         // Add the tokens, but don't exceed capacity
         let current_tokens = self.tokens.load(Ordering::Relaxed);
         let capacity = self.capacity.load(Ordering::Acquire);
         let new_tokens = current_tokens.saturating_add(tokens_to_add);
         let capped_tokens = new_tokens.min(capacity);
 
+        // Advance the last update time only by the time the added tokens took, keeping
+        // the progress towards the next token; a full bucket accrues nothing, so it
+        // starts over. Rounding up never credits time that has not passed.
+        let refill_time = if new_tokens >= capacity {
+            now
+        } else {
+            last + self.interval.ns_for(tokens_to_add).min(elapsed)
+        };
+        self.last_update.store(refill_time, Ordering::Release);
+        if self.track_waste && new_tokens > capacity {
+            let _ = self
+                .wasted_tokens
+                .fetch_add(new_tokens - capacity, Ordering::Relaxed);
+        }
+
         // Store the new token count
         self.tokens.store(capped_tokens, Ordering::Release);
+        #[cfg(feature = "saturation")]
+        self.saturation.update(now, capped_tokens == 0);
+
+        capped_tokens
+    }
+
+    /// Brings the saturation tracker up to date after the token count was changed at
+    /// `now`, in nanoseconds, other than by a refill or an admission.
+    #[cfg(feature = "saturation")]
+    fn track_saturation(&self, now: u64) {
+        self.saturation
+            .update(now, self.tokens.load(Ordering::Acquire) == 0);
+    }
+
+    /// Returns the tokens available as of `now`, in nanoseconds, without storing the
+    /// refill.
+    fn tokens_at(&self, now: u64) -> u64 {
+        let elapsed = now.saturating_sub(self.last_update.load(Ordering::Acquire));
+        self.tokens
+            .load(Ordering::Acquire)
+            .saturating_add(self.interval.tokens_in(elapsed))
+            .min(self.capacity.load(Ordering::Acquire))
+    }
+
+    /// Returns the time, in milliseconds and rounded up, until the next token accrues as
+    /// of `now`, in nanoseconds.
+    fn next_token_wait_ms(&self, now: u64) -> f64 {
+        let since_refill = now.saturating_sub(self.last_update.load(Ordering::Acquire));
+        float::ceil((self.interval.ns() - since_refill as f64) / NANOS_PER_MS as f64)
+    }
+
+    /// Returns the wait, as of `now` in nanoseconds, until `tokens` could be acquired.
+    fn wait_ms_at(&self, now: u64, tokens: u32) -> Option<u64> {
+        let tokens = tokens as u64;
+        let current_tokens = self.update_state(now);
+        if tokens <= current_tokens {
+            return Some(0);
+        }
+
+        if tokens > self.capacity.load(Ordering::Acquire) || !self.interval.is_positive() {
+            return None;
+        }
+        Some(self.interval.ms_for(tokens - current_tokens))
+    }
+
+    /// Returns the number of tokens currently available, including the fraction of the
+    /// next token that has accrued so far.
+    ///
+    /// `available_tokens` truncates this value, so at low rates it can report 0 while
+    /// the next token is almost complete. Admission decisions still use whole tokens;
+    /// this is intended for monitoring and progress reporting.
+    pub fn available_tokens_f64(&self) -> f64 {
+        let now = now_ns(&self.clock);
+        let current_tokens = self.update_state(now) as f64;
+        let ns_per_token = self.interval.ns();
+        if ns_per_token <= 0.0 {
+            return current_tokens;
+        }
+
+        let elapsed = now.saturating_sub(self.last_update.load(Ordering::Acquire));
+        let capacity = self.capacity.load(Ordering::Acquire) as f64;
+        (current_tokens + elapsed as f64 / ns_per_token).min(capacity)
+    }
+
+    /// Raises the number of available tokens to at least `min`.
+    ///
+    /// This grants extra budget ahead of a planned spike. The target is clamped to the
+    /// bucket's capacity, and the available count is only ever raised, never lowered:
+    /// a bucket already holding `min` tokens or more is left untouched.
+    pub fn ensure_available(&self, min: u32) {
+        let now = now_ns(&self.clock);
+        let _ = self.update_state(now);
+
+        let target = (min as u64).min(self.capacity.load(Ordering::Acquire));
+        let _ = self.tokens.fetch_max(target, Ordering::AcqRel);
+        #[cfg(feature = "saturation")]
+        self.track_saturation(now);
+    }
+
+    /// Admits if at least one token is available, without consuming anything.
+    fn probe_availability(&self) -> WideOutcome {
+        let now = now_ns(&self.clock);
+        let current_tokens = self.update_state(now);
+        if current_tokens > 0 {
+            return WideOutcome::Admitted {
+                remaining: current_tokens,
+            };
+        }
+
+        let wait_ms = self.time_until_next_token_ms().unwrap_or(1);
+        WideOutcome::Throttled {
+            retry_after_ms: wait_ms,
+            available: 0,
+        }
+    }
+
+    /// Makes the admission decision for `tokens`, in full-width counts, as advertised
+    /// to the caller.
+    fn acquire_wide(&self, tokens: u64) -> WideOutcome {
+        let outcome = self
+            .decide_wide(tokens)
+            .with_retry_slack(self.retry_slack_ms);
+        match outcome {
+            WideOutcome::Admitted { .. } => self.observer.on_acquire(tokens, true),
+            WideOutcome::Throttled { retry_after_ms, .. } => {
+                self.observer.on_acquire(tokens, false);
+                self.observer.on_reject(tokens, retry_after_ms);
+            }
+        }
+        outcome
+    }
+
+    /// Makes the admission decision for `tokens`, in full-width counts.
+    fn decide_wide(&self, tokens: u64) -> WideOutcome {
+        if tokens == 0 {
+            return match self.zero_token_policy {
+                ZeroTokenPolicy::AlwaysAdmit => WideOutcome::Admitted {
+                    remaining: self.update_state(now_ns(&self.clock)),
+                },
+                ZeroTokenPolicy::ReflectAvailability => self.probe_availability(),
+            };
+        }
+
+        let mut backoff = Backoff::new();
+        loop {
+            let now = now_ns(&self.clock);
+            let current_tokens = self.update_state(now);
+
+            if tokens > current_tokens {
+                let tokens_needed = tokens - current_tokens;
+                let wait_ms = self.interval.ms_for(tokens_needed);
+
+                // Report the count this decision was made on rather than re-reading it,
+                // which a concurrent refill could already have raised to `tokens`
+                return WideOutcome::Throttled {
+                    retry_after_ms: wait_ms,
+                    available: current_tokens,
+                };
+            }
+
+            // Try to acquire the tokens
+            let new_tokens = current_tokens - tokens;
+            if self
+                .tokens
+                .compare_exchange(
+                    current_tokens,
+                    new_tokens,
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                // A full bucket discards refills, so in strict mode the next token is
+                // timed from this admission rather than from the last refill
+                if self.strict && current_tokens == self.capacity.load(Ordering::Acquire) {
+                    self.last_update.store(now, Ordering::Release);
+                }
+                #[cfg(feature = "peak-burst")]
+                self.peak.record(now / NANOS_PER_MS, tokens);
+                #[cfg(feature = "saturation")]
+                if new_tokens == 0 {
+                    self.saturation.record_exhausted(now);
+                }
+                return WideOutcome::Admitted {
+                    remaining: new_tokens,
+                };
+            }
+
+            // If we couldn't update atomically, back off and retry the whole operation
+            backoff.spin();
+        }
+    }
 
-        capped_tokens as u32
+    /// Makes the admission decision `decide_wide` would make for `tokens`, without
+    /// refilling or consuming anything.
+    fn peek_wide(&self, tokens: u64) -> WideOutcome {
+        let current_tokens = self.tokens_at(now_ns(&self.clock));
+        if tokens == 0 {
+            return match self.zero_token_policy {
+                ZeroTokenPolicy::ReflectAvailability if current_tokens == 0 => {
+                    WideOutcome::Throttled {
+                        retry_after_ms: self.time_until_next_token_ms().unwrap_or(1),
+                        available: 0,
+                    }
+                }
+                _ => WideOutcome::Admitted {
+                    remaining: current_tokens,
+                },
+            };
+        }
+
+        if tokens > current_tokens {
+            return WideOutcome::Throttled {
+                retry_after_ms: self.interval.ms_for(tokens - current_tokens),
+                available: current_tokens,
+            };
+        }
+        WideOutcome::Admitted {
+            remaining: current_tokens - tokens,
+        }
     }
 
     /// Updates the rate and capacity of the token bucket.
@@ -155,113 +672,260 @@ where
     /// * `capacity` - The new capacity of the bucket (maximum tokens).
     /// * `tokens_per_second` - The new rate at which tokens are added to the bucket.
     fn set_rate(&self, capacity: u32, tokens_per_second: f64) {
-        // Update the atomic values
         self.capacity.store(capacity as u64, Ordering::Release);
+        self.store_rate(tokens_per_second);
+    }
+
+    /// Updates the refill rate of the token bucket.
+    fn store_rate(&self, tokens_per_second: f64) {
         self.tokens_per_second
-            .store(f64_to_u64(tokens_per_second), Ordering::Release);
+            .store(tokens_per_second.to_bits(), Ordering::Release);
 
-        // Calculate and store the new ms_per_token
+        // Calculate and store the new refill interval
         let ms_per_token = if tokens_per_second > 0.0 {
             1000.0 / tokens_per_second
         } else {
             0.0
         };
-        self.ms_per_token
-            .store(f64_to_u64(ms_per_token), Ordering::Release);
+        self.interval.store_ms(ms_per_token);
     }
 }
 
-impl<C> RateLimiter for TokenBucket<C>
+impl<C, O> RateLimiter for TokenBucket<C, O>
 where
     C: Clock,
+    O: Observer,
 {
     fn try_acquire(&self, tokens: u32) -> Result<()> {
-        if tokens == 0 {
-            return Ok(());
-        }
+        self.acquire_wide(tokens as u64).into_result(tokens as u64)
+    }
 
-        let now = self.clock.now();
-        let current_tokens = self.update_state(now);
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        self.acquire_wide(tokens).into_result(tokens)
+    }
 
-        if tokens > current_tokens {
-            let tokens_needed = tokens - current_tokens;
-            let ms_per_token = u64_to_f64(self.ms_per_token.load(Ordering::Acquire));
-            let wait_ms = (tokens_needed as f64 * ms_per_token).ceil() as u64;
-
-            return Err(RateLimitError::rate_limit_exceeded(
-                tokens,
-                current_tokens,
-                wait_ms,
-            ));
+    fn try_acquire_up_to(&self, max_tokens: u32) -> u32 {
+        let mut tokens = self.available_tokens_u64().min(max_tokens as u64);
+        while tokens > 0 {
+            match self.acquire_wide(tokens) {
+                WideOutcome::Admitted { .. } => break,
+                // A concurrent acquisition took some of the tokens first
+                WideOutcome::Throttled { available, .. } => tokens = available.min(tokens - 1),
+            }
         }
+        tokens as u32
+    }
 
-        // Try to acquire the tokens
-        let new_tokens = current_tokens - tokens;
-        if self
-            .tokens
-            .compare_exchange(
-                current_tokens as u64,
-                new_tokens as u64,
-                Ordering::AcqRel,
-                Ordering::Relaxed,
-            )
-            .is_ok()
-        {
-            Ok(())
-        } else {
-            // If we couldn't update atomically, retry the whole operation
-            self.try_acquire(tokens)
-        }
+    fn acquire_outcome(&self, tokens: u32) -> Outcome {
+        self.acquire_wide(tokens as u64)
+            .narrow(self.capacity_u64(), self.rate_per_second())
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        self.peek_wide(tokens as u64)
+            .with_retry_slack(self.retry_slack_ms)
+            .into_result(tokens as u64)
     }
 
     fn available_tokens(&self) -> u32 {
-        let now = self.clock.now();
-        self.update_state(now)
+        saturate_u32(self.available_tokens_u64())
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        self.update_state(now_ns(&self.clock))
     }
 
     fn capacity(&self) -> u32 {
-        self.capacity.load(Ordering::Acquire) as u32
+        saturate_u32(self.capacity_u64())
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.capacity.load(Ordering::Acquire)
     }
 
     fn rate_per_second(&self) -> f64 {
-        u64_to_f64(self.tokens_per_second.load(Ordering::Acquire))
+        f64::from_bits(self.tokens_per_second.load(Ordering::Acquire))
     }
 
-    fn time_until_next_token_ms(&self) -> Option<u64> {
-        let now = self.clock.now();
-        let last_update = self.last_update.load(Ordering::Acquire);
-        let ms_per_token = u64_to_f64(self.ms_per_token.load(Ordering::Acquire));
+    fn resolution_warning(&self) -> Option<&'static str> {
+        resolution_warning(&self.clock, self.rate_per_second())
+    }
+
+    fn acquire_probe(&self) -> (bool, Duration) {
+        // Pace the next attempt to the next refill, even if tokens are left over, so the
+        // caller converges on the rate instead of draining the burst
+        let admitted = matches!(self.acquire_wide(1), WideOutcome::Admitted { .. });
+        let wait_ms = self.next_token_wait_ms(now_ns(&self.clock)).max(0.0) as u64;
+        (admitted, Duration::from_millis(wait_ms))
+    }
 
-        if ms_per_token == 0.0 {
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        if !self.interval.is_positive() {
             return None;
         }
 
-        let next_token_time = last_update + ms_per_token.ceil() as u64;
-        if next_token_time > now {
-            Some(next_token_time - now)
+        // Time already spent towards the next token counts, rounding the rest up
+        let wait_ms = self.next_token_wait_ms(now_ns(&self.clock));
+        if wait_ms > 0.0 {
+            Some(wait_ms as u64)
         } else {
             None
         }
     }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.wait_ms_at(now_ns(&self.clock), tokens)
+    }
+
+    /// Returns the timeline of the next `n` tokens: the current burst, then one token
+    /// per refill interval counted from the last refill.
+    fn schedule(&self, n: u32) -> Schedule {
+        let now = now_ns(&self.clock);
+        let available = self.update_state(now);
+        let capacity = self.capacity.load(Ordering::Acquire);
+        let ms_per_token = self.interval.ms();
+
+        // A strict bucket restarts its refill when the first token leaves a full bucket
+        let refill_from = if self.strict && available == capacity {
+            now
+        } else {
+            self.last_update.load(Ordering::Acquire)
+        };
+        Schedule::new(
+            now / NANOS_PER_MS,
+            n,
+            saturate_u32(available),
+            refill_from as f64 / NANOS_PER_MS as f64 + ms_per_token,
+            ms_per_token,
+        )
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        let now = now_ns(&self.clock);
+        self.wait_ms_at(now, tokens)
+            .map(|wait_ms| (now / NANOS_PER_MS).saturating_add(wait_ms))
+    }
+}
+
+impl<C, O> AdjustableRateLimiter for TokenBucket<C, O>
+where
+    C: Clock,
+    O: Observer,
+{
+    fn refund(&self, tokens: u32) {
+        let now = now_ns(&self.clock);
+        let _ = self.update_state(now);
+        let capacity = self.capacity.load(Ordering::Acquire);
+        let _ = self
+            .tokens
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+                Some(current.saturating_add(tokens as u64).min(capacity))
+            });
+        #[cfg(feature = "saturation")]
+        self.track_saturation(now);
+    }
+
+    fn penalize(&self, tokens: u32) {
+        let now = now_ns(&self.clock);
+        let _ = self.update_state(now);
+        let _ = self
+            .tokens
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+                Some(current.saturating_sub(tokens as u64))
+            });
+        #[cfg(feature = "saturation")]
+        self.track_saturation(now);
+    }
+}
+
+impl<C, O> Resettable for TokenBucket<C, O>
+where
+    C: Clock,
+    O: Observer,
+{
+    fn reset(&self) {
+        let now = now_ns(&self.clock);
+        self.last_update.store(now, Ordering::Release);
+        self.tokens
+            .store(self.capacity.load(Ordering::Acquire), Ordering::Release);
+        #[cfg(feature = "saturation")]
+        self.track_saturation(now);
+    }
+
+    fn drain(&self) {
+        let now = now_ns(&self.clock);
+        self.last_update.store(now, Ordering::Release);
+        self.tokens.store(0, Ordering::Release);
+        #[cfg(feature = "saturation")]
+        self.saturation.record_exhausted(now);
+    }
+}
+
+impl<C, O> StatefulRateLimiter for TokenBucket<C, O>
+where
+    C: Clock,
+    O: Observer,
+{
+    fn snapshot(&self) -> BucketState {
+        let now = now_ns(&self.clock);
+        let tokens = self.update_state(now);
+        BucketState {
+            capacity: self.capacity.load(Ordering::Acquire),
+            tokens_per_second: self.rate_per_second(),
+            tokens,
+            last_update_ns: self.last_update.load(Ordering::Acquire),
+            taken_at_ns: now,
+        }
+    }
+
+    fn restore(&self, state: &BucketState, elapsed: Duration) {
+        // Credit the whole tokens accrued since the snapshot's refill here rather than
+        // leaving them to `update_state`, as they may reach back past this clock's epoch
+        let elapsed = state
+            .taken_at_ns
+            .saturating_sub(state.last_update_ns)
+            .saturating_add(elapsed.as_nanos().min(u64::MAX as u128) as u64);
+        let accrued = self.interval.tokens_in(elapsed);
+        let remainder = elapsed.saturating_sub(self.interval.ns_for(accrued));
+
+        let capacity = self.capacity.load(Ordering::Acquire);
+        let tokens = state.tokens.saturating_add(accrued).min(capacity);
+        let now = now_ns(&self.clock);
+        self.tokens.store(tokens, Ordering::Release);
+        self.last_update
+            .store(now.saturating_sub(remainder), Ordering::Release);
+        #[cfg(feature = "saturation")]
+        self.track_saturation(now);
+    }
 }
 
-impl<C> ReconfigurableRateLimiter for TokenBucket<C>
+impl<C, O> ReconfigurableRateLimiter for TokenBucket<C, O>
 where
     C: Clock,
+    O: Observer,
 {
+    /// Switches to the new capacity and rate and refills the bucket to the new
+    /// capacity.
+    ///
+    /// Reapplying the current configuration is a no-op, so it does not refill the
+    /// bucket; use [`reset`](Resettable::reset) for that.
     fn update_config(&self, capacity: u32, tokens_per_second: f64) -> Result<()> {
         if capacity == 0 {
             return Err(RateLimitError::invalid_config(
                 "capacity must be greater than 0",
             ));
         }
-        if tokens_per_second <= 0.0 {
-            return Err(RateLimitError::invalid_config(
-                "tokens_per_second must be positive",
-            ));
+        validate::try_rate(tokens_per_second, "tokens_per_second must be positive")?;
+        interval::try_rate(tokens_per_second)?;
+
+        // Reapplying the current configuration is a no-op
+        if self.capacity.load(Ordering::Acquire) == capacity as u64
+            && self.rate_per_second() == tokens_per_second
+        {
+            return Ok(());
         }
 
-        let now = self.clock.now();
+        let now = now_ns(&self.clock);
         let _ = self.update_state(now);
 
         // Update the rate and capacity first
@@ -269,20 +933,42 @@ where
 
         // Then update the available tokens to the new capacity
         self.tokens.store(capacity as u64, Ordering::Release);
+        #[cfg(feature = "saturation")]
+        self.track_saturation(now);
+
+        Ok(())
+    }
+
+    fn set_rate_only(&self, tokens_per_second: f64) -> Result<()> {
+        validate::try_rate(tokens_per_second, "tokens_per_second must be positive")?;
+        interval::try_rate(tokens_per_second)?;
 
+        // Credit the tokens accrued at the old rate before switching
+        let _ = self.update_state(now_ns(&self.clock));
+        self.store_rate(tokens_per_second);
         Ok(())
     }
 }
 
-impl<C> WithClock<C> for TokenBucket<C> {
+impl<C, O> WithClock<C> for TokenBucket<C, O> {
     fn with_clock(self, clock: C) -> Self {
         TokenBucket {
             capacity: self.capacity,
             tokens_per_second: self.tokens_per_second,
-            ms_per_token: self.ms_per_token,
+            interval: self.interval,
             clock,
             tokens: self.tokens,
             last_update: self.last_update,
+            zero_token_policy: self.zero_token_policy,
+            track_waste: self.track_waste,
+            wasted_tokens: self.wasted_tokens,
+            strict: self.strict,
+            retry_slack_ms: self.retry_slack_ms,
+            observer: self.observer,
+            #[cfg(feature = "peak-burst")]
+            peak: self.peak,
+            #[cfg(feature = "saturation")]
+            saturation: self.saturation,
         }
     }
 }
@@ -296,10 +982,138 @@ where
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::time::Duration;
+/// A builder for [`TokenBucket`].
+///
+/// Unlike [`TokenBucket::new`], building never panics: invalid settings are reported
+/// by [`build`](RateLimiterBuilder::build) as `RateLimitError::InvalidConfiguration`.
+/// The capacity defaults to 1, the rate to one token per second, and the bucket starts
+/// full unless [`initial_tokens`](Self::initial_tokens) says otherwise.
+///
+/// # Examples
+///
+/// ```
+/// use bucketboss::{RateLimiter, RateLimiterBuilder, TokenBucketBuilder};
+///
+/// let bucket = TokenBucketBuilder::default()
+///     .capacity(10)
+///     .tokens_per_second(5.0)
+///     .initial_tokens(0)
+///     .build()?;
+/// assert_eq!(bucket.available_tokens(), 0);
+/// # Ok::<(), bucketboss::RateLimitError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct TokenBucketBuilder<C = SystemClock> {
+    capacity: u32,
+    tokens_per_second: f64,
+    initial_tokens: Option<u32>,
+    zero_token_policy: ZeroTokenPolicy,
+    retry_slack: Duration,
+    clock: C,
+}
+
+#[cfg(feature = "std")]
+impl TokenBucketBuilder<SystemClock> {
+    /// Creates a builder with the default settings, using the system clock.
+    pub fn new() -> Self {
+        Self {
+            capacity: 1,
+            tokens_per_second: 1.0,
+            initial_tokens: None,
+            zero_token_policy: ZeroTokenPolicy::AlwaysAdmit,
+            retry_slack: Duration::ZERO,
+            clock: SystemClock,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for TokenBucketBuilder<SystemClock> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> TokenBucketBuilder<C> {
+    /// Sets the clock the bucket will use.
+    pub fn clock<D>(self, clock: D) -> TokenBucketBuilder<D> {
+        TokenBucketBuilder {
+            capacity: self.capacity,
+            tokens_per_second: self.tokens_per_second,
+            initial_tokens: self.initial_tokens,
+            zero_token_policy: self.zero_token_policy,
+            retry_slack: self.retry_slack,
+            clock,
+        }
+    }
+
+    /// Sets the number of tokens the bucket starts with, instead of starting full.
+    ///
+    /// Building fails if this exceeds the capacity.
+    pub fn initial_tokens(mut self, tokens: u32) -> Self {
+        self.initial_tokens = Some(tokens);
+        self
+    }
+
+    /// Sets how requests for zero tokens are treated.
+    pub fn zero_token_policy(mut self, policy: ZeroTokenPolicy) -> Self {
+        self.zero_token_policy = policy;
+        self
+    }
+
+    /// Sets the slack added to advertised retry times.
+    ///
+    /// See [`TokenBucket::with_retry_slack`].
+    pub fn retry_slack(mut self, slack: Duration) -> Self {
+        self.retry_slack = slack;
+        self
+    }
+}
+
+impl<C> RateLimiterBuilder for TokenBucketBuilder<C>
+where
+    C: Clock,
+{
+    type Limiter = TokenBucket<C>;
+
+    fn capacity(mut self, capacity: u32) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    fn tokens_per_second(mut self, tokens_per_second: f64) -> Self {
+        self.tokens_per_second = tokens_per_second;
+        self
+    }
+
+    fn build(self) -> Result<TokenBucket<C>> {
+        if self.capacity == 0 {
+            return Err(RateLimitError::invalid_config(
+                "capacity must be greater than 0",
+            ));
+        }
+        validate::try_rate(self.tokens_per_second, "tokens_per_second must be positive")?;
+        interval::try_rate(self.tokens_per_second)?;
+        if self.initial_tokens > Some(self.capacity) {
+            return Err(RateLimitError::invalid_config(
+                "initial_tokens must not exceed capacity",
+            ));
+        }
+
+        let bucket = TokenBucket::with_clock(self.capacity, self.tokens_per_second, self.clock)
+            .with_zero_token_policy(self.zero_token_policy)
+            .with_retry_slack(self.retry_slack);
+        if let Some(tokens) = self.initial_tokens {
+            bucket.tokens.store(tokens as u64, Ordering::Release);
+        }
+        Ok(bucket)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
 
     #[test]
     fn test_token_bucket_acquire() {
@@ -337,4 +1151,893 @@ mod tests {
         assert_eq!(bucket.available_tokens(), 2);
         assert!(bucket.try_acquire(2).is_ok());
     }
+
+    #[test]
+    fn test_token_bucket_ensure_available() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(10, 1.0, clock);
+
+        // Already above the minimum: nothing changes
+        assert!(bucket.try_acquire(2).is_ok());
+        bucket.ensure_available(5);
+        assert_eq!(bucket.available_tokens(), 8);
+
+        // Below the minimum: topped up to exactly `min`
+        assert!(bucket.try_acquire(7).is_ok());
+        bucket.ensure_available(5);
+        assert_eq!(bucket.available_tokens(), 5);
+
+        // Above capacity: clamped to capacity
+        bucket.ensure_available(50);
+        assert_eq!(bucket.available_tokens(), 10);
+    }
+
+    #[test]
+    fn test_token_bucket_zero_token_policy() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(1, 1.0, clock.clone());
+        assert!(bucket.try_acquire(1).is_ok());
+
+        // By default a zero-token request is admitted even when exhausted
+        assert!(bucket.try_acquire(0).is_ok());
+
+        let bucket = bucket.with_zero_token_policy(ZeroTokenPolicy::ReflectAvailability);
+        let err = bucket.try_acquire(0).unwrap_err();
+        assert_eq!(err, RateLimitError::rate_limit_exceeded(0, 0, 1000));
+
+        // Once a token accrues the probe succeeds without consuming it
+        clock.advance(1000);
+        assert!(bucket.try_acquire(0).is_ok());
+        assert_eq!(bucket.available_tokens(), 1);
+    }
+
+    #[test]
+    fn test_token_bucket_outcome_flags_burst_after_idle() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(20, 5.0, clock.clone());
+
+        // A steady client at the rate stays within one second of refill
+        for _ in 0..20 {
+            assert!(!bucket.acquire_outcome(1).used_burst());
+            clock.advance(200);
+        }
+
+        // After an idle period, the sixth request of a burst goes below 20 - 5 tokens
+        clock.advance(10_000);
+        let flags: Vec<_> = (0..8)
+            .map(|_| bucket.acquire_outcome(1).used_burst())
+            .collect();
+        assert_eq!(flags, [false, false, false, false, false, true, true, true]);
+    }
+
+    #[test]
+    fn test_token_bucket_try_acquire_up_to() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(10, 4.0, clock.clone());
+
+        assert_eq!(bucket.try_acquire_up_to(3), 3);
+        assert_eq!(bucket.try_acquire_up_to(50), 7);
+        assert_eq!(bucket.try_acquire_up_to(50), 0);
+
+        // Draining a queue takes whatever the refill allowed since the last batch
+        clock.advance(750);
+        assert_eq!(bucket.try_acquire_up_to(50), 3);
+        assert_eq!(bucket.available_tokens(), 0);
+        assert_eq!(bucket.try_acquire_up_to(0), 0);
+    }
+
+    #[test]
+    fn test_token_bucket_acquire_outcome() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(5, 2.0, clock.clone());
+
+        assert_eq!(
+            bucket.acquire_outcome(3),
+            Outcome::Admitted {
+                remaining: 2,
+                used_burst: true
+            }
+        );
+        assert_eq!(
+            bucket.acquire_outcome(4),
+            Outcome::Throttled {
+                retry_after: Duration::from_millis(1000),
+                available: 2,
+            }
+        );
+
+        // try_acquire reports the same decision as an error
+        assert_eq!(
+            bucket.try_acquire(4),
+            Err(RateLimitError::rate_limit_exceeded(4, 2, 1000))
+        );
+
+        clock.advance(1000);
+        assert_eq!(
+            bucket.acquire_outcome(4),
+            Outcome::Admitted {
+                remaining: 0,
+                used_burst: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_token_bucket_peek_acquire_leaves_state_untouched() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(5, 10.0, clock.clone());
+        assert!(bucket.try_acquire(5).is_ok());
+
+        // One and a half intervals later one slot is free, but nothing is stored
+        clock.advance(150);
+        assert!(bucket.peek_acquire(1).is_ok());
+        let err = bucket.peek_acquire(3).unwrap_err();
+        assert_eq!(err, RateLimitError::rate_limit_exceeded(3, 1, 200));
+        assert_eq!(bucket.tokens.load(Ordering::SeqCst), 0);
+
+        // The real attempt makes the same decision
+        assert_eq!(bucket.try_acquire(3).unwrap_err(), err);
+        assert!(bucket.try_acquire(1).is_ok());
+        assert!(bucket.peek_acquire(1).is_err());
+    }
+
+    #[test]
+    fn test_token_bucket_retry_at() {
+        let clock = crate::clock::MockClock::new(10_000);
+        let bucket = TokenBucket::with_clock(5, 2.0, clock.clone());
+        assert!(bucket.try_acquire(5).is_ok());
+
+        for tokens in 1..=5 {
+            let wait_ms = bucket.time_until_available_ms(tokens).unwrap();
+            assert!(wait_ms > 0);
+            assert_eq!(bucket.retry_at(tokens), Some(clock.now() + wait_ms));
+        }
+        assert_eq!(bucket.time_until_available_ms(6), None);
+        assert_eq!(bucket.retry_at(6), None);
+
+        // Once the clock reaches the timestamp the tokens can be acquired
+        let retry_at = bucket.retry_at(3).unwrap();
+        clock.set(retry_at);
+        assert_eq!(bucket.time_until_available_ms(3), Some(0));
+        assert!(bucket.try_acquire(3).is_ok());
+    }
+
+    #[test]
+    fn test_token_bucket_available_tokens_f64() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(2, 1.0, clock.clone());
+        assert_eq!(bucket.available_tokens_f64(), 2.0);
+        assert!(bucket.try_acquire(2).is_ok());
+
+        clock.advance(990);
+        assert_eq!(bucket.available_tokens(), 0);
+        assert!((bucket.available_tokens_f64() - 0.99).abs() < 1e-9);
+
+        clock.advance(10);
+        assert_eq!(bucket.available_tokens(), 1);
+        assert_eq!(bucket.available_tokens_f64(), 1.0);
+
+        clock.advance(250);
+        assert_eq!(bucket.available_tokens(), 1);
+        assert!((bucket.available_tokens_f64() - 1.25).abs() < 1e-9);
+
+        // Never exceeds capacity
+        clock.advance(10_000);
+        assert_eq!(bucket.available_tokens_f64(), 2.0);
+    }
+
+    #[test]
+    fn test_token_bucket_byte_scale_u64() {
+        const GIB: u64 = 1 << 30;
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock_u64(GIB * 8, GIB as f64, clock.clone());
+        assert_eq!(bucket.available_tokens_u64(), 8 * GIB);
+        assert_eq!(bucket.available_tokens(), u32::MAX);
+
+        // The capacity saturates instead of wrapping to 0
+        assert_eq!(bucket.capacity_u64(), 8 * GIB);
+        assert_eq!(bucket.capacity(), u32::MAX);
+
+        // A 5 GiB request does not fit in a u32
+        assert!(bucket.try_acquire_u64(5 * GIB).is_ok());
+        assert_eq!(bucket.available_tokens_u64(), 3 * GIB);
+        assert_eq!(bucket.available_tokens(), (3 * GIB) as u32);
+        assert_eq!(bucket.fill_level_u8(), 95);
+
+        // Rejections saturate the reported counts
+        let err = bucket.try_acquire_u64(5 * GIB).unwrap_err();
+        assert_eq!(
+            err,
+            RateLimitError::rate_limit_exceeded(u32::MAX, (3 * GIB) as u32, 2000)
+        );
+
+        clock.advance(2000);
+        assert!(bucket.try_acquire_u64(4 * GIB).is_ok());
+
+        // The u32 entry point shares the same state
+        assert!(bucket.try_acquire(1024).is_ok());
+        #[cfg(not(feature = "fixed"))]
+        assert_eq!(bucket.available_tokens_u64(), GIB - 1024);
+        // A GiB per second is within 2⁻¹⁷ ns of the fixed-point interval, not exact
+        #[cfg(feature = "fixed")]
+        assert!(bucket.available_tokens_u64().abs_diff(GIB - 1024) < GIB / 100_000);
+    }
+
+    #[test]
+    fn test_token_bucket_update_config_noop() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(10, 1.0, clock.clone());
+        assert!(bucket.try_acquire(8).is_ok());
+
+        // Reapplying the current configuration leaves the state untouched
+        assert!(bucket.update_config(10, 1.0).is_ok());
+        assert_eq!(bucket.available_tokens(), 2);
+
+        // A real change still applies
+        assert!(bucket.update_config(10, 2.0).is_ok());
+        assert_eq!(bucket.rate_per_second(), 2.0);
+        assert_eq!(bucket.available_tokens(), 10);
+    }
+
+    #[test]
+    fn test_token_bucket_take_stats_under_concurrent_scraping() {
+        use std::sync::atomic::AtomicBool;
+        use std::sync::Arc;
+        use std::thread;
+
+        use crate::{observer::AdmissionCounters, status::LimiterStats};
+
+        let bucket = Arc::new(
+            TokenBucket::with_clock(1000, 1.0, crate::clock::MockClock::new(0))
+                .with_observer(AdmissionCounters::new()),
+        );
+        let done = Arc::new(AtomicBool::new(false));
+
+        // A scraper takes the counts while four threads make 2000 decisions
+        let scraper = {
+            let bucket = Arc::clone(&bucket);
+            let done = Arc::clone(&done);
+            thread::spawn(move || {
+                let mut total = LimiterStats::default();
+                while !done.load(Ordering::Acquire) {
+                    let stats = bucket.observer().take_stats();
+                    total.admitted += stats.admitted;
+                    total.rejected += stats.rejected;
+                }
+                total
+            })
+        };
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let bucket = Arc::clone(&bucket);
+                thread::spawn(move || {
+                    for _ in 0..500 {
+                        let _ = bucket.try_acquire(1);
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        done.store(true, Ordering::Release);
+
+        let mut total = scraper.join().unwrap();
+        let last = bucket.observer().take_stats();
+        total.admitted += last.admitted;
+        total.rejected += last.rejected;
+        assert_eq!(
+            total,
+            LimiterStats {
+                admitted: 1000,
+                rejected: 1000
+            }
+        );
+        assert_eq!(bucket.observer().stats(), LimiterStats::default());
+    }
+
+    #[test]
+    fn test_token_bucket_wasted_tokens() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(10, 10.0, clock.clone()).with_waste_tracking();
+
+        assert!(bucket.try_acquire(5).is_ok());
+        clock.advance(300);
+        assert_eq!(bucket.available_tokens(), 8);
+        assert_eq!(bucket.stats().wasted_tokens, 0);
+
+        // Idling for 2s refills 20 tokens, but only 2 fit
+        clock.advance(2000);
+        assert_eq!(bucket.available_tokens(), 10);
+        assert_eq!(bucket.stats().wasted_tokens, 18);
+
+        // Untracked buckets report nothing
+        let untracked = TokenBucket::with_clock(1, 10.0, clock.clone());
+        clock.advance(1000);
+        assert_eq!(untracked.available_tokens(), 1);
+        assert_eq!(untracked.stats(), TokenBucketStats::default());
+    }
+
+    #[test]
+    fn test_token_bucket_reset_and_drain() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(10, 2.0, clock.clone());
+        assert!(bucket.try_acquire(7).is_ok());
+        clock.advance(200);
+
+        bucket.drain();
+        assert_eq!(bucket.available_tokens(), 0);
+        // The partial refill before the drain is discarded too
+        clock.advance(499);
+        assert_eq!(bucket.available_tokens(), 0);
+        clock.advance(1);
+        assert_eq!(bucket.available_tokens(), 1);
+
+        bucket.reset();
+        assert_eq!(bucket.available_tokens(), 10);
+        assert!(bucket.try_acquire(10).is_ok());
+        assert_eq!(bucket.time_until_next_token_ms(), Some(500));
+    }
+
+    #[test]
+    #[cfg(feature = "peak-burst")]
+    fn test_token_bucket_peak_burst() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(10, 5.0, clock.clone());
+        assert_eq!(bucket.peak_burst(), 0);
+
+        // A burst of 10 at once, then the refill trickling in over the same second
+        assert!(bucket.try_acquire(10).is_ok());
+        clock.advance(400);
+        assert!(bucket.try_acquire(2).is_ok());
+        clock.advance(400);
+        assert!(bucket.try_acquire(2).is_ok());
+        assert_eq!(bucket.peak_burst(), 14);
+
+        // Rejections and the quieter second that follows do not change the peak
+        assert!(bucket.try_acquire(1).is_err());
+        clock.advance(1000);
+        assert!(bucket.try_acquire(5).is_ok());
+        assert_eq!(bucket.peak_burst(), 14);
+    }
+
+    #[test]
+    #[cfg(feature = "saturation")]
+    fn test_token_bucket_saturated_since() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(2, 0.1, clock.clone());
+        assert_eq!(bucket.saturated_since(), None);
+
+        // Rejections while empty do not restart the stretch
+        assert!(bucket.try_acquire(2).is_ok());
+        for _ in 0..9 {
+            clock.advance(1000);
+            assert!(bucket.try_acquire(1).is_err());
+            assert!(bucket.saturated_since().is_some());
+        }
+        assert_eq!(bucket.saturated_since(), Some(Duration::from_secs(9)));
+
+        // The refill at t=10s ends it, and taking the token starts a new one
+        clock.advance(1000);
+        assert_eq!(bucket.saturated_since(), None);
+        clock.advance(500);
+        assert!(bucket.try_acquire(1).is_ok());
+        clock.advance(2500);
+        assert_eq!(bucket.saturated_since(), Some(Duration::from_millis(2500)));
+
+        // A refill seen only by an admission check ends the stretch too, so a
+        // penalty emptying the bucket later starts a new one
+        clock.advance(7500);
+        assert_eq!(bucket.available_tokens(), 1);
+        clock.advance(3000);
+        bucket.penalize(1);
+        clock.advance(1000);
+        assert_eq!(bucket.saturated_since(), Some(Duration::from_secs(1)));
+
+        // Restoring a non-empty state ends it
+        let mut state = bucket.snapshot();
+        state.tokens = 2;
+        bucket.restore(&state, Duration::ZERO);
+        assert_eq!(bucket.saturated_since(), None);
+    }
+
+    #[test]
+    fn test_token_bucket_penalize_and_refund() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(10, 1.0, clock.clone());
+
+        // A fine of 7 leaves room for only 3 more requests
+        bucket.penalize(7);
+        assert_eq!(bucket.available_tokens(), 3);
+        assert!(bucket.try_acquire(3).is_ok());
+        assert!(bucket.try_acquire(1).is_err());
+
+        // Penalties floor at empty, and the tokens come back at the normal rate
+        bucket.penalize(5);
+        assert_eq!(bucket.available_tokens(), 0);
+        clock.advance(2000);
+        assert_eq!(bucket.available_tokens(), 2);
+
+        // Refunds cap at capacity
+        bucket.refund(20);
+        assert_eq!(bucket.available_tokens(), 10);
+    }
+
+    /// A clock with a nanosecond source.
+    #[derive(Debug, Clone, Default)]
+    struct NanoClock(std::sync::Arc<AtomicU64>);
+
+    impl Clock for NanoClock {
+        fn now(&self) -> u64 {
+            self.0.load(Ordering::SeqCst) / NANOS_PER_MS
+        }
+
+        fn now_nanos(&self) -> u128 {
+            self.0.load(Ordering::SeqCst) as u128
+        }
+
+        fn resolution_ms(&self) -> f64 {
+            1e-6
+        }
+    }
+
+    #[test]
+    fn test_token_bucket_resolution_warning() {
+        /// A clock ticking every 10 ms.
+        #[derive(Debug, Clone)]
+        struct CoarseClock(crate::clock::MockClock);
+
+        impl Clock for CoarseClock {
+            fn now(&self) -> u64 {
+                self.0.now() / 10 * 10
+            }
+
+            fn resolution_ms(&self) -> f64 {
+                10.0
+            }
+        }
+
+        let coarse = CoarseClock(crate::clock::MockClock::new(0));
+        let bucket = TokenBucket::with_clock(100, 10_000.0, coarse.clone());
+        assert!(bucket.resolution_warning().is_some());
+
+        // One token per tick can still be paced
+        let bucket = TokenBucket::with_clock(100, 100.0, coarse);
+        assert_eq!(bucket.resolution_warning(), None);
+
+        // A nanosecond clock resolves the high rate, but not one above 10⁹ per second
+        let bucket = TokenBucket::with_clock(100, 10_000.0, NanoClock::default());
+        assert_eq!(bucket.resolution_warning(), None);
+        assert!(bucket.set_rate_only(1e10).is_ok());
+        assert!(bucket.resolution_warning().is_some());
+    }
+
+    #[test]
+    fn test_token_bucket_refills_below_a_millisecond() {
+        let clock = NanoClock::default();
+        let bucket = TokenBucket::with_clock(1000, 3000.0, clock.clone());
+        assert!(bucket.try_acquire(1000).is_ok());
+
+        // Tokens accrue every 333.3 µs, without waiting for the millisecond to turn
+        let _ = clock.0.fetch_add(400_000, Ordering::SeqCst);
+        assert_eq!(bucket.available_tokens(), 1);
+        for _ in 0..29 {
+            let _ = clock.0.fetch_add(100_000, Ordering::SeqCst);
+            let _ = bucket.available_tokens();
+        }
+
+        // Frequent updates lose no partial progress: 3.3 ms give exactly 9 tokens
+        assert_eq!(bucket.available_tokens(), 9);
+        assert_eq!(bucket.time_until_next_token_ms(), Some(1));
+    }
+
+    #[test]
+    fn test_token_bucket_const_construction() {
+        static NOW: AtomicU64 = AtomicU64::new(5_000);
+
+        #[derive(Debug)]
+        struct StaticClock;
+
+        impl Clock for StaticClock {
+            fn now(&self) -> u64 {
+                NOW.load(Ordering::SeqCst)
+            }
+        }
+
+        // 4 tokens, refilled every 250 ms
+        static BUCKET: TokenBucket<StaticClock> =
+            TokenBucket::const_with_clock(4, 250_000_000, StaticClock);
+
+        assert_eq!(BUCKET.capacity(), 4);
+        assert_eq!(BUCKET.rate_per_second(), 4.0);
+        assert!(BUCKET.try_acquire(4).is_ok());
+        assert_eq!(
+            BUCKET.try_acquire(1).unwrap_err().retry_after_ms(),
+            Some(250)
+        );
+
+        let _ = NOW.fetch_add(500, Ordering::SeqCst);
+        assert_eq!(BUCKET.available_tokens(), 2);
+    }
+
+    #[test]
+    fn test_token_bucket_next_token_counts_partial_refill() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(10, 3.0, clock.clone());
+        assert!(bucket.try_acquire(10).is_ok());
+
+        // One token accrued by 333.3 ms; the next is due at 666.7 ms rather than a full
+        // interval after the refill was noticed
+        clock.advance(500);
+        assert_eq!(bucket.available_tokens(), 1);
+        assert!(bucket.try_acquire(1).is_ok());
+        let reported = bucket.time_until_next_token_ms().unwrap();
+        assert_eq!(reported, 167);
+
+        // The reported wait is exactly the actual wait
+        clock.advance(reported - 1);
+        assert_eq!(bucket.available_tokens(), 0);
+        clock.advance(1);
+        assert_eq!(bucket.available_tokens(), 1);
+    }
+
+    #[test]
+    fn test_token_bucket_schedule() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(3, 4.0, clock.clone());
+        assert!(bucket.try_acquire(1).is_ok());
+        clock.advance(100);
+
+        let schedule = bucket.schedule(5);
+        assert_eq!(schedule.collect::<Vec<_>>(), [100, 100, 250, 500, 750]);
+        assert_eq!(bucket.available_tokens(), 2);
+        assert_schedule_is_kept(&bucket, &clock, schedule);
+
+        let strict = TokenBucket::strict_with_clock(4.0, clock.clone());
+        clock.set(1100);
+        let schedule = strict.schedule(3);
+        assert_eq!(schedule.collect::<Vec<_>>(), [1100, 1350, 1600]);
+        assert_schedule_is_kept(&strict, &clock, schedule);
+    }
+
+    /// Acquires one token at each scheduled time, checking none was available earlier.
+    fn assert_schedule_is_kept<L: RateLimiter>(
+        limiter: &L,
+        clock: &crate::clock::MockClock,
+        schedule: Schedule,
+    ) {
+        let mut previous = clock.now();
+        for at in schedule {
+            if at > previous {
+                clock.set(at - 1);
+                assert!(limiter.try_acquire(1).is_err(), "admitted before {at}");
+            }
+            clock.set(at);
+            assert!(limiter.try_acquire(1).is_ok(), "rejected at {at}");
+            previous = at;
+        }
+    }
+
+    #[test]
+    fn test_builder() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::builder()
+            .capacity(10)
+            .tokens_per_second(5.0)
+            .initial_tokens(0)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        assert_eq!(bucket.capacity(), 10);
+        assert_eq!(bucket.available_tokens(), 0);
+        clock.advance(1000);
+        assert_eq!(bucket.available_tokens(), 5);
+
+        // Starts full by default
+        let bucket = TokenBucketBuilder::default().capacity(3).build().unwrap();
+        assert_eq!(bucket.available_tokens(), 3);
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_config() {
+        let invalid = [
+            TokenBucketBuilder::new().capacity(0).build(),
+            TokenBucketBuilder::new().tokens_per_second(0.0).build(),
+            TokenBucketBuilder::new()
+                .tokens_per_second(f64::NAN)
+                .build(),
+            TokenBucketBuilder::new()
+                .capacity(2)
+                .initial_tokens(3)
+                .build(),
+        ];
+        for result in invalid {
+            assert!(result.unwrap_err().is_invalid_config());
+        }
+    }
+
+    #[test]
+    fn test_token_bucket_strict() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::strict_with_clock(10.0, clock.clone());
+        assert_eq!(bucket.capacity(), 1);
+
+        // Idle for part of an interval, so a plain capacity-1 bucket would admit a
+        // second request at 100 ms
+        clock.advance(50);
+        let mut admitted = Vec::new();
+        for _ in 0..2000 {
+            if bucket.try_acquire(1).is_ok() {
+                admitted.push(clock.now());
+            }
+            clock.advance(1);
+        }
+
+        assert_eq!(admitted[..3], [50, 150, 250]);
+        assert!(admitted.windows(2).all(|pair| pair[1] - pair[0] >= 100));
+        assert_eq!(admitted.len(), 20);
+    }
+
+    #[test]
+    fn test_token_bucket_acquire_probe() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(3, 10.0, clock.clone());
+        assert!(bucket.try_acquire(3).is_ok());
+
+        // A client that starts out of phase and then waits as suggested settles into
+        // one admission per 100 ms emission interval
+        clock.advance(30);
+        let mut probes = Vec::new();
+        for _ in 0..5 {
+            let (admitted, wait) = bucket.acquire_probe();
+            probes.push((admitted, wait.as_millis()));
+            clock.advance(wait.as_millis() as u64);
+        }
+        assert_eq!(
+            probes,
+            [
+                (false, 70),
+                (true, 100),
+                (true, 100),
+                (true, 100),
+                (true, 100)
+            ]
+        );
+
+        // Admitted from a full bucket partway through an interval
+        let bucket = TokenBucket::with_clock(3, 10.0, clock.clone());
+        clock.advance(40);
+        assert_eq!(bucket.acquire_probe(), (true, Duration::from_millis(60)));
+    }
+
+    #[test]
+    fn test_token_bucket_retry_slack() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(2, 4.0, clock.clone())
+            .with_retry_slack(Duration::from_millis(50));
+        assert!(bucket.try_acquire(2).is_ok());
+
+        // The advertised wait exceeds the true wait by the slack
+        assert_eq!(bucket.time_until_available_ms(1), Some(250));
+        let err = bucket.try_acquire(1).unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(300));
+        assert_eq!(
+            bucket.acquire_outcome(2).retry_after(),
+            Some(Duration::from_millis(550))
+        );
+
+        // Admission itself still happens at the true time
+        clock.advance(250);
+        assert!(bucket.try_acquire(1).is_ok());
+    }
+
+    #[test]
+    fn test_token_bucket_observer() {
+        #[derive(Debug, Default)]
+        struct Recorder {
+            decisions: std::sync::Mutex<Vec<(u64, bool)>>,
+            rejections: std::sync::Mutex<Vec<(u64, u64)>>,
+        }
+
+        impl Observer for Recorder {
+            fn on_acquire(&self, tokens: u64, granted: bool) {
+                self.decisions.lock().unwrap().push((tokens, granted));
+            }
+
+            fn on_reject(&self, tokens: u64, retry_after_ms: u64) {
+                self.rejections
+                    .lock()
+                    .unwrap()
+                    .push((tokens, retry_after_ms));
+            }
+        }
+
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(2, 4.0, clock.clone())
+            .with_retry_slack(Duration::from_millis(50))
+            .with_observer(Recorder::default());
+        assert!(bucket.try_acquire(2).is_ok());
+        assert!(bucket.try_acquire(1).is_err());
+        assert!(bucket.peek_acquire(1).is_err());
+        clock.advance(250);
+        assert!(bucket.try_acquire(1).is_ok());
+
+        // Peeking decides nothing, and rejections report the advertised wait
+        let observer = bucket.observer();
+        assert_eq!(
+            *observer.decisions.lock().unwrap(),
+            [(2, true), (1, false), (1, true)]
+        );
+        assert_eq!(*observer.rejections.lock().unwrap(), [(1, 300)]);
+    }
+
+    #[test]
+    fn test_token_bucket_set_rate_only() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(10, 1.0, clock.clone());
+        assert!(bucket.try_acquire(10).is_ok());
+        clock.advance(2000);
+
+        // The available tokens survive the change, unlike with update_config
+        assert!(bucket.set_rate_only(10.0).is_ok());
+        assert_eq!(bucket.available_tokens(), 2);
+        assert_eq!(bucket.capacity(), 10);
+        clock.advance(300);
+        assert_eq!(bucket.available_tokens(), 5);
+
+        assert!(bucket.set_rate_only(0.0).unwrap_err().is_invalid_config());
+    }
+
+    #[test]
+    #[cfg(feature = "no-panic")]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "capacity must be greater than 0")
+    )]
+    fn test_token_bucket_no_panic_clamps_input() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(0, -1.0, clock.clone());
+        assert_eq!(bucket.capacity(), 1);
+
+        // The rate is clamped to one too slow to refill a token within an hour
+        assert!(bucket.try_acquire(1).is_ok());
+        clock.advance(3_600_000);
+        assert!(bucket.try_acquire(1).is_err());
+
+        let bucket = TokenBucket::with_clock(1, 1e18, clock);
+        let max = interval::rate(crate::MAX_RATE);
+        assert!((bucket.rate_per_second() - max).abs() <= max * 1e-9);
+    }
+
+    #[test]
+    fn test_token_bucket_rejects_rates_above_max() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(10, 1.0, clock.clone());
+        assert_eq!(
+            bucket.update_config(10, 1e18).unwrap_err(),
+            RateLimitError::invalid_config("rate must not exceed MAX_RATE")
+        );
+        assert!(bucket.set_rate_only(f64::INFINITY).is_err());
+        assert!(TokenBucket::builder()
+            .tokens_per_second(1e18)
+            .build()
+            .unwrap_err()
+            .is_invalid_config());
+
+        // The maximum itself still refills sanely, which under `fixed` is the highest
+        // rate of the fixed-point interval
+        #[cfg(not(feature = "fixed"))]
+        let max = crate::MAX_RATE;
+        #[cfg(feature = "fixed")]
+        let max = interval::MAX_RATE;
+        assert!(bucket.update_config(10, max).is_ok());
+        assert!(bucket.try_acquire(10).is_ok());
+        clock.advance(1);
+        assert_eq!(bucket.available_tokens(), 10);
+        assert!(bucket.try_acquire(10).is_ok());
+    }
+
+    #[test]
+    fn test_token_bucket_rejects_non_finite_rates() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(10, 1.0, clock.clone());
+        let not_finite = RateLimitError::invalid_config("rate must be finite");
+        for rate in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert_eq!(bucket.update_config(10, rate).unwrap_err(), not_finite);
+            assert_eq!(bucket.set_rate_only(rate).unwrap_err(), not_finite);
+            let err = TokenBucket::builder()
+                .tokens_per_second(rate)
+                .build()
+                .unwrap_err();
+            assert_eq!(err, not_finite);
+            let mut state = bucket.snapshot();
+            state.tokens_per_second = rate;
+            let err = TokenBucket::from_state(state, clock.clone()).unwrap_err();
+            assert_eq!(err, not_finite);
+        }
+        assert_eq!(bucket.rate_per_second(), 1.0);
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-panic"))]
+    #[should_panic(expected = "rate must be finite")]
+    fn test_token_bucket_panics_on_nan_rate() {
+        let _ = TokenBucket::new(10, f64::NAN);
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-panic"))]
+    #[should_panic(expected = "rate must be finite")]
+    fn test_token_bucket_panics_on_infinite_rate() {
+        let _ = TokenBucket::with_clock(10, f64::INFINITY, crate::clock::MockClock::new(0));
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-panic"))]
+    #[should_panic(expected = "rate must not exceed MAX_RATE")]
+    fn test_token_bucket_panics_on_rate_above_max() {
+        let _ = TokenBucket::new(10, 1e18);
+    }
+
+    #[test]
+    fn test_token_bucket_state_keeps_sub_millisecond_progress() {
+        let clock = crate::clock::MockClock::new(0);
+        let bucket = TokenBucket::with_clock(10, 3.0, clock.clone());
+        assert!(bucket.try_acquire(10).is_ok());
+        clock.advance(500);
+
+        // The token refilled at 333.33 ms, and the next one accrues from there
+        let state = bucket.snapshot();
+        assert_eq!(state.tokens, 1);
+        assert_eq!(state.last_update_ns / 1000, 333_333);
+        assert_eq!(state.taken_at_ns, 500_000_000);
+
+        // Restored on a clock with another epoch, 166.67 ms are left to the next token
+        let clock = crate::clock::MockClock::new(10_000);
+        let restarted = TokenBucket::with_clock(10, 3.0, clock.clone());
+        restarted.restore(&state, Duration::ZERO);
+        assert_eq!(restarted.available_tokens(), 1);
+        clock.advance(166);
+        assert_eq!(restarted.available_tokens(), 1);
+        clock.advance(1);
+        assert_eq!(restarted.available_tokens(), 2);
+    }
+
+    #[test]
+    fn test_token_bucket_snapshot_restore() {
+        let clock = crate::clock::MockClock::new(1000);
+        let bucket = TokenBucket::with_clock(10, 2.0, clock.clone());
+        assert!(bucket.try_acquire(8).is_ok());
+        clock.advance(700);
+
+        let state = bucket.snapshot();
+        assert_eq!(state.capacity, 10);
+        assert_eq!(state.tokens_per_second, 2.0);
+        assert_eq!(state.tokens, 3);
+        assert_eq!(state.last_update_ns, 1_500_000_000);
+
+        // The partial token carries over, and no fresh burst is handed out
+        let restored = TokenBucket::from_state(state, clock.clone()).unwrap();
+        assert_eq!(restored.available_tokens(), 3);
+        clock.advance(300);
+        assert_eq!(restored.available_tokens(), 4);
+
+        // A timestamp from the future refills from now instead
+        let future = BucketState {
+            last_update_ns: 1_000_000_000_000,
+            ..state
+        };
+        let restored = TokenBucket::from_state(future, clock.clone()).unwrap();
+        assert_eq!(restored.available_tokens(), 3);
+        clock.advance(500);
+        assert_eq!(restored.available_tokens(), 4);
+
+        let invalid = BucketState {
+            capacity: 0,
+            ..state
+        };
+        assert!(TokenBucket::from_state(invalid, clock)
+            .unwrap_err()
+            .is_invalid_config());
+    }
 }
@@ -0,0 +1,311 @@
+//! Recording and replaying rate limiter decisions.
+//!
+//! A [`TraceLimiter`] wraps another limiter. In record mode it delegates every
+//! `try_acquire` to the inner limiter and logs the decision. In replay mode it ignores
+//! the inner limiter's state entirely and reproduces a previously recorded sequence of
+//! decisions, which makes it possible to reproduce a production incident locally or to
+//! drive a downstream system with exactly the same admit/reject pattern.
+//!
+//! With the `serde` feature enabled, [`TraceEntry`] can be serialized so traces can be
+//! stored and shipped between environments.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::{
+    clock::{Clock, SystemClock},
+    error::{RateLimitError, Result},
+    outcome::saturate_u32,
+    traits::{RateLimiter, Wrapper},
+};
+
+/// A single recorded admission decision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TraceEntry {
+    /// The clock time, in milliseconds, at which the decision was made.
+    pub timestamp: u64,
+    /// The number of tokens requested.
+    pub tokens: u32,
+    /// Whether the request was admitted.
+    pub allowed: bool,
+    /// The advertised retry-after in milliseconds, or 0 if the request was admitted.
+    pub retry_after_ms: u64,
+}
+
+#[derive(Debug)]
+enum Mode {
+    Record(Mutex<Vec<TraceEntry>>),
+    Replay {
+        entries: Vec<TraceEntry>,
+        cursor: AtomicUsize,
+    },
+}
+
+/// A rate limiter wrapper that records or replays a trace of decisions.
+///
+/// Once a replayed trace is exhausted, further requests are delegated to the inner
+/// limiter. Replay matches decisions to requests purely by order; the requested token
+/// count is not checked against the recording.
+#[derive(Debug)]
+pub struct TraceLimiter<L, C = SystemClock> {
+    inner: L,
+    clock: C,
+    mode: Mode,
+}
+
+impl<L, C> TraceLimiter<L, C>
+where
+    L: RateLimiter,
+    C: Clock,
+{
+    /// Creates a limiter that records every decision made by `inner`.
+    ///
+    /// Decisions are timestamped with `clock`.
+    pub fn record(inner: L, clock: C) -> Self {
+        Self {
+            inner,
+            clock,
+            mode: Mode::Record(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Creates a limiter that reproduces the decisions in `trace`, in order.
+    pub fn replay(inner: L, clock: C, trace: Vec<TraceEntry>) -> Self {
+        Self {
+            inner,
+            clock,
+            mode: Mode::Replay {
+                entries: trace,
+                cursor: AtomicUsize::new(0),
+            },
+        }
+    }
+
+    /// Returns whether this limiter is replaying a trace.
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.mode, Mode::Replay { .. })
+    }
+
+    /// Returns a copy of the trace.
+    ///
+    /// In record mode this is everything recorded so far; in replay mode it is the
+    /// trace being replayed.
+    pub fn trace(&self) -> Vec<TraceEntry> {
+        match &self.mode {
+            Mode::Record(entries) => entries.lock().unwrap().clone(),
+            Mode::Replay { entries, .. } => entries.clone(),
+        }
+    }
+
+    /// Returns the number of recorded decisions not yet replayed.
+    ///
+    /// Always returns 0 in record mode.
+    pub fn remaining(&self) -> usize {
+        match &self.mode {
+            Mode::Record(_) => 0,
+            Mode::Replay { entries, cursor } => {
+                entries.len().saturating_sub(cursor.load(Ordering::Acquire))
+            }
+        }
+    }
+
+    /// Records the result of `acquire` for `tokens`, or replays the next recorded
+    /// decision in its place.
+    fn decide(&self, tokens: u32, acquire: impl FnOnce(&L) -> Result<()>) -> Result<()> {
+        match &self.mode {
+            Mode::Record(entries) => {
+                let now = self.clock.now();
+                let result = acquire(&self.inner);
+                entries.lock().unwrap().push(TraceEntry {
+                    timestamp: now,
+                    tokens,
+                    allowed: result.is_ok(),
+                    retry_after_ms: result
+                        .as_ref()
+                        .err()
+                        .and_then(RateLimitError::retry_after_ms)
+                        .unwrap_or(0),
+                });
+                result
+            }
+            Mode::Replay { entries, cursor } => {
+                let index = cursor.fetch_add(1, Ordering::AcqRel);
+                match entries.get(index) {
+                    Some(entry) if entry.allowed => Ok(()),
+                    Some(entry) => Err(RateLimitError::rate_limit_exceeded(
+                        tokens,
+                        0,
+                        entry.retry_after_ms,
+                    )),
+                    None => acquire(&self.inner),
+                }
+            }
+        }
+    }
+}
+
+impl<L, C> RateLimiter for TraceLimiter<L, C>
+where
+    L: RateLimiter,
+    C: Clock,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        self.decide(tokens, |inner| inner.try_acquire(tokens))
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        self.decide(saturate_u32(tokens), |inner| inner.try_acquire_u64(tokens))
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        match &self.mode {
+            Mode::Replay { entries, cursor } => match entries.get(cursor.load(Ordering::Acquire)) {
+                Some(entry) if entry.allowed => Ok(()),
+                Some(entry) => Err(RateLimitError::rate_limit_exceeded(
+                    tokens,
+                    0,
+                    entry.retry_after_ms,
+                )),
+                None => self.inner.peek_acquire(tokens),
+            },
+            Mode::Record(_) => self.inner.peek_acquire(tokens),
+        }
+    }
+
+    fn available_tokens(&self) -> u32 {
+        self.inner.available_tokens()
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        self.inner.available_tokens_u64()
+    }
+
+    fn capacity(&self) -> u32 {
+        self.inner.capacity()
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.inner.capacity_u64()
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        self.inner.rate_per_second()
+    }
+
+    fn effective_rate_per_second(&self) -> f64 {
+        self.inner.effective_rate_per_second()
+    }
+
+    fn resolution_warning(&self) -> Option<&'static str> {
+        self.inner.resolution_warning()
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        self.inner.time_until_next_token_ms()
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.inner.time_until_available_ms(tokens)
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        self.inner.retry_at(tokens)
+    }
+}
+
+impl<L, C> Wrapper for TraceLimiter<L, C> {
+    type Inner = L;
+
+    fn get_ref(&self) -> &L {
+        &self.inner
+    }
+
+    fn get_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    fn into_inner(self) -> L {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, TokenBucket};
+
+    fn record_workload() -> Vec<TraceEntry> {
+        let clock = MockClock::new(0);
+        let limiter = TraceLimiter::record(
+            TokenBucket::with_clock(3, 2.0, clock.clone()),
+            clock.clone(),
+        );
+
+        for step in 0..10 {
+            let _ = limiter.try_acquire(1 + step % 2);
+            clock.advance(200);
+        }
+
+        limiter.trace()
+    }
+
+    #[test]
+    fn test_record_then_replay_reproduces_decisions() {
+        let trace = record_workload();
+        assert_eq!(trace.len(), 10);
+        assert!(trace.iter().any(|entry| entry.allowed));
+        assert!(trace.iter().any(|entry| !entry.allowed));
+
+        // Replay against a bucket whose own state would admit everything
+        let clock = MockClock::new(0);
+        let replay = TraceLimiter::replay(
+            TokenBucket::with_clock(1000, 1000.0, clock.clone()),
+            clock,
+            trace.clone(),
+        );
+        assert!(replay.is_replaying());
+
+        for entry in &trace {
+            let result = replay.try_acquire(entry.tokens);
+            assert_eq!(result.is_ok(), entry.allowed);
+            if !entry.allowed {
+                assert_eq!(
+                    result.unwrap_err().retry_after_ms(),
+                    Some(entry.retry_after_ms)
+                );
+            }
+        }
+        assert_eq!(replay.remaining(), 0);
+
+        // Once exhausted, replay falls back to the inner limiter
+        assert!(replay.try_acquire(1).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_trace_serde_round_trip() {
+        let trace = record_workload();
+        let json = serde_json::to_string(&trace).unwrap();
+        let decoded: Vec<TraceEntry> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, trace);
+    }
+
+    #[test]
+    fn test_unwrap_nested_wrappers() {
+        use crate::bounded::BoundedReconfigurable;
+
+        let clock = MockClock::new(0);
+        let bucket = TokenBucket::with_clock(10, 5.0, clock.clone());
+        let bounded = BoundedReconfigurable::new(bucket, 1..=20, 1.0..=10.0).unwrap();
+        let mut stack = TraceLimiter::record(bounded, clock);
+
+        assert!(stack.try_acquire(4).is_ok());
+        assert_eq!(stack.get_ref().get_ref().available_tokens(), 6);
+        assert_eq!(stack.get_mut().get_mut().capacity(), 10);
+
+        assert_eq!(stack.trace().len(), 1);
+        let bucket = stack.into_inner().into_inner();
+        assert_eq!(bucket.available_tokens(), 6);
+    }
+}
@@ -5,7 +5,13 @@
 
 use core::time::Duration;
 
-use crate::error::Result;
+use crate::{
+    error::{RateLimitError, Result},
+    float,
+    outcome::{used_burst, Outcome},
+    schedule::Schedule,
+    status::{BucketState, LimiterStatus},
+};
 
 /// A trait for rate limiting algorithms.
 ///
@@ -30,20 +36,171 @@ pub trait RateLimiter: Send + Sync + 'static {
     /// * `Err(RateLimitError::InvalidConfiguration)` if the rate limiter is misconfigured
     fn try_acquire(&self, tokens: u32) -> Result<()>;
 
+    /// Attempts to acquire a number of tokens that may not fit in a `u32`.
+    ///
+    /// This is intended for large-magnitude budgets such as byte counts. Counts above
+    /// `u32::MAX` are reported saturated in the returned error.
+    ///
+    /// The default implementation forwards counts that fit in a `u32` to `try_acquire`
+    /// and rejects larger ones without waiting, since they exceed any `u32` capacity.
+    /// The buckets override it to support their full `u64` range.
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        match u32::try_from(tokens) {
+            Ok(tokens) => self.try_acquire(tokens),
+            Err(_) => Err(RateLimitError::rate_limit_exceeded(
+                u32::MAX,
+                self.available_tokens(),
+                0,
+            )),
+        }
+    }
+
+    /// Acquires as many tokens as are available, up to `max_tokens`, and returns how
+    /// many were acquired.
+    ///
+    /// This never waits or fails: it returns 0 when no tokens are available. It suits
+    /// workloads that can proceed with fewer tokens than they would like, such as
+    /// draining a queue in batches at whatever rate the limiter permits.
+    ///
+    /// The default implementation acquires the available count with `try_acquire`,
+    /// retrying with the count a rejection reports if a concurrent acquisition took
+    /// some of the tokens first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bucketboss::{RateLimiter, TokenBucket};
+    ///
+    /// let bucket = TokenBucket::new(10, 1.0);
+    /// assert_eq!(bucket.try_acquire_up_to(4), 4);
+    /// assert_eq!(bucket.try_acquire_up_to(100), 6);
+    /// assert_eq!(bucket.try_acquire_up_to(100), 0);
+    /// ```
+    fn try_acquire_up_to(&self, max_tokens: u32) -> u32 {
+        let mut tokens = self.available_tokens().min(max_tokens);
+        while tokens > 0 {
+            match self.try_acquire(tokens) {
+                Ok(()) => break,
+                Err(RateLimitError::RateLimitExceeded { available, .. }) if available < tokens => {
+                    tokens = available;
+                }
+                Err(_) => return 0,
+            }
+        }
+        tokens
+    }
+
+    /// Reports whether `tokens` could be acquired now, without acquiring them.
+    ///
+    /// This makes the same decision as [`try_acquire`](Self::try_acquire), including
+    /// the `retry_after_ms` of a rejection, but leaves the limiter untouched, so a
+    /// scheduler can decide whether to enqueue work before committing to it. A
+    /// concurrent acquisition can of course still take the tokens before the caller
+    /// follows up with `try_acquire`.
+    ///
+    /// The default implementation derives the decision from
+    /// [`time_until_available_ms`](Self::time_until_available_ms) and
+    /// `available_tokens`. The buckets override it to compute their refill as of now
+    /// without storing it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bucketboss::{clock::MockClock, RateLimiter, TokenBucket};
+    ///
+    /// let bucket = TokenBucket::with_clock(5, 10.0, MockClock::new(0));
+    /// assert!(bucket.try_acquire(3).is_ok());
+    ///
+    /// let err = bucket.peek_acquire(4).unwrap_err();
+    /// assert_eq!(err.retry_after_ms(), Some(200));
+    /// assert_eq!(bucket.available_tokens(), 2);
+    /// ```
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        match self.time_until_available_ms(tokens) {
+            Some(0) => Ok(()),
+            wait => Err(RateLimitError::rate_limit_exceeded(
+                tokens,
+                self.available_tokens(),
+                // A request that can never fit is rejected without a wait
+                wait.unwrap_or(0),
+            )),
+        }
+    }
+
     /// Returns the number of tokens currently available.
     ///
     /// This is a non-consuming operation that doesn't affect the rate limiter state.
     /// It can be used to check the current rate limit status without consuming any tokens.
     fn available_tokens(&self) -> u32;
 
+    /// Returns the number of tokens currently available, without saturating at
+    /// `u32::MAX`.
+    ///
+    /// The default implementation widens `available_tokens`.
+    fn available_tokens_u64(&self) -> u64 {
+        u64::from(self.available_tokens())
+    }
+
     /// Returns the maximum number of tokens that can be held in the bucket.
     ///
     /// This represents the burst capacity of the rate limiter.
     fn capacity(&self) -> u32;
 
+    /// Returns the capacity without saturating at `u32::MAX`.
+    ///
+    /// Limiters whose capacity can exceed `u32::MAX` report it in full here, while
+    /// `capacity` saturates to `u32::MAX` rather than truncating. The default
+    /// implementation widens `capacity`.
+    fn capacity_u64(&self) -> u64 {
+        u64::from(self.capacity())
+    }
+
+    /// Returns how full the limiter is as a single byte, from 0 (empty) to 255 (full).
+    ///
+    /// This is `available_tokens_u64 * 255 / capacity_u64`, rounded down, intended for
+    /// packing the state of many limiters into compact telemetry. A limiter with zero
+    /// capacity reports 0.
+    fn fill_level_u8(&self) -> u8 {
+        let capacity = self.capacity_u64();
+        if capacity == 0 {
+            return 0;
+        }
+        let available = self.available_tokens_u64().min(capacity);
+        (u128::from(available) * 255 / u128::from(capacity)) as u8
+    }
+
     /// Returns the rate at which tokens are replenished, in tokens per second.
     fn rate_per_second(&self) -> f64;
 
+    /// Returns the expected end-to-end admission rate, in tokens per second.
+    ///
+    /// For a plain limiter this is the same as [`rate_per_second`](Self::rate_per_second),
+    /// which wrappers report from the innermost limiter. Wrappers that change how much
+    /// gets through override this to account for their own effect on top of the inner
+    /// limiter's effective rate: a circuit breaker that is not closed reports 0, for
+    /// example, and a scaled limiter converts the rate into the caller's units. Stacked
+    /// wrappers thus compose down to the rate that actually reaches the caller.
+    ///
+    /// The default implementation returns `rate_per_second`.
+    fn effective_rate_per_second(&self) -> f64 {
+        self.rate_per_second()
+    }
+
+    /// Returns a warning if the rate is finer than the limiter's clock can resolve, or
+    /// `None` if it is not.
+    ///
+    /// A clock that ticks every [`resolution_ms`](crate::Clock::resolution_ms)
+    /// milliseconds offers at most `1000 / resolution_ms` distinct refill points per
+    /// second. Above that rate the tokens of several refills arrive together at each
+    /// tick, so admissions come in bursts instead of being evenly paced, although the
+    /// total admitted over time is still right.
+    ///
+    /// The default implementation returns `None`, for limiters that cannot see their
+    /// clock; the buckets check their rate against their clock.
+    fn resolution_warning(&self) -> Option<&'static str> {
+        None
+    }
+
     /// Returns the time until the next token will be available, in milliseconds.
     ///
     /// Returns `None` if tokens are currently available or if the rate limiter is empty.
@@ -55,6 +212,162 @@ pub trait RateLimiter: Send + Sync + 'static {
     fn time_until_next_token(&self) -> Option<Duration> {
         self.time_until_next_token_ms().map(Duration::from_millis)
     }
+
+    /// Returns the time until `tokens` could be acquired, in milliseconds.
+    ///
+    /// Returns `Some(0)` if the tokens are available now, and `None` if they can never
+    /// be acquired because `tokens` exceeds the capacity or the rate is zero.
+    ///
+    /// The default implementation estimates the wait from `available_tokens` and
+    /// `rate_per_second`.
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        let available = self.available_tokens();
+        if tokens <= available {
+            return Some(0);
+        }
+        let rate = self.rate_per_second();
+        if tokens > self.capacity() || rate <= 0.0 {
+            return None;
+        }
+        Some(float::ceil((tokens - available) as f64 * 1000.0 / rate) as u64)
+    }
+
+    /// Returns the time until `tokens` could be acquired as a `Duration`.
+    ///
+    /// See [`time_until_available_ms`](Self::time_until_available_ms).
+    fn time_until_available(&self, tokens: u32) -> Option<Duration> {
+        self.time_until_available_ms(tokens)
+            .map(Duration::from_millis)
+    }
+
+    /// Returns the clock timestamp, in milliseconds, at which `tokens` could be acquired.
+    ///
+    /// This is the absolute counterpart of
+    /// [`time_until_available_ms`](Self::time_until_available_ms), suitable for storing
+    /// in a job scheduler: unlike a relative delay, it does not go stale while the retry
+    /// waits in a queue. The timestamp is expressed in the limiter's own clock.
+    ///
+    /// Returns `None` if the tokens can never be acquired, or if the limiter has no clock
+    /// to anchor the timestamp to. The default implementation always returns `None`.
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        let _ = tokens;
+        None
+    }
+
+    /// Returns the clock timestamps at which each of the next `n` tokens would become
+    /// available, without consuming anything.
+    ///
+    /// This lets a scheduler lay out a batch of tasks on a timeline up front: the tokens
+    /// available now are due immediately, the rest one refill interval apart. See
+    /// [`Schedule`] for the assumptions behind the timeline.
+    ///
+    /// The default implementation derives the timeline from
+    /// [`retry_at`](Self::retry_at) and the rate, and so is empty for limiters without
+    /// a clock.
+    fn schedule(&self, n: u32) -> Schedule {
+        let available = self.available_tokens().min(n);
+        let rate = self.rate_per_second();
+        let Some(now) = self.retry_at(0) else {
+            return Schedule::empty();
+        };
+        if rate <= 0.0 {
+            return Schedule::new(now, available, available, 0.0, 0.0);
+        }
+
+        let interval = 1000.0 / rate;
+        let first_refill = self
+            .retry_at(available.saturating_add(1))
+            .map_or(now as f64 + interval, |at| at as f64);
+        Schedule::new(now, n, available, first_refill, interval)
+    }
+
+    /// Attempts to acquire the specified number of tokens, describing the result as an
+    /// [`Outcome`] instead of a `Result`.
+    ///
+    /// The default implementation is derived from `try_acquire`. A configuration error
+    /// is reported as `Throttled` with a `retry_after` of `Duration::MAX`, since the
+    /// request can never succeed.
+    fn acquire_outcome(&self, tokens: u32) -> Outcome {
+        match self.try_acquire(tokens) {
+            Ok(()) => {
+                let remaining = self.available_tokens();
+                Outcome::Admitted {
+                    remaining,
+                    used_burst: used_burst(
+                        remaining as u64,
+                        self.capacity_u64(),
+                        self.rate_per_second(),
+                    ),
+                }
+            }
+            Err(RateLimitError::RateLimitExceeded {
+                available,
+                retry_after_ms,
+                ..
+            }) => Outcome::Throttled {
+                retry_after: Duration::from_millis(retry_after_ms),
+                available,
+            },
+            Err(RateLimitError::InvalidConfiguration { .. }) => Outcome::Throttled {
+                retry_after: Duration::MAX,
+                available: 0,
+            },
+        }
+    }
+
+    /// Attempts to acquire the specified number of tokens, returning only the wait on
+    /// rejection.
+    ///
+    /// This is `try_acquire` for callers that just need the wait, for example to set a
+    /// `Retry-After` header, and consumes the tokens on success in the same way. A
+    /// configuration error is reported as a wait of `Duration::MAX`, as in
+    /// [`acquire_outcome`](Self::acquire_outcome).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use bucketboss::{clock::MockClock, RateLimiter, TokenBucket};
+    ///
+    /// let bucket = TokenBucket::with_clock(1, 2.0, MockClock::new(0));
+    /// assert_eq!(bucket.check(1), Ok(()));
+    /// assert_eq!(bucket.check(1), Err(Duration::from_millis(500)));
+    /// ```
+    fn check(&self, tokens: u32) -> core::result::Result<(), Duration> {
+        self.try_acquire(tokens).map_err(|err| match err {
+            RateLimitError::RateLimitExceeded { retry_after_ms, .. } => {
+                Duration::from_millis(retry_after_ms)
+            }
+            RateLimitError::InvalidConfiguration { .. } => Duration::MAX,
+        })
+    }
+
+    /// Attempts to acquire one token and suggests how long to wait before the next
+    /// attempt.
+    ///
+    /// The suggestion is a pacing hint for clients that adapt to the limiter instead of
+    /// knowing its rate upfront: waiting as suggested before every call settles into one
+    /// admission per emission interval, without hammering the limiter with rejected
+    /// attempts. After a rejection it is the time until a token is available.
+    ///
+    /// The default implementation suggests a full emission interval after an
+    /// admission. [`TokenBucket`](crate::TokenBucket) and
+    /// [`LeakyBucket`](crate::LeakyBucket) override it to subtract the time already
+    /// accrued towards the next token or drain.
+    fn acquire_probe(&self) -> (bool, Duration) {
+        match self.acquire_outcome(1) {
+            Outcome::Admitted { .. } => (
+                true,
+                Duration::try_from_secs_f64(1.0 / self.rate_per_second()).unwrap_or(Duration::MAX),
+            ),
+            Outcome::Throttled { retry_after, .. } => (false, retry_after),
+        }
+    }
+
+    /// Returns a snapshot of the limiter's current state.
+    fn status(&self) -> LimiterStatus {
+        LimiterStatus::of(self)
+    }
 }
 
 /// A trait for rate limiters that can be configured with a custom clock.
@@ -87,6 +400,72 @@ pub trait ReconfigurableRateLimiter: RateLimiter {
     /// * `Ok(())` if the configuration was successfully updated
     /// * `Err(RateLimitError::InvalidConfiguration)` if the new configuration is invalid
     fn update_config(&self, capacity: u32, tokens_per_second: f64) -> Result<()>;
+
+    /// Changes only the refill rate, keeping the capacity and the tokens currently
+    /// available.
+    ///
+    /// Tokens accrued so far are credited at the old rate before the new one applies.
+    /// The default implementation calls `update_config` with the current capacity,
+    /// which for some limiters also resets the available tokens; the buckets override
+    /// it to leave them untouched.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the rate was successfully updated
+    /// * `Err(RateLimitError::InvalidConfiguration)` if the new rate is invalid
+    fn set_rate_only(&self, tokens_per_second: f64) -> Result<()> {
+        self.update_config(self.capacity(), tokens_per_second)
+    }
+}
+
+/// A trait for rate limiters whose available tokens can be adjusted directly.
+///
+/// Adjustments take effect immediately and are atomic with respect to concurrent
+/// acquisitions. They never move the limiter outside its normal range: the available
+/// count stays between 0 and the capacity.
+pub trait AdjustableRateLimiter: RateLimiter {
+    /// Returns `tokens` to the limiter, for example when an admitted request was
+    /// cancelled before doing any work.
+    ///
+    /// The available count is capped at the capacity.
+    fn refund(&self, tokens: u32);
+
+    /// Removes `tokens` from the limiter without admitting a request.
+    ///
+    /// This is the inverse of [`refund`](Self::refund), intended for tightening a limit
+    /// in response to out-of-band signals such as detected abuse. The available count
+    /// is floored at 0, and the removed tokens come back at the normal rate.
+    fn penalize(&self, tokens: u32);
+}
+
+/// A trait for rate limiters whose state can be snapshotted and restored, for example
+/// to survive a restart.
+pub trait StatefulRateLimiter: RateLimiter {
+    /// Takes a [`BucketState`] of the limiter's configuration and fill level.
+    ///
+    /// The tokens accrued up to now are credited first; the state's `last_update_ns`
+    /// keeps the progress towards the next one.
+    fn snapshot(&self) -> BucketState;
+
+    /// Replaces the limiter's fill level with that of `state`, a snapshot taken
+    /// `elapsed` ago, possibly by a limiter on a clock with another epoch.
+    ///
+    /// The refill or drain that accrued between the state's `last_update_ns` and now,
+    /// `elapsed` included, is credited. The limiter keeps its own configuration, and
+    /// the level is clamped to its capacity.
+    fn restore(&self, state: &BucketState, elapsed: Duration);
+}
+
+/// A trait for rate limiters that can be forced to empty or full, for example to reuse
+/// one limiter across test cases without waiting for real time to pass.
+pub trait Resettable: RateLimiter {
+    /// Makes the full capacity available, as if the limiter had been idle for a long
+    /// time.
+    fn reset(&self);
+
+    /// Makes nothing available, as if the full capacity had just been acquired. The
+    /// capacity comes back at the normal rate from now.
+    fn drain(&self);
 }
 
 /// A builder trait for creating rate limiters with a fluent interface.
@@ -111,6 +490,53 @@ pub trait RateLimiterBuilder: Sized {
     ///
     /// A new instance of the rate limiter with the specified configuration.
     fn build(self) -> Result<Self::Limiter>;
+
+    /// Attributes the built limiter's rejections to `label`.
+    ///
+    /// See [`DecoratedBuilder`](crate::DecoratedBuilder) for how decorators combine.
+    #[cfg(feature = "std")]
+    fn label(self, label: &'static str) -> crate::DecoratedBuilder<Self> {
+        crate::DecoratedBuilder::new(self).label(label)
+    }
+
+    /// Adds a random delay of up to `max` to the `retry_after` of the built limiter's
+    /// rejections.
+    ///
+    /// See [`DecoratedBuilder`](crate::DecoratedBuilder) for how decorators combine.
+    #[cfg(feature = "std")]
+    fn jitter(self, max: Duration) -> crate::DecoratedBuilder<Self> {
+        crate::DecoratedBuilder::new(self).jitter(max)
+    }
+
+    /// Calls `observer` with the result of every acquire from the built limiter.
+    ///
+    /// See [`DecoratedBuilder`](crate::DecoratedBuilder) for how decorators combine.
+    #[cfg(feature = "std")]
+    fn observer<P>(self, observer: P) -> crate::DecoratedBuilder<Self, P>
+    where
+        P: crate::Observer,
+    {
+        crate::DecoratedBuilder::new(self).observer(observer)
+    }
+}
+
+/// A trait for rate limiters that wrap another limiter.
+///
+/// This mirrors the `get_ref`/`get_mut`/`into_inner` convention of the `std::io`
+/// adapters, so wrapped limiters can always be reached to read their raw configuration
+/// or to reconfigure them. Stacks of wrappers can be peeled one layer at a time.
+pub trait Wrapper {
+    /// The type of the wrapped value.
+    type Inner;
+
+    /// Returns a reference to the wrapped value.
+    fn get_ref(&self) -> &Self::Inner;
+
+    /// Returns a mutable reference to the wrapped value.
+    fn get_mut(&mut self) -> &mut Self::Inner;
+
+    /// Consumes the wrapper, returning the wrapped value.
+    fn into_inner(self) -> Self::Inner;
 }
 
 #[cfg(test)]
@@ -129,7 +555,7 @@ mod tests {
             if tokens <= self.available {
                 Ok(())
             } else {
-                Err(crate::error::RateLimitError::rate_limit_exceeded(
+                Err(RateLimitError::rate_limit_exceeded(
                     tokens,
                     self.available,
                     1000,
@@ -180,5 +606,114 @@ mod tests {
         assert!(limiter.try_acquire(6).is_err());
 
         assert_eq!(limiter.time_until_next_token_ms(), None);
+
+        // Without a clock there is nothing to anchor a schedule to
+        assert_eq!(limiter.schedule(3).count(), 0);
+    }
+
+    #[test]
+    fn test_default_acquire_outcome() {
+        let limiter = TestRateLimiter {
+            available: 5,
+            capacity: 10,
+            rate: 1.0,
+        };
+
+        assert_eq!(
+            limiter.acquire_outcome(5),
+            Outcome::Admitted {
+                remaining: 5,
+                used_burst: true
+            }
+        );
+        assert_eq!(
+            limiter.acquire_outcome(6),
+            Outcome::Throttled {
+                retry_after: Duration::from_secs(1),
+                available: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn test_default_check() {
+        let limiter = TestRateLimiter {
+            available: 5,
+            capacity: 10,
+            rate: 1.0,
+        };
+
+        assert_eq!(limiter.check(5), Ok(()));
+        assert_eq!(limiter.check(6), Err(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_default_try_acquire_up_to() {
+        let limiter = TestRateLimiter {
+            available: 5,
+            capacity: 10,
+            rate: 1.0,
+        };
+        assert_eq!(limiter.try_acquire_up_to(3), 3);
+        assert_eq!(limiter.try_acquire_up_to(8), 5);
+
+        let empty = TestRateLimiter {
+            available: 0,
+            capacity: 10,
+            rate: 1.0,
+        };
+        assert_eq!(empty.try_acquire_up_to(8), 0);
+    }
+
+    #[test]
+    fn test_default_peek_acquire() {
+        let limiter = TestRateLimiter {
+            available: 5,
+            capacity: 10,
+            rate: 2.0,
+        };
+        assert!(limiter.peek_acquire(5).is_ok());
+        assert_eq!(
+            limiter.peek_acquire(8).unwrap_err(),
+            RateLimitError::rate_limit_exceeded(8, 5, 1500)
+        );
+        assert_eq!(
+            limiter.peek_acquire(11).unwrap_err(),
+            RateLimitError::rate_limit_exceeded(11, 5, 0)
+        );
+    }
+
+    #[test]
+    fn test_default_acquire_probe() {
+        let limiter = |available| TestRateLimiter {
+            available,
+            capacity: 10,
+            rate: 4.0,
+        };
+
+        assert_eq!(
+            limiter(1).acquire_probe(),
+            (true, Duration::from_millis(250))
+        );
+        assert_eq!(limiter(0).acquire_probe(), (false, Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_fill_level_u8() {
+        let fill = |available, capacity| {
+            TestRateLimiter {
+                available,
+                capacity,
+                rate: 1.0,
+            }
+            .fill_level_u8()
+        };
+
+        assert_eq!(fill(0, 10), 0);
+        assert_eq!(fill(10, 10), 255);
+        assert_eq!(fill(5, 10), 127);
+        assert_eq!(fill(u32::MAX, u32::MAX), 255);
+        assert_eq!(fill(1, u32::MAX), 0);
+        assert_eq!(fill(0, 0), 0);
     }
 }
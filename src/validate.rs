@@ -0,0 +1,136 @@
+//! Constructor input checks shared by the bucket implementations.
+//!
+//! By default invalid constructor input panics. With the `no-panic` feature the checks
+//! become `debug_assert!`s and invalid values are clamped to the nearest valid one, so
+//! release builds never carry the panic path.
+//!
+//! Input given at runtime, such as a reconfiguration, is checked with the `try_`
+//! variants instead, which report invalid values as errors.
+
+use crate::error::{RateLimitError, Result};
+
+/// The highest rate, in tokens per second, that a limiter accepts.
+///
+/// Above this the time per token falls below a femtosecond, where the refill arithmetic
+/// no longer resolves individual tokens: every clock tick would fill any bucket at
+/// once. Constructors reject higher rates like non-positive ones (clamping them to this
+/// value under `no-panic`), and reconfiguration reports them as `InvalidConfiguration`.
+pub const MAX_RATE: f64 = 1e15;
+
+/// The message reported for rates above [`MAX_RATE`].
+const RATE_TOO_HIGH: &str = "rate must not exceed MAX_RATE";
+
+/// The message reported for NaN and infinite rates.
+const RATE_NOT_FINITE: &str = "rate must be finite";
+
+/// The rate substituted for a non-positive rate when the `no-panic` feature is enabled.
+///
+/// It is small enough that the limiter effectively never refills.
+#[cfg_attr(not(feature = "no-panic"), allow(dead_code))]
+pub(crate) const MIN_CLAMPED_RATE: f64 = f64::EPSILON;
+
+/// Checks that a capacity is non-zero, clamping it to 1 under `no-panic`.
+#[inline]
+pub(crate) const fn capacity(value: u64, message: &'static str) -> u64 {
+    #[cfg(not(feature = "no-panic"))]
+    assert!(value > 0, "{}", message);
+    #[cfg(feature = "no-panic")]
+    debug_assert!(value > 0, "{}", message);
+
+    if value > 0 {
+        value
+    } else {
+        1
+    }
+}
+
+/// Checks that a rate is finite, positive and at most [`MAX_RATE`], clamping it to
+/// [`MIN_CLAMPED_RATE`] or `MAX_RATE` under `no-panic`.
+///
+/// NaN is clamped like a non-positive rate and infinity like one above `MAX_RATE`.
+#[inline]
+pub(crate) fn rate(value: f64, message: &'static str) -> f64 {
+    #[cfg(not(feature = "no-panic"))]
+    {
+        assert!(value.is_finite(), "{}", RATE_NOT_FINITE);
+        assert!(value > 0.0, "{}", message);
+        assert!(value <= MAX_RATE, "{}", RATE_TOO_HIGH);
+        value
+    }
+    #[cfg(feature = "no-panic")]
+    {
+        debug_assert!(value.is_finite(), "{}", RATE_NOT_FINITE);
+        debug_assert!(value > 0.0, "{}", message);
+        debug_assert!(value <= MAX_RATE, "{}", RATE_TOO_HIGH);
+        if value > 0.0 {
+            value.min(MAX_RATE)
+        } else {
+            MIN_CLAMPED_RATE
+        }
+    }
+}
+
+/// Checks that an already validated rate is at most `max`, the limit of a limiter whose
+/// arithmetic resolves less than [`MAX_RATE`], clamping it to `max` under `no-panic`.
+#[inline]
+pub(crate) fn rate_at_most(value: f64, max: f64, message: &'static str) -> f64 {
+    #[cfg(not(feature = "no-panic"))]
+    assert!(value <= max, "{}", message);
+    #[cfg(feature = "no-panic")]
+    debug_assert!(value <= max, "{}", message);
+
+    value.min(max)
+}
+
+/// Checks that an already validated rate is at least `min`, the limit of a limiter whose
+/// arithmetic cannot represent arbitrarily slow rates, clamping it to `min` under
+/// `no-panic`.
+#[cfg_attr(not(feature = "fixed"), allow(dead_code))]
+#[inline]
+pub(crate) fn rate_at_least(value: f64, min: f64, message: &'static str) -> f64 {
+    #[cfg(not(feature = "no-panic"))]
+    assert!(value >= min, "{}", message);
+    #[cfg(feature = "no-panic")]
+    debug_assert!(value >= min, "{}", message);
+
+    value.max(min)
+}
+
+/// Checks that a rate is finite, positive and at most [`MAX_RATE`], reporting `message`
+/// if it is not positive.
+#[inline]
+pub(crate) fn try_rate(value: f64, message: &'static str) -> Result<()> {
+    if !value.is_finite() {
+        return Err(RateLimitError::invalid_config(RATE_NOT_FINITE));
+    }
+    if value <= 0.0 {
+        return Err(RateLimitError::invalid_config(message));
+    }
+    if value > MAX_RATE {
+        return Err(RateLimitError::invalid_config(RATE_TOO_HIGH));
+    }
+    Ok(())
+}
+
+/// Checks that an already validated rate is at most `max`, reporting `message` if not.
+#[cfg_attr(
+    not(any(feature = "fixed", all(feature = "serde", feature = "std"))),
+    allow(dead_code)
+)]
+#[inline]
+pub(crate) fn try_rate_at_most(value: f64, max: f64, message: &'static str) -> Result<()> {
+    if value > max {
+        return Err(RateLimitError::invalid_config(message));
+    }
+    Ok(())
+}
+
+/// Checks that an already validated rate is at least `min`, reporting `message` if not.
+#[cfg_attr(not(feature = "fixed"), allow(dead_code))]
+#[inline]
+pub(crate) fn try_rate_at_least(value: f64, min: f64, message: &'static str) -> Result<()> {
+    if value < min {
+        return Err(RateLimitError::invalid_config(message));
+    }
+    Ok(())
+}
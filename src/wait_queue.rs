@@ -0,0 +1,461 @@
+//! First-come-first-served async acquisition.
+//!
+//! [`AsyncRateLimiter::acquire`](crate::AsyncRateLimiter::acquire) sleeps and retries,
+//! so when many tasks wait on one limiter, whichever happens to retry first after a
+//! refill wins. Under sustained load some callers can lose every race and starve.
+//! [`WaitQueue`] lines the waiters up instead: only the waiter at the head of the
+//! queue tries to acquire, and the others wait their turn in arrival order.
+//!
+//! Under heavy oversubscription the line grows and each waiter waits longer than the
+//! last. With [`with_max_queue_wait`](WaitQueue::with_max_queue_wait), the queue sheds
+//! load instead: a new acquire is rejected outright when the waiters ahead of it would
+//! take longer than the limit to serve at the limiter's rate.
+//!
+//! This module is only available with the `async` feature.
+
+use core::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tokio::sync::{Mutex, Notify};
+
+use crate::{
+    error::{RateLimitError, Result},
+    traits::{AdjustableRateLimiter, RateLimiter, Wrapper},
+};
+
+/// A wrapper whose [`acquire`](Self::acquire) grants tokens in arrival order.
+///
+/// Waiters queue on a fair lock, so they reach the head of the queue in the order
+/// their `acquire` was first polled. The head waiter sleeps until the inner limiter
+/// advertises its tokens, or until tokens are returned through the wrapper's
+/// [`refund`](AdjustableRateLimiter::refund), and keeps the head until it has them. A
+/// waiter for many tokens therefore holds up the ones behind it, even if they would fit
+/// now; that is the price of never letting small requests starve a large one.
+///
+/// Dropping an `acquire` future leaves the queue without consuming anything. The
+/// synchronous [`RateLimiter`] methods are forwarded to the inner limiter unchanged, so
+/// `try_acquire` through the wrapper does not wait its turn.
+///
+/// # Shedding
+///
+/// With [`with_max_queue_wait`](Self::with_max_queue_wait), each acquire projects its
+/// wait as the time the inner limiter's rate takes to produce the tokens of every
+/// waiter in line plus its own, less those available now. Since the line is served in
+/// order, that is when its turn comes. A request whose projected wait exceeds the
+/// limit is rejected immediately without joining the line, and an admitted one gives
+/// up once it has waited that long. The projection assumes the rate is spent on the
+/// line, so synchronous `try_acquire` calls alongside can still make an admitted
+/// request run out of time.
+///
+/// # Examples
+///
+/// ```
+/// use bucketboss::{TokenBucket, WaitQueue};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let queue = WaitQueue::new(TokenBucket::new(10, 10.0));
+/// assert!(queue.acquire(1).await.is_ok());
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct WaitQueue<L> {
+    inner: L,
+    /// Held by the waiter at the head of the queue.
+    head: Mutex<()>,
+    /// Wakes the head waiter when tokens are returned.
+    released: Notify,
+    /// The longest projected wait a new waiter may face, if the queue sheds load.
+    max_queue_wait_ms: Option<u64>,
+    /// The tokens the waiters in line are queued for.
+    queued: AtomicU64,
+}
+
+/// Releases a waiter's place in line when its acquire completes or is dropped.
+struct Place<'a> {
+    queued: &'a AtomicU64,
+    tokens: u64,
+}
+
+impl Drop for Place<'_> {
+    fn drop(&mut self) {
+        let _ = self.queued.fetch_sub(self.tokens, Ordering::AcqRel);
+    }
+}
+
+impl<L> WaitQueue<L>
+where
+    L: RateLimiter,
+{
+    /// Creates a new wrapper queueing the async acquires of `inner`.
+    pub fn new(inner: L) -> Self {
+        Self {
+            inner,
+            head: Mutex::new(()),
+            released: Notify::new(),
+            max_queue_wait_ms: None,
+            queued: AtomicU64::new(0),
+        }
+    }
+
+    /// Sheds acquires projected to wait longer than `max_queue_wait`, and gives up on
+    /// admitted ones that have not acquired their tokens within it.
+    ///
+    /// See [Shedding](Self#shedding) for how the wait is projected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use bucketboss::{TokenBucket, WaitQueue};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let queue = WaitQueue::new(TokenBucket::new(10, 10.0))
+    ///     .with_max_queue_wait(Duration::from_millis(500));
+    /// assert!(queue.acquire(1).await.is_ok());
+    /// # }
+    /// ```
+    pub fn with_max_queue_wait(mut self, max_queue_wait: Duration) -> Self {
+        self.max_queue_wait_ms = Some(max_queue_wait.as_millis().min(u64::MAX as u128) as u64);
+        self
+    }
+
+    /// Returns the longest projected wait a new acquire may face, if the queue sheds
+    /// load.
+    pub fn max_queue_wait(&self) -> Option<Duration> {
+        self.max_queue_wait_ms.map(Duration::from_millis)
+    }
+
+    /// Returns the number of tokens the waiters in line are queued for.
+    pub fn queued_tokens(&self) -> u64 {
+        self.queued.load(Ordering::Acquire)
+    }
+
+    /// Returns the projected wait, in milliseconds, of a request for `tokens` joining
+    /// the line now, or `None` if the inner limiter does not refill.
+    pub fn projected_wait_ms(&self, tokens: u32) -> Option<u64> {
+        self.projection(self.queued_tokens(), tokens)
+    }
+
+    /// Projects the wait for `tokens` behind `queued` tokens.
+    fn projection(&self, queued: u64, tokens: u32) -> Option<u64> {
+        let needed = (queued + tokens as u64).saturating_sub(self.inner.available_tokens_u64());
+        if needed == 0 {
+            return Some(0);
+        }
+        let rate = self.inner.rate_per_second();
+        if rate.is_nan() || rate <= 0.0 {
+            return None;
+        }
+        Some((needed as f64 * 1000.0 / rate).ceil() as u64)
+    }
+
+    /// Joins the line for `tokens`, unless the queue sheds the request.
+    fn join(&self, tokens: u32) -> Result<Place<'_>> {
+        let mut queued = self.queued.load(Ordering::Acquire);
+        loop {
+            if let Some(max_ms) = self.max_queue_wait_ms {
+                match self.projection(queued, tokens) {
+                    Some(wait_ms) if wait_ms <= max_ms => {}
+                    projected => {
+                        return Err(RateLimitError::rate_limit_exceeded(
+                            tokens,
+                            self.inner.available_tokens(),
+                            projected.unwrap_or(0),
+                        ))
+                    }
+                }
+            }
+            // Join only once admitted, so shed requests never make the line look
+            // longer to concurrent arrivals
+            match self.queued.compare_exchange_weak(
+                queued,
+                queued + tokens as u64,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    return Ok(Place {
+                        queued: &self.queued,
+                        tokens: tokens as u64,
+                    })
+                }
+                Err(actual) => queued = actual,
+            }
+        }
+    }
+
+    /// Waits in line until `tokens` can be acquired, then acquires them.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` once the tokens have been acquired
+    /// * `Err(RateLimitError::RateLimitExceeded)` immediately if `tokens` exceeds the
+    ///   capacity, since waiting would never succeed
+    /// * `Err(RateLimitError::RateLimitExceeded)` immediately if the request is shed,
+    ///   with its projected wait as `retry_after_ms`
+    /// * `Err(RateLimitError::RateLimitExceeded)` if an admitted request has not
+    ///   acquired its tokens within the maximum queue wait
+    /// * `Err(RateLimitError::InvalidConfiguration)` if the rate limiter is misconfigured
+    pub async fn acquire(&self, tokens: u32) -> Result<()> {
+        if tokens > self.inner.capacity() {
+            return self.inner.try_acquire(tokens);
+        }
+
+        let place = self.join(tokens)?;
+        let Some(max_ms) = self.max_queue_wait_ms else {
+            return self.wait_turn(tokens).await;
+        };
+        match tokio::time::timeout(Duration::from_millis(max_ms), self.wait_turn(tokens)).await {
+            Ok(result) => result,
+            Err(_) => {
+                drop(place);
+                Err(RateLimitError::rate_limit_exceeded(
+                    tokens,
+                    self.inner.available_tokens(),
+                    self.projected_wait_ms(tokens).unwrap_or(0),
+                ))
+            }
+        }
+    }
+
+    /// Waits for the head of the line, then until `tokens` can be acquired.
+    async fn wait_turn(&self, tokens: u32) -> Result<()> {
+        let _head = self.head.lock().await;
+        loop {
+            match self.inner.try_acquire(tokens) {
+                Ok(()) => return Ok(()),
+                Err(RateLimitError::RateLimitExceeded { retry_after_ms, .. }) => {
+                    // Never sleep for zero time, which would spin on the executor
+                    let wait = Duration::from_millis(retry_after_ms.max(1));
+                    let _ = tokio::time::timeout(wait, self.released.notified()).await;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl<L> RateLimiter for WaitQueue<L>
+where
+    L: RateLimiter,
+{
+    fn try_acquire(&self, tokens: u32) -> Result<()> {
+        self.inner.try_acquire(tokens)
+    }
+
+    fn try_acquire_u64(&self, tokens: u64) -> Result<()> {
+        self.inner.try_acquire_u64(tokens)
+    }
+
+    fn peek_acquire(&self, tokens: u32) -> Result<()> {
+        self.inner.peek_acquire(tokens)
+    }
+
+    fn available_tokens(&self) -> u32 {
+        self.inner.available_tokens()
+    }
+
+    fn available_tokens_u64(&self) -> u64 {
+        self.inner.available_tokens_u64()
+    }
+
+    fn capacity(&self) -> u32 {
+        self.inner.capacity()
+    }
+
+    fn capacity_u64(&self) -> u64 {
+        self.inner.capacity_u64()
+    }
+
+    fn rate_per_second(&self) -> f64 {
+        self.inner.rate_per_second()
+    }
+
+    fn effective_rate_per_second(&self) -> f64 {
+        self.inner.effective_rate_per_second()
+    }
+
+    fn resolution_warning(&self) -> Option<&'static str> {
+        self.inner.resolution_warning()
+    }
+
+    fn time_until_next_token_ms(&self) -> Option<u64> {
+        self.inner.time_until_next_token_ms()
+    }
+
+    fn time_until_available_ms(&self, tokens: u32) -> Option<u64> {
+        self.inner.time_until_available_ms(tokens)
+    }
+
+    fn retry_at(&self, tokens: u32) -> Option<u64> {
+        self.inner.retry_at(tokens)
+    }
+}
+
+impl<L> AdjustableRateLimiter for WaitQueue<L>
+where
+    L: AdjustableRateLimiter,
+{
+    /// Returns `tokens` to the inner limiter and wakes the head waiter to retry.
+    fn refund(&self, tokens: u32) {
+        self.inner.refund(tokens);
+        self.released.notify_one();
+    }
+
+    fn penalize(&self, tokens: u32) {
+        self.inner.penalize(tokens);
+    }
+}
+
+impl<L> Wrapper for WaitQueue<L> {
+    type Inner = L;
+
+    fn get_ref(&self) -> &L {
+        &self.inner
+    }
+
+    fn get_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    fn into_inner(self) -> L {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex as StdMutex};
+
+    use futures::future::join_all;
+    use tokio::time::Instant;
+
+    use super::*;
+    use crate::{asynchronous::TokioClock, clock::MockClock, traits::Resettable, TokenBucket};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_waiters_complete_in_arrival_order() {
+        let bucket = TokenBucket::with_clock(1, 10.0, TokioClock::new());
+        bucket.drain();
+        let queue = Arc::new(WaitQueue::new(bucket));
+        let completed = Arc::new(StdMutex::new(Vec::new()));
+
+        let mut tasks = Vec::new();
+        for i in 0..100 {
+            let queue = Arc::clone(&queue);
+            let completed = Arc::clone(&completed);
+            tasks.push(tokio::spawn(async move {
+                queue.acquire(1).await.unwrap();
+                completed.lock().unwrap().push(i);
+            }));
+            // Let the task join the queue before the next one arrives
+            tokio::task::yield_now().await;
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let completed = completed.lock().unwrap();
+        assert_eq!(*completed, (0..100).collect::<Vec<_>>());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_oversubscribed_acquires_are_shed() {
+        let bucket = TokenBucket::with_clock(1, 10.0, TokioClock::new());
+        let queue = WaitQueue::new(bucket).with_max_queue_wait(Duration::from_millis(250));
+        let start = Instant::now();
+
+        // One token is available and one more arrives every 100 ms, so only the
+        // waiters projected to be served within 250 ms join the line
+        let results = join_all((0..10).map(|_| queue.acquire(1))).await;
+        let admitted = results.iter().filter(|result| result.is_ok()).count();
+        assert_eq!(admitted, 3);
+        assert_eq!(start.elapsed(), Duration::from_millis(200));
+
+        // Shed requests leave the line alone, so each one sees the same projected wait
+        for shed in &results[3..] {
+            assert_eq!(shed.as_ref().unwrap_err().retry_after_ms(), Some(300));
+        }
+        assert_eq!(queue.queued_tokens(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_projection_holds_for_large_waiters() {
+        let bucket = TokenBucket::with_clock(3, 10.0, TokioClock::new());
+        bucket.drain();
+        let queue =
+            Arc::new(WaitQueue::new(bucket).with_max_queue_wait(Duration::from_millis(400)));
+        let start = Instant::now();
+
+        // A later small request cannot take the tokens the large one ahead is waiting for
+        let large = {
+            let queue = Arc::clone(&queue);
+            tokio::spawn(async move { (queue.acquire(3).await, start.elapsed()) })
+        };
+        tokio::task::yield_now().await;
+        assert_eq!(queue.projected_wait_ms(1), Some(400));
+        let small = {
+            let queue = Arc::clone(&queue);
+            tokio::spawn(async move { (queue.acquire(1).await, start.elapsed()) })
+        };
+        tokio::task::yield_now().await;
+        assert!(queue.acquire(1).await.is_err());
+
+        let (result, elapsed) = large.await.unwrap();
+        assert!(result.is_ok());
+        assert_eq!(elapsed, Duration::from_millis(300));
+        let (result, elapsed) = small.await.unwrap();
+        assert!(result.is_ok());
+        assert_eq!(elapsed, Duration::from_millis(400));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_dropped_waiter_leaves_line() {
+        let bucket = TokenBucket::with_clock(1, 1.0, TokioClock::new());
+        let queue = WaitQueue::new(bucket).with_max_queue_wait(Duration::from_secs(2));
+        assert!(queue.acquire(1).await.is_ok());
+
+        let timed_out = tokio::time::timeout(Duration::from_millis(10), queue.acquire(1)).await;
+        assert!(timed_out.is_err());
+        assert_eq!(queue.queued_tokens(), 0);
+        assert_eq!(queue.projected_wait_ms(2), Some(2000));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_admitted_waiter_gives_up_at_max_queue_wait() {
+        // The limiter's clock is stopped, so the token a waiter is admitted for with
+        // a projected wait of 100 ms never arrives
+        let bucket = TokenBucket::with_clock(1, 10.0, MockClock::new(0));
+        let queue = WaitQueue::new(bucket).with_max_queue_wait(Duration::from_millis(250));
+        assert!(queue.acquire(1).await.is_ok());
+        assert_eq!(queue.projected_wait_ms(1), Some(100));
+
+        let start = Instant::now();
+        let err = queue.acquire(1).await.unwrap_err();
+        assert_eq!(err.retry_after_ms(), Some(100));
+        assert_eq!(start.elapsed(), Duration::from_millis(250));
+        assert_eq!(queue.queued_tokens(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refund_wakes_head_waiter() {
+        let bucket = TokenBucket::with_clock(1, 0.1, TokioClock::new());
+        bucket.drain();
+        let queue = Arc::new(WaitQueue::new(bucket));
+        let start = Instant::now();
+
+        let waiter = {
+            let queue = Arc::clone(&queue);
+            tokio::spawn(async move { queue.acquire(1).await })
+        };
+        tokio::task::yield_now().await;
+        queue.refund(1);
+
+        assert!(waiter.await.unwrap().is_ok());
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert!(queue.acquire(2).await.is_err());
+    }
+}
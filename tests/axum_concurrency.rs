@@ -0,0 +1,133 @@
+//! Integration tests for rate limiting the full Axum request path under concurrent load.
+//!
+//! The limiter is shared as a plain `Arc<TokenBucket>` with no `Mutex`, relying on the
+//! bucket's atomics for correctness. Requests are driven through the router with
+//! `tower::ServiceExt::oneshot`, so no real server is spun up.
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use bucketboss::{clock::MockClock, RateLimiter, TokenBucket};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+type SharedLimiter = Arc<TokenBucket<MockClock>>;
+
+/// Middleware that admits a request only if the shared bucket has a token.
+async fn rate_limit(
+    State(limiter): State<SharedLimiter>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match limiter.try_acquire(1) {
+        Ok(()) => next.run(request).await,
+        Err(err) => {
+            // Retry-After is expressed in whole seconds, rounded up
+            let retry_after_secs = err.retry_after_ms().unwrap_or(0).div_ceil(1000).max(1);
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after_secs.to_string())],
+            )
+                .into_response()
+        }
+    }
+}
+
+fn app(limiter: SharedLimiter) -> Router {
+    Router::new()
+        .route("/", get(|| async { "Hello, World!" }))
+        .layer(middleware::from_fn_with_state(limiter, rate_limit))
+}
+
+/// Test that exactly `capacity` concurrent requests succeed and the rest are throttled
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_axum_middleware_concurrent_load() {
+    let capacity = 50;
+    let total_requests = 200;
+
+    // The clock never advances, so no tokens are replenished during the test
+    let clock = MockClock::new(0);
+    let limiter = Arc::new(TokenBucket::with_clock(capacity, 1.0, clock));
+    let app = app(limiter.clone());
+
+    let handles: Vec<_> = (0..total_requests)
+        .map(|_| {
+            let app = app.clone();
+            tokio::spawn(async move {
+                let request = Request::builder().uri("/").body(Body::empty()).unwrap();
+                app.oneshot(request).await.unwrap()
+            })
+        })
+        .collect();
+
+    let mut ok = 0;
+    let mut throttled = 0;
+    for handle in handles {
+        let response = handle.await.unwrap();
+        match response.status() {
+            StatusCode::OK => ok += 1,
+            StatusCode::TOO_MANY_REQUESTS => {
+                throttled += 1;
+                let retry_after = response
+                    .headers()
+                    .get(header::RETRY_AFTER)
+                    .expect("429 responses must carry Retry-After")
+                    .to_str()
+                    .unwrap()
+                    .parse::<u64>()
+                    .expect("Retry-After must be a whole number of seconds");
+                assert!(retry_after >= 1);
+            }
+            status => panic!("unexpected status {}", status),
+        }
+    }
+
+    assert_eq!(ok, capacity);
+    assert_eq!(throttled, total_requests - capacity);
+    assert_eq!(limiter.available_tokens(), 0);
+}
+
+/// Test that the in-crate tower layer limits an Axum router the same way
+#[cfg(feature = "tower")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_rate_limit_layer_concurrent_load() {
+    let capacity = 50;
+    let total_requests = 200;
+
+    let layer =
+        bucketboss::RateLimitLayer::new(TokenBucket::with_clock(capacity, 1.0, MockClock::new(0)));
+    let app = Router::new()
+        .route("/", get(|| async { "Hello, World!" }))
+        .layer(layer.clone());
+
+    let handles: Vec<_> = (0..total_requests)
+        .map(|_| {
+            let app = app.clone();
+            tokio::spawn(async move {
+                let request = Request::builder().uri("/").body(Body::empty()).unwrap();
+                app.oneshot(request).await.unwrap()
+            })
+        })
+        .collect();
+
+    let mut ok = 0;
+    for handle in handles {
+        let response = handle.await.unwrap();
+        match response.status() {
+            StatusCode::OK => ok += 1,
+            StatusCode::TOO_MANY_REQUESTS => {
+                assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+            }
+            status => panic!("unexpected status {}", status),
+        }
+    }
+
+    assert_eq!(ok, capacity);
+    assert_eq!(layer.limiter().available_tokens(), 0);
+}
@@ -5,19 +5,22 @@
 
 use bucketboss::{
     clock::{Clock, MockClock},
-    LeakyBucket, RateLimitError, RateLimiter, ReconfigurableRateLimiter, TokenBucket,
+    testing::{ClockDriver, Decision, Op, Simulation},
+    CircuitBreakerLimiter, LabeledLimiter, LeakyBucket, RateLimitError, RateLimiter, ScaledLimiter,
+    TokenBucket, Wrapper,
 };
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Barrier};
 use std::thread;
-use std::time::Duration;
 
 /// Test that the token bucket correctly enforces rate limits
 #[test]
 fn test_token_bucket_integration() {
     // Create a token bucket with capacity 5 and rate 10 tokens per second
     let clock = MockClock::new(0);
-    let bucket = TokenBucket::with_clock(5, 10.0, clock.clone());
+    let mut driver = ClockDriver::new(clock.clone());
+    let bucket = driver.register(Arc::new(TokenBucket::with_clock(5, 10.0, clock)));
+    let bucket = driver.limiter(bucket);
 
     // Should be able to acquire all 5 tokens immediately
     for _ in 0..5 {
@@ -29,9 +32,10 @@ fn test_token_bucket_integration() {
         bucket.try_acquire(1),
         Err(RateLimitError::RateLimitExceeded { .. })
     ));
+    assert_eq!(driver.snapshot()[0].time_until_next_token_ms, Some(100));
 
     // Advance time by 100ms (1 token should be replenished)
-    clock.advance(100);
+    assert_eq!(driver.advance(100)[0].available, 1);
 
     // Should be able to acquire 1 token
     assert!(bucket.try_acquire(1).is_ok());
@@ -139,6 +143,132 @@ fn test_custom_clock() {
     assert!(lb.try_acquire(1).is_ok());
 }
 
+/// Test that contended acquisitions are never lost and no thread is starved
+#[test]
+fn test_token_bucket_contention_fairness() {
+    let num_threads = 8;
+    let tokens_per_round = num_threads / 2;
+    let rounds = 400;
+
+    // Each round refills half as many tokens as there are threads, and every thread
+    // asks for one, so half of the requests must be rejected
+    let clock = MockClock::new(0);
+    let bucket = Arc::new(TokenBucket::with_clock(
+        tokens_per_round,
+        1000.0,
+        clock.clone(),
+    ));
+    let start = Arc::new(Barrier::new(num_threads as usize + 1));
+    let end = Arc::new(Barrier::new(num_threads as usize + 1));
+
+    let handles: Vec<_> = (0..num_threads)
+        .map(|_| {
+            let bucket = bucket.clone();
+            let start = start.clone();
+            let end = end.clone();
+            thread::spawn(move || {
+                let mut successes = 0;
+                for _ in 0..rounds {
+                    let _ = start.wait();
+                    if bucket.try_acquire(1).is_ok() {
+                        successes += 1;
+                    }
+                    let _ = end.wait();
+                }
+                successes
+            })
+        })
+        .collect();
+
+    for _ in 0..rounds {
+        let _ = start.wait();
+        let _ = end.wait();
+        // Contention never turns into a rejection while tokens are left
+        assert_eq!(bucket.available_tokens(), 0);
+        clock.advance(tokens_per_round as u64);
+    }
+
+    let successes: Vec<u32> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert_eq!(successes.iter().sum::<u32>(), tokens_per_round * rounds);
+    // A fair share is half of the rounds; no thread gets less than a quarter of that
+    assert!(
+        successes.iter().all(|&count| count >= rounds / 8),
+        "starved thread: {successes:?}"
+    );
+}
+
+/// Test that rejections under contention report a real, insufficient token count
+#[test]
+fn test_rejection_available_is_consistent_under_contention() {
+    let num_threads = 8;
+    let clock = MockClock::new(0);
+    let limiters: Vec<Arc<dyn RateLimiter>> = vec![
+        Arc::new(TokenBucket::with_clock(20, 1000.0, clock.clone())),
+        Arc::new(LeakyBucket::with_clock(1000.0, Some(20), clock.clone())),
+    ];
+
+    for limiter in limiters {
+        let barrier = Arc::new(Barrier::new(num_threads + 1));
+        let handles: Vec<_> = (0..num_threads as u32)
+            .map(|thread_index| {
+                let limiter = limiter.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    let _ = barrier.wait();
+                    let mut rejections = 0;
+                    for i in 0..5_000u32 {
+                        let requested = 1 + (i + thread_index) % 6;
+                        if let Err(RateLimitError::RateLimitExceeded {
+                            requested: reported,
+                            available,
+                            ..
+                        }) = limiter.try_acquire(requested)
+                        {
+                            assert_eq!(reported, requested);
+                            assert!(available < requested, "{available} >= {requested}");
+                            rejections += 1;
+                        }
+                    }
+                    rejections
+                })
+            })
+            .collect();
+
+        // Refill concurrently so the count keeps moving under the acquiring threads
+        let _ = barrier.wait();
+        for _ in 0..2_000 {
+            clock.advance(1);
+            thread::yield_now();
+        }
+
+        let rejections: u32 = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        assert!(rejections > 0);
+    }
+}
+
+/// Test a leaky bucket scenario written as a scripted timeline
+#[test]
+fn test_leaky_bucket_simulation() {
+    let clock = MockClock::new(0);
+    let bucket = LeakyBucket::with_clock(10.0, Some(3), clock.clone());
+
+    // A burst of 3 fills the bucket, then requests pass as it drains at 1 per 100ms
+    let steps = Simulation::new(clock, &bucket)
+        .at(0, Op::Acquire(3))
+        .at(0, Op::Acquire(1))
+        .at(50, Op::Acquire(1))
+        .at(100, Op::Acquire(1))
+        .at(100, Op::Acquire(1))
+        .at(300, Op::Acquire(2))
+        .run();
+
+    let admitted: Vec<_> = steps
+        .iter()
+        .map(|step| matches!(step.decision, Decision::Acquired(o) if o.is_admitted()))
+        .collect();
+    assert_eq!(admitted, [true, false, false, true, false, true]);
+}
+
 /// A test clock that can be advanced manually
 #[derive(Debug, Clone)]
 struct TestClock {
@@ -162,3 +292,43 @@ impl Clock for TestClock {
         self.now.load(Ordering::Relaxed)
     }
 }
+
+/// Test that the effective rate of a wrapper stack accounts for every layer
+#[test]
+fn test_effective_rate_through_wrappers() {
+    let clock = MockClock::new(0);
+    let bucket = TokenBucket::with_clock(10, 10.0, clock.clone());
+    let scaled = ScaledLimiter::new(bucket, 1024).unwrap();
+    let breaker = CircuitBreakerLimiter::with_clock(scaled, 1, 1000, 5000, clock.clone()).unwrap();
+    let limiter = LabeledLimiter::new(breaker, "uploads");
+
+    assert_eq!(limiter.rate_per_second(), 10_240.0);
+    assert_eq!(limiter.effective_rate_per_second(), 10_240.0);
+
+    // An open breaker lets nothing through, whatever the bucket's nominal rate
+    limiter.get_ref().record_failure();
+    assert_eq!(limiter.rate_per_second(), 10_240.0);
+    assert_eq!(limiter.effective_rate_per_second(), 0.0);
+}
+
+/// Test that a wrapper stack reports and admits the full width of a u64 bucket
+#[test]
+fn test_u64_counts_through_wrappers() {
+    const GIB: u64 = 1 << 30;
+    let clock = MockClock::new(0);
+    let bucket = TokenBucket::with_clock_u64(8 * GIB, GIB as f64, clock.clone());
+    let breaker = CircuitBreakerLimiter::with_clock(bucket, 1, 1000, 5000, clock.clone()).unwrap();
+    let limiter = LabeledLimiter::new(breaker, "bytes");
+
+    assert_eq!(limiter.capacity_u64(), 8 * GIB);
+    assert!(limiter.try_acquire_u64(5 * GIB).is_ok());
+    assert_eq!(limiter.available_tokens_u64(), 3 * GIB);
+    assert_eq!(limiter.fill_level_u8(), 95);
+
+    // Peeking decides like the bucket without taking anything
+    assert!(limiter.peek_acquire(1024).is_ok());
+    assert_eq!(limiter.get_ref().get_ref().available_tokens_u64(), 3 * GIB);
+    let err = limiter.try_acquire_u64(4 * GIB).unwrap_err();
+    assert_eq!(err.rejected_by(), Some("bytes"));
+    assert_eq!(err.retry_after_ms(), Some(1000));
+}
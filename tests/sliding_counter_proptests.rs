@@ -0,0 +1,79 @@
+//! Property tests for the SlidingWindowCounter rate limiter.
+
+use proptest::prelude::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use bucketboss::{clock::Clock, RateLimiter, SlidingWindowCounter};
+
+// A mock clock that can be advanced manually
+#[derive(Debug, Clone)]
+struct TestClock {
+    now: Arc<AtomicU64>,
+}
+
+impl TestClock {
+    fn new(initial_time: u64) -> Self {
+        Self {
+            now: Arc::new(AtomicU64::new(initial_time)),
+        }
+    }
+
+    fn advance(&self, ms: u64) {
+        let _ = self.now.fetch_add(ms, Ordering::SeqCst);
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::Relaxed)
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 500,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn test_sliding_counter_never_admits_twice_the_limit(
+        limit in 1u32..100u32,
+        window_ms in 1u64..2000u64,
+        start in 0u64..10_000u64,
+        requests in prop::collection::vec((0u64..500u64, 0u32..50u32), 1..200),
+    ) {
+        let clock = TestClock::new(start);
+        let limiter = SlidingWindowCounter::with_clock(limit, window_ms, clock.clone());
+
+        let mut admitted = Vec::new();
+        for (advance, tokens) in requests {
+            clock.advance(advance);
+            if limiter.try_acquire(tokens).is_ok() {
+                admitted.push((clock.now(), tokens as u64));
+            }
+
+            // Admissions never push the estimate past the limit
+            let estimated = limiter.estimated();
+            assert!(estimated <= limit as f64, "Estimate {} exceeded limit {}", estimated, limit);
+        }
+
+        // The estimate can be fooled by an uneven previous window, but never by more
+        // than a whole window's worth
+        for &(from, _) in &admitted {
+            let in_window: u64 = admitted
+                .iter()
+                .filter(|&&(at, _)| at >= from && at < from + window_ms)
+                .map(|&(_, tokens)| tokens)
+                .sum();
+            assert!(
+                in_window <= 2 * limit as u64,
+                "Admitted {} tokens within {}ms of {} with a limit of {}",
+                in_window,
+                window_ms,
+                from,
+                limit
+            );
+        }
+    }
+}